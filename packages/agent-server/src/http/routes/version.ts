@@ -0,0 +1,44 @@
+import fs from 'node:fs';
+import path from 'node:path';
+
+import { CURRENT_PROTOCOL_VERSION } from '@assistant/shared';
+
+import type { HttpRouteHandler } from '../types';
+
+const PACKAGE_JSON_PATH = path.resolve(__dirname, '../../../package.json');
+
+let serverVersion: string | null | undefined;
+
+function readServerVersion(): string | null {
+  if (serverVersion === undefined) {
+    try {
+      const parsed = JSON.parse(fs.readFileSync(PACKAGE_JSON_PATH, 'utf8')) as {
+        version?: unknown;
+      };
+      serverVersion = typeof parsed.version === 'string' ? parsed.version : null;
+    } catch {
+      serverVersion = null;
+    }
+  }
+  return serverVersion;
+}
+
+export const handleVersionRoutes: HttpRouteHandler = async (
+  _context,
+  req,
+  _res,
+  url,
+  _segments,
+  helpers,
+) => {
+  // GET /api/version
+  if (url.pathname !== '/api/version' || req.method !== 'GET') {
+    return false;
+  }
+
+  helpers.sendJson(200, {
+    version: readServerVersion(),
+    protocolVersion: CURRENT_PROTOCOL_VERSION,
+  });
+  return true;
+};
@@ -22,6 +22,7 @@ import { handlePanelRoutes } from './routes/panels';
 import { handleStaticRoutes } from './routes/static';
 import { handleSearchRoutes } from './routes/search';
 import { createVoiceRouteHandler } from './routes/voice';
+import { handleVersionRoutes } from './routes/version';
 import type { VoiceService } from '../voice/service';
 import type { HttpContext, HttpHelpers, HttpRouteHandler } from './types';
 
@@ -181,6 +182,7 @@ export function createHttpServer(options: {
       const pluginRoutes = pluginRegistry?.getHttpRoutes?.() ?? [];
       const handlers: HttpRouteHandler[] = [
         handleStaticRoutes,
+        handleVersionRoutes,
         handleAttachmentRoutes,
         handleExternalRoutes,
         handlePanelRoutes,
@@ -45,6 +45,9 @@ export { Session } from './ws/session';
 
 dotenv.config();
 
+/** Largest WebSocket message accepted (the `ws` default). */
+const WS_MAX_PAYLOAD_BYTES = 100 * 1024 * 1024;
+
 export let agentRegistry: AgentRegistry | undefined;
 
 function createSessionWorkingDirResolver(
@@ -213,6 +216,16 @@ export async function startServer(
   const wss = new WebSocketServer({
     server: httpServer,
     path: '/ws',
+    maxPayload: WS_MAX_PAYLOAD_BYTES,
+  });
+
+  // Answer the desktop proxy's capability announcement with what this server supports
+  wss.on('headers', (headers, req) => {
+    if (req.headers['x-assistant-capabilities'] !== undefined) {
+      headers.push(
+        `X-Assistant-Capabilities: multiplexing, audio-streaming, max-message-size=${WS_MAX_PAYLOAD_BYTES}`,
+      );
+    }
   });
 
   wss.on('connection', (ws) => {
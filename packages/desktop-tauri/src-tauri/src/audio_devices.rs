@@ -0,0 +1,413 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use serde::Serialize;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audio_processing::{AudioProcessing, AudioProcessor};
+use crate::AppState;
+
+/// How often the device list is polled for added/removed devices.
+const DEVICE_POLL_INTERVAL_SECS: u64 = 3;
+
+/// Interval between `mic-level` events while previewing a microphone.
+const MIC_LEVEL_INTERVAL_MS: u64 = 50;
+
+/// An audio device as reported by the OS.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// The device TTS playback should use, emitted as `audio-output-changed`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActiveAudioDevice {
+    /// Device to play on (`None` when the OS reports no output devices).
+    pub device: Option<AudioDevice>,
+    /// The user-selected device, if any.
+    pub selected_id: Option<String>,
+    /// Whether the selected device is unavailable and the default is used instead.
+    pub fallback: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Input,
+    Output,
+}
+
+fn describe(device: &cpal::Device, default_id: Option<&str>) -> Option<AudioDevice> {
+    let id = device.id().ok()?.to_string();
+    let name = device
+        .description()
+        .map(|description| description.name().to_string())
+        .unwrap_or_else(|_| id.clone());
+    Some(AudioDevice {
+        is_default: default_id == Some(id.as_str()),
+        id,
+        name,
+    })
+}
+
+fn devices(direction: Direction) -> Result<Vec<AudioDevice>, String> {
+    let host = cpal::default_host();
+    let default = match direction {
+        Direction::Input => host.default_input_device(),
+        Direction::Output => host.default_output_device(),
+    };
+    let default_id = default
+        .and_then(|device| device.id().ok())
+        .map(|id| id.to_string());
+    let devices: Vec<cpal::Device> = match direction {
+        Direction::Input => host.input_devices().map_err(|e| e.to_string())?.collect(),
+        Direction::Output => host.output_devices().map_err(|e| e.to_string())?.collect(),
+    };
+    Ok(devices
+        .iter()
+        .filter_map(|device| describe(device, default_id.as_deref()))
+        .collect())
+}
+
+/// Enumerate devices off the async runtime (enumeration can block on some hosts).
+async fn list_devices(direction: Direction) -> Result<Vec<AudioDevice>, String> {
+    tokio::task::spawn_blocking(move || devices(direction))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Pick the selected device when it is present, otherwise the OS default.
+fn resolve_active(selected_id: Option<&str>, devices: &[AudioDevice]) -> ActiveAudioDevice {
+    let selected = selected_id.and_then(|id| devices.iter().find(|device| device.id == id));
+    let default = devices.iter().find(|device| device.is_default);
+    ActiveAudioDevice {
+        device: selected.or(default).cloned(),
+        selected_id: selected_id.map(str::to_string),
+        fallback: selected_id.is_some() && selected.is_none(),
+    }
+}
+
+async fn selected_output(app: &AppHandle) -> Option<String> {
+    let state: State<'_, AppState> = app.state();
+    let settings = state.settings.lock().await;
+    settings.audio_output_device.clone()
+}
+
+/// Watch for the selected output disappearing (or coming back) and tell the
+/// web client which device to play on.
+pub async fn run_audio_output_monitor(app: AppHandle) {
+    let mut last: Option<ActiveAudioDevice> = None;
+    loop {
+        match list_devices(Direction::Output).await {
+            Ok(devices) => {
+                let selected = selected_output(&app).await;
+                let active = resolve_active(selected.as_deref(), &devices);
+                if last.as_ref() != Some(&active) {
+                    if active.fallback {
+                        log::warn!(
+                            target: "audio",
+                            "Output device {} unavailable, using default",
+                            active.selected_id.as_deref().unwrap_or_default()
+                        );
+                    }
+                    let _ = app.emit("audio-output-changed", &active);
+                    last = Some(active);
+                }
+            }
+            Err(e) => log::warn!(target: "audio", "Failed to list output devices: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(DEVICE_POLL_INTERVAL_SECS)).await;
+    }
+}
+
+/// List audio output devices available for TTS playback.
+#[tauri::command]
+pub async fn list_audio_outputs() -> Result<Vec<AudioDevice>, String> {
+    list_devices(Direction::Output).await
+}
+
+/// Get the output device TTS playback should currently use.
+#[tauri::command]
+pub async fn get_audio_output(app: AppHandle) -> Result<ActiveAudioDevice, String> {
+    let devices = list_devices(Direction::Output).await?;
+    let selected = selected_output(&app).await;
+    Ok(resolve_active(selected.as_deref(), &devices))
+}
+
+/// Select the TTS output device (`None` follows the OS default) and persist it.
+#[tauri::command]
+pub async fn set_audio_output(
+    app: AppHandle,
+    device_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ActiveAudioDevice, String> {
+    let devices = list_devices(Direction::Output).await?;
+    let device_id = device_id.filter(|id| !id.trim().is_empty());
+    if let Some(id) = &device_id {
+        if !devices.iter().any(|device| &device.id == id) {
+            return Err(format!("Unknown output device: {}", id));
+        }
+    }
+    state.settings.lock().await.audio_output_device = device_id.clone();
+    state.save().await?;
+
+    let active = resolve_active(device_id.as_deref(), &devices);
+    let _ = app.emit("audio-output-changed", &active);
+    Ok(active)
+}
+
+/// Input level of the previewed microphone, emitted as `mic-level` (0.0–1.0).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MicLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Accumulates samples between `mic-level` events.
+#[derive(Default)]
+struct LevelMeter {
+    sum_squares: f64,
+    count: u64,
+    peak: f32,
+}
+
+impl LevelMeter {
+    fn add(&mut self, samples: impl Iterator<Item = f32>) {
+        for sample in samples {
+            let sample = sample.abs().min(1.0);
+            self.sum_squares += f64::from(sample * sample);
+            self.count += 1;
+            self.peak = self.peak.max(sample);
+        }
+    }
+
+    fn take(&mut self) -> MicLevel {
+        let meter = std::mem::take(self);
+        let rms = if meter.count == 0 {
+            0.0
+        } else {
+            (meter.sum_squares / meter.count as f64).sqrt() as f32
+        };
+        MicLevel {
+            rms,
+            peak: meter.peak,
+        }
+    }
+}
+
+/// The running microphone preview, stopped by dropping (or sending on) its channel.
+pub struct MicPreview {
+    stop_tx: Mutex<Option<mpsc::Sender<()>>>,
+}
+
+impl MicPreview {
+    pub fn new() -> Self {
+        Self {
+            stop_tx: Mutex::new(None),
+        }
+    }
+
+    fn replace(&self, stop_tx: Option<mpsc::Sender<()>>) {
+        let mut current = self.stop_tx.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(previous) = std::mem::replace(&mut *current, stop_tx) {
+            let _ = previous.send(());
+        }
+    }
+}
+
+fn input_device(device_id: Option<&str>) -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match device_id {
+        Some(id) => {
+            let id = id.parse::<cpal::DeviceId>().map_err(|e| e.to_string())?;
+            host.device_by_id(&id)
+                .ok_or_else(|| format!("Unknown input device: {}", id))
+        }
+        None => host
+            .default_input_device()
+            .ok_or_else(|| "No input device available".to_string()),
+    }
+}
+
+fn build_level_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    meter: Arc<Mutex<LevelMeter>>,
+    mut processor: AudioProcessor,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let mut frame = Vec::new();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                frame.clear();
+                frame.extend(data.iter().map(|sample| sample.to_sample::<f32>()));
+                processor.process(&mut frame);
+                let mut meter = meter.lock().unwrap_or_else(|e| e.into_inner());
+                meter.add(frame.iter().copied());
+            },
+            |e| log::warn!(target: "audio", "Microphone stream error: {}", e),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// Capture from the device through echo/noise processing and emit `mic-level` until
+/// told to stop.
+///
+/// Runs on its own thread because audio streams are not `Send` on every platform.
+fn run_mic_preview(
+    app: AppHandle,
+    device_id: Option<String>,
+    processing: Arc<AudioProcessing>,
+    stop_rx: mpsc::Receiver<()>,
+    ready_tx: mpsc::Sender<Result<(), String>>,
+) {
+    let meter = Arc::new(Mutex::new(LevelMeter::default()));
+    let stream = input_device(device_id.as_deref()).and_then(|device| {
+        let supported = device.default_input_config().map_err(|e| e.to_string())?;
+        let config = supported.config();
+        let processor = AudioProcessor::new(processing);
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => {
+                build_level_stream::<f32>(&device, &config, meter.clone(), processor)
+            }
+            SampleFormat::I16 => {
+                build_level_stream::<i16>(&device, &config, meter.clone(), processor)
+            }
+            SampleFormat::U16 => {
+                build_level_stream::<u16>(&device, &config, meter.clone(), processor)
+            }
+            SampleFormat::I32 => {
+                build_level_stream::<i32>(&device, &config, meter.clone(), processor)
+            }
+            format => Err(format!("Unsupported sample format: {}", format)),
+        }?;
+        stream.play().map_err(|e| e.to_string())?;
+        Ok(stream)
+    });
+    let stream = match stream {
+        Ok(stream) => {
+            let _ = ready_tx.send(Ok(()));
+            stream
+        }
+        Err(e) => {
+            let _ = ready_tx.send(Err(e));
+            return;
+        }
+    };
+
+    while let Err(mpsc::RecvTimeoutError::Timeout) =
+        stop_rx.recv_timeout(Duration::from_millis(MIC_LEVEL_INTERVAL_MS))
+    {
+        let level = meter.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let _ = app.emit("mic-level", level);
+    }
+    drop(stream);
+}
+
+/// List microphones available for voice sessions.
+#[tauri::command]
+pub async fn list_audio_inputs() -> Result<Vec<AudioDevice>, String> {
+    list_devices(Direction::Input).await
+}
+
+/// Select the microphone (`None` follows the OS default) and persist it.
+#[tauri::command]
+pub async fn set_audio_input(
+    device_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let device_id = device_id.filter(|id| !id.trim().is_empty());
+    if let Some(id) = &device_id {
+        let devices = list_devices(Direction::Input).await?;
+        if !devices.iter().any(|device| &device.id == id) {
+            return Err(format!("Unknown input device: {}", id));
+        }
+    }
+    state.settings.lock().await.audio_input_device = device_id;
+    state.save().await
+}
+
+/// Start emitting `mic-level` events for a microphone (defaults to the selected
+/// one), replacing any running preview.
+#[tauri::command]
+pub async fn start_mic_preview(
+    app: AppHandle,
+    device_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let device_id = match device_id.filter(|id| !id.trim().is_empty()) {
+        Some(id) => Some(id),
+        None => state.settings.lock().await.audio_input_device.clone(),
+    };
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let processing = state.audio_processing.clone();
+    state.mic_preview.replace(Some(stop_tx));
+    std::thread::spawn(move || run_mic_preview(app, device_id, processing, stop_rx, ready_tx));
+
+    let started = tokio::task::spawn_blocking(move || {
+        ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err("Microphone preview stopped".to_string()))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if started.is_err() {
+        state.mic_preview.replace(None);
+    }
+    started
+}
+
+/// Stop the microphone preview.
+#[tauri::command]
+pub async fn stop_mic_preview(state: State<'_, AppState>) -> Result<(), String> {
+    state.mic_preview.replace(None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_active, AudioDevice, LevelMeter};
+
+    fn device(id: &str, is_default: bool) -> AudioDevice {
+        AudioDevice {
+            id: id.to_string(),
+            name: id.to_string(),
+            is_default,
+        }
+    }
+
+    #[test]
+    fn falls_back_to_default_when_selected_device_is_gone() {
+        let devices = vec![device("speakers", true), device("headphones", false)];
+        let active = resolve_active(Some("headphones"), &devices);
+        assert_eq!(active.device.unwrap().id, "headphones");
+        assert!(!active.fallback);
+
+        let active = resolve_active(Some("headphones"), &devices[..1]);
+        assert_eq!(active.device.unwrap().id, "speakers");
+        assert!(active.fallback);
+
+        let active = resolve_active(None, &devices);
+        assert_eq!(active.device.unwrap().id, "speakers");
+        assert!(!active.fallback);
+    }
+
+    #[test]
+    fn measures_rms_and_peak_between_events() {
+        let mut meter = LevelMeter::default();
+        meter.add([0.5, -0.5, 0.5, -1.5].into_iter());
+        let level = meter.take();
+        assert_eq!(level.peak, 1.0);
+        assert!((level.rms - 0.661).abs() < 0.001);
+        assert_eq!(meter.take().rms, 0.0);
+    }
+}
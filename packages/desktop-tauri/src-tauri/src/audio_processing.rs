@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::State;
+
+use crate::{now_ms, AppState};
+
+/// How long the mic stays suppressed after TTS playback stops (room reverb tail).
+const ECHO_HANGOVER_MS: u64 = 300;
+
+/// Gain applied to the mic while TTS is audible on the speakers.
+const ECHO_SUPPRESSION_GAIN: f32 = 0.05;
+
+/// Gain applied to frames at the background noise floor.
+const NOISE_GATE_GAIN: f32 = 0.1;
+
+/// Frames quieter than this multiple of the noise floor are treated as noise.
+const NOISE_GATE_RATIO: f32 = 2.5;
+
+/// Per-frame rise of the noise floor estimate (it drops immediately on quieter frames).
+const NOISE_FLOOR_RISE: f32 = 0.002;
+
+/// Smoothing of gain changes between frames, to avoid clicks.
+const GAIN_SMOOTHING: f32 = 0.3;
+
+/// Capture processing toggles plus the far-end (TTS playback) activity they react to.
+pub struct AudioProcessing {
+    echo_cancellation: AtomicBool,
+    noise_suppression: AtomicBool,
+    playback_until_ms: AtomicU64,
+}
+
+impl AudioProcessing {
+    pub fn new(echo_cancellation: bool, noise_suppression: bool) -> Self {
+        Self {
+            echo_cancellation: AtomicBool::new(echo_cancellation),
+            noise_suppression: AtomicBool::new(noise_suppression),
+            playback_until_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_echo_cancellation(&self, enabled: bool) {
+        self.echo_cancellation.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_noise_suppression(&self, enabled: bool) {
+        self.noise_suppression.store(enabled, Ordering::Relaxed);
+    }
+
+    fn set_playback_active(&self, active: bool) {
+        let until = if active {
+            u64::MAX
+        } else {
+            now_ms() + ECHO_HANGOVER_MS
+        };
+        self.playback_until_ms.store(until, Ordering::Relaxed);
+    }
+
+    fn playback_active(&self) -> bool {
+        now_ms() < self.playback_until_ms.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-stream processing state applied to captured frames before they are used.
+pub struct AudioProcessor {
+    shared: Arc<AudioProcessing>,
+    noise_floor: Option<f32>,
+    gain: f32,
+}
+
+impl AudioProcessor {
+    pub fn new(shared: Arc<AudioProcessing>) -> Self {
+        Self {
+            shared,
+            noise_floor: None,
+            gain: 1.0,
+        }
+    }
+
+    /// Process one captured frame in place.
+    pub fn process(&mut self, frame: &mut [f32]) {
+        if frame.is_empty() {
+            return;
+        }
+        let echo =
+            self.shared.echo_cancellation.load(Ordering::Relaxed) && self.shared.playback_active();
+        let noise = self.shared.noise_suppression.load(Ordering::Relaxed);
+        let target = self.target_gain(frame, echo, noise);
+
+        // Ramp across the frame from the previous gain to the new one
+        let start = self.gain;
+        self.gain += (target - self.gain) * GAIN_SMOOTHING;
+        let step = (self.gain - start) / frame.len() as f32;
+        for (index, sample) in frame.iter_mut().enumerate() {
+            *sample *= start + step * (index + 1) as f32;
+        }
+    }
+
+    fn target_gain(&mut self, frame: &[f32], echo: bool, noise: bool) -> f32 {
+        let rms =
+            (frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len() as f32).sqrt();
+        let floor = match self.noise_floor {
+            Some(floor) if rms >= floor => floor + (rms - floor) * NOISE_FLOOR_RISE,
+            _ => rms,
+        };
+        self.noise_floor = Some(floor);
+
+        let mut gain = 1.0;
+        if echo {
+            gain *= ECHO_SUPPRESSION_GAIN;
+        }
+        if noise && rms < floor * NOISE_GATE_RATIO {
+            gain *= NOISE_GATE_GAIN;
+        }
+        gain
+    }
+}
+
+/// Tell capture processing whether TTS audio is currently playing, so the mic
+/// does not pick it back up from the speakers.
+#[tauri::command]
+pub async fn set_tts_playback_active(
+    active: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.audio_processing.set_playback_active(active);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioProcessing, AudioProcessor};
+    use std::sync::Arc;
+
+    fn rms(frame: &[f32]) -> f32 {
+        (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+    }
+
+    fn settle(processor: &mut AudioProcessor, amplitude: f32) -> f32 {
+        let mut frame = vec![amplitude; 256];
+        for _ in 0..30 {
+            frame = vec![amplitude; 256];
+            processor.process(&mut frame);
+        }
+        rms(&frame)
+    }
+
+    #[test]
+    fn gates_background_noise_but_keeps_speech() {
+        let mut processor = AudioProcessor::new(Arc::new(AudioProcessing::new(false, true)));
+        assert!(settle(&mut processor, 0.01) < 0.002);
+        assert!(settle(&mut processor, 0.3) > 0.29);
+    }
+
+    #[test]
+    fn suppresses_mic_while_tts_plays() {
+        let shared = Arc::new(AudioProcessing::new(true, false));
+        let mut processor = AudioProcessor::new(shared.clone());
+        shared.set_playback_active(true);
+        assert!(settle(&mut processor, 0.3) < 0.02);
+
+        shared.set_echo_cancellation(false);
+        assert!(settle(&mut processor, 0.3) > 0.29);
+    }
+}
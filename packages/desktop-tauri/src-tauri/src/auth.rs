@@ -0,0 +1,476 @@
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::backend_host::BackendHost;
+use crate::{now_ms, AppState};
+
+/// Refresh this long before the access token expires (capped at half the token lifetime).
+const REFRESH_LEAD_SECS: u64 = 60;
+/// Delay between refresh attempts after a failed refresh.
+const REFRESH_RETRY_SECS: u64 = 15;
+const REFRESH_REQUEST_TIMEOUT_SECS: u64 = 15;
+/// How long a paused request waits for the web client to complete a re-login.
+const RELOGIN_TIMEOUT_SECS: u64 = 60;
+/// Bytes of a 401 body inspected for expiry markers.
+const EXPIRY_BODY_SCAN_BYTES: usize = 2048;
+/// Phrases in 401 bodies that mean the session lapsed rather than access being denied.
+const EXPIRY_MARKERS: &[&str] = &[
+    "expired",
+    "invalid_token",
+    "unauthenticated",
+    "not authenticated",
+    "login required",
+];
+/// Path segments of login pages a lapsed session is redirected to.
+const LOGIN_SEGMENTS: &[&str] = &["login", "signin", "sign-in", "sso", "authorize"];
+
+/// Access token plus the information needed to refresh it.
+#[derive(Debug, Clone)]
+struct TokenSet {
+    access_token: String,
+    refresh_token: Option<String>,
+    refresh_url: Option<String>,
+    issued_at_ms: u64,
+    expires_at_ms: u64,
+}
+
+impl TokenSet {
+    fn can_refresh(&self) -> bool {
+        self.refresh_token.is_some() && self.refresh_url.is_some()
+    }
+}
+
+/// Shared access-token store used by the HTTP proxy, WS handshakes and the refresh scheduler.
+pub struct AuthManager {
+    app: AppHandle,
+    tokens: RwLock<Option<TokenSet>>,
+    changed: Notify,
+    /// Wakes every request waiting for new tokens during a re-login.
+    updated: Notify,
+    /// Held while re-authenticating so only one refresh or re-login runs at a time.
+    reauth: tokio::sync::Mutex<()>,
+    reauthenticating: AtomicBool,
+}
+
+impl AuthManager {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            tokens: RwLock::new(None),
+            changed: Notify::new(),
+            updated: Notify::new(),
+            reauth: tokio::sync::Mutex::new(()),
+            reauthenticating: AtomicBool::new(false),
+        }
+    }
+
+    /// `Authorization` header value for the current access token, if any.
+    pub fn authorization_header(&self) -> Option<String> {
+        let tokens = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        tokens
+            .as_ref()
+            .map(|tokens| format!("Bearer {}", tokens.access_token))
+    }
+
+    fn snapshot(&self) -> Option<TokenSet> {
+        self.tokens
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn replace(&self, tokens: Option<TokenSet>) {
+        *self.tokens.write().unwrap_or_else(|e| e.into_inner()) = tokens;
+        self.changed.notify_one();
+        self.updated.notify_waiters();
+    }
+
+    /// Hold a request back while a re-authentication is in progress.
+    pub async fn wait_for_reauth(&self) {
+        if self.reauthenticating.load(Ordering::Acquire) {
+            drop(self.reauth.lock().await);
+        }
+    }
+
+    /// Recover from an expired session that a request sent with `failed` ran into.
+    ///
+    /// Refreshes the token when possible; otherwise emits `auth-required` so the web
+    /// client runs its login flow, and waits for it to call `set_auth_tokens`.
+    /// Concurrent callers share one attempt. Returns whether there are new tokens
+    /// to replay the request with.
+    pub async fn reauthenticate(&self, failed: Option<&str>) -> bool {
+        let _guard = self.reauth.lock().await;
+        // Another request already recovered while this one waited
+        if let Some(current) = self.authorization_header() {
+            if Some(current.as_str()) != failed {
+                return true;
+            }
+        }
+        self.reauthenticating.store(true, Ordering::Release);
+        let recovered = self.recover().await;
+        self.reauthenticating.store(false, Ordering::Release);
+        recovered
+    }
+
+    async fn recover(&self) -> bool {
+        if let Some(tokens) = self.snapshot().filter(TokenSet::can_refresh) {
+            match refresh_tokens(&self.app, &tokens).await {
+                Ok(refreshed) => {
+                    log::info!(target: "auth", "Access token refreshed after session expiry");
+                    let expires_at_ms = refreshed.expires_at_ms;
+                    self.replace(Some(refreshed));
+                    let _ = self.app.emit(
+                        "auth-refreshed",
+                        serde_json::json!({ "expires_at": expires_at_ms }),
+                    );
+                    return true;
+                }
+                Err(e) => log::warn!(target: "auth", "Refresh after session expiry failed: {}", e),
+            }
+        }
+
+        let updated = self.updated.notified();
+        tokio::pin!(updated);
+        updated.as_mut().enable();
+        log::info!(target: "auth", "Session expired, asking the web client to log in again");
+        let _ = self.app.emit("auth-required", serde_json::json!({}));
+        let relogin = tokio::time::timeout(Duration::from_secs(RELOGIN_TIMEOUT_SECS), updated);
+        relogin.await.is_ok() && self.authorization_header().is_some()
+    }
+}
+
+fn is_login_path(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        LOGIN_SEGMENTS
+            .iter()
+            .any(|login| segment.eq_ignore_ascii_case(login))
+    })
+}
+
+/// Whether a backend response means the session expired: a 419, a redirect to a
+/// login page, or a 401 whose body or `WWW-Authenticate` mentions expiry.
+pub fn is_session_expired(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    request_path: &str,
+    final_url: &reqwest::Url,
+    body: &[u8],
+) -> bool {
+    if status.as_u16() == 419 {
+        return true;
+    }
+    if status.is_redirection() {
+        let location = headers
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|location| final_url.join(location).ok());
+        return location.is_some_and(|location| is_login_path(location.path()));
+    }
+    if !is_login_path(request_path) && is_login_path(final_url.path()) {
+        return true;
+    }
+    if status != reqwest::StatusCode::UNAUTHORIZED {
+        return false;
+    }
+    let challenge = headers
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let body = String::from_utf8_lossy(&body[..body.len().min(EXPIRY_BODY_SCAN_BYTES)])
+        .to_ascii_lowercase();
+    EXPIRY_MARKERS
+        .iter()
+        .any(|marker| challenge.contains(marker) || body.contains(marker))
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshResponse {
+    #[serde(alias = "accessToken")]
+    access_token: String,
+    #[serde(default, alias = "refreshToken")]
+    refresh_token: Option<String>,
+    #[serde(default, alias = "expiresIn")]
+    expires_in: Option<u64>,
+}
+
+/// Expiry of a token issued at `issued_at_ms` that lives for `expires_in` seconds,
+/// saturating so a huge lifetime cannot overflow.
+fn expires_at(issued_at_ms: u64, expires_in: u64) -> u64 {
+    issued_at_ms.saturating_add(expires_in.saturating_mul(1000))
+}
+
+/// When a token issued at `issued_at_ms` and expiring at `expires_at_ms` should be refreshed.
+fn refresh_due_at(issued_at_ms: u64, expires_at_ms: u64) -> u64 {
+    let lifetime = expires_at_ms.saturating_sub(issued_at_ms);
+    let lead = (REFRESH_LEAD_SECS * 1000).min(lifetime / 2);
+    expires_at_ms.saturating_sub(lead)
+}
+
+/// The refresh endpoint as a path relative to the backend URL. An absolute URL is
+/// accepted only on the backend's origin, under its path, so page content cannot
+/// send the refresh token to another host.
+fn backend_refresh_path(backend_url: &str, refresh_url: &str) -> Result<String, String> {
+    let refresh_url = refresh_url.trim();
+    if !refresh_url.contains("://") {
+        return Ok(refresh_url.to_string());
+    }
+    let url =
+        reqwest::Url::parse(refresh_url).map_err(|e| format!("Invalid refresh URL: {}", e))?;
+    let backend = reqwest::Url::parse(backend_url).map_err(|e| e.to_string())?;
+    if url.origin() != backend.origin() {
+        return Err(format!(
+            "Refresh URL must be on the backend origin {}",
+            backend.origin().ascii_serialization()
+        ));
+    }
+    let base = backend.path().trim_end_matches('/');
+    let path = url
+        .path()
+        .strip_prefix(base)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .ok_or_else(|| format!("Refresh URL must be under the backend URL {}", backend_url))?;
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    })
+}
+
+/// Resolve a refresh path from `backend_refresh_path` against the backend URL.
+fn resolve_refresh_url(backend_url: &str, refresh_path: &str) -> String {
+    format!(
+        "{}/{}",
+        backend_url.trim_end_matches('/'),
+        refresh_path.trim_start_matches('/')
+    )
+}
+
+async fn refresh_tokens(app: &AppHandle, tokens: &TokenSet) -> Result<TokenSet, String> {
+    let (Some(refresh_token), Some(refresh_url)) = (&tokens.refresh_token, &tokens.refresh_url)
+    else {
+        return Err("No refresh token available".to_string());
+    };
+
+    let state: State<'_, AppState> = app.state();
+    let (builder, backend) = {
+        let settings = state.settings.lock().await;
+        (
+            crate::app_client_builder(&settings, &state),
+            BackendHost::from_settings(&settings),
+        )
+    };
+
+    let client = builder
+        .timeout(Duration::from_secs(REFRESH_REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let url = resolve_refresh_url(&backend.url, refresh_url);
+    let mut request = client
+        .post(&url)
+        .json(&serde_json::json!({ "refresh_token": refresh_token }));
+    if let Some(host) = backend.host_header {
+        request = request.header("host", host);
+    }
+    let resp = request.send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Refresh failed with status {}", resp.status()));
+    }
+    let body: RefreshResponse = resp.json().await.map_err(|e| e.to_string())?;
+
+    let issued_at_ms = now_ms();
+    let expires_at_ms = match body.expires_in {
+        Some(expires_in) => expires_at(issued_at_ms, expires_in),
+        None => {
+            issued_at_ms.saturating_add(tokens.expires_at_ms.saturating_sub(tokens.issued_at_ms))
+        }
+    };
+    Ok(TokenSet {
+        access_token: body.access_token,
+        refresh_token: body.refresh_token.or_else(|| tokens.refresh_token.clone()),
+        refresh_url: tokens.refresh_url.clone(),
+        issued_at_ms,
+        expires_at_ms,
+    })
+}
+
+/// Track access-token expiry and refresh shortly before it lapses.
+///
+/// Emits `auth-refreshed` after each successful rotation and `auth-expired`
+/// when the token expires without a successful refresh.
+pub async fn run_refresh_scheduler(app: AppHandle, auth: Arc<AuthManager>) {
+    let mut retry_at_ms: Option<u64> = None;
+
+    loop {
+        let Some(tokens) = auth.snapshot() else {
+            retry_at_ms = None;
+            auth.changed.notified().await;
+            continue;
+        };
+
+        let now = now_ms();
+        let wake_at = if tokens.can_refresh() {
+            retry_at_ms
+                .unwrap_or_else(|| refresh_due_at(tokens.issued_at_ms, tokens.expires_at_ms))
+                .min(tokens.expires_at_ms)
+        } else {
+            tokens.expires_at_ms
+        };
+
+        if wake_at > now {
+            tokio::select! {
+                _ = auth.changed.notified() => {
+                    retry_at_ms = None;
+                    continue;
+                }
+                _ = tokio::time::sleep(Duration::from_millis(wake_at - now)) => {}
+            }
+        }
+
+        if now_ms() >= tokens.expires_at_ms {
+            log::info!(target: "auth", "Access token expired");
+            retry_at_ms = None;
+            auth.replace(None);
+            let _ = app.emit(
+                "auth-expired",
+                serde_json::json!({ "expires_at": tokens.expires_at_ms }),
+            );
+            continue;
+        }
+
+        match refresh_tokens(&app, &tokens).await {
+            Ok(refreshed) => {
+                log::info!(target: "auth", "Access token refreshed");
+                retry_at_ms = None;
+                let expires_at_ms = refreshed.expires_at_ms;
+                auth.replace(Some(refreshed));
+                let _ = app.emit(
+                    "auth-refreshed",
+                    serde_json::json!({ "expires_at": expires_at_ms }),
+                );
+            }
+            Err(e) => {
+                log::error!(target: "auth", "Token refresh failed: {}", e);
+                retry_at_ms = Some(now_ms() + REFRESH_RETRY_SECS * 1000);
+            }
+        }
+    }
+}
+
+/// Set the access token used by the proxies, with optional refresh details.
+/// `refresh_url` is a backend path, or an absolute URL on the backend's origin.
+#[tauri::command]
+pub async fn set_auth_tokens(
+    access_token: String,
+    expires_in: u64,
+    refresh_token: Option<String>,
+    refresh_url: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if access_token.trim().is_empty() {
+        return Err("Access token must not be empty".to_string());
+    }
+    let refresh_url = match refresh_url.filter(|url| !url.trim().is_empty()) {
+        Some(url) => {
+            let backend_url = state.settings.lock().await.backend_url.clone();
+            Some(backend_refresh_path(&backend_url, &url)?)
+        }
+        None => None,
+    };
+    let issued_at_ms = now_ms();
+    state.auth.replace(Some(TokenSet {
+        access_token,
+        refresh_token: refresh_token.filter(|token| !token.is_empty()),
+        refresh_url,
+        issued_at_ms,
+        expires_at_ms: expires_at(issued_at_ms, expires_in),
+    }));
+    Ok(())
+}
+
+/// Forget the current access token.
+#[tauri::command]
+pub async fn clear_auth_tokens(state: State<'_, AppState>) -> Result<(), String> {
+    state.auth.replace(None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backend_refresh_path, expires_at, is_session_expired, refresh_due_at, resolve_refresh_url,
+    };
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Url};
+
+    #[test]
+    fn refreshes_one_minute_before_expiry_for_long_lived_tokens() {
+        assert_eq!(refresh_due_at(0, 3_600_000), 3_540_000);
+    }
+
+    #[test]
+    fn refreshes_at_half_lifetime_for_short_lived_tokens() {
+        assert_eq!(refresh_due_at(1_000, 61_000), 31_000);
+    }
+
+    #[test]
+    fn saturates_huge_token_lifetimes() {
+        assert_eq!(expires_at(1_000, 60), 61_000);
+        assert_eq!(expires_at(1_000, u64::MAX), u64::MAX);
+        assert_eq!(
+            refresh_due_at(1_000, expires_at(1_000, u64::MAX)),
+            u64::MAX - 60_000
+        );
+    }
+
+    #[test]
+    fn resolves_relative_refresh_url_against_backend() {
+        assert_eq!(
+            resolve_refresh_url("https://assistant/", "/api/auth/refresh"),
+            "https://assistant/api/auth/refresh"
+        );
+    }
+
+    #[test]
+    fn accepts_refresh_urls_only_on_the_backend_origin() {
+        let backend = "https://assistant:8443/app";
+        assert_eq!(
+            backend_refresh_path(backend, "/api/auth/refresh").as_deref(),
+            Ok("/api/auth/refresh")
+        );
+        assert_eq!(
+            backend_refresh_path(backend, "https://assistant:8443/app/api/refresh?v=2").as_deref(),
+            Ok("/api/refresh?v=2")
+        );
+        assert!(backend_refresh_path(backend, "https://assistant:8443/other/refresh").is_err());
+        assert!(backend_refresh_path(backend, "https://auth.example/token").is_err());
+        assert!(backend_refresh_path(backend, "http://assistant:8443/api/refresh").is_err());
+        assert!(backend_refresh_path(backend, "https://assistant/api/refresh").is_err());
+    }
+
+    #[test]
+    fn detects_session_expiry() {
+        let api = Url::parse("https://assistant/api/sessions").unwrap();
+        let none = HeaderMap::new();
+        let expired = |status: u16, headers: &HeaderMap, url: &Url, body: &str| {
+            let status = StatusCode::from_u16(status).unwrap();
+            is_session_expired(status, headers, "/api/sessions", url, body.as_bytes())
+        };
+
+        assert!(expired(419, &none, &api, ""));
+        assert!(expired(401, &none, &api, r#"{"error":"Token expired"}"#));
+        assert!(!expired(401, &none, &api, r#"{"error":"Forbidden"}"#));
+        assert!(!expired(200, &none, &api, "{}"));
+
+        let login = Url::parse("https://assistant/auth/login?next=/api/sessions").unwrap();
+        assert!(expired(200, &none, &login, "<html>Sign in</html>"));
+
+        let mut redirect = HeaderMap::new();
+        redirect.insert("location", HeaderValue::from_static("/sso/start"));
+        assert!(expired(302, &redirect, &api, ""));
+    }
+}
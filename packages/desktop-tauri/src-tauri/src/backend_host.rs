@@ -0,0 +1,155 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::AppSettings;
+
+/// How the proxies reach a backend addressed by IP that expects a virtual host
+/// name, e.g. connecting to `https://10.0.0.5` while presenting
+/// `Host: assistant.internal`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendHost {
+    /// Backend URL with the TLS server name in place of the IP address; requests
+    /// are built against this so the name is used for SNI and certificate checks.
+    pub url: String,
+    /// Server name pinned to the configured IP address and port.
+    pub pin: Option<(String, SocketAddr)>,
+    /// `Host` header to send when it differs from the server name in `url`.
+    pub host_header: Option<String>,
+}
+
+impl BackendHost {
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        resolve(
+            &settings.backend_url,
+            settings.host_header_override.as_deref(),
+            settings.tls_server_name_override.as_deref(),
+        )
+    }
+}
+
+/// The IP address a URL names as its host, if it does.
+fn host_ip(url: &reqwest::Url) -> Option<IpAddr> {
+    url.host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+fn check_name(name: &str, setting: &str) -> Result<(), String> {
+    if name.is_empty()
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '/' | '?' | '#' | '@'))
+    {
+        return Err(format!("Invalid {}: {}", setting, name));
+    }
+    Ok(())
+}
+
+/// Check the overrides against the backend URL before they are saved. They only
+/// make sense when the backend URL names an IP address.
+pub fn validate(
+    backend_url: &str,
+    host_header: Option<&str>,
+    tls_server_name: Option<&str>,
+) -> Result<(), String> {
+    if let Some(host) = host_header {
+        check_name(host, "host header override")?;
+    }
+    if let Some(name) = tls_server_name {
+        check_name(name, "TLS server name override")?;
+        if name.contains(':') {
+            return Err(format!("TLS server name must not include a port: {}", name));
+        }
+    }
+    if host_header.is_none() && tls_server_name.is_none() {
+        return Ok(());
+    }
+    let url = reqwest::Url::parse(backend_url).map_err(|e| e.to_string())?;
+    match host_ip(&url) {
+        Some(_) => Ok(()),
+        None => Err(
+            "Host and TLS server name overrides need a backend URL with an IP address".to_string(),
+        ),
+    }
+}
+
+/// Work out how to address the backend. The TLS server name defaults to the
+/// host header override (without its port), so a single override is enough for
+/// the common case.
+pub fn resolve(
+    backend_url: &str,
+    host_header: Option<&str>,
+    tls_server_name: Option<&str>,
+) -> BackendHost {
+    let direct = BackendHost {
+        url: backend_url.to_string(),
+        pin: None,
+        host_header: None,
+    };
+    let Some(server_name) = tls_server_name
+        .or_else(|| host_header.map(|host| host.split(':').next().unwrap_or(host)))
+        .map(str::to_string)
+    else {
+        return direct;
+    };
+    let Ok(mut url) = reqwest::Url::parse(backend_url) else {
+        return direct;
+    };
+    let Some(ip) = host_ip(&url) else {
+        return direct;
+    };
+    let Some(port) = url.port_or_known_default() else {
+        return direct;
+    };
+    if url.set_host(Some(&server_name)).is_err() {
+        return direct;
+    }
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", server_name, port),
+        None => server_name.clone(),
+    };
+    BackendHost {
+        url: url.as_str().trim_end_matches('/').to_string(),
+        pin: Some((server_name, SocketAddr::new(ip, port))),
+        host_header: host_header
+            .filter(|host| *host != authority)
+            .map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, validate};
+
+    #[test]
+    fn pins_the_virtual_host_to_the_ip() {
+        let host = resolve("https://10.0.0.5/work", Some("assistant.internal"), None);
+        assert_eq!(host.url, "https://assistant.internal/work");
+        assert_eq!(
+            host.pin,
+            Some((
+                "assistant.internal".to_string(),
+                "10.0.0.5:443".parse().unwrap()
+            ))
+        );
+        assert_eq!(host.host_header, None);
+
+        let split = resolve(
+            "http://[::1]:8080",
+            Some("assistant.internal"),
+            Some("edge.example"),
+        );
+        assert_eq!(split.url, "http://edge.example:8080");
+        assert_eq!(split.pin.unwrap().1, "[::1]:8080".parse().unwrap());
+        assert_eq!(split.host_header.as_deref(), Some("assistant.internal"));
+
+        let plain = resolve("https://assistant", None, None);
+        assert_eq!(plain.url, "https://assistant");
+        assert!(plain.pin.is_none());
+
+        assert!(validate("https://10.0.0.5", Some("assistant.internal"), None).is_ok());
+        assert!(validate("https://assistant", Some("assistant.internal"), None).is_err());
+        assert!(validate("https://10.0.0.5", None, Some("a b")).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Shortest initial delay accepted, so a backend that is down is not hammered.
+const MIN_DELAY_MS: u64 = 100;
+
+/// How reconnects back off: opening the backend WebSocket, resuming event
+/// streams, and probing a backend the circuit breaker marked down.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectBackoff {
+    /// Delay before the first retry.
+    pub initial_delay_ms: u64,
+    /// Factor applied to the delay after each further failure.
+    pub multiplier: f64,
+    /// Upper bound for a single delay.
+    pub max_delay_ms: u64,
+    /// Attempts in a row before giving up (probes never give up).
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 500,
+            multiplier: 2.0,
+            max_delay_ms: 30_000,
+            max_attempts: 5,
+        }
+    }
+}
+
+impl ReconnectBackoff {
+    /// Check the settings before they are saved.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.multiplier.is_finite() || self.multiplier < 1.0 {
+            return Err("The reconnect multiplier must be at least 1".to_string());
+        }
+        if self.initial_delay_ms < MIN_DELAY_MS {
+            return Err(format!(
+                "The initial reconnect delay must be at least {} ms",
+                MIN_DELAY_MS
+            ));
+        }
+        if self.initial_delay_ms > self.max_delay_ms {
+            return Err("The initial reconnect delay cannot exceed the maximum delay".to_string());
+        }
+        if self.max_attempts == 0 {
+            return Err("At least one reconnect attempt is required".to_string());
+        }
+        Ok(())
+    }
+
+    /// Delay before retry number `retry` (0 for the first).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self.initial_delay_ms as f64 * self.multiplier.powi(retry.min(64) as i32);
+        Duration::from_millis(delay.min(self.max_delay_ms as f64) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReconnectBackoff;
+    use std::time::Duration;
+
+    #[test]
+    fn grows_up_to_the_maximum() {
+        let backoff = ReconnectBackoff {
+            initial_delay_ms: 1000,
+            multiplier: 1.5,
+            max_delay_ms: 3000,
+            max_attempts: 4,
+        };
+        assert!(backoff.validate().is_ok());
+        let delays: Vec<_> = (0..4).map(|retry| backoff.delay(retry)).collect();
+        assert_eq!(delays, [1000, 1500, 2250, 3000].map(Duration::from_millis));
+
+        let shrinking = ReconnectBackoff {
+            multiplier: 0.5,
+            ..ReconnectBackoff::default()
+        };
+        assert!(shrinking.validate().is_err());
+        let hammering = ReconnectBackoff {
+            initial_delay_ms: 0,
+            ..ReconnectBackoff::default()
+        };
+        assert!(hammering.validate().is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+use crate::{logging, mime, AppState};
+
+/// Number of days of usage kept in the history file.
+const BANDWIDTH_HISTORY_DAYS: usize = 30;
+/// Minimum delay between writes of the usage file.
+const BANDWIDTH_SAVE_INTERVAL_SECS: u64 = 60;
+
+/// Bytes sent to and received from the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteCounts {
+    pub sent: u64,
+    pub received: u64,
+}
+
+impl ByteCounts {
+    fn add(&mut self, sent: u64, received: u64) {
+        self.sent += sent;
+        self.received += received;
+    }
+}
+
+/// Usage returned by `get_bandwidth_usage`; days are keyed by UTC date (`YYYY-MM-DD`).
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthUsage {
+    pub session: ByteCounts,
+    pub today: ByteCounts,
+    pub days: BTreeMap<String, ByteCounts>,
+}
+
+struct MeterState {
+    session: ByteCounts,
+    days: BTreeMap<String, ByteCounts>,
+    last_saved: Instant,
+    dirty: bool,
+}
+
+/// Counts proxied traffic for the current session and per day (persisted next to the settings file).
+pub struct BandwidthMeter {
+    path: PathBuf,
+    state: Mutex<MeterState>,
+}
+
+fn today() -> String {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    logging::format_timestamp(now_ms)[..10].to_string()
+}
+
+impl BandwidthMeter {
+    pub fn load(path: PathBuf) -> Self {
+        let days = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            state: Mutex::new(MeterState {
+                session: ByteCounts::default(),
+                days,
+                last_saved: Instant::now(),
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Add proxied traffic, persisting the daily totals at most once a minute.
+    pub fn record(&self, sent: u64, received: u64) {
+        if sent == 0 && received == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.session.add(sent, received);
+        state.days.entry(today()).or_default().add(sent, received);
+        while state.days.len() > BANDWIDTH_HISTORY_DAYS {
+            state.days.pop_first();
+        }
+        state.dirty = true;
+        if state.last_saved.elapsed() >= Duration::from_secs(BANDWIDTH_SAVE_INTERVAL_SECS) {
+            self.save_locked(&mut state);
+        }
+    }
+
+    fn save_locked(&self, state: &mut MeterState) {
+        state.last_saved = Instant::now();
+        state.dirty = false;
+        let result = serde_json::to_string_pretty(&state.days)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&self.path, data).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::error!(target: "bandwidth", "Failed to save usage: {}", e);
+        }
+    }
+
+    fn usage(&self) -> BandwidthUsage {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.dirty {
+            self.save_locked(&mut state);
+        }
+        BandwidthUsage {
+            session: state.session,
+            today: state.days.get(&today()).copied().unwrap_or_default(),
+            days: state.days.clone(),
+        }
+    }
+}
+
+/// Whether a request is a speculative prefetch of media, which data-saver mode blocks.
+pub fn is_media_prefetch(headers: &hyper::HeaderMap, path: &str) -> bool {
+    let is_prefetch = ["sec-purpose", "purpose", "x-moz"].iter().any(|name| {
+        headers
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().contains("prefetch"))
+    });
+    if !is_prefetch {
+        return false;
+    }
+    let is_media_type = |mime: &str| {
+        ["image/", "video/", "audio/"]
+            .iter()
+            .any(|prefix| mime.starts_with(prefix))
+    };
+    let accepts_media = headers
+        .get("accept")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| is_media_type(accept.trim_start()));
+    accepts_media || is_media_type(mime::mime_type_for_path(Path::new(path)))
+}
+
+/// Get proxied traffic for this session, today, and the last 30 days.
+#[tauri::command]
+pub async fn get_bandwidth_usage(state: State<'_, AppState>) -> Result<BandwidthUsage, String> {
+    Ok(state.bandwidth.usage())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_media_prefetch;
+
+    #[test]
+    fn detects_media_prefetches() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(!is_media_prefetch(&headers, "/api/files/photo.png"));
+
+        headers.insert("sec-purpose", "prefetch".parse().unwrap());
+        assert!(is_media_prefetch(&headers, "/api/files/photo.png"));
+        assert!(!is_media_prefetch(&headers, "/api/sessions"));
+
+        headers.insert("accept", "video/webm,*/*".parse().unwrap());
+        assert!(is_media_prefetch(&headers, "/api/attachments/1"));
+    }
+}
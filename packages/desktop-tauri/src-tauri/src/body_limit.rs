@@ -0,0 +1,128 @@
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::body::{Body, Bytes};
+use hyper::{Response, StatusCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Emitter};
+
+use crate::{full_body, ProxyBody};
+
+/// Why a request body could not be read.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The body is larger than the configured limit.
+    TooLarge,
+    Failed(String),
+}
+
+/// Whether `len` bytes exceed a limit of `max_bytes` (0 for unlimited).
+pub fn exceeds(max_bytes: u64, len: u64) -> bool {
+    max_bytes > 0 && len > max_bytes
+}
+
+/// Buffer a request body, giving up as soon as it grows past `max_bytes`
+/// (0 for unlimited) rather than after it is fully in memory.
+pub async fn collect<B>(body: B, max_bytes: u64) -> Result<Bytes, ReadError>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let max = match max_bytes {
+        0 => usize::MAX,
+        max => usize::try_from(max).unwrap_or(usize::MAX),
+    };
+    match Limited::new(body, max).collect().await {
+        Ok(collected) => Ok(collected.to_bytes()),
+        Err(e) if e.downcast_ref::<LengthLimitError>().is_some() => Err(ReadError::TooLarge),
+        Err(e) => Err(ReadError::Failed(e.to_string())),
+    }
+}
+
+/// Cap on the size of request bodies accepted by the HTTP proxy, so a runaway
+/// upload cannot exhaust memory or tie up the backend.
+pub struct BodyLimit {
+    app: AppHandle,
+    /// Largest accepted body in bytes (0 for unlimited).
+    max_bytes: AtomicU64,
+}
+
+impl BodyLimit {
+    pub fn new(app: AppHandle, max_bytes: u64) -> Self {
+        Self {
+            app,
+            max_bytes: AtomicU64::new(max_bytes),
+        }
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_bytes(&self, max_bytes: u64) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+    }
+
+    /// Answer 413 for a body over the limit and report it to the web client
+    /// with a `request-body-too-large` event. `received` is the declared length
+    /// or the bytes read before the limit was hit.
+    pub fn reject(
+        &self,
+        method: &hyper::Method,
+        path: &str,
+        received: Option<u64>,
+    ) -> Response<ProxyBody> {
+        let max_bytes = self.max_bytes();
+        log::warn!(
+            target: "proxy",
+            "Rejected {} {}: request body over the {}-byte limit",
+            method,
+            path,
+            max_bytes
+        );
+        let _ = self.app.emit(
+            "request-body-too-large",
+            serde_json::json!({
+                "method": method.as_str(),
+                "path": path,
+                "max_bytes": max_bytes,
+                "received_bytes": received,
+            }),
+        );
+        Response::builder()
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .header("content-type", "application/json")
+            .body(full_body(
+                serde_json::json!({
+                    "error": "request_body_too_large",
+                    "message": format!(
+                        "Request body exceeds the desktop proxy limit of {} bytes",
+                        max_bytes
+                    ),
+                    "max_bytes": max_bytes,
+                })
+                .to_string(),
+            ))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect, exceeds, ReadError};
+    use http_body_util::Full;
+    use hyper::body::Bytes;
+
+    #[tokio::test]
+    async fn stops_reading_past_the_limit() {
+        let body = || Full::new(Bytes::from(vec![0u8; 1024]));
+        assert_eq!(collect(body(), 1024).await.unwrap().len(), 1024);
+        assert_eq!(collect(body(), 0).await.unwrap().len(), 1024);
+        assert!(matches!(
+            collect(body(), 1023).await,
+            Err(ReadError::TooLarge)
+        ));
+
+        assert!(exceeds(10, 11));
+        assert!(!exceeds(10, 10));
+        assert!(!exceeds(0, u64::MAX));
+    }
+}
@@ -0,0 +1,81 @@
+/// Part of a resource requested with a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range; send the whole resource.
+    Full,
+    /// Inclusive byte range within the resource.
+    Partial { start: u64, end: u64 },
+    /// The range lies outside the resource (`416 Range Not Satisfiable`).
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Resolve a `Range` header against a resource of `len` bytes. Only single
+    /// byte ranges are served partially; anything else gets the full resource,
+    /// which the spec allows a server to do for any range it does not support.
+    pub fn parse(header: Option<&str>, len: u64) -> Self {
+        let Some(spec) = header.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+            return Self::Full;
+        };
+        if spec.contains(',') {
+            return Self::Full;
+        }
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+        let (start, end) = match (start.trim(), end.trim()) {
+            // Suffix range: the last `n` bytes
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => return Self::Unsatisfiable,
+                Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+                Err(_) => return Self::Full,
+            },
+            (start, "") => match start.parse::<u64>() {
+                Ok(start) => (start, len.saturating_sub(1)),
+                Err(_) => return Self::Full,
+            },
+            (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+                (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+                _ => return Self::Full,
+            },
+        };
+        if len == 0 || start >= len {
+            Self::Unsatisfiable
+        } else {
+            Self::Partial { start, end }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ByteRange;
+
+    #[test]
+    fn resolves_single_byte_ranges() {
+        let partial = |start, end| ByteRange::Partial { start, end };
+        assert_eq!(ByteRange::parse(Some("bytes=0-99"), 1000), partial(0, 99));
+        assert_eq!(
+            ByteRange::parse(Some("bytes=500-"), 1000),
+            partial(500, 999)
+        );
+        assert_eq!(
+            ByteRange::parse(Some("bytes=-100"), 1000),
+            partial(900, 999)
+        );
+        assert_eq!(
+            ByteRange::parse(Some("bytes=900-5000"), 1000),
+            partial(900, 999)
+        );
+        assert_eq!(
+            ByteRange::parse(Some("bytes=1000-"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            ByteRange::parse(Some("bytes=0-1,5-9"), 1000),
+            ByteRange::Full
+        );
+        assert_eq!(ByteRange::parse(Some("items=0-1"), 1000), ByteRange::Full);
+        assert_eq!(ByteRange::parse(None, 1000), ByteRange::Full);
+    }
+}
@@ -0,0 +1,592 @@
+use chrono::{
+    DateTime, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc,
+};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tauri::State;
+
+use crate::tools::LocalTool;
+use crate::{AppState, ClientKey};
+
+/// Consent scope covering the calendar tools.
+pub const CONSENT_SCOPE: &str = "calendar";
+
+const CALDAV_REQUEST_TIMEOUT_SECS: u64 = 20;
+const DEFAULT_LIST_DAYS: i64 = 7;
+const DEFAULT_EVENT_MINUTES: i64 = 60;
+
+/// CalDAV calendar the tools read from and write to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CalendarAccount {
+    /// Calendar collection URL (e.g. `https://dav.example.com/calendars/me/personal/`).
+    url: String,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+/// Calendar account status returned by `get_calendar_account` (without the password).
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarAccountStatus {
+    pub url: String,
+    pub username: Option<String>,
+}
+
+/// An event returned by `calendar_list_events`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub title: String,
+    pub start: String,
+    pub end: Option<String>,
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// The configured CalDAV account, persisted next to the settings file.
+pub struct CalendarStore {
+    account: RwLock<Option<CalendarAccount>>,
+    path: PathBuf,
+    /// CalDAV client, rebuilt when the settings it was built from change.
+    client: Mutex<Option<(ClientKey, reqwest::Client)>>,
+}
+
+impl CalendarStore {
+    pub fn load(path: PathBuf) -> Self {
+        let account = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok());
+        Self {
+            account: RwLock::new(account),
+            path,
+            client: Mutex::new(None),
+        }
+    }
+
+    /// The CalDAV client, with the app's proxy, PAC and DNS settings. The server is
+    /// not the backend, so certificates are always validated.
+    async fn client(&self, state: &AppState) -> Result<reqwest::Client, String> {
+        let settings = state.settings.lock().await.clone();
+        let key = ClientKey::from_settings(&settings);
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((built_for, client)) = client.as_ref() {
+            if *built_for == key {
+                return Ok(client.clone());
+            }
+        }
+        let built = crate::external_client_builder(&settings, state)
+            .timeout(Duration::from_secs(CALDAV_REQUEST_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| e.to_string())?;
+        *client = Some((key, built.clone()));
+        Ok(built)
+    }
+
+    fn account(&self) -> Option<CalendarAccount> {
+        self.account
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set(&self, account: Option<CalendarAccount>) -> Result<(), String> {
+        match &account {
+            Some(account) => {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                let data = serde_json::to_string_pretty(account).map_err(|e| e.to_string())?;
+                fs::write(&self.path, data).map_err(|e| e.to_string())?;
+                // The file holds the CalDAV password
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            None => {
+                if self.path.exists() {
+                    fs::remove_file(&self.path).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        *self.account.write().unwrap_or_else(|e| e.into_inner()) = account;
+        Ok(())
+    }
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    vec![
+        LocalTool {
+            name: "calendar_list_events",
+            description: "List calendar events between two times (defaults to the next 7 days).",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "start": { "type": "string", "description": "RFC 3339 start time" },
+                    "end": { "type": "string", "description": "RFC 3339 end time" }
+                }
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "calendar_create_event",
+            description: "Create a calendar event, optionally with a reminder before it starts.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "start": { "type": "string", "description": "RFC 3339 start time" },
+                    "end": { "type": "string", "description": "RFC 3339 end time (defaults to one hour after start)" },
+                    "location": { "type": "string" },
+                    "description": { "type": "string" },
+                    "reminder_minutes": { "type": "integer", "description": "Alert this many minutes before the start" }
+                },
+                "required": ["title", "start"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+    ]
+}
+
+#[derive(Deserialize)]
+struct ListEventsArgs {
+    #[serde(default)]
+    start: Option<String>,
+    #[serde(default)]
+    end: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreateEventArgs {
+    title: String,
+    start: String,
+    #[serde(default)]
+    end: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    reminder_minutes: Option<u32>,
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid time {:?}: {}", value, e))
+}
+
+fn ics_utc(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Parse an iCalendar DATE or DATE-TIME value to RFC 3339 (floating times are local).
+fn parse_ics_time(value: &str) -> Option<(String, bool)> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&time).to_rfc3339(), false));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        let local = Local.from_local_datetime(&time).earliest()?;
+        return Some((local.to_rfc3339(), false));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some((date.format("%Y-%m-%d").to_string(), true))
+}
+
+fn escape_ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape_ics_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Extract the VEVENTs of an iCalendar document.
+fn parse_events(ics: &str) -> Vec<CalendarEvent> {
+    // Unfold continuation lines (RFC 5545 §3.1)
+    let unfolded = ics
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut events = Vec::new();
+    let mut current: Option<CalendarEvent> = None;
+    let mut depth = 0;
+    for line in unfolded.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = key
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        match (name.as_str(), value) {
+            ("BEGIN", "VEVENT") if current.is_none() => {
+                current = Some(CalendarEvent {
+                    uid: String::new(),
+                    title: String::new(),
+                    start: String::new(),
+                    end: None,
+                    all_day: false,
+                    location: None,
+                    description: None,
+                });
+                depth = 0;
+            }
+            // Nested components (e.g. VALARM) have their own properties
+            ("BEGIN", _) if current.is_some() => depth += 1,
+            ("END", "VEVENT") if depth == 0 => {
+                if let Some(event) = current.take().filter(|event| !event.start.is_empty()) {
+                    events.push(event);
+                }
+            }
+            ("END", _) if current.is_some() => depth -= 1,
+            _ => {
+                let Some(event) = current.as_mut().filter(|_| depth == 0) else {
+                    continue;
+                };
+                match name.as_str() {
+                    "UID" => event.uid = value.to_string(),
+                    "SUMMARY" => event.title = unescape_ics_text(value),
+                    "LOCATION" => event.location = Some(unescape_ics_text(value)),
+                    "DESCRIPTION" => event.description = Some(unescape_ics_text(value)),
+                    "DTSTART" => {
+                        if let Some((start, all_day)) = parse_ics_time(value) {
+                            event.start = start;
+                            event.all_day = all_day;
+                        }
+                    }
+                    "DTEND" => event.end = parse_ics_time(value).map(|(end, _)| end),
+                    _ => {}
+                }
+            }
+        }
+    }
+    events
+}
+
+/// Collect the `calendar-data` payloads of a CalDAV multistatus response.
+fn calendar_data(xml: &str) -> Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut payloads = Vec::new();
+    let mut current: Option<String> = None;
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(start) if start.local_name().as_ref() == b"calendar-data" => {
+                current = Some(String::new());
+            }
+            Event::Text(text) => {
+                if let Some(data) = current.as_mut() {
+                    data.push_str(&text.unescape().map_err(|e| e.to_string())?);
+                }
+            }
+            Event::CData(cdata) => {
+                if let Some(data) = current.as_mut() {
+                    data.push_str(&String::from_utf8_lossy(&cdata));
+                }
+            }
+            Event::End(end) if end.local_name().as_ref() == b"calendar-data" => {
+                payloads.extend(current.take());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(payloads)
+}
+
+fn event_ics(uid: &str, args: &CreateEventArgs) -> Result<String, String> {
+    let start = parse_time(&args.start)?;
+    let end = match &args.end {
+        Some(end) => parse_time(end)?,
+        None => start + ChronoDuration::minutes(DEFAULT_EVENT_MINUTES),
+    };
+    if end < start {
+        return Err("Event end is before its start".to_string());
+    }
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Assistant Desktop//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", ics_utc(&Utc::now())),
+        format!("DTSTART:{}", ics_utc(&start)),
+        format!("DTEND:{}", ics_utc(&end)),
+        format!("SUMMARY:{}", escape_ics_text(&args.title)),
+    ];
+    if let Some(location) = &args.location {
+        lines.push(format!("LOCATION:{}", escape_ics_text(location)));
+    }
+    if let Some(description) = &args.description {
+        lines.push(format!("DESCRIPTION:{}", escape_ics_text(description)));
+    }
+    if let Some(minutes) = args.reminder_minutes {
+        lines.extend([
+            "BEGIN:VALARM".to_string(),
+            "ACTION:DISPLAY".to_string(),
+            format!("DESCRIPTION:{}", escape_ics_text(&args.title)),
+            format!("TRIGGER:-PT{}M", minutes),
+            "END:VALARM".to_string(),
+        ]);
+    }
+    lines.extend(["END:VEVENT".to_string(), "END:VCALENDAR".to_string()]);
+    Ok(lines.join("\r\n") + "\r\n")
+}
+
+/// Whether `account` would send its password in the clear.
+fn sends_password_in_clear(account: &CalendarAccount) -> bool {
+    account.password.is_some()
+        && reqwest::Url::parse(&account.url).map_or(true, |url| url.scheme() != "https")
+}
+
+fn caldav_request(
+    client: &reqwest::Client,
+    account: &CalendarAccount,
+    method: reqwest::Method,
+    url: &str,
+) -> Result<reqwest::RequestBuilder, String> {
+    if sends_password_in_clear(account) {
+        return Err("Calendar passwords are only sent over https".to_string());
+    }
+    let mut request = client.request(method, url);
+    if let Some(username) = &account.username {
+        request = request.basic_auth(username, account.password.as_deref());
+    }
+    Ok(request)
+}
+
+async fn list_events(
+    client: &reqwest::Client,
+    account: &CalendarAccount,
+    args: ListEventsArgs,
+) -> Result<Vec<CalendarEvent>, String> {
+    let start = match &args.start {
+        Some(start) => parse_time(start)?,
+        None => Utc::now(),
+    };
+    let end = match &args.end {
+        Some(end) => parse_time(end)?,
+        None => start + ChronoDuration::days(DEFAULT_LIST_DAYS),
+    };
+    let range = format!(r#"start="{}" end="{}""#, ics_utc(&start), ics_utc(&end));
+    // Ask the server to expand recurring events into the requested range
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data><C:expand {range}/></C:calendar-data></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR"><C:comp-filter name="VEVENT">
+    <C:time-range {range}/>
+  </C:comp-filter></C:comp-filter></C:filter>
+</C:calendar-query>"#
+    );
+
+    let method = reqwest::Method::from_bytes(b"REPORT").map_err(|e| e.to_string())?;
+    let response = caldav_request(client, account, method, &account.url)?
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Calendar server returned {}", response.status()));
+    }
+    let xml = response.text().await.map_err(|e| e.to_string())?;
+
+    let mut events: Vec<CalendarEvent> = calendar_data(&xml)?
+        .iter()
+        .flat_map(|ics| parse_events(ics))
+        .collect();
+    events.sort_by(|a, b| a.start.cmp(&b.start));
+    Ok(events)
+}
+
+async fn create_event(
+    client: &reqwest::Client,
+    account: &CalendarAccount,
+    args: CreateEventArgs,
+) -> Result<Value, String> {
+    let uid = format!("{:032x}@assistant-desktop", rand::random::<u128>());
+    let ics = event_ics(&uid, &args)?;
+    let url = format!(
+        "{}/{}.ics",
+        account.url.trim_end_matches('/'),
+        uid.split('@').next().unwrap_or_default()
+    );
+    let response = caldav_request(client, account, reqwest::Method::PUT, &url)?
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .header("If-None-Match", "*")
+        .body(ics)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Calendar server returned {}", response.status()));
+    }
+    Ok(json!({ "uid": uid, "title": args.title, "start": args.start }))
+}
+
+/// Run a calendar tool.
+pub async fn call(state: &AppState, name: &str, arguments: Value) -> Result<Value, String> {
+    let account = state
+        .calendar
+        .account()
+        .ok_or_else(|| "No calendar account is configured".to_string())?;
+    let client = state.calendar.client(state).await?;
+    let arguments = if arguments.is_null() {
+        json!({})
+    } else {
+        arguments
+    };
+    match name {
+        "calendar_list_events" => {
+            let args = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let events = list_events(&client, &account, args).await?;
+            serde_json::to_value(events).map_err(|e| e.to_string())
+        }
+        "calendar_create_event" => {
+            let args = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            create_event(&client, &account, args).await
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+/// Configure the CalDAV calendar used by the calendar tools. A password is only
+/// accepted for an `https` calendar, since Basic auth sends it in the clear.
+#[tauri::command]
+pub async fn set_calendar_account(
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| e.to_string())?;
+    if !matches!(parsed.scheme(), "https" | "http") {
+        return Err("Calendar URL must be http(s)".to_string());
+    }
+    let account = CalendarAccount {
+        url: url.trim().to_string(),
+        username: username.filter(|name| !name.is_empty()),
+        password,
+    };
+    if sends_password_in_clear(&account) {
+        return Err("A calendar password requires an https URL".to_string());
+    }
+    state.calendar.set(Some(account))
+}
+
+/// Forget the configured calendar account.
+#[tauri::command]
+pub async fn clear_calendar_account(state: State<'_, AppState>) -> Result<(), String> {
+    state.calendar.set(None)
+}
+
+/// Get the configured calendar account, if any (the password is never returned).
+#[tauri::command]
+pub async fn get_calendar_account(
+    state: State<'_, AppState>,
+) -> Result<Option<CalendarAccountStatus>, String> {
+    Ok(state
+        .calendar
+        .account()
+        .map(|account| CalendarAccountStatus {
+            url: account.url,
+            username: account.username,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{calendar_data, parse_events, sends_password_in_clear, CalendarAccount};
+
+    #[test]
+    fn parses_events_from_caldav_report() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response><d:propstat><d:prop><cal:calendar-data>BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:1
+SUMMARY:Dentist\, downtown
+DTSTART:20261020T140000Z
+DTEND:20261020T150000Z
+BEGIN:VALARM
+DESCRIPTION:ignored
+END:VALARM
+END:VEVENT
+BEGIN:VEVENT
+UID:2
+SUMMARY:Holiday &amp; trip
+DTSTART;VALUE=DATE:20261024
+END:VEVENT
+END:VCALENDAR
+</cal:calendar-data></d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+        let events: Vec<_> = calendar_data(xml)
+            .unwrap()
+            .iter()
+            .flat_map(|ics| parse_events(ics))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].title, "Dentist, downtown");
+        assert_eq!(events[0].start, "2026-10-20T14:00:00+00:00");
+        assert_eq!(events[0].description, None);
+        assert_eq!(events[1].title, "Holiday & trip");
+        assert_eq!(events[1].start, "2026-10-24");
+        assert!(events[1].all_day);
+    }
+
+    #[test]
+    fn refuses_passwords_over_plain_http() {
+        let account = |url: &str, password: Option<&str>| CalendarAccount {
+            url: url.to_string(),
+            username: Some("me".to_string()),
+            password: password.map(str::to_string),
+        };
+        assert!(sends_password_in_clear(&account(
+            "http://dav.example/cal/",
+            Some("pw")
+        )));
+        assert!(!sends_password_in_clear(&account(
+            "https://dav.example/cal/",
+            Some("pw")
+        )));
+        assert!(!sends_password_in_clear(&account(
+            "http://dav.example/cal/",
+            None
+        )));
+    }
+}
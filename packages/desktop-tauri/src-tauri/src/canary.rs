@@ -0,0 +1,130 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::AppState;
+
+/// Canary routing state, reported by `get_canary_routing` and `canary-routing`
+/// events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanaryStatus {
+    pub url: Option<String>,
+    /// Share of sessions sent to the canary, 0–100.
+    pub percent: u8,
+    /// Whether this session is routed to the canary.
+    pub active: bool,
+}
+
+/// Whether a session in `bucket` (0–99) goes to a canary taking `percent` of
+/// traffic. Raising the percentage only ever moves sessions onto the canary.
+fn routes_to_canary(bucket: u8, percent: u8) -> bool {
+    bucket < percent
+}
+
+/// Weighted routing between the backend and a canary backend. Each app session
+/// draws a fixed bucket, so all of its requests stick to the same backend.
+pub struct Canary {
+    app: AppHandle,
+    bucket: u8,
+    routing: Mutex<(Option<String>, u8)>,
+}
+
+impl Canary {
+    pub fn new(app: AppHandle, url: Option<String>, percent: u8) -> Self {
+        Self {
+            app,
+            bucket: rand::random::<u8>() % 100,
+            routing: Mutex::new((url, percent.min(100))),
+        }
+    }
+
+    /// The canary URL if this session is routed to it.
+    pub fn url_for_session(&self) -> Option<String> {
+        let routing = self.routing.lock().unwrap_or_else(|e| e.into_inner());
+        routing
+            .0
+            .clone()
+            .filter(|_| routes_to_canary(self.bucket, routing.1))
+    }
+
+    pub fn status(&self) -> CanaryStatus {
+        let routing = self.routing.lock().unwrap_or_else(|e| e.into_inner());
+        CanaryStatus {
+            url: routing.0.clone(),
+            percent: routing.1,
+            active: routing.0.is_some() && routes_to_canary(self.bucket, routing.1),
+        }
+    }
+
+    fn set(&self, url: Option<String>, percent: u8) {
+        let before = self.status();
+        *self.routing.lock().unwrap_or_else(|e| e.into_inner()) = (url, percent);
+        let after = self.status();
+        if after.active != before.active {
+            log::info!(
+                target: "http-proxy",
+                "Session moved to the {} backend",
+                if after.active { "canary" } else { "main" }
+            );
+        }
+        if after != before {
+            let _ = self.app.emit("canary-routing", after);
+        }
+    }
+}
+
+/// Send `percent` of sessions to the canary backend at `url` (omit it to turn
+/// canary routing off). Takes effect for new requests without a restart.
+#[tauri::command]
+pub async fn set_canary_routing(
+    url: Option<String>,
+    percent: u8,
+    state: State<'_, AppState>,
+) -> Result<CanaryStatus, String> {
+    if percent > 100 {
+        return Err("Canary percentage must be between 0 and 100".to_string());
+    }
+    let url = url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    if let Some(url) = &url {
+        let parsed =
+            reqwest::Url::parse(url).map_err(|e| format!("Invalid canary URL {}: {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Canary URL must use http or https: {}", url));
+        }
+    }
+    {
+        let mut settings = state.settings.lock().await;
+        settings.canary_backend_url = url.clone();
+        settings.canary_percent = percent;
+    }
+    state.canary.set(url, percent);
+    state.save().await?;
+    Ok(state.canary.status())
+}
+
+/// Current canary routing and whether this session uses the canary.
+#[tauri::command]
+pub async fn get_canary_routing(state: State<'_, AppState>) -> Result<CanaryStatus, String> {
+    Ok(state.canary.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::routes_to_canary;
+
+    #[test]
+    fn splits_sessions_by_bucket() {
+        let canary = (0..100)
+            .filter(|&bucket| routes_to_canary(bucket, 10))
+            .count();
+        assert_eq!(canary, 10);
+        assert!(!routes_to_canary(0, 0));
+        assert!(routes_to_canary(99, 100));
+        // Sessions on the canary stay there as the percentage grows
+        for bucket in 0..100 {
+            assert!(!routes_to_canary(bucket, 10) || routes_to_canary(bucket, 50));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::{AppState, ProxyState, UpstreamResult};
+
+/// How often the probe loop checks whether the circuit opened.
+const OPEN_CHECK_INTERVAL_SECS: u64 = 1;
+const PROBE_TIMEOUT_SECS: u64 = 5;
+/// Unauthenticated endpoint used to check whether the backend is back.
+const PROBE_PATH: &str = "/api/version";
+
+/// Circuit state reported by `get_backend_circuit` and `backend-circuit` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    pub open: bool,
+    pub consecutive_failures: u32,
+    /// Unix time in milliseconds when the circuit opened, while it is open.
+    pub opened_at: Option<u64>,
+}
+
+#[derive(Default)]
+struct Circuit {
+    failures: u32,
+    /// When the circuit opened, as an instant and as Unix milliseconds.
+    opened: Option<(Instant, u64)>,
+}
+
+impl Circuit {
+    /// Count a failure. Returns `true` if this opened the circuit.
+    fn fail(&mut self, threshold: u32) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        if threshold == 0 || self.failures < threshold || self.opened.is_some() {
+            return false;
+        }
+        let opened_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        self.opened = Some((Instant::now(), opened_at));
+        true
+    }
+
+    /// Reset after a success. Returns `true` if this closed the circuit.
+    fn succeed(&mut self) -> bool {
+        self.failures = 0;
+        self.opened.take().is_some()
+    }
+
+    fn status(&self) -> CircuitStatus {
+        CircuitStatus {
+            open: self.opened.is_some(),
+            consecutive_failures: self.failures,
+            opened_at: self.opened.map(|(_, at)| at),
+        }
+    }
+}
+
+/// Stops sending requests to a backend that keeps failing, so callers get an
+/// immediate 503 instead of waiting out the connect timeout each time. While open,
+/// the backend is probed periodically and the circuit closes once it answers.
+pub struct CircuitBreaker {
+    app: AppHandle,
+    /// Consecutive failures that open the circuit (0 disables it).
+    threshold: AtomicU32,
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(app: AppHandle, threshold: u32) -> Self {
+        Self {
+            app,
+            threshold: AtomicU32::new(threshold),
+            circuit: Mutex::new(Circuit::default()),
+        }
+    }
+
+    pub fn set_threshold(&self, threshold: u32) {
+        self.threshold.store(threshold, Ordering::Relaxed);
+        if threshold == 0 {
+            self.close();
+        }
+    }
+
+    /// Whether requests currently fail fast.
+    pub fn is_open(&self) -> bool {
+        self.circuit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .opened
+            .is_some()
+    }
+
+    /// Count the outcome of a request sent to the backend. Connection failures
+    /// and gateway errors count as failures; any other answer closes the circuit.
+    pub fn record(&self, result: &UpstreamResult) {
+        let failed = match result {
+            Err(crate::UpstreamError::Send(_)) => true,
+            Err(crate::UpstreamError::Body(_)) => return,
+            Ok(response) => matches!(
+                response.status,
+                reqwest::StatusCode::BAD_GATEWAY
+                    | reqwest::StatusCode::SERVICE_UNAVAILABLE
+                    | reqwest::StatusCode::GATEWAY_TIMEOUT
+            ),
+        };
+        if !failed {
+            self.close();
+            return;
+        }
+        let threshold = self.threshold.load(Ordering::Relaxed);
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        if !circuit.fail(threshold) {
+            return;
+        }
+        let status = circuit.status();
+        drop(circuit);
+        log::warn!(
+            target: "http-proxy",
+            "Backend failed {} times in a row, failing fast until it recovers",
+            status.consecutive_failures
+        );
+        let _ = self.app.emit("backend-circuit", status);
+    }
+
+    fn close(&self) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        if !circuit.succeed() {
+            return;
+        }
+        let status = circuit.status();
+        drop(circuit);
+        log::info!(target: "http-proxy", "Backend reachable again, circuit closed");
+        let _ = self.app.emit("backend-circuit", status);
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        self.circuit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .status()
+    }
+}
+
+/// Probe the backend while the circuit is open, backing off per the reconnect
+/// settings, until the proxy that owns the client shuts down.
+pub async fn run_probe(proxy_state: Weak<ProxyState>) {
+    // Probes since the circuit opened
+    let mut probes = 0;
+    loop {
+        let delay = match proxy_state.upgrade() {
+            Some(state) if state.circuit.is_open() => state.reconnect.delay(probes),
+            Some(_) => {
+                probes = 0;
+                Duration::from_secs(OPEN_CHECK_INTERVAL_SECS)
+            }
+            None => break,
+        };
+        tokio::time::sleep(delay).await;
+        let Some(proxy_state) = proxy_state.upgrade() else {
+            break;
+        };
+        if !proxy_state.circuit.is_open() {
+            continue;
+        }
+        probes += 1;
+        let url = format!(
+            "{}{}",
+            proxy_state.backend_base().trim_end_matches('/'),
+            PROBE_PATH
+        );
+        let request = proxy_state.http_client.get(&url);
+        let result = crate::send_upstream(request, Duration::from_secs(PROBE_TIMEOUT_SECS)).await;
+        proxy_state.circuit.record(&result);
+    }
+}
+
+/// Whether the proxy is currently failing fast because the backend is down.
+#[tauri::command]
+pub async fn get_backend_circuit(state: State<'_, AppState>) -> Result<CircuitStatus, String> {
+    Ok(state.circuit.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Circuit;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let mut circuit = Circuit::default();
+        assert!(!circuit.fail(3));
+        assert!(!circuit.fail(3));
+        assert!(circuit.fail(3));
+        assert!(!circuit.fail(3));
+        assert!(circuit.status().open);
+        assert_eq!(circuit.status().consecutive_failures, 4);
+
+        assert!(circuit.succeed());
+        assert!(!circuit.succeed());
+        assert!(!circuit.status().open);
+
+        // A threshold of 0 never opens the circuit
+        for _ in 0..10 {
+            assert!(!circuit.fail(0));
+        }
+    }
+}
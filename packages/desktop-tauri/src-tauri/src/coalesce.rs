@@ -0,0 +1,148 @@
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Request headers that can change the backend's answer to a GET.
+const KEY_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "accept",
+    "accept-encoding",
+    "accept-language",
+];
+
+/// Coalescing key for a request, or `None` if it must be sent on its own: only
+/// body-less GETs without `Range` or a cache bypass are shared.
+pub fn request_key(
+    method: &hyper::Method,
+    url: &str,
+    headers: &hyper::HeaderMap,
+    authorization: Option<&str>,
+    has_body: bool,
+) -> Option<String> {
+    if method != hyper::Method::GET || has_body || headers.contains_key("range") {
+        return None;
+    }
+    let bypass = headers
+        .get_all("cache-control")
+        .iter()
+        .chain(headers.get_all("pragma"))
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| {
+            let value = value.to_ascii_lowercase();
+            value.contains("no-cache") || value.contains("no-store")
+        });
+    if bypass {
+        return None;
+    }
+    let mut key = url.to_string();
+    for name in KEY_HEADERS {
+        let value = match (*name, authorization) {
+            ("authorization", Some(authorization)) => authorization.to_string(),
+            _ => headers
+                .get_all(*name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        key.push('\n');
+        key.push_str(&value);
+    }
+    Some(key)
+}
+
+/// Shares one in-flight fetch between concurrent callers with the same key.
+pub struct Coalescer<T: Clone> {
+    in_flight: Mutex<HashMap<String, Shared<BoxFuture<'static, T>>>>,
+}
+
+/// Removes the leader's entry once it finishes or its request is dropped, so
+/// later callers start a fresh fetch.
+struct LeaderGuard<'a, T: Clone> {
+    coalescer: &'a Coalescer<T>,
+    key: &'a str,
+}
+
+impl<T: Clone> Drop for LeaderGuard<'_, T> {
+    fn drop(&mut self) {
+        self.coalescer
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(self.key);
+    }
+}
+
+impl<T: Clone> Coalescer<T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Await the in-flight fetch for `key`, or start one with `fetch`. Returns the
+    /// result and whether it was shared with an earlier caller.
+    pub async fn run(&self, key: &str, fetch: impl FnOnce() -> BoxFuture<'static, T>) -> (T, bool) {
+        let (shared, leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            match in_flight.get(key) {
+                Some(shared) => (shared.clone(), false),
+                None => {
+                    let shared = fetch().shared();
+                    in_flight.insert(key.to_string(), shared.clone());
+                    (shared, true)
+                }
+            }
+        };
+        let _guard = leader.then(|| LeaderGuard {
+            coalescer: self,
+            key,
+        });
+        (shared.await, !leader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{request_key, Coalescer};
+    use futures_util::FutureExt;
+    use hyper::{HeaderMap, Method};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn shares_concurrent_fetches() {
+        let coalescer = Coalescer::<usize>::new();
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let fetch = || {
+            let fetches = fetches.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                fetches.fetch_add(1, Ordering::SeqCst)
+            }
+            .boxed()
+        };
+
+        let (first, second) = tokio::join!(coalescer.run("a", fetch), coalescer.run("a", fetch));
+        assert_eq!(first, (0, false));
+        assert_eq!(second, (0, true));
+
+        // Finished fetches are not reused
+        assert_eq!(coalescer.run("a", fetch).await, (1, false));
+    }
+
+    #[test]
+    fn only_keys_plain_gets() {
+        let mut headers = HeaderMap::new();
+        let key = |method: &Method, headers: &HeaderMap| {
+            request_key(method, "https://a/api/x", headers, None, false)
+        };
+        assert!(key(&Method::GET, &headers).is_some());
+        assert!(key(&Method::POST, &headers).is_none());
+
+        headers.insert("cache-control", "no-cache".parse().unwrap());
+        assert!(key(&Method::GET, &headers).is_none());
+    }
+}
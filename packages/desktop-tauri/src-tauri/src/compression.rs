@@ -0,0 +1,140 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// Bodies smaller than this are sent to the webview uncompressed.
+const MIN_COMPRESS_BYTES: usize = 1024;
+/// Brotli quality used on the local hop (favours speed over ratio).
+const BROTLI_QUALITY: u32 = 4;
+const BROTLI_WINDOW_BITS: u32 = 22;
+
+/// Content encoding applied by the proxy on the proxy → webview hop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Pick the encoding to use for a webview `Accept-Encoding` header, preferring brotli.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let accepts = |name: &str| {
+        accept_encoding.split(',').any(|entry| {
+            let mut parts = entry.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let rejected = parts.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            coding.eq_ignore_ascii_case(name) && !rejected
+        })
+    };
+    if accepts("br") {
+        Some(Encoding::Brotli)
+    } else if accepts("gzip") {
+        Some(Encoding::Gzip)
+    } else {
+        None
+    }
+}
+
+/// Text-like content types that are worth compressing.
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/x-ndjson"
+                | "image/svg+xml"
+        )
+}
+
+fn compress(body: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Brotli => {
+            let mut writer =
+                brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW_BITS);
+            writer.write_all(body)?;
+            Ok(writer.into_inner())
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Compress an uncompressed, text-like response body for the webview when its
+/// `Accept-Encoding` allows it and the result is smaller.
+pub fn compress_response(
+    body: &[u8],
+    request_headers: &hyper::HeaderMap,
+    response_headers: &reqwest::header::HeaderMap,
+) -> Option<(Encoding, Vec<u8>)> {
+    if body.len() < MIN_COMPRESS_BYTES || response_headers.contains_key("content-encoding") {
+        return None;
+    }
+    let content_type = response_headers.get("content-type")?.to_str().ok()?;
+    if !is_compressible(content_type) {
+        return None;
+    }
+    let encoding = negotiate(request_headers.get("accept-encoding")?.to_str().ok()?)?;
+    let compressed = compress(body, encoding).ok()?;
+    (compressed.len() < body.len()).then_some((encoding, compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_response, negotiate, Encoding};
+    use std::io::Read;
+
+    #[test]
+    fn negotiates_preferred_encoding() {
+        assert_eq!(negotiate("gzip, deflate, br"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("gzip, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("identity"), None);
+    }
+
+    #[test]
+    fn compresses_large_json_responses() {
+        let body = br#"{"message":"hello"}"#.repeat(200);
+        let mut request_headers = hyper::HeaderMap::new();
+        request_headers.insert("accept-encoding", "gzip".parse().unwrap());
+        let mut response_headers = reqwest::header::HeaderMap::new();
+        response_headers.insert("content-type", "application/json".parse().unwrap());
+
+        let (encoding, compressed) =
+            compress_response(&body, &request_headers, &response_headers).unwrap();
+        assert_eq!(encoding, Encoding::Gzip);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        response_headers.insert("content-encoding", "gzip".parse().unwrap());
+        assert!(compress_response(&body, &request_headers, &response_headers).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Longest a request waits in the queue for a free slot before it is rejected.
+const QUEUE_TIMEOUT_SECS: u64 = 30;
+
+/// Why a request was not given a slot.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rejected {
+    /// The queue of waiting requests is full.
+    QueueFull,
+    /// No slot became free within the queue timeout.
+    TimedOut,
+}
+
+/// Caps the number of requests in flight to the backend. Requests over the limit
+/// wait in a bounded queue; the rest are rejected so the caller can answer 429.
+pub struct RequestLimiter {
+    /// `None` when unlimited.
+    slots: Option<Arc<Semaphore>>,
+    waiting: AtomicUsize,
+    max_queued: usize,
+}
+
+/// Decrements the waiting count when a queued request leaves the queue.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RequestLimiter {
+    /// `max_in_flight` of 0 means unlimited.
+    pub fn new(max_in_flight: usize, max_queued: usize) -> Self {
+        Self {
+            slots: (max_in_flight > 0).then(|| Arc::new(Semaphore::new(max_in_flight))),
+            waiting: AtomicUsize::new(0),
+            max_queued,
+        }
+    }
+
+    /// Wait for a slot. The returned permit frees it when dropped (`None` when
+    /// unlimited).
+    pub async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>, Rejected> {
+        let Some(slots) = &self.slots else {
+            return Ok(None);
+        };
+        if let Ok(permit) = slots.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+        if self.waiting.fetch_add(1, Ordering::Relaxed) >= self.max_queued {
+            self.waiting.fetch_sub(1, Ordering::Relaxed);
+            return Err(Rejected::QueueFull);
+        }
+        let _waiting = Waiting(&self.waiting);
+        match tokio::time::timeout(
+            Duration::from_secs(QUEUE_TIMEOUT_SECS),
+            slots.clone().acquire_owned(),
+        )
+        .await
+        {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            // The semaphore is never closed
+            Ok(Err(_)) | Err(_) => Err(Rejected::TimedOut),
+        }
+    }
+
+    /// Requests currently waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rejected, RequestLimiter};
+
+    #[tokio::test]
+    async fn rejects_requests_beyond_the_queue() {
+        let limiter = RequestLimiter::new(1, 0);
+        let permit = limiter.acquire().await.unwrap();
+        assert!(permit.is_some());
+        assert_eq!(limiter.acquire().await.unwrap_err(), Rejected::QueueFull);
+        drop(permit);
+        assert!(limiter.acquire().await.unwrap().is_some());
+
+        let unlimited = RequestLimiter::new(0, 0);
+        assert!(unlimited.acquire().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,371 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::ws_capabilities::WsCapabilities;
+use crate::{AppState, ClientKey};
+
+/// How often the WS proxy pings the backend to measure round-trip time.
+pub const RTT_PING_INTERVAL_SECS: u64 = 10;
+/// Number of RTT samples kept for the rolling statistics.
+const RTT_WINDOW: usize = 20;
+/// Marks pings sent by the proxy so their pongs are consumed rather than forwarded.
+const RTT_PING_PREFIX: &[u8] = b"assistant-rtt:";
+/// How often the quality score is recomputed and emitted.
+const QUALITY_INTERVAL_SECS: u64 = 10;
+const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+/// Proxied HTTP requests older than this no longer count towards the error rate.
+const REQUEST_WINDOW_SECS: u64 = 60;
+/// Responses smaller than this are dominated by latency and say little about throughput.
+const THROUGHPUT_MIN_BYTES: u64 = 32 * 1024;
+/// Throughput that earns a full throughput score.
+const GOOD_THROUGHPUT_BYTES_PER_SEC: f64 = 1024.0 * 1024.0;
+
+/// Rolling round-trip-time statistics for the backend WebSocket leg.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RttSummary {
+    pub samples: usize,
+    pub last_ms: Option<f64>,
+    pub average_ms: Option<f64>,
+    pub min_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+/// Combined connection quality, emitted as `connection-quality` events.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityReport {
+    /// 0 (unusable) to 100 (excellent).
+    pub score: u8,
+    /// Signal bars, 0 to 4.
+    pub bars: u8,
+    pub reachable: bool,
+    pub health_latency_ms: Option<f64>,
+    pub ws_rtt_ms: Option<f64>,
+    /// Share of proxied HTTP requests in the last minute that failed or returned 5xx.
+    pub error_rate: f64,
+    pub throughput_bytes_per_sec: Option<f64>,
+}
+
+/// Proxy statistics returned by `get_proxy_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyStats {
+    pub ws_rtt: RttSummary,
+    /// The latest connection quality report.
+    pub quality: Option<QualityReport>,
+    /// Capabilities negotiated on the latest backend WebSocket connection.
+    pub ws_capabilities: Option<WsCapabilities>,
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn summarize(samples: &VecDeque<Duration>) -> RttSummary {
+    if samples.is_empty() {
+        return RttSummary::default();
+    }
+    let total: Duration = samples.iter().sum();
+    RttSummary {
+        samples: samples.len(),
+        last_ms: samples.back().copied().map(duration_ms),
+        average_ms: Some(duration_ms(total) / samples.len() as f64),
+        min_ms: samples.iter().min().copied().map(duration_ms),
+        max_ms: samples.iter().max().copied().map(duration_ms),
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Ping payload of `prefix` followed by the send time.
+pub fn stamped_ping_payload(prefix: &[u8]) -> Vec<u8> {
+    let mut payload = prefix.to_vec();
+    payload.extend_from_slice(&now_micros().to_be_bytes());
+    payload
+}
+
+/// Round-trip time for a pong answering a [`stamped_ping_payload`] with `prefix`.
+pub fn rtt_from_stamped_pong(prefix: &[u8], payload: &[u8]) -> Option<Duration> {
+    let sent_at = payload.strip_prefix(prefix)?;
+    let sent_at = u64::from_be_bytes(sent_at.try_into().ok()?);
+    Some(Duration::from_micros(now_micros().saturating_sub(sent_at)))
+}
+
+/// Payload for an RTT ping sent to the backend.
+pub fn rtt_ping_payload() -> Vec<u8> {
+    stamped_ping_payload(RTT_PING_PREFIX)
+}
+
+/// Round-trip time for a pong answering one of our pings, or `None` for other pongs.
+pub fn rtt_from_pong(payload: &[u8]) -> Option<Duration> {
+    rtt_from_stamped_pong(RTT_PING_PREFIX, payload)
+}
+
+/// Outcome of a proxied HTTP request.
+struct RequestSample {
+    at: Instant,
+    ok: bool,
+    bytes: u64,
+    elapsed: Duration,
+}
+
+/// Inputs to the quality score.
+struct QualityInputs {
+    reachable: bool,
+    latency_ms: Option<f64>,
+    error_rate: f64,
+    throughput_bytes_per_sec: Option<f64>,
+}
+
+/// Weighted score from latency (50%), errors (30%) and throughput (20%), skipping
+/// components without data. An unreachable backend scores 0.
+fn score(inputs: &QualityInputs) -> (u8, u8) {
+    if !inputs.reachable {
+        return (0, 0);
+    }
+    // Full marks up to 100 ms, nothing from 1 s
+    let latency = inputs
+        .latency_ms
+        .map(|ms| (100.0 - (ms - 100.0) / 9.0).clamp(0.0, 100.0));
+    let errors = Some((1.0 - inputs.error_rate * 2.0).clamp(0.0, 1.0) * 100.0);
+    let throughput = inputs
+        .throughput_bytes_per_sec
+        .map(|rate| (rate / GOOD_THROUGHPUT_BYTES_PER_SEC * 100.0).min(100.0));
+    let (total, weight) = [(latency, 0.5), (errors, 0.3), (throughput, 0.2)]
+        .into_iter()
+        .filter_map(|(component, weight)| component.map(|value| (value * weight, weight)))
+        .fold((0.0, 0.0), |(total, weights), (value, weight)| {
+            (total + value, weights + weight)
+        });
+    let score = (total / weight).round().clamp(0.0, 100.0) as u8;
+    let bars = match score {
+        80.. => 4,
+        60..=79 => 3,
+        35..=59 => 2,
+        _ => 1,
+    };
+    (score, bars)
+}
+
+/// Collects WS round-trip samples, proxied request outcomes and health checks,
+/// and reports a combined quality score to the UI.
+pub struct ConnectionQuality {
+    app: AppHandle,
+    samples: Mutex<VecDeque<Duration>>,
+    requests: Mutex<VecDeque<RequestSample>>,
+    report: Mutex<Option<QualityReport>>,
+}
+
+impl ConnectionQuality {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            samples: Mutex::new(VecDeque::with_capacity(RTT_WINDOW)),
+            requests: Mutex::new(VecDeque::new()),
+            report: Mutex::new(None),
+        }
+    }
+
+    /// Record an RTT sample.
+    pub fn record_rtt(&self, rtt: Duration) {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == RTT_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(rtt);
+    }
+
+    /// Record a proxied HTTP request; `ok` is false for transport errors and 5xx responses.
+    pub fn record_request(&self, ok: bool, bytes: u64, elapsed: Duration) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        requests.push_back(RequestSample {
+            at: Instant::now(),
+            ok,
+            bytes,
+            elapsed,
+        });
+        let window = Duration::from_secs(REQUEST_WINDOW_SECS);
+        while requests
+            .front()
+            .is_some_and(|sample| sample.at.elapsed() > window)
+        {
+            requests.pop_front();
+        }
+    }
+
+    /// Error rate and throughput over the request window.
+    fn request_stats(&self) -> (f64, Option<f64>) {
+        let window = Duration::from_secs(REQUEST_WINDOW_SECS);
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let recent: Vec<&RequestSample> = requests
+            .iter()
+            .filter(|sample| sample.at.elapsed() <= window)
+            .collect();
+        if recent.is_empty() {
+            return (0.0, None);
+        }
+        let failed = recent.iter().filter(|sample| !sample.ok).count();
+        let (bytes, elapsed) = recent
+            .iter()
+            .filter(|sample| sample.ok && sample.bytes >= THROUGHPUT_MIN_BYTES)
+            .fold((0u64, Duration::ZERO), |(bytes, elapsed), sample| {
+                (bytes + sample.bytes, elapsed + sample.elapsed)
+            });
+        let throughput =
+            (bytes > 0 && !elapsed.is_zero()).then(|| bytes as f64 / elapsed.as_secs_f64());
+        (failed as f64 / recent.len() as f64, throughput)
+    }
+
+    /// Combine the collected signals with a health check result and emit the report.
+    fn publish(&self, health_latency: Option<Duration>) -> QualityReport {
+        let (error_rate, throughput_bytes_per_sec) = self.request_stats();
+        let ws_rtt_ms = self.rtt_summary().average_ms;
+        let health_latency_ms = health_latency.map(duration_ms);
+        let inputs = QualityInputs {
+            reachable: health_latency.is_some(),
+            latency_ms: ws_rtt_ms.or(health_latency_ms),
+            error_rate,
+            throughput_bytes_per_sec,
+        };
+        let (score, bars) = score(&inputs);
+        let report = QualityReport {
+            score,
+            bars,
+            reachable: inputs.reachable,
+            health_latency_ms,
+            ws_rtt_ms,
+            error_rate,
+            throughput_bytes_per_sec,
+        };
+        *self.report.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+        let _ = self.app.emit("connection-quality", &report);
+        report
+    }
+
+    /// Forget samples from a previous backend connection.
+    pub fn reset(&self) {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    pub fn rtt_summary(&self) -> RttSummary {
+        summarize(&self.samples.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Time a request to the backend; any HTTP response counts as reachable.
+async fn health_check(client: &reqwest::Client, backend_url: &str) -> Option<Duration> {
+    let started = Instant::now();
+    let url = format!("{}/api/version", backend_url.trim_end_matches('/'));
+    match client
+        .get(&url)
+        .timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS))
+        .send()
+        .await
+    {
+        Ok(_) => Some(started.elapsed()),
+        Err(e) => {
+            log::warn!(target: "connection-quality", "Health check failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Periodically health-check the backend and emit `connection-quality` reports.
+pub async fn run_quality_monitor(app: AppHandle) {
+    // Rebuilt when the settings it was built from change
+    let mut client: Option<(ClientKey, reqwest::Client)> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(QUALITY_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let state: State<'_, AppState> = app.state();
+        let settings = state.settings.lock().await.clone();
+        let key = ClientKey::from_settings(&settings);
+        if client
+            .as_ref()
+            .is_none_or(|(built_for, _)| *built_for != key)
+        {
+            match crate::with_pac(crate::http_client_builder(&settings), &settings, &state.pac)
+                .build()
+            {
+                Ok(built) => client = Some((key, built)),
+                Err(e) => {
+                    log::error!(target: "connection-quality", "Failed to create HTTP client: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some((key, http_client)) = &client else {
+            continue;
+        };
+        let health_latency = health_check(http_client, &key.backend.url).await;
+        state.connection_quality.publish(health_latency);
+    }
+}
+
+/// Get proxy statistics (backend WebSocket round-trip time, connection quality and
+/// negotiated capabilities).
+#[tauri::command]
+pub async fn get_proxy_stats(state: State<'_, AppState>) -> Result<ProxyStats, String> {
+    Ok(ProxyStats {
+        ws_rtt: state.connection_quality.rtt_summary(),
+        quality: state
+            .connection_quality
+            .report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        ws_capabilities: state.ws_capabilities.current(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rtt_from_pong, rtt_ping_payload, score, summarize, QualityInputs};
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    #[test]
+    fn measures_rtt_only_for_proxy_pings() {
+        assert!(rtt_from_pong(&rtt_ping_payload()).is_some());
+        assert!(rtt_from_pong(b"client-ping").is_none());
+    }
+
+    #[test]
+    fn summarizes_rolling_window() {
+        let samples: VecDeque<Duration> = [10, 30, 20].map(Duration::from_millis).into();
+        let summary = summarize(&samples);
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.last_ms, Some(20.0));
+        assert_eq!(summary.average_ms, Some(20.0));
+        assert_eq!(summary.min_ms, Some(10.0));
+        assert_eq!(summary.max_ms, Some(30.0));
+    }
+
+    #[test]
+    fn scores_connection_quality() {
+        let inputs = |latency_ms, error_rate, throughput| QualityInputs {
+            reachable: true,
+            latency_ms: Some(latency_ms),
+            error_rate,
+            throughput_bytes_per_sec: throughput,
+        };
+        assert_eq!(score(&inputs(40.0, 0.0, Some(2e6))), (100, 4));
+        assert_eq!(score(&inputs(40.0, 0.0, None)), (100, 4));
+        assert_eq!(score(&inputs(550.0, 0.1, None)), (61, 3));
+        assert_eq!(score(&inputs(2000.0, 0.5, Some(1e4))), (0, 1));
+        let unreachable = QualityInputs {
+            reachable: false,
+            ..inputs(40.0, 0.0, None)
+        };
+        assert_eq!(score(&unreachable), (0, 0));
+    }
+}
@@ -0,0 +1,127 @@
+use flate2::write::{GzDecoder, ZlibDecoder};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Encodings the proxy can decode, advertised upstream in decode mode.
+pub const DECODABLE_ACCEPT_ENCODING: &str = "gzip, deflate, br";
+const BROTLI_BUFFER_BYTES: usize = 4096;
+
+/// How compressed backend responses are forwarded to the webview.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncodingMode {
+    /// Forward the compressed bytes and `Content-Encoding` header untouched.
+    #[default]
+    Passthrough,
+    /// Decompress gzip, deflate and brotli bodies and strip `Content-Encoding`.
+    Decode,
+}
+
+/// Incremental decoder for one `Content-Encoding`, fed a body chunk by chunk.
+pub enum StreamDecoder {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+impl StreamDecoder {
+    /// Decoder for a `Content-Encoding` header value, or `None` if it is not
+    /// supported (including stacked encodings such as `gzip, br`).
+    pub fn for_encoding(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip(GzDecoder::new(Vec::new()))),
+            "deflate" => Some(Self::Deflate(ZlibDecoder::new(Vec::new()))),
+            "br" => Some(Self::Brotli(Box::new(brotli::DecompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_BYTES,
+            )))),
+            _ => None,
+        }
+    }
+
+    /// Decoder for a response, if it is encoded with something this can decode.
+    pub fn for_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        headers
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::for_encoding)
+    }
+
+    fn output(&mut self) -> &mut Vec<u8> {
+        match self {
+            Self::Gzip(decoder) => decoder.get_mut(),
+            Self::Deflate(decoder) => decoder.get_mut(),
+            Self::Brotli(decoder) => decoder.get_mut(),
+        }
+    }
+
+    /// Feed a chunk of the encoded body, returning the output decoded so far.
+    pub fn push(&mut self, chunk: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(decoder) => decoder.write_all(chunk)?,
+            Self::Deflate(decoder) => decoder.write_all(chunk)?,
+            Self::Brotli(decoder) => decoder.write_all(chunk)?,
+        }
+        Ok(std::mem::take(self.output()))
+    }
+
+    /// Finish the body, returning the remaining output. Fails on a truncated body.
+    pub fn finish(&mut self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(decoder) => decoder.try_finish()?,
+            Self::Deflate(decoder) => decoder.try_finish()?,
+            Self::Brotli(decoder) => decoder.close()?,
+        }
+        Ok(std::mem::take(self.output()))
+    }
+}
+
+/// Decode a fully buffered body, or `None` if its encoding is not supported.
+pub fn decode(
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+) -> Option<std::io::Result<Vec<u8>>> {
+    let mut decoder = StreamDecoder::for_headers(headers)?;
+    Some(decoder.push(body).and_then(|mut decoded| {
+        decoded.extend(decoder.finish()?);
+        Ok(decoded)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, StreamDecoder};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use std::io::Write;
+
+    #[test]
+    fn decodes_gzip_and_brotli() {
+        let text = "hello hello hello hello ".repeat(200);
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", HeaderValue::from_static("gzip"));
+        assert_eq!(decode(&headers, &gzip).unwrap().unwrap(), text.as_bytes());
+
+        let mut brotli = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        brotli.write_all(text.as_bytes()).unwrap();
+        let brotli = brotli.into_inner();
+        let mut decoder = StreamDecoder::for_encoding("br").unwrap();
+        let mut decoded = Vec::new();
+        for chunk in brotli.chunks(7) {
+            decoded.extend(decoder.push(chunk).unwrap());
+        }
+        decoded.extend(decoder.finish().unwrap());
+        assert_eq!(decoded, text.as_bytes());
+
+        headers.insert("content-encoding", HeaderValue::from_static("zstd"));
+        assert!(decode(&headers, b"").is_none());
+        assert!(decode(&headers, &gzip[..10]).is_none());
+        headers.insert("content-encoding", HeaderValue::from_static("gzip"));
+        assert!(decode(&headers, &gzip[..10]).unwrap().is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::AppState;
+
+/// Pointer to the data directory, always kept in the default app data directory.
+const POINTER_FILE: &str = "data_dir.json";
+/// Files that belong to one launch and are not carried over by a migration.
+const SKIP_ON_MIGRATION: &[&str] = &[POINTER_FILE, "startup_attempts"];
+
+/// A data directory switch scheduled for the next launch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingSwitch {
+    /// New location (`None` for the default directory).
+    path: Option<PathBuf>,
+    /// Copy the current data into the new location before switching.
+    migrate: bool,
+}
+
+/// Contents of the pointer file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DataDirConfig {
+    #[serde(default)]
+    data_dir_override: Option<PathBuf>,
+    #[serde(default)]
+    pending: Option<PendingSwitch>,
+}
+
+impl DataDirConfig {
+    fn load(default_dir: &Path) -> Self {
+        fs::read_to_string(default_dir.join(POINTER_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the pointer file via a rename, so a crash never leaves it half-written.
+    fn save(&self, default_dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(default_dir).map_err(|e| e.to_string())?;
+        let path = default_dir.join(POINTER_FILE);
+        let temp = path.with_extension("json.tmp");
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&temp, data).map_err(|e| e.to_string())?;
+        fs::rename(&temp, &path).map_err(|e| e.to_string())
+    }
+}
+
+/// Data directory in use for this launch.
+pub struct DataDir {
+    pub path: PathBuf,
+    pub default: PathBuf,
+    /// Why a scheduled switch did not happen, if it failed.
+    pub migration_error: Option<String>,
+}
+
+impl DataDir {
+    /// Log directory: the platform's app log directory, or `logs` inside an
+    /// overridden data directory.
+    pub fn log_dir(&self, app: &AppHandle) -> PathBuf {
+        if self.path != self.default {
+            return self.path.join("logs");
+        }
+        app.path()
+            .app_log_dir()
+            .unwrap_or_else(|_| std::env::temp_dir().join("assistant-desktop-logs"))
+    }
+}
+
+/// Copy the contents of `from` into `to`, overwriting files that exist in both.
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> std::io::Result<u64> {
+    fs::create_dir_all(to)?;
+    let mut copied = 0;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|skipped| name == *skipped) {
+            continue;
+        }
+        let target = to.join(&name);
+        if entry.file_type()?.is_dir() {
+            copied += copy_dir(&entry.path(), &target, &[])?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// Whether one folder is inside the other. Copying either into the other would
+/// copy the target into itself until the disk is full.
+fn nested(a: &Path, b: &Path) -> bool {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let (a, b) = (canonical(a), canonical(b));
+    a != b && (a.starts_with(&b) || b.starts_with(&a))
+}
+
+/// Whether `path` holds files a migration would overwrite.
+fn has_data(path: &Path) -> bool {
+    fs::read_dir(path).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let name = entry.file_name();
+            !SKIP_ON_MIGRATION.iter().any(|skipped| name == *skipped)
+        })
+    })
+}
+
+/// Check that the data in `from` can be copied into `to`.
+fn check_migration(from: &Path, to: &Path) -> Result<(), String> {
+    if nested(from, to) {
+        return Err("The data folders cannot be inside one another".to_string());
+    }
+    if has_data(to) {
+        return Err(format!(
+            "{} is not empty; choose an empty folder to copy the data into",
+            to.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve the data directory, first applying a switch scheduled by
+/// `choose_data_dir` or `reset_data_dir`. Runs before logging is set up.
+pub fn prepare(app: &AppHandle) -> DataDir {
+    let default = app
+        .path()
+        .app_data_dir()
+        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| ".".into()));
+    let mut config = DataDirConfig::load(&default);
+    let current = config
+        .data_dir_override
+        .clone()
+        .unwrap_or_else(|| default.clone());
+
+    let mut migration_error = None;
+    if let Some(pending) = config.pending.take() {
+        let target = pending.path.clone().unwrap_or_else(|| default.clone());
+        let result = if pending.migrate && target != current && current.exists() {
+            check_migration(&current, &target).and_then(|_| {
+                copy_dir(&current, &target, SKIP_ON_MIGRATION).map_err(|e| e.to_string())
+            })
+        } else {
+            fs::create_dir_all(&target)
+                .map(|_| 0)
+                .map_err(|e| e.to_string())
+        };
+        match result {
+            Ok(copied) => {
+                eprintln!(
+                    "[data-dir] Switched to {} ({} files migrated)",
+                    target.display(),
+                    copied
+                );
+                config.data_dir_override = pending.path;
+            }
+            Err(e) => {
+                eprintln!("[data-dir] Failed to switch to {}: {}", target.display(), e);
+                migration_error = Some(format!("Could not use {}: {}", target.display(), e));
+            }
+        }
+        // Only now does the switch take effect; a failed copy keeps the old directory
+        if let Err(e) = config.save(&default) {
+            eprintln!("[data-dir] Failed to save {}: {}", POINTER_FILE, e);
+        }
+    }
+
+    DataDir {
+        path: config.data_dir_override.unwrap_or_else(|| default.clone()),
+        default,
+        migration_error,
+    }
+}
+
+/// Check that `path` can hold the app's data, copied from `current` with `migrate`.
+fn validate_target(path: &Path, current: &Path, migrate: bool) -> Result<(), String> {
+    if nested(path, current) {
+        return Err(
+            "The new data folder cannot be inside the current one, or contain it".to_string(),
+        );
+    }
+    if migrate && path != current {
+        check_migration(current, path)?;
+    }
+    fs::create_dir_all(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+    let probe = path.join(".assistant-write-test");
+    fs::write(&probe, b"").map_err(|e| format!("Cannot write to {}: {}", path.display(), e))?;
+    let _ = fs::remove_file(probe);
+    Ok(())
+}
+
+fn schedule(state: &AppState, path: Option<PathBuf>, migrate: bool) -> Result<(), String> {
+    let default = &state.data_dir.default;
+    let mut config = DataDirConfig::load(default);
+    config.pending = Some(PendingSwitch { path, migrate });
+    config.save(default)
+}
+
+/// Data directory status returned by `get_data_dir`.
+#[derive(Debug, Serialize)]
+pub struct DataDirInfo {
+    pub current: PathBuf,
+    pub default: PathBuf,
+    /// Directory that takes effect on the next launch, if a switch is scheduled.
+    pub pending: Option<PathBuf>,
+    pub migration_error: Option<String>,
+}
+
+/// Get the data directory in use and any switch scheduled for the next launch.
+#[tauri::command]
+pub async fn get_data_dir(state: State<'_, AppState>) -> Result<DataDirInfo, String> {
+    let data_dir = &state.data_dir;
+    let pending = DataDirConfig::load(&data_dir.default)
+        .pending
+        .map(|pending| pending.path.unwrap_or_else(|| data_dir.default.clone()));
+    Ok(DataDirInfo {
+        current: data_dir.path.clone(),
+        default: data_dir.default.clone(),
+        pending,
+        migration_error: data_dir.migration_error.clone(),
+    })
+}
+
+/// Pick a folder for settings, logs and other app data. The switch happens on the
+/// next launch; with `migrate`, existing data is copied there first. Returns the
+/// chosen folder, or `None` if the picker was cancelled.
+#[tauri::command]
+pub async fn choose_data_dir(
+    app: AppHandle,
+    migrate: bool,
+    state: State<'_, AppState>,
+) -> Result<Option<PathBuf>, String> {
+    let picker = app
+        .dialog()
+        .file()
+        .set_title("Choose a folder for Assistant data");
+    let picked = tauri::async_runtime::spawn_blocking(move || picker.blocking_pick_folder())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    validate_target(&path, &state.data_dir.path, migrate)?;
+    let path_override = (path != state.data_dir.default).then(|| path.clone());
+    schedule(&state, path_override, migrate)?;
+    log::info!(target: "data-dir", "Data folder switches to {} on next launch", path.display());
+    Ok(Some(path))
+}
+
+/// Go back to the default data directory on the next launch.
+#[tauri::command]
+pub async fn reset_data_dir(migrate: bool, state: State<'_, AppState>) -> Result<(), String> {
+    validate_target(&state.data_dir.default, &state.data_dir.path, migrate)?;
+    schedule(&state, None, migrate)?;
+    log::info!(target: "data-dir", "Data folder switches back to the default on next launch");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_migration, copy_dir, nested};
+    use std::fs;
+
+    #[test]
+    fn copies_data_recursively() {
+        let root = std::env::temp_dir().join(format!("assistant-data-dir-{}", std::process::id()));
+        let from = root.join("from");
+        let to = root.join("to");
+        fs::create_dir_all(from.join("logs")).unwrap();
+        fs::write(from.join("settings.json"), "{}").unwrap();
+        fs::write(from.join("data_dir.json"), "{}").unwrap();
+        fs::write(from.join("logs").join("app.log"), "line").unwrap();
+
+        assert_eq!(copy_dir(&from, &to, &["data_dir.json"]).unwrap(), 2);
+        assert_eq!(fs::read_to_string(to.join("settings.json")).unwrap(), "{}");
+        assert!(to.join("logs").join("app.log").exists());
+        assert!(!to.join("data_dir.json").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn refuses_nested_or_non_empty_migration_targets() {
+        let root = std::env::temp_dir().join(format!("assistant-data-nest-{}", std::process::id()));
+        let parent = root.join("home");
+        let default = parent.join("app-data");
+        let other = root.join("other");
+        fs::create_dir_all(&default).unwrap();
+        fs::create_dir_all(&other).unwrap();
+
+        assert!(nested(&parent, &default));
+        assert!(nested(&default, &parent));
+        assert!(!nested(&default, &default));
+        assert!(check_migration(&parent, &default).is_err());
+        assert!(check_migration(&default, &parent).is_err());
+
+        fs::write(default.join("data_dir.json"), "{}").unwrap();
+        assert!(check_migration(&other, &default).is_ok());
+        fs::write(default.join("settings.json"), "{}").unwrap();
+        assert!(check_migration(&other, &default).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
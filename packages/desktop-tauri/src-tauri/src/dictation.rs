@@ -0,0 +1,248 @@
+use base64::Engine;
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
+
+use crate::{input, shortcuts, AppState};
+
+const OVERLAY_LABEL: &str = "dictation-overlay";
+const OVERLAY_WIDTH: f64 = 520.0;
+const OVERLAY_HEIGHT: f64 = 72.0;
+const OVERLAY_BOTTOM_MARGIN: f64 = 96.0;
+
+/// Self-contained overlay page; interim text is pushed in with `__setDictationText`.
+const OVERLAY_HTML: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><style>
+html,body{margin:0;height:100%;background:#1f2328;color:#f0f3f6;overflow:hidden;
+font:15px/1.4 -apple-system,BlinkMacSystemFont,"Segoe UI",sans-serif;cursor:default;user-select:none}
+body{display:flex;align-items:center;gap:12px;padding:0 18px;box-sizing:border-box}
+#dot{flex:none;width:10px;height:10px;border-radius:50%;background:#f85149}
+#text{white-space:nowrap;overflow:hidden;text-overflow:ellipsis;direction:rtl;text-align:left}
+#text.idle{color:#8b949e}
+</style></head><body><div id="dot"></div><div id="text" class="idle">Listening…</div>
+<script>
+window.__setDictationText = function (text) {
+  var el = document.getElementById('text');
+  el.className = text ? '' : 'idle';
+  el.textContent = text ? '‎' + text : 'Listening…';
+};
+</script></body></html>"#;
+
+/// Payload of the `dictation-started` / `dictation-stopped` events.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct DictationEvent {
+    id: u64,
+}
+
+struct DictationSession {
+    id: u64,
+    typed_any: bool,
+}
+
+/// The dictation session started by the hotkey; transcripts are only typed while it runs.
+pub struct Dictation {
+    next_id: Mutex<u64>,
+    active: Mutex<Option<DictationSession>>,
+}
+
+impl Dictation {
+    pub fn new() -> Self {
+        Self {
+            next_id: Mutex::new(0),
+            active: Mutex::new(None),
+        }
+    }
+
+    /// Start a session, or stop the running one. Returns `(id, started)`.
+    fn toggle(&self) -> (u64, bool) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(session) = active.take() {
+            return (session.id, false);
+        }
+        let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+        *next_id += 1;
+        *active = Some(DictationSession {
+            id: *next_id,
+            typed_any: false,
+        });
+        (*next_id, true)
+    }
+
+    fn stop(&self, id: Option<u64>) -> Option<u64> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if active
+            .as_ref()
+            .is_some_and(|session| id.is_none_or(|id| session.id == id))
+        {
+            return active.take().map(|session| session.id);
+        }
+        None
+    }
+
+    fn is_active(&self, id: u64) -> bool {
+        let active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        active.as_ref().is_some_and(|session| session.id == id)
+    }
+
+    /// Text to type for a final transcript segment, separated from the previous one.
+    fn next_segment(&self, id: u64, text: &str) -> Option<String> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        let session = active.as_mut().filter(|session| session.id == id)?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Some(String::new());
+        }
+        let segment = if session.typed_any {
+            format!(" {}", text)
+        } else {
+            text.to_string()
+        };
+        session.typed_any = true;
+        Some(segment)
+    }
+}
+
+fn show_overlay(app: &AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(OVERLAY_LABEL) {
+        set_overlay_text(app, "");
+        return window.show().map_err(|e| e.to_string());
+    }
+
+    let html = base64::engine::general_purpose::STANDARD.encode(OVERLAY_HTML);
+    let url =
+        tauri::Url::parse(&format!("data:text/html;base64,{}", html)).map_err(|e| e.to_string())?;
+    let mut builder = WebviewWindowBuilder::new(app, OVERLAY_LABEL, WebviewUrl::External(url))
+        .title("Dictation")
+        .inner_size(OVERLAY_WIDTH, OVERLAY_HEIGHT)
+        .decorations(false)
+        .resizable(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible_on_all_workspaces(true)
+        .focused(false)
+        .focusable(false);
+
+    // Bottom center of the primary display
+    if let Ok(Some(monitor)) = app.primary_monitor() {
+        let scale = monitor.scale_factor();
+        let size = monitor.size().to_logical::<f64>(scale);
+        let origin = monitor.position().to_logical::<f64>(scale);
+        builder = builder.position(
+            origin.x + (size.width - OVERLAY_WIDTH) / 2.0,
+            origin.y + size.height - OVERLAY_HEIGHT - OVERLAY_BOTTOM_MARGIN,
+        );
+    }
+    builder.build().map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn set_overlay_text(app: &AppHandle, text: &str) {
+    if let Some(window) = app.get_webview_window(OVERLAY_LABEL) {
+        let text = serde_json::to_string(text).unwrap_or_default();
+        let _ = window.eval(format!("window.__setDictationText({})", text));
+    }
+}
+
+fn hide_overlay(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(OVERLAY_LABEL) {
+        let _ = window.hide();
+    }
+}
+
+fn stopped(app: &AppHandle, id: u64) {
+    hide_overlay(app);
+    let _ = app.emit("dictation-stopped", DictationEvent { id });
+}
+
+/// Register the dictation shortcut in place of `previous`. An empty shortcut disables it.
+///
+/// Each press toggles dictation. The web client transcribes while a session is
+/// running and reports text through `update_dictation`.
+pub fn register_shortcut(app: &AppHandle, previous: &str, shortcut: &str) -> Result<(), String> {
+    shortcuts::replace_shortcut(app, previous, shortcut, |app| {
+        let state: State<'_, AppState> = app.state();
+        let (id, started) = state.dictation.toggle();
+        if !started {
+            stopped(app, id);
+            return;
+        }
+        if let Err(e) = show_overlay(app) {
+            log::error!(target: "dictation", "Failed to show overlay: {}", e);
+        }
+        let _ = app.emit("dictation-started", DictationEvent { id });
+    })
+}
+
+/// Report transcription for a running dictation session.
+///
+/// Interim text is shown in the overlay; final text is typed into the focused
+/// application. Only accepted while the session started by the hotkey is running.
+#[tauri::command]
+pub async fn update_dictation(
+    app: AppHandle,
+    id: u64,
+    text: String,
+    is_final: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !state.dictation.is_active(id) {
+        return Err("Dictation session is not active".to_string());
+    }
+    if !is_final {
+        set_overlay_text(&app, &text);
+        return Ok(());
+    }
+    if text.contains('\0') {
+        return Err("Text must not contain NUL characters".to_string());
+    }
+
+    let segment = state
+        .dictation
+        .next_segment(id, &text)
+        .ok_or_else(|| "Dictation session is not active".to_string())?;
+    set_overlay_text(&app, "");
+    if segment.is_empty() {
+        return Ok(());
+    }
+    tauri::async_runtime::spawn_blocking(move || input::type_text(&segment))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// End dictation (e.g. when transcription fails); `id` limits it to that session.
+#[tauri::command]
+pub async fn stop_dictation(
+    app: AppHandle,
+    id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(id) = state.dictation.stop(id) {
+        stopped(&app, id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dictation;
+
+    #[test]
+    fn types_final_segments_only_while_active() {
+        let dictation = Dictation::new();
+        let (id, started) = dictation.toggle();
+        assert!(started);
+        assert_eq!(
+            dictation.next_segment(id, " hello ").as_deref(),
+            Some("hello")
+        );
+        assert_eq!(
+            dictation.next_segment(id, "world").as_deref(),
+            Some(" world")
+        );
+        assert_eq!(dictation.next_segment(id + 1, "stale"), None);
+
+        assert_eq!(dictation.toggle(), (id, false));
+        assert!(!dictation.is_active(id));
+        assert_eq!(dictation.next_segment(id, "late"), None);
+        assert_eq!(dictation.stop(None), None);
+    }
+}
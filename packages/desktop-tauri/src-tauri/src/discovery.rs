@@ -0,0 +1,141 @@
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// DNS-SD service type advertised by assistant backends.
+const SERVICE_TYPE: &str = "_assistant._tcp.local.";
+/// How long to listen for answers when the caller does not say.
+const DEFAULT_DISCOVERY_MS: u64 = 3000;
+const MAX_DISCOVERY_MS: u64 = 30_000;
+
+/// A backend found on the local network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscoveredBackend {
+    /// Instance name, e.g. `Home server`.
+    pub name: String,
+    /// Candidate `backend_url`.
+    pub url: String,
+    /// mDNS host name, e.g. `nas.local.`.
+    pub host: String,
+    pub addresses: Vec<String>,
+}
+
+/// Build the backend URL for a resolved service. The TXT record may carry
+/// `scheme` (`http` or `https`, default `http`) and `path`. IPv4 addresses are
+/// preferred because `.local` names do not resolve everywhere.
+fn backend_url(
+    addresses: &[IpAddr],
+    port: u16,
+    scheme: Option<&str>,
+    path: Option<&str>,
+) -> Option<String> {
+    let address = addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .or_else(|| addresses.first())?;
+    let scheme = match scheme {
+        Some("https") => "https",
+        _ => "http",
+    };
+    let host = match address {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    };
+    let path = path
+        .map(|path| path.trim_matches('/'))
+        .filter(|path| !path.is_empty())
+        .map(|path| format!("/{}", path))
+        .unwrap_or_default();
+    Some(format!("{}://{}:{}{}", scheme, host, port, path))
+}
+
+/// Instance name from a full service name (`Home server._assistant._tcp.local.`).
+fn instance_name(fullname: &str) -> &str {
+    fullname
+        .strip_suffix(SERVICE_TYPE)
+        .map(|name| name.trim_end_matches('.'))
+        .unwrap_or(fullname)
+}
+
+/// Browse the local network for `_assistant._tcp` services for `timeout_ms`
+/// (default 3 s) and return the backends that answered, sorted by name.
+#[tauri::command]
+pub async fn discover_backends(timeout_ms: Option<u64>) -> Result<Vec<DiscoveredBackend>, String> {
+    let timeout = Duration::from_millis(
+        timeout_ms
+            .unwrap_or(DEFAULT_DISCOVERY_MS)
+            .min(MAX_DISCOVERY_MS),
+    );
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+    let events = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("Failed to browse for backends: {}", e))?;
+
+    // Keyed by full name so re-announcements replace earlier answers
+    let mut found = BTreeMap::new();
+    let _ = tokio::time::timeout(timeout, async {
+        while let Ok(event) = events.recv_async().await {
+            match event {
+                ServiceEvent::ServiceResolved(info) => {
+                    let mut addresses: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+                    addresses.sort();
+                    let Some(url) = backend_url(
+                        &addresses,
+                        info.get_port(),
+                        info.get_property_val_str("scheme"),
+                        info.get_property_val_str("path"),
+                    ) else {
+                        continue;
+                    };
+                    found.insert(
+                        info.get_fullname().to_string(),
+                        DiscoveredBackend {
+                            name: instance_name(info.get_fullname()).to_string(),
+                            url,
+                            host: info.get_hostname().to_string(),
+                            addresses: addresses.iter().map(IpAddr::to_string).collect(),
+                        },
+                    );
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    found.remove(&fullname);
+                }
+                _ => {}
+            }
+        }
+    })
+    .await;
+    let _ = daemon.shutdown();
+
+    let mut backends: Vec<DiscoveredBackend> = found.into_values().collect();
+    backends.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.url.cmp(&b.url)));
+    log::info!(target: "discovery", "Found {} backend(s) on the local network", backends.len());
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backend_url, instance_name};
+    use std::net::IpAddr;
+
+    #[test]
+    fn builds_urls_from_service_records() {
+        let v4: IpAddr = "192.168.1.20".parse().unwrap();
+        let v6: IpAddr = "fe80::1".parse().unwrap();
+        assert_eq!(
+            backend_url(&[v6, v4], 3000, None, None).as_deref(),
+            Some("http://192.168.1.20:3000")
+        );
+        assert_eq!(
+            backend_url(&[v6], 443, Some("https"), Some("/assistant/")).as_deref(),
+            Some("https://[fe80::1]:443/assistant")
+        );
+        assert_eq!(backend_url(&[], 3000, None, None), None);
+        assert_eq!(
+            instance_name("Home server._assistant._tcp.local."),
+            "Home server"
+        );
+    }
+}
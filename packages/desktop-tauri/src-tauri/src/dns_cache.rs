@@ -0,0 +1,229 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::doh::DohResolver;
+use crate::AppState;
+
+/// How long a last-known-good answer may stand in while the resolver is failing.
+const LAST_KNOWN_GOOD_SECS: u64 = 24 * 60 * 60;
+
+struct CacheEntry {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+/// Cached answer for a host, if any.
+#[derive(Debug, PartialEq)]
+enum Cached {
+    Fresh(Vec<SocketAddr>),
+    /// Expired, but usable if a new lookup fails.
+    Stale(Vec<SocketAddr>),
+    Miss,
+}
+
+fn cached(entry: Option<&CacheEntry>, ttl: Duration, now: Instant) -> Cached {
+    let Some(entry) = entry else {
+        return Cached::Miss;
+    };
+    let age = now.saturating_duration_since(entry.resolved_at);
+    if age < ttl {
+        Cached::Fresh(entry.addrs.clone())
+    } else if age < Duration::from_secs(LAST_KNOWN_GOOD_SECS) {
+        Cached::Stale(entry.addrs.clone())
+    } else {
+        Cached::Miss
+    }
+}
+
+/// In-process DNS cache for the proxy's upstream connections.
+///
+/// The system resolver does not report record TTLs, so answers are kept for the
+/// configured TTL. When a lookup fails, the last answer that worked is used instead.
+/// Lookups go to a DNS-over-HTTPS endpoint instead of the system resolver when
+/// one is set.
+pub struct DnsCache {
+    ttl_secs: AtomicU64,
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    doh: Mutex<Option<Arc<DohResolver>>>,
+}
+
+impl DnsCache {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            ttl_secs: AtomicU64::new(ttl_secs),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            doh: Mutex::new(None),
+        }
+    }
+
+    pub fn set_ttl(&self, ttl_secs: u64) {
+        self.ttl_secs.store(ttl_secs, Ordering::Relaxed);
+    }
+
+    /// Switch between DNS-over-HTTPS at `endpoint` and the system resolver,
+    /// dropping answers from the previous one.
+    pub fn set_doh(&self, endpoint: Option<&str>) -> Result<(), String> {
+        let resolver = endpoint
+            .map(|endpoint| DohResolver::new(endpoint.to_string()))
+            .transpose()?;
+        let mut doh = self.doh.lock().unwrap_or_else(|e| e.into_inner());
+        if doh.as_ref().map(|doh| doh.endpoint()) != endpoint {
+            *doh = resolver.map(Arc::new);
+            drop(doh);
+            self.flush();
+        }
+        Ok(())
+    }
+
+    fn doh(&self) -> Option<Arc<DohResolver>> {
+        self.doh.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Forget all cached answers, e.g. after switching VPNs.
+    pub fn flush(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let flushed = entries.len();
+        entries.clear();
+        flushed
+    }
+
+    /// Resolve `host` through the cache.
+    pub async fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+        let ttl = Duration::from_secs(self.ttl_secs.load(Ordering::Relaxed));
+        lookup(
+            self.entries.clone(),
+            self.doh(),
+            ttl,
+            host.to_string(),
+            port,
+        )
+        .await
+    }
+}
+
+async fn resolve(
+    doh: Option<&DohResolver>,
+    host: &str,
+    port: u16,
+) -> std::io::Result<Vec<SocketAddr>> {
+    match doh {
+        Some(doh) if host.parse::<IpAddr>().is_err() => Ok(doh
+            .resolve(host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect()),
+        _ => tokio::net::lookup_host((host, port))
+            .await
+            .map(|addrs| addrs.collect()),
+    }
+}
+
+async fn lookup(
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    doh: Option<Arc<DohResolver>>,
+    ttl: Duration,
+    host: String,
+    port: u16,
+) -> std::io::Result<Vec<SocketAddr>> {
+    let with_port = |addrs: Vec<SocketAddr>| {
+        addrs
+            .into_iter()
+            .map(|addr| SocketAddr::new(addr.ip(), port))
+            .collect::<Vec<_>>()
+    };
+    let previous = cached(
+        entries.lock().unwrap_or_else(|e| e.into_inner()).get(&host),
+        ttl,
+        Instant::now(),
+    );
+    if let Cached::Fresh(addrs) = previous {
+        return Ok(with_port(addrs));
+    }
+
+    let result = resolve(doh.as_deref(), &host, port).await;
+    match result {
+        Ok(addrs) => {
+            if !ttl.is_zero() && !addrs.is_empty() {
+                entries.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                    host,
+                    CacheEntry {
+                        addrs: addrs.clone(),
+                        resolved_at: Instant::now(),
+                    },
+                );
+            }
+            Ok(addrs)
+        }
+        Err(e) => match previous {
+            Cached::Stale(addrs) => {
+                log::warn!(
+                    target: "dns",
+                    "Lookup of {} failed ({}), using last known address",
+                    host,
+                    e
+                );
+                Ok(with_port(addrs))
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+impl Resolve for DnsCache {
+    fn resolve(&self, name: Name) -> Resolving {
+        let entries = self.entries.clone();
+        let doh = self.doh();
+        let ttl = Duration::from_secs(self.ttl_secs.load(Ordering::Relaxed));
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            // reqwest replaces the port with the one from the URL
+            let addrs = lookup(entries, doh, ttl, host, 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Clear the DNS cache so the backend hostname is looked up again.
+#[tauri::command]
+pub async fn flush_dns_cache(state: State<'_, AppState>) -> Result<(), String> {
+    let flushed = state.dns_cache.flush();
+    log::info!(target: "dns", "Flushed {} cached DNS entries", flushed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached, CacheEntry, Cached};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn expires_to_last_known_good() {
+        let resolved_at = Instant::now();
+        let entry = CacheEntry {
+            addrs: vec!["10.0.0.1:0".parse().unwrap()],
+            resolved_at,
+        };
+        let ttl = Duration::from_secs(60);
+        let at = |secs| resolved_at + Duration::from_secs(secs);
+
+        assert_eq!(
+            cached(Some(&entry), ttl, at(30)),
+            Cached::Fresh(entry.addrs.clone())
+        );
+        assert_eq!(
+            cached(Some(&entry), ttl, at(120)),
+            Cached::Stale(entry.addrs.clone())
+        );
+        assert_eq!(
+            cached(Some(&entry), ttl, at(2 * 24 * 60 * 60)),
+            Cached::Miss
+        );
+        assert_eq!(cached(None, ttl, at(0)), Cached::Miss);
+    }
+}
@@ -0,0 +1,195 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+const DOH_TIMEOUT_SECS: u64 = 5;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Check a DNS-over-HTTPS endpoint before it is saved.
+pub fn validate(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| format!("Invalid DNS-over-HTTPS URL {}: {}", url, e))?;
+    if parsed.scheme() != "https" {
+        return Err(format!("DNS-over-HTTPS URL must use https: {}", url));
+    }
+    Ok(())
+}
+
+/// RFC 8484 query for `host`. The ID is 0, as recommended for caching.
+fn encode_query(host: &str, record_type: u16) -> io::Result<Vec<u8>> {
+    // Header: ID, flags (recursion desired), one question
+    let mut query = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid host name: {}", host),
+            ));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Position after the (possibly compressed) name starting at `pos`.
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            len if len & 0xC0 == 0xC0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(pos)?,
+        *message.get(pos + 1)?,
+    ]))
+}
+
+/// Addresses in the answer section of a DNS response. CNAME chains are resolved
+/// by the server, so only A and AAAA records are read.
+fn parse_response(message: &[u8]) -> io::Result<Vec<IpAddr>> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "Malformed DNS response");
+    let flags = read_u16(message, 2).ok_or_else(malformed)?;
+    match flags & 0x000F {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => {
+            return Err(io::Error::other(format!(
+                "DNS-over-HTTPS server answered with error code {}",
+                rcode
+            )))
+        }
+    }
+    let questions = read_u16(message, 4).ok_or_else(malformed)?;
+    let answers = read_u16(message, 6).ok_or_else(malformed)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos).ok_or_else(malformed)? + 4;
+    }
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(message, pos).ok_or_else(malformed)?;
+        let record_type = read_u16(message, pos).ok_or_else(malformed)?;
+        let len = read_u16(message, pos + 8).ok_or_else(malformed)? as usize;
+        let data = message
+            .get(pos + 10..pos + 10 + len)
+            .ok_or_else(malformed)?;
+        match (record_type, len) {
+            (TYPE_A, 4) => addrs.push(IpAddr::V4(Ipv4Addr::new(
+                data[0], data[1], data[2], data[3],
+            ))),
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().map_err(|_| malformed())?;
+                addrs.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => {}
+        }
+        pos += 10 + len;
+    }
+    Ok(addrs)
+}
+
+/// Resolves backend host names over DNS-over-HTTPS (RFC 8484) instead of the
+/// system resolver.
+pub struct DohResolver {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl DohResolver {
+    pub fn new(endpoint: String) -> Result<Self, String> {
+        validate(&endpoint)?;
+        let client = reqwest::Client::builder()
+            .https_only(true)
+            .timeout(Duration::from_secs(DOH_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { endpoint, client })
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> io::Result<Vec<IpAddr>> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header("content-type", "application/dns-message")
+            .header("accept", "application/dns-message")
+            .body(encode_query(host, record_type)?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)?;
+        let body = response.bytes().await.map_err(io::Error::other)?;
+        parse_response(&body)
+    }
+
+    /// IPv4 and IPv6 addresses for `host`, IPv4 first.
+    pub async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let (v4, v6) = tokio::join!(self.query(host, TYPE_A), self.query(host, TYPE_AAAA));
+        let addrs: Vec<IpAddr> = match (v4, v6) {
+            (Err(e), Err(_)) => return Err(e),
+            (v4, v6) => v4
+                .unwrap_or_default()
+                .into_iter()
+                .chain(v6.unwrap_or_default())
+                .collect(),
+        };
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No addresses for {} over DNS-over-HTTPS", host),
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_query, parse_response, TYPE_A};
+    use std::net::IpAddr;
+
+    #[test]
+    fn round_trips_dns_messages() {
+        let query = encode_query("assistant.example", TYPE_A).unwrap();
+        assert_eq!(
+            &query[12..],
+            b"\x09assistant\x07example\x00\x00\x01\x00\x01"
+        );
+        assert!(encode_query("a..b", TYPE_A).is_err());
+
+        // Response echoing the question, with a CNAME and an A record that both
+        // point back at it through compression
+        let mut response = query.clone();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = 2;
+        response
+            .extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x04\x01a\xc0\x0c");
+        response
+            .extend_from_slice(b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04\x0a\x00\x00\x05");
+        assert_eq!(
+            parse_response(&response).unwrap(),
+            vec!["10.0.0.5".parse::<IpAddr>().unwrap()]
+        );
+
+        // NXDOMAIN is an empty answer
+        response[3] = 0x83;
+        assert!(parse_response(&response).unwrap().is_empty());
+        assert!(parse_response(&response[..3]).is_err());
+    }
+}
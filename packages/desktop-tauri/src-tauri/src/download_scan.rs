@@ -0,0 +1,218 @@
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+use crate::{tools, AppState};
+
+/// Longest a scanner may run before the file is flagged.
+const SCAN_TIMEOUT_SECS: u64 = 300;
+
+/// Outcome of the configured post-download scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    /// No scanner is configured.
+    NotScanned,
+    Clean,
+    /// The scanner reported a problem or could not run; the file is never opened.
+    Flagged,
+}
+
+/// Scanner arguments with `{path}` replaced, or the path appended if no argument uses it.
+fn scan_args(command: &[String], path: &Path) -> Vec<String> {
+    let path = path.to_string_lossy();
+    let mut args: Vec<String> = command
+        .iter()
+        .skip(1)
+        .map(|arg| arg.replace("{path}", &path))
+        .collect();
+    if !command.iter().skip(1).any(|arg| arg.contains("{path}")) {
+        args.push(path.into_owned());
+    }
+    args
+}
+
+async fn run_command(command: &[String], path: &Path) -> Result<(), String> {
+    let program = command.first().ok_or("Scanner command is empty")?;
+    let child = tokio::process::Command::new(program)
+        .args(scan_args(command, path))
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(Duration::from_secs(SCAN_TIMEOUT_SECS), child)
+        .await
+        .map_err(|_| format!("Scanner timed out after {}s", SCAN_TIMEOUT_SECS))?
+        .map_err(|e| format!("Failed to run scanner: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = [stdout.trim(), stderr.trim()]
+        .into_iter()
+        .find(|text| !text.is_empty())
+        .unwrap_or_default()
+        .lines()
+        .last()
+        .unwrap_or_default()
+        .to_string();
+    Err(match output.status.code() {
+        Some(code) => format!("Scanner exited with {}: {}", code, detail),
+        None => format!("Scanner was terminated: {}", detail),
+    })
+}
+
+#[cfg(windows)]
+mod amsi {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Largest file handed to AMSI, which scans an in-memory buffer.
+    const MAX_SCAN_BYTES: u64 = 256 * 1024 * 1024;
+    /// `AMSI_RESULT_BLOCKED_BY_ADMIN_START`; results from here on are blocked or malware.
+    const AMSI_RESULT_BLOCKED: i32 = 0x4000;
+
+    #[link(name = "amsi")]
+    extern "system" {
+        fn AmsiInitialize(app_name: *const u16, context: *mut *mut c_void) -> i32;
+        fn AmsiUninitialize(context: *mut c_void);
+        fn AmsiOpenSession(context: *mut c_void, session: *mut *mut c_void) -> i32;
+        fn AmsiCloseSession(context: *mut c_void, session: *mut c_void);
+        fn AmsiScanBuffer(
+            context: *mut c_void,
+            buffer: *const c_void,
+            length: u32,
+            content_name: *const u16,
+            session: *mut c_void,
+            result: *mut i32,
+        ) -> i32;
+    }
+
+    fn wide(text: &std::ffi::OsStr) -> Vec<u16> {
+        text.encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    /// Scan the file with the registered antimalware provider (usually Defender).
+    pub fn scan(path: &Path) -> Result<(), String> {
+        let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+        if size > MAX_SCAN_BYTES {
+            return Err(format!("File is too large for AMSI ({} bytes)", size));
+        }
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        let app_name = wide(std::ffi::OsStr::new("Assistant"));
+        let content_name = wide(path.as_os_str());
+
+        // SAFETY: every handle is checked before use and released on all paths,
+        // and the buffers outlive the calls that borrow them.
+        unsafe {
+            let mut context = std::ptr::null_mut();
+            let hr = AmsiInitialize(app_name.as_ptr(), &mut context);
+            if hr < 0 {
+                return Err(format!("AmsiInitialize failed: {:#x}", hr));
+            }
+            let mut session = std::ptr::null_mut();
+            let hr = AmsiOpenSession(context, &mut session);
+            if hr < 0 {
+                AmsiUninitialize(context);
+                return Err(format!("AmsiOpenSession failed: {:#x}", hr));
+            }
+            let mut result = 0i32;
+            let hr = AmsiScanBuffer(
+                context,
+                data.as_ptr().cast(),
+                data.len() as u32,
+                content_name.as_ptr(),
+                session,
+                &mut result,
+            );
+            AmsiCloseSession(context, session);
+            AmsiUninitialize(context);
+            if hr < 0 {
+                return Err(format!("AmsiScanBuffer failed: {:#x}", hr));
+            }
+            if result >= AMSI_RESULT_BLOCKED {
+                return Err(format!("AMSI flagged the file (result {})", result));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run the configured scanner on a completed download.
+///
+/// `command` is the scanner's argv (`{path}` is replaced by the file path, which is
+/// otherwise appended); exit code 0 means clean. On Windows, `["amsi"]` scans with
+/// the Antimalware Scan Interface instead. Returns the status and, when flagged, why.
+pub async fn scan(command: &[String], path: &Path) -> (ScanStatus, Option<String>) {
+    if command.is_empty() {
+        return (ScanStatus::NotScanned, None);
+    }
+    #[cfg(windows)]
+    let result = if command.len() == 1 && command[0].eq_ignore_ascii_case("amsi") {
+        let path = path.to_path_buf();
+        tauri::async_runtime::spawn_blocking(move || amsi::scan(&path))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+    } else {
+        run_command(command, path).await
+    };
+    #[cfg(not(windows))]
+    let result = run_command(command, path).await;
+
+    match result {
+        Ok(()) => (ScanStatus::Clean, None),
+        Err(e) => {
+            log::warn!(target: "downloads", "Scan flagged {}: {}", path.display(), e);
+            (ScanStatus::Flagged, Some(e))
+        }
+    }
+}
+
+/// Set the scanner run on completed downloads (an empty list disables it). A
+/// scanner runs any program, so setting one asks the user in a native dialog
+/// showing the full argv. Returns whether the scanner was saved.
+#[tauri::command]
+pub async fn set_download_scan_command(
+    app: AppHandle,
+    command: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let command: Vec<String> = command
+        .into_iter()
+        .filter(|arg| !arg.trim().is_empty())
+        .collect();
+    if !command.is_empty() {
+        let message = format!(
+            "Run this command on every completed download?\n\n{}",
+            serde_json::to_string_pretty(&command).unwrap_or_default()
+        );
+        if !tools::confirm(&app, "Download Scanner", &message, "Use Scanner").await? {
+            return Ok(false);
+        }
+    }
+    state.settings.lock().await.download_scan_command = command;
+    state.save().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scan_args;
+    use std::path::Path;
+
+    #[test]
+    fn substitutes_or_appends_path() {
+        let path = Path::new("/tmp/report.pdf");
+        let command = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            scan_args(&command(&["clamdscan", "--no-summary"]), path),
+            vec!["--no-summary", "/tmp/report.pdf"]
+        );
+        assert_eq!(
+            scan_args(&command(&["scan", "--file={path}", "--quiet"]), path),
+            vec!["--file=/tmp/report.pdf", "--quiet"]
+        );
+    }
+}
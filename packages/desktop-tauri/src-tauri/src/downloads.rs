@@ -0,0 +1,672 @@
+use base64::Engine;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::backend_host::BackendHost;
+use crate::download_scan::{self, ScanStatus};
+use crate::{now_ms, quarantine, tools, AppState};
+
+const DOWNLOAD_CONNECT_TIMEOUT_SECS: u64 = 10;
+const SIDECAR_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Downloading,
+    Completed,
+    Failed,
+}
+
+/// Result of checking the file against a SHA-256 digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    /// No digest was available.
+    Unverified,
+    Verified,
+    /// The file did not match its digest; it was deleted and the download failed.
+    Corrupt,
+}
+
+/// A file downloaded by the app. Times are Unix milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Download {
+    pub id: u64,
+    pub url: String,
+    pub path: String,
+    pub status: DownloadStatus,
+    pub received: u64,
+    pub total: Option<u64>,
+    /// Hex SHA-256 of the received file.
+    pub sha256: Option<String>,
+    /// Hex SHA-256 the file was expected to have.
+    pub expected_sha256: Option<String>,
+    pub verification: Verification,
+    /// Whether the file carries the OS "downloaded from the internet" marker.
+    pub quarantined: bool,
+    pub scan: ScanStatus,
+    /// Why the scan flagged the file.
+    pub scan_detail: Option<String>,
+    pub error: Option<String>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// Downloads made during this session.
+pub struct Downloads {
+    next_id: AtomicU64,
+    entries: Mutex<Vec<Download>>,
+}
+
+impl Downloads {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn get(&self, id: u64) -> Option<Download> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|download| download.id == id)
+            .cloned()
+    }
+
+    /// Record progress without an event; the taskbar progress bar shows it live.
+    fn set_progress(&self, id: u64, received: u64, total: Option<u64>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(download) = entries.iter_mut().find(|download| download.id == id) {
+            download.received = received;
+            download.total = total;
+        }
+    }
+
+    fn update(
+        &self,
+        app: &AppHandle,
+        id: u64,
+        change: impl FnOnce(&mut Download),
+    ) -> Option<Download> {
+        let updated = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let download = entries.iter_mut().find(|download| download.id == id)?;
+            change(download);
+            download.clone()
+        };
+        let _ = app.emit("download-updated", &updated);
+        Some(updated)
+    }
+}
+
+/// Normalize a hex or base64 SHA-256 digest to lowercase hex.
+fn normalize_digest(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Some(value.to_ascii_lowercase());
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .ok()
+        .filter(|bytes| bytes.len() == 32)?;
+    Some(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// SHA-256 digest announced by the server, from `Repr-Digest`/`Content-Digest`
+/// (`sha-256=:<base64>:`), `Digest` (`SHA-256=<base64>`) or `X-Checksum-Sha256` (hex).
+fn header_digest(headers: &HeaderMap) -> Option<String> {
+    for name in ["repr-digest", "content-digest", "digest"] {
+        let Some(value) = headers.get(name).and_then(|value| value.to_str().ok()) else {
+            continue;
+        };
+        for entry in value.split(',') {
+            let Some((algorithm, digest)) = entry.split_once('=') else {
+                continue;
+            };
+            if algorithm.trim().eq_ignore_ascii_case("sha-256") {
+                if let Some(digest) = normalize_digest(digest.trim().trim_matches(':')) {
+                    return Some(digest);
+                }
+            }
+        }
+    }
+    headers
+        .get("x-checksum-sha256")
+        .and_then(|value| value.to_str().ok())
+        .and_then(normalize_digest)
+}
+
+/// `<url>.sha256`: the sidecar digest file, with the query and fragment dropped.
+fn sidecar_url(url: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(url).ok()?;
+    let path = format!("{}.sha256", url.path());
+    url.set_path(&path);
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url.into())
+}
+
+/// Fetch `<url>.sha256` (as written by `sha256sum`) when the server sent no digest header.
+async fn sidecar_digest(request: reqwest::RequestBuilder) -> Option<String> {
+    let response = request
+        .timeout(Duration::from_secs(SIDECAR_TIMEOUT_SECS))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let text = response.text().await.ok()?;
+    normalize_digest(text.split_whitespace().next()?)
+}
+
+/// A safe file name for the Downloads folder. Trailing dots and spaces are dropped
+/// as Windows does when saving, so `setup.exe.` is checked as `setup.exe`.
+fn sanitize_file_name(name: &str) -> String {
+    let name = name.trim().replace(['/', '\\', ':', '\0'], "_");
+    let name = name.trim_start_matches('.').trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        "download".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Decode `%XX` escapes; `None` if an escape is malformed or the result is not UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// File name from a `Content-Disposition` value. The RFC 6266 `filename*`
+/// (`UTF-8''<percent-encoded>`) is preferred over the plain `filename`.
+fn disposition_file_name(value: &str) -> Option<String> {
+    let params = value
+        .split(';')
+        .filter_map(|part| part.trim().split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()));
+    let mut plain = None;
+    for (key, value) in params {
+        if key.eq_ignore_ascii_case("filename*") {
+            let mut parts = value.splitn(3, '\'');
+            let (charset, _language, encoded) = (parts.next()?, parts.next(), parts.next());
+            if charset.eq_ignore_ascii_case("utf-8") {
+                if let Some(name) = encoded.and_then(percent_decode) {
+                    return Some(name);
+                }
+            }
+        } else if key.eq_ignore_ascii_case("filename") && plain.is_none() {
+            plain = Some(value.trim_matches('"').to_string());
+        }
+    }
+    plain
+}
+
+/// File name from `Content-Disposition`, or the last URL path segment.
+fn response_file_name(response: &reqwest::Response) -> String {
+    let disposition = response
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(disposition_file_name);
+    disposition
+        .or_else(|| {
+            response
+                .url()
+                .path_segments()
+                .and_then(|mut segments| segments.next_back().map(str::to_string))
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "download".to_string())
+}
+
+/// `<path>.part`, which a download is written to until it is complete.
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Reserve `dir/name`, or `dir/name (n).ext` if that is taken, by creating its
+/// `.part` file. Creating it fails if another download holds the name, so two
+/// downloads of the same name never share a path.
+async fn reserve_path(dir: &Path, name: &str) -> Result<(PathBuf, tokio::fs::File), String> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    let candidates = std::iter::once(dir.join(name))
+        .chain((1..).map(|n| dir.join(format!("{} ({}){}", stem, n, extension))));
+    for path in candidates {
+        if tokio::fs::try_exists(&path).await.unwrap_or(true) {
+            continue;
+        }
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(part_path(&path))
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    unreachable!("candidate names are unbounded")
+}
+
+fn open_path(app: &AppHandle, download: &Download) -> Result<(), String> {
+    if download.status != DownloadStatus::Completed {
+        return Err("Download has not completed".to_string());
+    }
+    if download.verification == Verification::Corrupt {
+        return Err("Download failed checksum verification".to_string());
+    }
+    if download.scan == ScanStatus::Flagged {
+        return Err("Download was flagged by the virus scanner".to_string());
+    }
+    app.opener()
+        .open_path(download.path.clone(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+async fn receive(
+    app: &AppHandle,
+    id: u64,
+    mut response: reqwest::Response,
+    mut file: tokio::fs::File,
+) -> Result<String, String> {
+    let state: State<'_, AppState> = app.state();
+    let total = response.content_length();
+    let transfer_id = state.transfers.start(total);
+    let result = async {
+        let mut hasher = Sha256::new();
+        let mut received = 0u64;
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+            received += chunk.len() as u64;
+            state.transfers.update(transfer_id, received);
+            state.downloads.set_progress(id, received, total);
+        }
+        file.flush().await.map_err(|e| e.to_string())?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+    .await;
+    state.transfers.finish(transfer_id);
+    result
+}
+
+/// Download a file into the user's Downloads folder.
+///
+/// `url` may be a backend path (`/api/…`), which is fetched with the backend
+/// credentials, or an absolute `http(s)` URL. Other hosts are always fetched with
+/// certificate validation, whatever `skip_cert_validation` says. The file is
+/// written to `<name>.part` and checked against `expected_sha256`, the server's
+/// digest header, or a `<url>.sha256` sidecar, in that order; a mismatch deletes it
+/// and fails the download. Otherwise it is marked as downloaded from the internet
+/// (failing the download if that fails), renamed into place and passed to the
+/// configured virus scanner. With `open`, the file is opened once complete unless
+/// the scanner flagged it.
+#[tauri::command]
+pub async fn download_file(
+    app: AppHandle,
+    url: String,
+    file_name: Option<String>,
+    expected_sha256: Option<String>,
+    open: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Download, String> {
+    let (builder, url, is_backend, backend, allow_executables, scan_command) = {
+        let settings = state.settings.lock().await;
+        let backend = BackendHost::from_settings(&settings);
+        let backend_base = settings.backend_url.trim_end_matches('/');
+        let backend_target = backend.url.trim_end_matches('/');
+        let url = url.trim();
+        let (url, is_backend) = if url.starts_with('/') {
+            (format!("{}{}", backend_target, url), true)
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            match url
+                .strip_prefix(backend_base)
+                .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+            {
+                Some(rest) => (format!("{}{}", backend_target, rest), true),
+                None => (url.to_string(), false),
+            }
+        } else {
+            return Err(format!("Unsupported download URL: {}", url));
+        };
+        let builder = if is_backend {
+            crate::app_client_builder(&settings, &state)
+        } else {
+            crate::external_client_builder(&settings, &state)
+        };
+        (
+            builder,
+            url,
+            is_backend,
+            backend,
+            settings.allow_executable_downloads,
+            settings.download_scan_command.clone(),
+        )
+    };
+    let expected_sha256 = match expected_sha256.filter(|digest| !digest.trim().is_empty()) {
+        Some(digest) => Some(normalize_digest(&digest).ok_or("Invalid SHA-256 digest")?),
+        None => None,
+    };
+
+    let client = builder
+        .connect_timeout(Duration::from_secs(DOWNLOAD_CONNECT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let authorization = is_backend
+        .then(|| state.auth.authorization_header())
+        .flatten();
+    let host_header = backend.host_header.filter(|_| is_backend);
+    let get = |url: &str| {
+        let mut request = client.get(url);
+        if let Some(authorization) = &authorization {
+            request = request.header("authorization", authorization);
+        }
+        if let Some(host) = &host_header {
+            request = request.header("host", host);
+        }
+        request
+    };
+
+    let response = get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let header_sha256 = header_digest(response.headers());
+    let dir = app.path().download_dir().map_err(|e| e.to_string())?;
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let name = sanitize_file_name(&file_name.unwrap_or_else(|| response_file_name(&response)));
+    if !allow_executables && quarantine::is_executable(Path::new(&name)) {
+        return Err(format!(
+            "Refusing to download executable file {}; allow it with set_allow_executable_downloads",
+            name
+        ));
+    }
+    let (path, part_file) = reserve_path(&dir, &name).await?;
+    let part_path = part_path(&path);
+
+    let download = Download {
+        id: state.downloads.next_id.fetch_add(1, Ordering::Relaxed),
+        url: url.clone(),
+        path: path.to_string_lossy().into_owned(),
+        status: DownloadStatus::Downloading,
+        received: 0,
+        total: response.content_length(),
+        sha256: None,
+        expected_sha256: expected_sha256.clone().or_else(|| header_sha256.clone()),
+        verification: Verification::Unverified,
+        quarantined: false,
+        scan: ScanStatus::NotScanned,
+        scan_detail: None,
+        error: None,
+        started_at: now_ms(),
+        finished_at: None,
+    };
+    let id = download.id;
+    state
+        .downloads
+        .entries
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(download.clone());
+    let _ = app.emit("download-updated", &download);
+
+    let fail = |e: String, verification: Verification| {
+        log::error!(target: "downloads", "Download of {} failed: {}", url, e);
+        state.downloads.update(&app, id, |download| {
+            download.status = DownloadStatus::Failed;
+            download.verification = verification;
+            download.error = Some(e.clone());
+            download.finished_at = Some(now_ms());
+        });
+        e
+    };
+
+    let sha256 = match receive(&app, id, response, part_file).await {
+        Ok(sha256) => sha256,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(fail(e, Verification::Unverified));
+        }
+    };
+
+    let expected = match expected_sha256.or(header_sha256) {
+        Some(digest) => Some(digest),
+        None => match sidecar_url(&url) {
+            Some(sidecar) => sidecar_digest(get(&sidecar)).await,
+            None => None,
+        },
+    };
+    let verification = match &expected {
+        None => Verification::Unverified,
+        Some(expected) if *expected == sha256 => Verification::Verified,
+        Some(expected) => {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            state.downloads.update(&app, id, |download| {
+                download.sha256 = Some(sha256.clone());
+                download.expected_sha256 = Some(expected.clone());
+            });
+            let e = format!("Checksum mismatch: expected {}, got {}", expected, sha256);
+            return Err(fail(e, Verification::Corrupt));
+        }
+    };
+
+    // Mark before the rename, so the file never appears under its real name unmarked
+    let marked = quarantine::mark_downloaded(&part_path, &url)
+        .map_err(|e| format!("Failed to mark the file as downloaded: {}", e));
+    let quarantined = match marked {
+        Ok(quarantined) => tokio::fs::rename(&part_path, &path)
+            .await
+            .map(|_| quarantined)
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    let quarantined = match quarantined {
+        Ok(quarantined) => quarantined,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(fail(e, verification));
+        }
+    };
+
+    let (scan, scan_detail) = download_scan::scan(&scan_command, &path).await;
+    let download = state
+        .downloads
+        .update(&app, id, |download| {
+            download.status = DownloadStatus::Completed;
+            download.sha256 = Some(sha256);
+            download.expected_sha256 = expected;
+            download.verification = verification;
+            download.quarantined = quarantined;
+            download.scan = scan;
+            download.scan_detail = scan_detail;
+            download.finished_at = Some(now_ms());
+        })
+        .ok_or("Download entry missing")?;
+
+    if open.unwrap_or(false) && scan != ScanStatus::Flagged {
+        open_path(&app, &download)?;
+    }
+    Ok(download)
+}
+
+/// Allow or refuse downloading executables and installers. A saved executable can
+/// be run with `download_file(open)`, so allowing them asks the user in a native
+/// dialog first. Returns whether the setting was saved.
+#[tauri::command]
+pub async fn set_allow_executable_downloads(
+    app: AppHandle,
+    allow: bool,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    if allow {
+        let message = "Allow the assistant to download executables and installers? \
+                       Downloaded programs can be opened and run on this device.";
+        if !tools::confirm(&app, "Executable Downloads", message, "Allow").await? {
+            return Ok(false);
+        }
+    }
+    state.settings.lock().await.allow_executable_downloads = allow;
+    state.save().await?;
+    Ok(true)
+}
+
+/// List this session's downloads, newest first.
+#[tauri::command]
+pub async fn list_downloads(state: State<'_, AppState>) -> Result<Vec<Download>, String> {
+    let mut downloads = state
+        .downloads
+        .entries
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    downloads.reverse();
+    Ok(downloads)
+}
+
+/// Open a completed download. Files that failed verification or scanning are refused.
+#[tauri::command]
+pub async fn open_download(
+    app: AppHandle,
+    id: u64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let download = state
+        .downloads
+        .get(id)
+        .ok_or_else(|| format!("No download {}", id))?;
+    open_path(&app, &download)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        disposition_file_name, header_digest, part_path, reserve_path, sanitize_file_name,
+        sidecar_url,
+    };
+    use crate::quarantine;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use std::path::Path;
+
+    #[test]
+    fn reads_sha256_digest_headers() {
+        // SHA-256 of "hello"
+        let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let base64 = "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=";
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "repr-digest",
+            HeaderValue::from_str(&format!("sha-512=:abc:, sha-256=:{}:", base64)).unwrap(),
+        );
+        assert_eq!(header_digest(&headers).as_deref(), Some(hex));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "digest",
+            HeaderValue::from_str(&format!("SHA-256={}", base64)).unwrap(),
+        );
+        assert_eq!(header_digest(&headers).as_deref(), Some(hex));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-checksum-sha256",
+            HeaderValue::from_str(&hex.to_uppercase()).unwrap(),
+        );
+        assert_eq!(header_digest(&headers).as_deref(), Some(hex));
+
+        assert_eq!(header_digest(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn strips_trailing_dots_and_spaces_from_file_names() {
+        for name in ["setup.exe.", "setup.exe ", "setup.exe. . "] {
+            let name = sanitize_file_name(name);
+            assert_eq!(name, "setup.exe");
+            assert!(quarantine::is_executable(Path::new(&name)));
+        }
+        assert_eq!(sanitize_file_name("../.profile"), "_.profile");
+        assert_eq!(sanitize_file_name(" . "), "download");
+    }
+
+    #[test]
+    fn appends_sidecar_suffix_to_the_path() {
+        assert_eq!(
+            sidecar_url("https://example.com/f.zip?sig=x#top").as_deref(),
+            Some("https://example.com/f.zip.sha256")
+        );
+        assert_eq!(sidecar_url("not a url"), None);
+    }
+
+    #[test]
+    fn prefers_extended_content_disposition_file_name() {
+        assert_eq!(
+            disposition_file_name(
+                "attachment; filename=\"fallback.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+            )
+            .as_deref(),
+            Some("résumé.pdf")
+        );
+        assert_eq!(
+            disposition_file_name("attachment; filename*=utf-8'en'a%20b.txt").as_deref(),
+            Some("a b.txt")
+        );
+        assert_eq!(
+            disposition_file_name("attachment; filename=\"plain.txt\"").as_deref(),
+            Some("plain.txt")
+        );
+        assert_eq!(
+            disposition_file_name("attachment; filename=x.txt; filename*=UTF-8''bad%zz").as_deref(),
+            Some("x.txt")
+        );
+    }
+
+    #[tokio::test]
+    async fn reserves_distinct_paths_for_the_same_name() {
+        let dir = std::env::temp_dir().join(format!("assistant-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.pdf"), b"done").unwrap();
+
+        let (first, _) = reserve_path(&dir, "report.pdf").await.unwrap();
+        let (second, _) = reserve_path(&dir, "report.pdf").await.unwrap();
+        assert_eq!(first, dir.join("report (1).pdf"));
+        assert_eq!(second, dir.join("report (2).pdf"));
+        assert!(part_path(&first).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use tauri::{AppHandle, State, Url};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::{tools, AppState};
+
+/// Normalize allowlist entries: lowercase hosts, with any scheme, path or `*.` removed.
+fn normalize_allowlist(entries: Vec<String>) -> Vec<String> {
+    let mut domains: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let entry = entry.trim().to_ascii_lowercase();
+            let entry = entry
+                .split_once("://")
+                .map_or(entry.as_str(), |(_, rest)| rest);
+            let host = entry.split(['/', '?', '#']).next().unwrap_or_default();
+            host.trim_start_matches("*.").trim_matches('.').to_string()
+        })
+        .filter(|domain| !domain.is_empty())
+        .collect();
+    domains.sort();
+    domains.dedup();
+    domains
+}
+
+/// Set the domains whose links open without confirmation. Trusting new domains
+/// skips the prompt that guards against planted links, so any domain not already
+/// on the list is shown in a native dialog first. Returns whether the list was saved.
+#[tauri::command]
+pub async fn set_link_allowlist(
+    app: AppHandle,
+    domains: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let domains = normalize_allowlist(domains);
+    let added: Vec<&String> = {
+        let settings = state.settings.lock().await;
+        domains
+            .iter()
+            .filter(|domain| !settings.link_allowlist.contains(domain))
+            .collect()
+    };
+    if !added.is_empty() {
+        let message = format!(
+            "Open links to these sites (and their subdomains) without asking?\n\n{}",
+            added
+                .iter()
+                .map(|domain| domain.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        if !tools::confirm(&app, "Trusted Sites", &message, "Trust").await? {
+            return Ok(false);
+        }
+    }
+    state.settings.lock().await.link_allowlist = domains;
+    state.save().await?;
+    Ok(true)
+}
+
+/// Whether the link's host is an allowlisted domain or one of its subdomains.
+fn is_allowlisted(url: &Url, allowlist: &[String]) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    allowlist.iter().any(|domain| {
+        host == *domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Open a link in the default browser or mail client.
+///
+/// Links to allowlisted domains open directly. Anything else is shown in full in a
+/// native confirmation dialog first, since links in assistant output may have been
+/// planted by prompt injection. Returns whether the link was opened.
+#[tauri::command]
+pub async fn open_external_url(
+    app: AppHandle,
+    url: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| e.to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https" | "mailto") {
+        return Err(format!("Blocked external URL: {}", url));
+    }
+
+    let allowlisted = {
+        let settings = state.settings.lock().await;
+        is_allowlisted(&parsed, &settings.link_allowlist)
+    };
+    if !allowlisted {
+        let message = match parsed.host_str() {
+            Some(host) => format!(
+                "Open this link? {} is not on your list of trusted sites.\n\n{}",
+                host, parsed
+            ),
+            None => format!("Open this link?\n\n{}", parsed),
+        };
+        if !tools::confirm(&app, "Open Link", &message, "Open").await? {
+            log::info!(target: "links", "Declined to open {}", parsed);
+            return Ok(false);
+        }
+    }
+
+    app.opener()
+        .open_url(parsed.as_str(), None::<&str>)
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_allowlisted, normalize_allowlist};
+    use tauri::Url;
+
+    #[test]
+    fn matches_allowlisted_domains_and_subdomains() {
+        let allowlist = normalize_allowlist(vec![
+            "https://GitHub.com/".to_string(),
+            "*.example.org".to_string(),
+            " ".to_string(),
+        ]);
+        assert_eq!(allowlist, vec!["example.org", "github.com"]);
+
+        let allowed = |url: &str| is_allowlisted(&Url::parse(url).unwrap(), &allowlist);
+        assert!(allowed("https://github.com/kcosr/assistant"));
+        assert!(allowed("https://docs.example.org/page"));
+        assert!(!allowed("https://notgithub.com/"));
+        assert!(!allowed("https://github.com.evil.test/"));
+        assert!(!allowed("mailto:someone@github.com"));
+    }
+}
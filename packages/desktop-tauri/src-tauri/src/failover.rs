@@ -0,0 +1,197 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::backend_host::BackendHost;
+use crate::{AppState, ClientKey};
+
+/// How often the backends are health-checked while fallbacks are configured.
+const FAILOVER_INTERVAL_SECS: u64 = 10;
+const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+/// Unauthenticated endpoint used to check whether a backend is up.
+const HEALTH_CHECK_PATH: &str = "/api/version";
+
+/// Backend the proxies currently send requests to, reported by
+/// `get_active_backend` and `active-backend` events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveBackend {
+    pub url: String,
+    /// Position in the list: 0 is `backend_url`, then `fallback_backend_urls`.
+    pub index: usize,
+    pub primary: bool,
+}
+
+/// Check a fallback list before it is saved.
+pub fn validate(urls: &[String]) -> Result<(), String> {
+    for url in urls {
+        let parsed =
+            reqwest::Url::parse(url).map_err(|e| format!("Invalid fallback URL {}: {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Fallback URL must use http or https: {}", url));
+        }
+    }
+    Ok(())
+}
+
+/// Index of the backend to use: the first healthy one in priority order, so the
+/// primary is taken back as soon as it recovers. With none healthy the current
+/// one is kept rather than flapping.
+fn choose(healthy: &[bool], current: usize) -> usize {
+    healthy.iter().position(|&up| up).unwrap_or(current)
+}
+
+/// Tracks which of the configured backends the proxies use. The primary is
+/// `backend_url`; `fallback_backend_urls` are tried in order when it is down.
+pub struct Failover {
+    app: AppHandle,
+    /// Active fallback, or `None` while on the primary.
+    active: Mutex<Option<ActiveBackend>>,
+}
+
+impl Failover {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            active: Mutex::new(None),
+        }
+    }
+
+    /// URL of the active fallback, or `None` while on the primary.
+    pub fn fallback_url(&self) -> Option<String> {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|active| active.url.clone())
+    }
+
+    pub fn status(&self, primary_url: &str) -> ActiveBackend {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| ActiveBackend {
+                url: primary_url.to_string(),
+                index: 0,
+                primary: true,
+            })
+    }
+
+    /// Switch to the backend at `index` in `urls`, emitting `active-backend` if
+    /// that changes anything.
+    fn activate(&self, urls: &[String], index: usize) {
+        let next = (index > 0).then(|| ActiveBackend {
+            url: urls[index].clone(),
+            index,
+            primary: false,
+        });
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if *active == next {
+            return;
+        }
+        *active = next;
+        drop(active);
+        log::warn!(
+            target: "http-proxy",
+            "Switching to backend {} ({})",
+            urls[index],
+            if index == 0 { "primary" } else { "fallback" }
+        );
+        let status = self.status(&urls[0]);
+        let _ = self.app.emit("active-backend", status);
+    }
+
+    /// Go back to the primary, e.g. after the backend list changed.
+    pub fn reset(&self, primary_url: &str) {
+        self.activate(&[primary_url.to_string()], 0);
+    }
+}
+
+async fn is_healthy(client: &reqwest::Client, url: &str) -> bool {
+    let url = format!("{}{}", url.trim_end_matches('/'), HEALTH_CHECK_PATH);
+    match client
+        .get(&url)
+        .timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS))
+        .send()
+        .await
+    {
+        Ok(response) => !response.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+/// Health-check the backends in priority order and move the proxies to the
+/// first one that answers.
+pub async fn run_failover_monitor(app: AppHandle) {
+    // Rebuilt when the settings it was built from change
+    let mut client: Option<(ClientKey, reqwest::Client)> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(FAILOVER_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let state: State<'_, AppState> = app.state();
+        let settings = state.settings.lock().await.clone();
+        let primary = BackendHost::from_settings(&settings);
+        if settings.fallback_backend_urls.is_empty() {
+            state.failover.reset(&primary.url);
+            continue;
+        }
+        let key = ClientKey::from_settings(&settings);
+        if client
+            .as_ref()
+            .is_none_or(|(built_for, _)| *built_for != key)
+        {
+            match crate::with_pac(crate::http_client_builder(&settings), &settings, &state.pac)
+                .build()
+            {
+                Ok(built) => client = Some((key, built)),
+                Err(e) => {
+                    log::error!(target: "http-proxy", "Failed to create HTTP client: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some((_, http_client)) = &client else {
+            continue;
+        };
+
+        let urls: Vec<String> = std::iter::once(primary.url.clone())
+            .chain(settings.fallback_backend_urls.iter().cloned())
+            .collect();
+        let current = state.failover.status(&primary.url);
+        let current = urls.iter().position(|url| *url == current.url).unwrap_or(0);
+        // Backends after the first healthy one need no check
+        let mut healthy = Vec::with_capacity(urls.len());
+        for url in &urls {
+            let up = is_healthy(http_client, url).await;
+            healthy.push(up);
+            if up {
+                break;
+            }
+        }
+        state.failover.activate(&urls, choose(&healthy, current));
+    }
+}
+
+/// Which backend the proxies are currently using.
+#[tauri::command]
+pub async fn get_active_backend(state: State<'_, AppState>) -> Result<ActiveBackend, String> {
+    let settings = state.settings.lock().await;
+    Ok(state
+        .failover
+        .status(&BackendHost::from_settings(&settings).url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::choose;
+
+    #[test]
+    fn prefers_the_first_healthy_backend() {
+        assert_eq!(choose(&[true], 2), 0);
+        assert_eq!(choose(&[false, false, true], 0), 2);
+        assert_eq!(choose(&[false, true], 2), 1);
+        // Nothing answers: stay put
+        assert_eq!(choose(&[false, false, false], 1), 1);
+    }
+}
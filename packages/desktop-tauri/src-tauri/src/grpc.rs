@@ -0,0 +1,54 @@
+/// Whether `content_type` is gRPC or gRPC-web (`application/grpc`, `application/grpc+proto`,
+/// `application/grpc-web-text`, ...).
+pub fn is_grpc_content_type(content_type: &str) -> bool {
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    content_type == "application/grpc"
+        || content_type.starts_with("application/grpc+")
+        || content_type.starts_with("application/grpc-web")
+}
+
+/// Whether a request is native gRPC, which needs HTTP/2 and trailers end to end.
+/// gRPC-web works over HTTP/1.1 and carries its trailers in the body.
+pub fn is_native_grpc(headers: &hyper::HeaderMap) -> bool {
+    headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            is_grpc_content_type(value)
+                && !value
+                    .trim()
+                    .to_ascii_lowercase()
+                    .starts_with("application/grpc-web")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_grpc_content_type, is_native_grpc};
+    use hyper::HeaderMap;
+
+    #[test]
+    fn recognizes_grpc_requests() {
+        assert!(is_grpc_content_type("application/grpc"));
+        assert!(is_grpc_content_type("application/grpc+proto"));
+        assert!(is_grpc_content_type(
+            "application/grpc-web-text; charset=utf-8"
+        ));
+        assert!(!is_grpc_content_type("application/grpcx"));
+        assert!(!is_grpc_content_type("application/json"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/grpc+proto".parse().unwrap());
+        assert!(is_native_grpc(&headers));
+        headers.insert(
+            "content-type",
+            "application/grpc-web+proto".parse().unwrap(),
+        );
+        assert!(!is_native_grpc(&headers));
+    }
+}
@@ -0,0 +1,84 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+/// Head start each connection attempt gets before the next one starts (RFC 8305).
+const ATTEMPT_DELAY_MS: u64 = 250;
+
+/// Alternate address families, starting with the family of the first address,
+/// so one unreachable family does not have to time out address by address.
+pub fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_v6 = first.is_ipv6();
+    let (mut preferred, mut fallback): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_v6);
+    let mut interleaved = Vec::with_capacity(preferred.len() + fallback.len());
+    preferred.reverse();
+    fallback.reverse();
+    loop {
+        match (preferred.pop(), fallback.pop()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Connect to the first of `addrs` that answers, racing attempts in
+/// [`interleave`] order. Each attempt starts when the previous one fails or
+/// has had its head start; the rest are dropped once one succeeds.
+pub async fn connect(addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut pending = interleave(addrs.to_vec()).into_iter();
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    loop {
+        if let Some(addr) = pending.next() {
+            attempts.spawn(TcpStream::connect(addr));
+        } else if attempts.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "No addresses to connect to")
+            }));
+        }
+        let delay = tokio::time::sleep(Duration::from_millis(ATTEMPT_DELAY_MS));
+        tokio::select! {
+            Some(result) = attempts.join_next() => match result.map_err(io::Error::other)? {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            },
+            _ = delay, if pending.len() > 0 => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{connect, interleave};
+    use std::net::SocketAddr;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn races_address_families() {
+        let addrs: Vec<SocketAddr> = ["[::1]:1", "[::2]:1", "10.0.0.1:1", "[::3]:1"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let order: Vec<String> = interleave(addrs).iter().map(|a| a.to_string()).collect();
+        assert_eq!(order, ["[::1]:1", "10.0.0.1:1", "[::2]:1", "[::3]:1"]);
+
+        // A refused address falls through to the next one without waiting
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = closed.local_addr().unwrap();
+        drop(closed);
+        let stream = connect(&[refused, listener.local_addr().unwrap()])
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+        assert!(connect(&[refused]).await.is_err());
+        assert!(connect(&[]).await.is_err());
+    }
+}
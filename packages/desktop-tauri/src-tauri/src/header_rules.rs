@@ -0,0 +1,136 @@
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+/// Which side of the exchange a rule edits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Headers sent to the backend.
+    Request,
+    /// Headers returned to the webview.
+    Response,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Append a value, keeping any existing ones.
+    Add,
+    /// Replace all values, adding the header if it is missing.
+    Replace,
+    /// Drop the header.
+    Remove,
+}
+
+/// A header edit applied to requests whose path starts with `path_prefix`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderRule {
+    #[serde(default = "default_path_prefix")]
+    pub path_prefix: String,
+    pub direction: Direction,
+    pub action: Action,
+    pub name: String,
+    /// Required for `add` and `replace`.
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+fn default_path_prefix() -> String {
+    "/".to_string()
+}
+
+/// Check a rule list before it is saved.
+pub fn validate(rules: &[HeaderRule]) -> Result<(), String> {
+    for rule in rules {
+        if !rule.path_prefix.starts_with('/') {
+            return Err(format!(
+                "Header rule path prefix must start with '/': {}",
+                rule.path_prefix
+            ));
+        }
+        HeaderName::from_bytes(rule.name.as_bytes())
+            .map_err(|_| format!("Invalid header name in rule: {}", rule.name))?;
+        match (&rule.value, rule.action) {
+            (_, Action::Remove) => {}
+            (Some(value), _) => {
+                HeaderValue::from_str(value)
+                    .map_err(|_| format!("Invalid value for header {}", rule.name))?;
+            }
+            (None, _) => return Err(format!("Header rule for {} needs a value", rule.name)),
+        }
+    }
+    Ok(())
+}
+
+/// Apply the rules for `direction` that match `path`, in order.
+pub fn apply(rules: &[HeaderRule], direction: Direction, path: &str, headers: &mut HeaderMap) {
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.direction == direction && path.starts_with(&rule.path_prefix))
+    {
+        // Rules are validated when saved
+        let Ok(name) = HeaderName::from_bytes(rule.name.as_bytes()) else {
+            continue;
+        };
+        let value = rule
+            .value
+            .as_deref()
+            .and_then(|value| HeaderValue::from_str(value).ok());
+        match (rule.action, value) {
+            (Action::Remove, _) => {
+                headers.remove(&name);
+            }
+            (Action::Add, Some(value)) => {
+                headers.append(name, value);
+            }
+            (Action::Replace, Some(value)) => {
+                headers.insert(name, value);
+            }
+            (_, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, validate, Action, Direction, HeaderRule};
+    use hyper::header::HeaderMap;
+
+    fn rule(path_prefix: &str, action: Action, name: &str, value: Option<&str>) -> HeaderRule {
+        HeaderRule {
+            path_prefix: path_prefix.to_string(),
+            direction: Direction::Request,
+            action,
+            name: name.to_string(),
+            value: value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn edits_headers_for_matching_paths() {
+        let rules = vec![
+            rule("/", Action::Remove, "x-tracking-id", None),
+            rule("/api", Action::Replace, "x-gateway-key", Some("secret")),
+            rule("/api", Action::Add, "via", Some("desktop")),
+            rule("/files", Action::Replace, "x-files", Some("1")),
+        ];
+        assert!(validate(&rules).is_ok());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tracking-id", "abc".parse().unwrap());
+        headers.insert("x-gateway-key", "old".parse().unwrap());
+        headers.insert("via", "1.1 webview".parse().unwrap());
+        apply(&rules, Direction::Request, "/api/chat", &mut headers);
+        assert!(!headers.contains_key("x-tracking-id"));
+        assert_eq!(headers["x-gateway-key"], "secret");
+        assert_eq!(headers.get_all("via").iter().count(), 2);
+        assert!(!headers.contains_key("x-files"));
+
+        let mut headers = HeaderMap::new();
+        apply(&rules, Direction::Response, "/api/chat", &mut headers);
+        assert!(headers.is_empty());
+
+        assert!(validate(&[rule("/", Action::Add, "x-a", None)]).is_err());
+        assert!(validate(&[rule("/", Action::Remove, "bad header", None)]).is_err());
+    }
+}
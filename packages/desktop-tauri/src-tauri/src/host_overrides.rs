@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Static host name → IP address mappings that bypass DNS, like `/etc/hosts`.
+pub type HostOverrides = BTreeMap<String, IpAddr>;
+
+/// Check a mapping table before it is saved.
+pub fn validate(overrides: &HostOverrides) -> Result<(), String> {
+    for host in overrides.keys() {
+        if host.is_empty()
+            || host
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, ':' | '/' | '?' | '#' | '@'))
+        {
+            return Err(format!("Invalid host name in host overrides: {}", host));
+        }
+    }
+    Ok(())
+}
+
+/// Host names are matched case-insensitively, ignoring a trailing dot.
+pub fn normalize(overrides: HostOverrides) -> HostOverrides {
+    overrides
+        .into_iter()
+        .map(|(host, ip)| (host.trim_end_matches('.').to_ascii_lowercase(), ip))
+        .collect()
+}
+
+/// The overridden address for `host:port`, if it is mapped.
+pub fn lookup(overrides: &HostOverrides, host: &str, port: u16) -> Option<SocketAddr> {
+    if overrides.is_empty() {
+        return None;
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    overrides.get(&host).map(|ip| SocketAddr::new(*ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lookup, normalize, validate, HostOverrides};
+
+    #[test]
+    fn maps_hosts_without_dns() {
+        let overrides = normalize(HostOverrides::from([
+            ("Assistant".to_string(), "10.0.0.5".parse().unwrap()),
+            ("v6.internal.".to_string(), "fd00::5".parse().unwrap()),
+        ]));
+        assert!(validate(&overrides).is_ok());
+        assert_eq!(
+            lookup(&overrides, "assistant", 443),
+            Some("10.0.0.5:443".parse().unwrap())
+        );
+        assert_eq!(
+            lookup(&overrides, "V6.internal", 80),
+            Some("[fd00::5]:80".parse().unwrap())
+        );
+        assert_eq!(lookup(&overrides, "other", 80), None);
+
+        let bad = HostOverrides::from([("a:1".to_string(), "10.0.0.5".parse().unwrap())]);
+        assert!(validate(&bad).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use reqwest::Version;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long requests skip HTTP/3 after it failed.
+const FALLBACK_SECS: u64 = 5 * 60;
+
+/// Whether HTTP/3 support was compiled in (`--features http3`).
+pub const SUPPORTED: bool = cfg!(feature = "http3");
+
+/// Check the `http3` setting before it is saved.
+pub fn validate(enabled: bool) -> Result<(), String> {
+    if enabled && !SUPPORTED {
+        return Err("HTTP/3 support requires building with --features http3".to_string());
+    }
+    Ok(())
+}
+
+/// Experimental HTTP/3 (QUIC) to the backend. Requests that fail over QUIC are
+/// resent over TCP, and later requests stay on TCP for a while.
+pub struct Http3 {
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl Http3 {
+    pub fn new() -> Self {
+        Self {
+            failed_at: Mutex::new(None),
+        }
+    }
+
+    /// HTTP/3 unless it failed recently; `None` leaves the choice to ALPN.
+    pub fn version(&self) -> Option<Version> {
+        let failed_at = *self.failed_at.lock().unwrap_or_else(|e| e.into_inner());
+        match failed_at {
+            Some(at) if at.elapsed() < Duration::from_secs(FALLBACK_SECS) => None,
+            _ => Some(Version::HTTP_3),
+        }
+    }
+
+    /// Note the version an HTTP/3 request was answered over (`None` if it failed).
+    pub fn record(&self, answered_over: Option<Version>) {
+        if answered_over == Some(Version::HTTP_3) {
+            return;
+        }
+        let mut failed_at = self.failed_at.lock().unwrap_or_else(|e| e.into_inner());
+        if failed_at.is_none_or(|at| at.elapsed() >= Duration::from_secs(FALLBACK_SECS)) {
+            log::warn!(
+                target: "http-proxy",
+                "HTTP/3 to the backend failed, using HTTP/2 or HTTP/1.1 for {} minutes",
+                FALLBACK_SECS / 60
+            );
+        }
+        *failed_at = Some(Instant::now());
+    }
+}
+
+/// Send `request`, resending it over TCP if it asked for HTTP/3 and that failed.
+pub async fn send(request: reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    if request.version() != Version::HTTP_3 {
+        return client.execute(request).await;
+    }
+    let fallback = request.try_clone();
+    match (client.execute(request).await, fallback) {
+        (Err(e), Some(mut fallback)) => {
+            log::debug!(target: "http-proxy", "HTTP/3 request failed ({}), retrying over TCP", e);
+            *fallback.version_mut() = Version::HTTP_11;
+            client.execute(fallback).await
+        }
+        (result, _) => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Http3;
+    use reqwest::Version;
+
+    #[test]
+    fn falls_back_after_failure() {
+        let http3 = Http3::new();
+        assert_eq!(http3.version(), Some(Version::HTTP_3));
+        http3.record(Some(Version::HTTP_3));
+        assert_eq!(http3.version(), Some(Version::HTTP_3));
+        http3.record(Some(Version::HTTP_2));
+        assert_eq!(http3.version(), None);
+    }
+}
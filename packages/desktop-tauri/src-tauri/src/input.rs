@@ -0,0 +1,30 @@
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+
+/// Primary shortcut modifier for the current platform (Cmd on macOS, Ctrl elsewhere).
+#[cfg(target_os = "macos")]
+const SHORTCUT_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const SHORTCUT_MODIFIER: Key = Key::Control;
+
+fn new_enigo() -> Result<Enigo, String> {
+    Enigo::new(&Settings::default()).map_err(|e| e.to_string())
+}
+
+/// Press the platform copy shortcut in the frontmost application.
+pub fn send_copy_shortcut() -> Result<(), String> {
+    let mut enigo = new_enigo()?;
+    enigo
+        .key(SHORTCUT_MODIFIER, Direction::Press)
+        .map_err(|e| e.to_string())?;
+    let result = enigo.key(Key::Unicode('c'), Direction::Click);
+    enigo
+        .key(SHORTCUT_MODIFIER, Direction::Release)
+        .map_err(|e| e.to_string())?;
+    result.map_err(|e| e.to_string())
+}
+
+/// Type `text` into whichever application currently has keyboard focus.
+pub fn type_text(text: &str) -> Result<(), String> {
+    let mut enigo = new_enigo()?;
+    enigo.text(text).map_err(|e| e.to_string())
+}
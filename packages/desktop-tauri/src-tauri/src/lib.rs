@@ -1,24 +1,169 @@
 use base64::Engine;
-use futures_util::{SinkExt, StreamExt};
-use http_body_util::{BodyExt, Full};
-use hyper::body::{Bytes, Incoming};
-use hyper::server::conn::http1;
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
 use hyper::service::service_fn;
 use hyper::{Request, Response, StatusCode};
-use hyper_util::rt::TokioIo;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::fs;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tauri::async_runtime::Mutex;
 use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_opener::OpenerExt;
 use tokio::net::TcpListener;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+#[cfg(desktop)]
+mod audio_devices;
+mod audio_processing;
+mod auth;
+mod backend_host;
+mod backoff;
+mod bandwidth;
+mod body_limit;
+mod byte_range;
+mod calendar;
+mod canary;
+mod circuit_breaker;
+mod coalesce;
+mod compression;
+mod concurrency;
+mod connection_quality;
+mod content_encoding;
+mod data_dir;
+#[cfg(desktop)]
+mod dictation;
+mod discovery;
+mod dns_cache;
+mod doh;
+mod download_scan;
+mod downloads;
+mod external_links;
+mod failover;
+mod grpc;
+mod happy_eyeballs;
+mod header_rules;
+mod host_overrides;
+mod http3;
+#[cfg(desktop)]
+mod input;
+mod local_files;
+mod logging;
+mod mime;
+mod notes_search;
+mod notifications;
+mod ocr;
+mod pac;
+mod path_rewrite;
+mod path_timeouts;
+mod quarantine;
+#[cfg(desktop)]
+mod quick_capture;
+mod rate_limit;
+mod redirects;
+mod request_ids;
+mod response_cache;
+mod response_stream;
+mod retry;
+mod safe_mode;
+mod scheduler;
+mod server_timing;
+#[cfg(desktop)]
+mod shortcuts;
+mod shutdown;
+mod sse;
+mod system_info;
+mod system_proxy;
+mod tcp_tuning;
+#[cfg(desktop)]
+mod terminal;
+#[cfg(desktop)]
+mod text_insertion;
+mod timers;
+mod tools;
+mod transfers;
+mod uploads;
+mod upstream_proxy;
+mod upstreams;
+mod url_preview;
+mod url_rewrite;
+mod version_check;
+mod warmup;
+mod watch_folders;
+mod ws_capabilities;
+mod ws_chunking;
+mod ws_close;
+mod ws_events;
+mod ws_handshake;
+mod ws_keepalive;
+mod ws_limits;
+mod ws_msgpack;
+mod ws_mux;
+mod ws_priority;
+mod ws_sessions;
+mod ws_stats;
+
+use audio_processing::AudioProcessing;
+use auth::AuthManager;
+use backend_host::BackendHost;
+use backoff::ReconnectBackoff;
+use bandwidth::BandwidthMeter;
+use body_limit::BodyLimit;
+use calendar::CalendarStore;
+use canary::Canary;
+use circuit_breaker::CircuitBreaker;
+use coalesce::Coalescer;
+use concurrency::RequestLimiter;
+use connection_quality::ConnectionQuality;
+use content_encoding::{ContentEncodingMode, StreamDecoder};
+use data_dir::DataDir;
+use dns_cache::DnsCache;
+use downloads::Downloads;
+use failover::Failover;
+use header_rules::HeaderRule;
+use host_overrides::HostOverrides;
+use http3::Http3;
+use local_files::LocalFileGrants;
+use logging::LogFormat;
+use notes_search::NotesIndex;
+use notifications::{NotificationRules, Notifier};
+use pac::{Pac, PacRoute};
+use path_rewrite::PathRewrite;
+use path_timeouts::PathTimeout;
+use rate_limit::{RateLimit, RateLimiter};
+use redirects::RedirectPolicy;
+use request_ids::RecentRequests;
+use response_cache::ResponseCache;
+use response_stream::UpstreamStream;
+use retry::RetryPolicy;
+use safe_mode::StartupGuard;
+use scheduler::Scheduler;
+use shutdown::{Shutdown, ShutdownSignal};
+use tcp_tuning::TcpOptions;
+use timers::Timers;
+use transfers::TransferTracker;
+use upstream_proxy::UpstreamProxy;
+use upstreams::Upstream;
+use version_check::VersionCheck;
+use watch_folders::{WatchFolder, WatchFolderStats};
+use ws_capabilities::{NegotiatedCapabilities, WsCapabilities};
+use ws_events::{WsEvents, WsSession};
+use ws_limits::WsLimits;
+use ws_mux::WsMux;
+use ws_sessions::WsSessions;
 
 /// Desktop app settings persisted to disk.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,13 +176,295 @@ pub struct AppSettings {
     #[serde(default = "default_skip_cert_validation")]
     pub skip_cert_validation: bool,
 
+    /// Backends to fail over to, in order, while `backend_url` is unreachable.
+    #[serde(default)]
+    pub fallback_backend_urls: Vec<String>,
+
+    /// Canary backend that `canary_percent` of sessions are routed to.
+    #[serde(default)]
+    pub canary_backend_url: Option<String>,
+
+    /// Share of sessions, 0–100, routed to `canary_backend_url`.
+    #[serde(default)]
+    pub canary_percent: u8,
+
+    /// `Host` header presented to a backend addressed by IP (e.g. `https://10.0.0.5`).
+    #[serde(default)]
+    pub host_header_override: Option<String>,
+
+    /// TLS server name (SNI) for a backend addressed by IP; defaults to the host
+    /// header override.
+    #[serde(default)]
+    pub tls_server_name_override: Option<String>,
+
+    /// HTTP proxy to reach the backend through (e.g. a corporate proxy).
+    #[serde(default)]
+    pub upstream_proxy: Option<UpstreamProxy>,
+
+    /// Proxy auto-config script (`http(s)://` or `file://`) that picks a proxy per
+    /// backend host when no `upstream_proxy` is set.
+    #[serde(default)]
+    pub proxy_pac_url: Option<String>,
+
+    /// Use the OS proxy settings (and `HTTP(S)_PROXY`/`NO_PROXY`) when neither
+    /// `upstream_proxy` nor `proxy_pac_url` is set.
+    #[serde(default = "default_use_system_proxy")]
+    pub use_system_proxy: bool,
+
     /// Local HTTP proxy port (assigned automatically).
     #[serde(default)]
     pub proxy_port: u16,
 
-    /// Local WebSocket proxy port (assigned automatically).
+    /// Local WebSocket proxy port (assigned automatically). Deprecated: the HTTP
+    /// proxy port accepts WebSocket upgrades too.
     #[serde(default)]
     pub ws_proxy_port: u16,
+
+    /// Global shortcut that captures the selected text in the frontmost app
+    /// (empty to disable).
+    #[serde(default = "default_quick_capture_shortcut")]
+    pub quick_capture_shortcut: String,
+
+    /// Global shortcut that authorizes typing an assistant snippet into the
+    /// focused application (empty to disable).
+    #[serde(default = "default_insert_text_shortcut")]
+    pub insert_text_shortcut: String,
+
+    /// Global shortcut that starts/stops dictation into the focused application
+    /// (empty to disable).
+    #[serde(default = "default_dictation_shortcut")]
+    pub dictation_shortcut: String,
+
+    /// Folders whose new or changed files are uploaded automatically.
+    #[serde(default)]
+    pub watch_folders: Vec<WatchFolder>,
+
+    /// Log line format (`pretty` or `json` lines).
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// Data-saver mode: block media prefetches and ask the backend for lighter
+    /// responses (`Save-Data: on`).
+    #[serde(default)]
+    pub data_saver: bool,
+
+    /// Serve cacheable GET responses from memory, revalidating stale ones in the
+    /// background (`resource-updated` events report changes).
+    #[serde(default)]
+    pub stale_while_revalidate: bool,
+
+    /// Cache GET responses that carry an `ETag` or `Last-Modified` and confirm
+    /// stale ones with a conditional request, serving `304`s from the cache.
+    #[serde(default)]
+    pub revalidate_cached_responses: bool,
+
+    /// Keep cached responses on disk so they survive restarts.
+    #[serde(default)]
+    pub persist_response_cache: bool,
+
+    /// Open the upstream connection as soon as the proxy starts and keep it alive
+    /// with periodic requests.
+    #[serde(default = "default_warm_up_connections")]
+    pub warm_up_connections: bool,
+
+    /// Fetch the endpoints the web client loads first once the proxy is ready.
+    #[serde(default)]
+    pub prefetch_on_startup: bool,
+
+    /// TCP keepalive idle time and probe interval in seconds for proxy and upstream
+    /// connections (0 disables), so idle sessions keep their NAT mappings.
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+
+    /// Disable Nagle's algorithm on proxy and upstream connections.
+    #[serde(default = "default_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+
+    /// Idle backend connections kept open for reuse, per host (0 disables reuse).
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+
+    /// How long an idle backend connection is kept for reuse, in seconds (0 keeps
+    /// it until the backend closes it).
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+
+    /// How long backend DNS answers are cached, in seconds (0 disables caching).
+    #[serde(default = "default_dns_cache_ttl_secs")]
+    pub dns_cache_ttl_secs: u64,
+
+    /// Host names resolved to fixed IP addresses instead of through DNS (e.g.
+    /// `assistant` → `10.0.0.5` on networks without internal DNS).
+    #[serde(default)]
+    pub host_overrides: HostOverrides,
+
+    /// DNS-over-HTTPS endpoint (e.g. `https://1.1.1.1/dns-query`) used for
+    /// backend name lookups instead of the system resolver.
+    #[serde(default)]
+    pub dns_over_https_url: Option<String>,
+
+    /// How long to wait for a connection to the backend, in seconds.
+    #[serde(default = "default_proxy_connect_timeout_secs")]
+    pub proxy_connect_timeout_secs: u64,
+
+    /// How long a proxied request may take, in seconds: until the response headers
+    /// for streamed responses, or the whole body for buffered ones.
+    #[serde(default = "default_proxy_request_timeout_secs")]
+    pub proxy_request_timeout_secs: u64,
+
+    /// Request timeouts for backend path prefixes (e.g. long-running streams),
+    /// overriding `proxy_request_timeout_secs`; the longest matching prefix wins.
+    #[serde(default)]
+    pub proxy_path_timeouts: Vec<PathTimeout>,
+
+    /// Retries with exponential backoff for proxied requests that fail transiently.
+    #[serde(default)]
+    pub proxy_retry: RetryPolicy,
+
+    /// Backoff for reconnecting the backend WebSocket and event streams, and for
+    /// probing a backend the circuit breaker marked down.
+    #[serde(default)]
+    pub reconnect_backoff: ReconnectBackoff,
+
+    /// Whether backend redirects are followed by the proxy or passed to the webview.
+    #[serde(default)]
+    pub proxy_redirects: RedirectPolicy,
+
+    /// Extra upstreams for path prefixes (e.g. `/files` → an object store); other
+    /// paths go to `backend_url`.
+    #[serde(default)]
+    pub proxy_upstreams: Vec<Upstream>,
+
+    /// Backend path rewrites (e.g. `/v1` → `/api/v1`) for backends deployed under
+    /// a different base path than the web client expects.
+    #[serde(default)]
+    pub proxy_path_rewrites: Vec<PathRewrite>,
+
+    /// Header edits for proxied requests and responses, applied in order to
+    /// requests whose path matches each rule's prefix.
+    #[serde(default)]
+    pub proxy_header_rules: Vec<HeaderRule>,
+
+    /// Consecutive backend failures after which the proxy fails fast with 503
+    /// until the backend answers again (0 disables).
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+
+    /// Most proxied requests in flight to the backend at once (0 for unlimited).
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// Requests that may wait for a free slot before further ones get a 429.
+    #[serde(default = "default_max_queued_requests")]
+    pub max_queued_requests: usize,
+
+    /// Token-bucket rate limits for backend path prefixes; the longest matching
+    /// prefix applies and other paths are not limited.
+    #[serde(default)]
+    pub proxy_rate_limits: Vec<RateLimit>,
+
+    /// Only speak HTTP/1.1 to the backend, for servers that mishandle HTTP/2.
+    #[serde(default)]
+    pub force_http1: bool,
+
+    /// Experimental: try HTTP/3 (QUIC) to HTTPS backends first, falling back to
+    /// HTTP/2 or HTTP/1.1. Needs a build with the `http3` feature.
+    #[serde(default)]
+    pub http3: bool,
+
+    /// Upload rate limit in bytes per second for proxied request bodies and
+    /// file uploads (0 for unlimited).
+    #[serde(default)]
+    pub upload_limit_bytes_per_sec: u64,
+
+    /// Largest request body the HTTP proxy accepts, in bytes (0 for unlimited).
+    #[serde(default = "default_max_request_body_bytes")]
+    pub max_request_body_bytes: u64,
+
+    /// Proxied request bodies larger than this (or of unknown length) are piped
+    /// upstream as they arrive instead of being buffered (0 always buffers).
+    #[serde(default = "default_stream_request_threshold_bytes")]
+    pub stream_request_threshold_bytes: u64,
+
+    /// Response content types (e.g. `text/html`, `application/json`) whose absolute
+    /// backend URLs are rewritten to the local proxy origin (empty to disable).
+    #[serde(default)]
+    pub rewrite_content_types: Vec<String>,
+
+    /// Compress text responses (gzip/brotli) on the proxy → webview hop.
+    #[serde(default)]
+    pub compress_responses: bool,
+
+    /// Whether compressed backend responses are forwarded as-is or decompressed
+    /// (e.g. for URL rewriting or clients that mishandle an encoding).
+    #[serde(default)]
+    pub response_content_encoding: ContentEncodingMode,
+
+    /// Share one backend WebSocket between all webview sockets.
+    #[serde(default)]
+    pub multiplex_websockets: bool,
+
+    /// Largest WebSocket message the WS proxy accepts from either side, in bytes.
+    #[serde(default = "default_ws_max_message_bytes")]
+    pub ws_max_message_bytes: usize,
+
+    /// Largest single WebSocket frame the WS proxy accepts, in bytes.
+    #[serde(default = "default_ws_max_frame_bytes")]
+    pub ws_max_frame_bytes: usize,
+
+    /// Split text messages larger than this many bytes into chunks for backends
+    /// that announce chunking support (0 sends them whole).
+    #[serde(default)]
+    pub ws_chunk_bytes: usize,
+
+    /// Relay backend MessagePack frames to web clients as JSON text frames, and
+    /// client JSON messages to the backend as MessagePack.
+    #[serde(default)]
+    pub ws_msgpack_transcoding: bool,
+
+    /// Close WebSocket sessions that carried no messages for this many seconds
+    /// (0 keeps them open).
+    #[serde(default)]
+    pub ws_idle_timeout_secs: u64,
+
+    /// Output device for TTS playback (`None` follows the OS default).
+    #[serde(default)]
+    pub audio_output_device: Option<String>,
+
+    /// Microphone for voice sessions (`None` follows the OS default).
+    #[serde(default)]
+    pub audio_input_device: Option<String>,
+
+    /// Suppress TTS playback picked up by the microphone.
+    #[serde(default = "default_audio_processing_enabled")]
+    pub echo_cancellation: bool,
+
+    /// Attenuate steady background noise in captured audio.
+    #[serde(default = "default_audio_processing_enabled")]
+    pub noise_suppression: bool,
+
+    /// Local tool consent scopes the user granted (e.g. `calendar`).
+    #[serde(default)]
+    pub tool_consents: Vec<String>,
+
+    /// Folders indexed for the notes search tool.
+    #[serde(default)]
+    pub notes_folders: Vec<String>,
+
+    /// Domains (and their subdomains) whose links open without a confirmation dialog.
+    /// Only set through `set_link_allowlist`, which asks the user first.
+    #[serde(default)]
+    pub link_allowlist: Vec<String>,
+
+    /// Whether the download manager may save executables and installers.
+    /// Only set through `set_allow_executable_downloads`, which asks the user first.
+    #[serde(default)]
+    pub allow_executable_downloads: bool,
+
+    /// Scanner run on completed downloads, as argv (`{path}` is the file); empty disables.
+    /// Only set through `set_download_scan_command`, which asks the user first.
+    #[serde(default)]
+    pub download_scan_command: Vec<String>,
 }
 
 fn default_backend_url() -> String {
@@ -48,9 +475,110 @@ fn default_skip_cert_validation() -> bool {
     true
 }
 
-const HTTP_PROXY_CONNECT_TIMEOUT_SECS: u64 = 10;
-const HTTP_PROXY_REQUEST_TIMEOUT_SECS: u64 = 30;
+fn default_audio_processing_enabled() -> bool {
+    true
+}
+
+fn default_warm_up_connections() -> bool {
+    true
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    30
+}
+
+fn default_tcp_nodelay() -> bool {
+    true
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    32
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_ws_max_message_bytes() -> usize {
+    64 << 20
+}
+
+fn default_ws_max_frame_bytes() -> usize {
+    16 << 20
+}
+
+fn default_use_system_proxy() -> bool {
+    true
+}
+
+fn default_dns_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_proxy_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_proxy_request_timeout_secs() -> u64 {
+    120
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_max_concurrent_requests() -> usize {
+    64
+}
+
+fn default_max_queued_requests() -> usize {
+    256
+}
+
+fn default_max_request_body_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_stream_request_threshold_bytes() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_quick_capture_shortcut() -> String {
+    if cfg!(desktop) {
+        DEFAULT_QUICK_CAPTURE_SHORTCUT.to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn default_insert_text_shortcut() -> String {
+    if cfg!(desktop) {
+        DEFAULT_INSERT_TEXT_SHORTCUT.to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn default_dictation_shortcut() -> String {
+    if cfg!(desktop) {
+        DEFAULT_DICTATION_SHORTCUT.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Request headers that describe the webview's connection to the proxy.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
 const DEFAULT_BACKEND_URL: &str = "https://assistant";
+const DEFAULT_QUICK_CAPTURE_SHORTCUT: &str = "CmdOrCtrl+Shift+E";
+const DEFAULT_INSERT_TEXT_SHORTCUT: &str = "CmdOrCtrl+Alt+V";
+const DEFAULT_DICTATION_SHORTCUT: &str = "CmdOrCtrl+Alt+D";
 
 fn resolve_default_backend_url(env_value: Option<&str>) -> String {
     let trimmed = env_value.unwrap_or_default().trim();
@@ -63,7 +591,7 @@ fn resolve_default_backend_url(env_value: Option<&str>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{resolve_default_backend_url, DEFAULT_BACKEND_URL};
+    use super::{accepts_trailers, resolve_default_backend_url, DEFAULT_BACKEND_URL};
 
     #[test]
     fn falls_back_to_default_backend_url_when_env_missing() {
@@ -85,6 +613,14 @@ mod tests {
             DEFAULT_BACKEND_URL
         );
     }
+
+    #[test]
+    fn detects_trailers_in_te_header() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(!accepts_trailers(&headers));
+        headers.insert("te", "gzip, Trailers;q=1".parse().unwrap());
+        assert!(accepts_trailers(&headers));
+    }
 }
 
 impl Default for AppSettings {
@@ -92,57 +628,450 @@ impl Default for AppSettings {
         Self {
             backend_url: default_backend_url(),
             skip_cert_validation: default_skip_cert_validation(),
+            fallback_backend_urls: Vec::new(),
+            canary_backend_url: None,
+            canary_percent: 0,
+            host_header_override: None,
+            tls_server_name_override: None,
             proxy_port: 0,
             ws_proxy_port: 0,
+            quick_capture_shortcut: default_quick_capture_shortcut(),
+            insert_text_shortcut: default_insert_text_shortcut(),
+            dictation_shortcut: default_dictation_shortcut(),
+            watch_folders: Vec::new(),
+            log_format: LogFormat::default(),
+            data_saver: false,
+            stale_while_revalidate: false,
+            revalidate_cached_responses: false,
+            persist_response_cache: false,
+            warm_up_connections: default_warm_up_connections(),
+            prefetch_on_startup: false,
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            tcp_nodelay: default_tcp_nodelay(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+            dns_cache_ttl_secs: default_dns_cache_ttl_secs(),
+            host_overrides: HostOverrides::new(),
+            dns_over_https_url: None,
+            proxy_connect_timeout_secs: default_proxy_connect_timeout_secs(),
+            proxy_request_timeout_secs: default_proxy_request_timeout_secs(),
+            proxy_path_timeouts: Vec::new(),
+            proxy_retry: RetryPolicy::default(),
+            reconnect_backoff: ReconnectBackoff::default(),
+            proxy_redirects: RedirectPolicy::default(),
+            proxy_upstreams: Vec::new(),
+            proxy_path_rewrites: Vec::new(),
+            proxy_header_rules: Vec::new(),
+            circuit_breaker_threshold: default_circuit_breaker_threshold(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            max_queued_requests: default_max_queued_requests(),
+            proxy_rate_limits: Vec::new(),
+            force_http1: false,
+            http3: false,
+            upstream_proxy: None,
+            proxy_pac_url: None,
+            use_system_proxy: default_use_system_proxy(),
+            upload_limit_bytes_per_sec: 0,
+            max_request_body_bytes: default_max_request_body_bytes(),
+            stream_request_threshold_bytes: default_stream_request_threshold_bytes(),
+            rewrite_content_types: Vec::new(),
+            compress_responses: false,
+            response_content_encoding: ContentEncodingMode::default(),
+            multiplex_websockets: false,
+            ws_max_message_bytes: default_ws_max_message_bytes(),
+            ws_max_frame_bytes: default_ws_max_frame_bytes(),
+            ws_chunk_bytes: 0,
+            ws_msgpack_transcoding: false,
+            ws_idle_timeout_secs: 0,
+            audio_output_device: None,
+            audio_input_device: None,
+            echo_cancellation: default_audio_processing_enabled(),
+            noise_suppression: default_audio_processing_enabled(),
+            tool_consents: Vec::new(),
+            notes_folders: Vec::new(),
+            link_allowlist: Vec::new(),
+            allow_executable_downloads: false,
+            download_scan_command: Vec::new(),
         }
     }
 }
 
+/// HTTP client settings shared by the proxy and other outbound requests it makes on the
+/// app's behalf. Unless `upstream_proxy` is set, reqwest picks up the OS proxy
+/// settings and proxy environment variables while `use_system_proxy` is on. A PAC
+/// script replaces those; see `with_pac`.
+///
+/// There is no overall timeout, since streamed proxy responses may stay open
+/// indefinitely; callers set their own.
+///
+/// HTTPS backends are offered HTTP/2 via ALPN, so concurrent requests share one
+/// multiplexed connection, unless `force_http1` is set.
+///
+/// hyper's connector already races IPv6 and IPv4 addresses (happy eyeballs), so
+/// the WebSocket connection uses `happy_eyeballs::connect` to match.
+fn http_client_builder(settings: &AppSettings) -> reqwest::ClientBuilder {
+    let mut builder =
+        network_client_builder(settings).danger_accept_invalid_certs(settings.skip_cert_validation);
+    if let Some((server_name, addr)) = BackendHost::from_settings(settings).pin {
+        builder = builder.resolve(&server_name, addr);
+    }
+    builder
+}
+
+/// The network settings of `http_client_builder` (proxy, host overrides, TCP and
+/// pool options) without the backend-only ones: certificates are always
+/// validated and the backend's server name is not pinned.
+fn network_client_builder(settings: &AppSettings) -> reqwest::ClientBuilder {
+    let tcp = TcpOptions::from_settings(settings);
+    let backend = BackendHost::from_settings(settings);
+    let mut builder = reqwest::Client::builder()
+        .tcp_keepalive(tcp.keepalive)
+        .tcp_nodelay(tcp.nodelay)
+        .pool_max_idle_per_host(settings.pool_max_idle_per_host)
+        .pool_idle_timeout(
+            (settings.pool_idle_timeout_secs > 0)
+                .then(|| Duration::from_secs(settings.pool_idle_timeout_secs)),
+        )
+        .connect_timeout(Duration::from_secs(settings.proxy_connect_timeout_secs))
+        .redirect(settings.proxy_redirects.client_policy(&backend.url));
+    for (host, ip) in &settings.host_overrides {
+        // reqwest takes the port from the URL
+        builder = builder.resolve(host, SocketAddr::new(*ip, 0));
+    }
+    if let Some(proxy) = &settings.upstream_proxy {
+        match proxy.client_proxy() {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => log::error!(target: "http-proxy", "{}", e),
+        }
+    } else if !settings.use_system_proxy {
+        builder = builder.no_proxy();
+    }
+    if settings.force_http1 {
+        builder.http1_only()
+    } else {
+        builder.http2_adaptive_window(true)
+    }
+}
+
+/// Settings a cached health-check client was built from; it is rebuilt when
+/// they change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClientKey {
+    skip_cert_validation: bool,
+    backend: BackendHost,
+    upstream_proxy: Option<UpstreamProxy>,
+    proxy_pac_url: Option<String>,
+    use_system_proxy: bool,
+}
+
+impl ClientKey {
+    fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            skip_cert_validation: settings.skip_cert_validation,
+            backend: BackendHost::from_settings(settings),
+            upstream_proxy: settings.upstream_proxy.clone(),
+            proxy_pac_url: settings.proxy_pac_url.clone(),
+            use_system_proxy: settings.use_system_proxy,
+        }
+    }
+}
+
+/// Have backend clients ask the PAC script for their proxy, when one is
+/// configured and no fixed upstream proxy overrides it.
+fn with_pac(
+    builder: reqwest::ClientBuilder,
+    settings: &AppSettings,
+    pac: &Arc<Pac>,
+) -> reqwest::ClientBuilder {
+    if settings.upstream_proxy.is_none() && settings.proxy_pac_url.is_some() {
+        builder.proxy(pac.client_proxy())
+    } else {
+        builder
+    }
+}
+
+/// Client for requests the app makes on its own (downloads, uploads, token
+/// refresh), with the same proxy, PAC, host override and DNS settings as proxied
+/// traffic. Redirects are followed regardless of the proxy's redirect mode, which
+/// only applies to responses relayed to the webview. Backend requests should be
+/// built against `BackendHost::url` and send its `host_header`.
+fn app_client_builder(settings: &AppSettings, state: &AppState) -> reqwest::ClientBuilder {
+    with_pac(http_client_builder(settings), settings, &state.pac)
+        .dns_resolver(state.dns_cache.clone())
+        .redirect(reqwest::redirect::Policy::default())
+}
+
+/// Like `app_client_builder`, for requests to hosts other than the backend: they
+/// keep the proxy, PAC and DNS settings, but `skip_cert_validation` and the
+/// backend pin never apply to them.
+fn external_client_builder(settings: &AppSettings, state: &AppState) -> reqwest::ClientBuilder {
+    with_pac(network_client_builder(settings), settings, &state.pac)
+        .dns_resolver(state.dns_cache.clone())
+        .redirect(reqwest::redirect::Policy::default())
+}
+
 struct ProxyState {
+    /// Backend URL requests are built against (see `BackendHost`).
     backend_url: String,
+    /// Address the backend's server name is pinned to, if overridden.
+    backend_addr: Option<SocketAddr>,
+    /// `Host` header to present when it differs from the server name.
+    host_header: Option<String>,
+    skip_cert_validation: bool,
+    failover: Arc<Failover>,
+    canary: Arc<Canary>,
     http_client: reqwest::Client,
+    /// HTTP/2-only client for native gRPC, which needs HTTP/2 even to plain
+    /// `http://` backends.
+    grpc_client: reqwest::Client,
+    /// Base timeout for proxied requests, extended for slow uploads.
+    request_timeout: Duration,
+    path_timeouts: Vec<PathTimeout>,
+    retry: RetryPolicy,
+    reconnect: ReconnectBackoff,
+    redirects: RedirectPolicy,
+    upstreams: Vec<Upstream>,
+    path_rewrites: Vec<PathRewrite>,
+    header_rules: Vec<HeaderRule>,
+    circuit: Arc<CircuitBreaker>,
+    limiter: RequestLimiter,
+    rate_limiter: Arc<RateLimiter>,
+    auth: Arc<AuthManager>,
+    local_files: Arc<LocalFileGrants>,
+    notifier: Arc<Notifier>,
+    connection_quality: Arc<ConnectionQuality>,
+    recent_requests: Arc<RecentRequests>,
+    bandwidth: Arc<BandwidthMeter>,
+    data_saver: Arc<AtomicBool>,
+    upload_limit: Arc<AtomicU64>,
+    body_limit: Arc<BodyLimit>,
+    stream_request_threshold: Arc<AtomicU64>,
+    rewrite_content_types: Vec<String>,
+    compress_responses: Arc<AtomicBool>,
+    decode_content_encoding: Arc<AtomicBool>,
+    ws_mux: Option<Arc<WsMux>>,
+    ws_limits: WsLimits,
+    /// Chunk size offered to the backend (0 when chunking is off).
+    ws_chunk_bytes: usize,
+    /// Set when the backend speaks MessagePack instead of JSON text frames.
+    ws_msgpack_transcoding: bool,
+    ws_idle_timeout: Option<Duration>,
+    ws_sessions: Arc<WsSessions>,
+    ws_events: Arc<WsEvents>,
+    get_coalescer: Coalescer<UpstreamResult>,
+    response_cache: Arc<ResponseCache>,
+    warm_up_connections: Arc<AtomicBool>,
+    tcp: TcpOptions,
+    dns_cache: Arc<DnsCache>,
+    host_overrides: HostOverrides,
+    upstream_proxy: Option<UpstreamProxy>,
+    pac: Option<Arc<Pac>>,
+    use_system_proxy: bool,
+    /// Set when HTTP/3 is enabled and can be used (QUIC bypasses upstream proxies).
+    http3: Option<Http3>,
+    /// Capabilities announced to the backend on each WebSocket handshake.
+    local_capabilities: WsCapabilities,
+    ws_capabilities: Arc<NegotiatedCapabilities>,
 }
 
 impl ProxyState {
-    fn new(backend_url: String, skip_cert_validation: bool) -> Self {
-        let http_client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(skip_cert_validation)
-            .connect_timeout(Duration::from_secs(HTTP_PROXY_CONNECT_TIMEOUT_SECS))
-            .timeout(Duration::from_secs(HTTP_PROXY_REQUEST_TIMEOUT_SECS))
+    fn new(settings: &AppSettings, app_state: &AppState) -> Self {
+        let http_client = with_pac(http_client_builder(settings), settings, &app_state.pac)
+            .dns_resolver(app_state.dns_cache.clone())
             .build()
             .expect("Failed to create HTTP client");
+        let grpc_client = with_pac(http_client_builder(settings), settings, &app_state.pac)
+            .dns_resolver(app_state.dns_cache.clone())
+            .http2_prior_knowledge()
+            .build()
+            .expect("Failed to create gRPC client");
+
+        let backend = BackendHost::from_settings(settings);
 
         Self {
-            backend_url,
+            backend_url: backend.url,
+            backend_addr: backend.pin.map(|(_, addr)| addr),
+            host_header: backend.host_header,
+            skip_cert_validation: settings.skip_cert_validation,
+            failover: app_state.failover.clone(),
+            canary: app_state.canary.clone(),
             http_client,
+            grpc_client,
+            request_timeout: Duration::from_secs(settings.proxy_request_timeout_secs),
+            path_timeouts: settings.proxy_path_timeouts.clone(),
+            retry: settings.proxy_retry.clone(),
+            reconnect: settings.reconnect_backoff.clone(),
+            redirects: settings.proxy_redirects.clone(),
+            upstreams: settings.proxy_upstreams.clone(),
+            path_rewrites: settings.proxy_path_rewrites.clone(),
+            header_rules: settings.proxy_header_rules.clone(),
+            circuit: app_state.circuit.clone(),
+            limiter: RequestLimiter::new(
+                settings.max_concurrent_requests,
+                settings.max_queued_requests,
+            ),
+            rate_limiter: app_state.rate_limiter.clone(),
+            auth: app_state.auth.clone(),
+            local_files: app_state.local_files.clone(),
+            notifier: app_state.notifier.clone(),
+            connection_quality: app_state.connection_quality.clone(),
+            recent_requests: app_state.recent_requests.clone(),
+            bandwidth: app_state.bandwidth.clone(),
+            data_saver: app_state.data_saver.clone(),
+            upload_limit: app_state.upload_limit.clone(),
+            body_limit: app_state.body_limit.clone(),
+            stream_request_threshold: app_state.stream_request_threshold.clone(),
+            rewrite_content_types: settings.rewrite_content_types.clone(),
+            compress_responses: app_state.compress_responses.clone(),
+            decode_content_encoding: app_state.decode_content_encoding.clone(),
+            ws_mux: settings
+                .multiplex_websockets
+                .then(|| Arc::new(WsMux::new(settings.ws_msgpack_transcoding))),
+            ws_limits: WsLimits::from_settings(settings),
+            ws_chunk_bytes: settings.ws_chunk_bytes,
+            ws_msgpack_transcoding: settings.ws_msgpack_transcoding,
+            ws_idle_timeout: (settings.ws_idle_timeout_secs > 0)
+                .then(|| Duration::from_secs(settings.ws_idle_timeout_secs)),
+            ws_sessions: app_state.ws_sessions.clone(),
+            ws_events: app_state.ws_events.clone(),
+            get_coalescer: Coalescer::new(),
+            response_cache: app_state.response_cache.clone(),
+            warm_up_connections: app_state.warm_up_connections.clone(),
+            tcp: TcpOptions::from_settings(settings),
+            dns_cache: app_state.dns_cache.clone(),
+            host_overrides: settings.host_overrides.clone(),
+            upstream_proxy: settings.upstream_proxy.clone(),
+            pac: settings
+                .proxy_pac_url
+                .as_ref()
+                .map(|_| app_state.pac.clone()),
+            use_system_proxy: settings.use_system_proxy,
+            http3: (settings.http3
+                && http3::SUPPORTED
+                && settings.upstream_proxy.is_none()
+                && settings.proxy_pac_url.is_none())
+            .then(Http3::new),
+            local_capabilities: WsCapabilities::local(
+                settings.compress_responses,
+                settings.multiplex_websockets,
+                settings.ws_chunk_bytes > 0,
+                settings.ws_max_message_bytes,
+            ),
+            ws_capabilities: app_state.ws_capabilities.clone(),
         }
     }
 
-    fn ws_url(&self) -> String {
+    /// Backend other than the primary that requests currently go to: the fallback
+    /// in use, or the canary this session is routed to.
+    fn alternate_backend(&self) -> Option<String> {
+        self.failover
+            .fallback_url()
+            .or_else(|| self.canary.url_for_session())
+    }
+
+    /// Base URL requests go to.
+    fn backend_base(&self) -> String {
+        self.alternate_backend()
+            .unwrap_or_else(|| self.backend_url.clone())
+    }
+
+    /// Backend WebSocket URL for the path (with its query) a client requested.
+    fn ws_url(&self, path: &str) -> String {
         let url = self
-            .backend_url
+            .backend_base()
             .replace("http://", "ws://")
             .replace("https://", "wss://");
-        format!("{}/ws", url.trim_end_matches('/'))
+        format!(
+            "{}{}",
+            url.trim_end_matches('/'),
+            path_rewrite::rewrite(&self.path_rewrites, path)
+        )
+    }
+}
+
+/// The `ProxyState` both local proxies serve from. Settings changes swap in a new
+/// one without rebinding the listeners; requests and WebSocket connections already
+/// in flight finish on the state they started with.
+struct SharedProxyState(RwLock<Arc<ProxyState>>);
+
+impl SharedProxyState {
+    fn new(proxy_state: Arc<ProxyState>) -> Self {
+        Self(RwLock::new(proxy_state))
+    }
+
+    fn load(&self) -> Arc<ProxyState> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn store(&self, proxy_state: Arc<ProxyState>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = proxy_state;
     }
 }
 
+/// The local proxies, once they are listening.
+struct RunningProxies {
+    shared: Arc<SharedProxyState>,
+    shutdown: Shutdown,
+}
+
 struct AppState {
     settings: Mutex<AppSettings>,
     settings_path: PathBuf,
-    proxy_shutdown_tx: Mutex<Option<oneshot::Sender<()>>>,
-    ws_proxy_shutdown_tx: Mutex<Option<oneshot::Sender<()>>>,
+    data_dir: DataDir,
+    proxy: Mutex<Option<RunningProxies>>,
+    auth: Arc<AuthManager>,
+    notification_rules: Arc<NotificationRules>,
+    notifier: Arc<Notifier>,
+    local_files: Arc<LocalFileGrants>,
+    #[cfg(desktop)]
+    insert_gesture: text_insertion::InsertGesture,
+    #[cfg(desktop)]
+    mic_preview: audio_devices::MicPreview,
+    #[cfg(desktop)]
+    dictation: dictation::Dictation,
+    #[cfg(desktop)]
+    terminals: terminal::Terminals,
+    watch_folder_stats: WatchFolderStats,
+    transfers: TransferTracker,
+    startup: StartupGuard,
+    connection_quality: Arc<ConnectionQuality>,
+    recent_requests: Arc<RecentRequests>,
+    bandwidth: Arc<BandwidthMeter>,
+    data_saver: Arc<AtomicBool>,
+    upload_limit: Arc<AtomicU64>,
+    body_limit: Arc<BodyLimit>,
+    stream_request_threshold: Arc<AtomicU64>,
+    compress_responses: Arc<AtomicBool>,
+    decode_content_encoding: Arc<AtomicBool>,
+    response_cache: Arc<ResponseCache>,
+    warm_up_connections: Arc<AtomicBool>,
+    dns_cache: Arc<DnsCache>,
+    pac: Arc<Pac>,
+    circuit: Arc<CircuitBreaker>,
+    failover: Arc<Failover>,
+    canary: Arc<Canary>,
+    rate_limiter: Arc<RateLimiter>,
+    ws_capabilities: Arc<NegotiatedCapabilities>,
+    ws_sessions: Arc<WsSessions>,
+    ws_events: Arc<WsEvents>,
+    audio_processing: Arc<AudioProcessing>,
+    calendar: CalendarStore,
+    notes_index: NotesIndex,
+    scheduler: Scheduler,
+    timers: Timers,
+    downloads: Downloads,
+    version_check: VersionCheck,
 }
 
 impl AppState {
-    fn load(app: &AppHandle) -> Self {
-        let settings_path = app
-            .path()
-            .app_data_dir()
-            .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| ".".into()))
-            .join("settings.json");
-
-        let settings = if settings_path.exists() {
+    fn load(app: &AppHandle, data_dir: DataDir) -> Self {
+        let settings_path = data_dir.path.join("settings.json");
+
+        let startup = StartupGuard::begin(settings_path.parent().unwrap_or(Path::new(".")));
+        let settings = if startup.safe_mode() {
+            log::warn!(target: "safe-mode", "Repeated failed starts, using default settings");
+            AppSettings::default()
+        } else if settings_path.exists() {
             fs::read_to_string(&settings_path)
                 .ok()
                 .and_then(|data| serde_json::from_str(&data).ok())
@@ -151,15 +1080,107 @@ impl AppState {
             AppSettings::default()
         };
 
+        let notification_rules = Arc::new(NotificationRules::load(
+            settings_path.with_file_name("notification_rules.json"),
+        ));
+        let notifier = Arc::new(Notifier::new(app.clone(), notification_rules.clone()));
+        let bandwidth = Arc::new(BandwidthMeter::load(
+            settings_path.with_file_name("bandwidth_usage.json"),
+        ));
+        let calendar = CalendarStore::load(settings_path.with_file_name("calendar_account.json"));
+        let scheduler = Scheduler::load(settings_path.with_file_name("scheduled_tasks.json"));
+        let data_saver = Arc::new(AtomicBool::new(settings.data_saver));
+        let warm_up_connections = Arc::new(AtomicBool::new(settings.warm_up_connections));
+        let dns_cache = Arc::new(DnsCache::new(settings.dns_cache_ttl_secs));
+        if let Err(e) = dns_cache.set_doh(settings.dns_over_https_url.as_deref()) {
+            log::error!(target: "dns", "{}", e);
+        }
+        let circuit = Arc::new(CircuitBreaker::new(
+            app.clone(),
+            settings.circuit_breaker_threshold,
+        ));
+        let canary = Arc::new(Canary::new(
+            app.clone(),
+            settings.canary_backend_url.clone(),
+            settings.canary_percent,
+        ));
+        let rate_limiter = Arc::new(RateLimiter::new(
+            app.clone(),
+            settings.proxy_rate_limits.clone(),
+        ));
+        let response_cache = Arc::new(ResponseCache::new(
+            app.clone(),
+            settings_path.with_file_name("response_cache"),
+            &settings,
+        ));
+        let upload_limit = Arc::new(AtomicU64::new(settings.upload_limit_bytes_per_sec));
+        let body_limit = Arc::new(BodyLimit::new(app.clone(), settings.max_request_body_bytes));
+        let stream_request_threshold =
+            Arc::new(AtomicU64::new(settings.stream_request_threshold_bytes));
+        let compress_responses = Arc::new(AtomicBool::new(settings.compress_responses));
+        let decode_content_encoding = Arc::new(AtomicBool::new(
+            settings.response_content_encoding == ContentEncodingMode::Decode,
+        ));
+        let audio_processing = Arc::new(AudioProcessing::new(
+            settings.echo_cancellation,
+            settings.noise_suppression,
+        ));
+
         Self {
             settings: Mutex::new(settings),
             settings_path,
-            proxy_shutdown_tx: Mutex::new(None),
-            ws_proxy_shutdown_tx: Mutex::new(None),
+            data_dir,
+            proxy: Mutex::new(None),
+            auth: Arc::new(AuthManager::new(app.clone())),
+            notification_rules,
+            notifier,
+            local_files: Arc::new(LocalFileGrants::new()),
+            #[cfg(desktop)]
+            insert_gesture: text_insertion::InsertGesture::new(),
+            #[cfg(desktop)]
+            mic_preview: audio_devices::MicPreview::new(),
+            #[cfg(desktop)]
+            dictation: dictation::Dictation::new(),
+            #[cfg(desktop)]
+            terminals: terminal::Terminals::new(),
+            watch_folder_stats: WatchFolderStats::new(),
+            transfers: TransferTracker::new(app.clone()),
+            startup,
+            connection_quality: Arc::new(ConnectionQuality::new(app.clone())),
+            recent_requests: Arc::new(RecentRequests::default()),
+            bandwidth,
+            data_saver,
+            upload_limit,
+            body_limit,
+            stream_request_threshold,
+            compress_responses,
+            decode_content_encoding,
+            response_cache,
+            warm_up_connections,
+            dns_cache,
+            pac: Arc::new(Pac::new()),
+            circuit,
+            failover: Arc::new(Failover::new(app.clone())),
+            canary,
+            rate_limiter,
+            ws_capabilities: Arc::new(NegotiatedCapabilities::new(app.clone())),
+            ws_sessions: Arc::new(WsSessions::default()),
+            ws_events: Arc::new(WsEvents::new(app.clone())),
+            audio_processing,
+            calendar,
+            notes_index: NotesIndex::new(),
+            scheduler,
+            timers: Timers::new(),
+            downloads: Downloads::new(),
+            version_check: VersionCheck::new(),
         }
     }
 
     async fn save(&self) -> Result<(), String> {
+        // Keep the user's settings file intact while running on safe-mode defaults
+        if self.startup.safe_mode() {
+            return Ok(());
+        }
         let settings = self.settings.lock().await;
         if let Some(parent) = self.settings_path.parent() {
             fs::create_dir_all(parent).map_err(|e| e.to_string())?;
@@ -169,81 +1190,788 @@ impl AppState {
     }
 }
 
-/// Handle HTTP requests by proxying to backend
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Milliseconds since the Unix epoch, or 0 if the clock is before it.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Response body type produced by the HTTP proxy.
+type ProxyBody = BoxBody<Bytes, BoxError>;
+
+fn full_body(bytes: impl Into<Bytes>) -> ProxyBody {
+    Full::new(bytes.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+/// Whether the client advertised `TE: trailers`.
+fn accepts_trailers(headers: &hyper::HeaderMap) -> bool {
+    headers
+        .get_all("te")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|token| {
+            token
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .eq_ignore_ascii_case("trailers")
+        })
+}
+
+/// Make a revalidation request conditional on the cached response's validators.
+fn conditional_request(
+    mut request: reqwest::RequestBuilder,
+    cached: &UpstreamResponse,
+) -> reqwest::RequestBuilder {
+    if let Some(etag) = cached.headers.get(reqwest::header::ETAG) {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(modified) = cached.headers.get(reqwest::header::LAST_MODIFIED) {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, modified);
+    }
+    request
+}
+
+/// Backend response with its body (and trailers, if any) read, or with the body
+/// left to be streamed.
+#[derive(Clone)]
+struct UpstreamResponse {
+    status: reqwest::StatusCode,
+    headers: reqwest::header::HeaderMap,
+    /// URL the response came from, after redirects.
+    url: reqwest::Url,
+    /// HTTP version the response arrived over.
+    version: reqwest::Version,
+    body: Bytes,
+    trailers: Option<hyper::HeaderMap>,
+    /// Unread body to forward as it arrives (`body` is then empty).
+    stream: Option<UpstreamStream>,
+    /// Time until the response headers arrived.
+    ttfb: Duration,
+    /// Time spent reading the body.
+    download: Duration,
+}
+
+impl UpstreamResponse {
+    fn session_expired(&self, request_path: &str) -> bool {
+        auth::is_session_expired(
+            self.status,
+            &self.headers,
+            request_path,
+            &self.url,
+            &self.body,
+        )
+    }
+}
+
+#[derive(Clone)]
+enum UpstreamError {
+    Send(String),
+    Body(String),
+}
+
+type UpstreamResult = Result<UpstreamResponse, UpstreamError>;
+
+/// Send a request upstream. `timeout` covers the response headers and, for
+/// buffered responses, the body; streamed bodies are not limited.
+async fn send_upstream(request: reqwest::RequestBuilder, timeout: Duration) -> UpstreamResult {
+    let sent_at = Instant::now();
+    let deadline = tokio::time::Instant::from_std(sent_at + timeout);
+    let resp = tokio::time::timeout_at(deadline, http3::send(request))
+        .await
+        .map_err(|_| UpstreamError::Send("request timed out".to_string()))?
+        .map_err(|e| UpstreamError::Send(e.to_string()))?;
+    let ttfb = sent_at.elapsed();
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let url = resp.url().clone();
+    let version = resp.version();
+    if response_stream::should_stream(status, &headers) {
+        return Ok(UpstreamResponse {
+            status,
+            headers,
+            url,
+            version,
+            body: Bytes::new(),
+            trailers: None,
+            stream: Some(UpstreamStream::new(resp)),
+            ttfb,
+            download: Duration::ZERO,
+        });
+    }
+    let collected = tokio::time::timeout_at(
+        deadline,
+        hyper::Response::<reqwest::Body>::from(resp)
+            .into_body()
+            .collect(),
+    )
+    .await
+    .map_err(|_| UpstreamError::Body("response timed out".to_string()))?
+    .map_err(|e| UpstreamError::Body(e.to_string()))?;
+    let trailers = collected.trailers().cloned();
+    Ok(UpstreamResponse {
+        status,
+        headers,
+        url,
+        version,
+        body: collected.to_bytes(),
+        trailers,
+        stream: None,
+        ttfb,
+        download: sent_at.elapsed() - ttfb,
+    })
+}
+
+/// Handle HTTP requests by proxying to backend, tagging each with a request ID
+/// that is sent upstream, echoed in the response, and kept for diagnostics
 async fn handle_http_request(
     req: Request<Incoming>,
     proxy_state: Arc<ProxyState>,
-) -> Result<Response<Full<Bytes>>, Infallible> {
+) -> Result<Response<ProxyBody>, Infallible> {
+    let received_at = Instant::now();
+    let request_id = request_ids::for_request(req.headers());
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let Ok(mut response) = proxy_http_request(req, proxy_state.clone(), &request_id).await;
+    if let Ok(value) = request_id.parse() {
+        response.headers_mut().insert(request_ids::HEADER, value);
+    }
+    proxy_state.recent_requests.record(
+        request_id,
+        &method,
+        &path,
+        response.status().as_u16(),
+        received_at.elapsed(),
+    );
+    Ok(response)
+}
+
+async fn proxy_http_request(
+    req: Request<Incoming>,
+    proxy_state: Arc<ProxyState>,
+    request_id: &str,
+) -> Result<Response<ProxyBody>, Infallible> {
+    let received_at = Instant::now();
     let uri = req.uri().clone();
     let headers = req.headers().clone();
     let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
     let method = req.method().clone();
+    let wants_trailers = accepts_trailers(&headers);
 
-    // Build backend URL
-    let backend_url = format!("{}{}", proxy_state.backend_url.trim_end_matches('/'), path);
-    // Collect request body
-    let body_bytes = match req.collect().await {
-        Ok(collected) => collected.to_bytes(),
-        Err(e) => {
-            eprintln!("[proxy] Failed to read request body: {}", e);
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Full::new(Bytes::from("Failed to read request body")))
-                .unwrap());
+    // Serve explicitly granted local files without touching the backend
+    if path.starts_with(local_files::LOCAL_FILES_ROUTE) {
+        return Ok(local_files::serve(&proxy_state.local_files, path, &method, &headers).await);
+    }
+
+    // Throttle runaway clients before they reach the cache or the backend
+    if let Err(retry_after) = proxy_state.rate_limiter.check(uri.path()) {
+        return Ok(Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(
+                "retry-after",
+                retry_after.as_secs_f64().ceil().max(1.0).to_string(),
+            )
+            .body(full_body("Rate limit exceeded for this path"))
+            .unwrap());
+    }
+
+    let data_saver = proxy_state.data_saver.load(Ordering::Relaxed);
+    // Byte ranges refer to the encoded body, so range requests keep their own
+    // Accept-Encoding (usually `identity`) and are never decoded
+    let decode_content_encoding = proxy_state.decode_content_encoding.load(Ordering::Relaxed)
+        && !headers.contains_key("range");
+    if data_saver && bandwidth::is_media_prefetch(&headers, uri.path()) {
+        return Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(full_body("Media prefetch blocked by data saver"))
+            .unwrap());
+    }
+
+    // Build backend URL, on the upstream configured for this path if there is one
+    let route = upstreams::route(&proxy_state.upstreams, uri.path());
+    let alternate = proxy_state.alternate_backend();
+    let backend_base = alternate.as_deref().unwrap_or(&proxy_state.backend_url);
+    let backend_url = format!(
+        "{}{}",
+        route
+            .map_or(backend_base, |route| route.url.as_str())
+            .trim_end_matches('/'),
+        path_rewrite::rewrite(&proxy_state.path_rewrites, path)
+    );
+    // Pipe large request bodies (and those of unknown length) upstream as they
+    // arrive; buffer the rest so the request can be replayed
+    let content_length = headers
+        .get("content-length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let stream_threshold = proxy_state.stream_request_threshold.load(Ordering::Relaxed);
+    // gRPC messages are always piped through, so client streams flow as they are sent
+    let native_grpc = grpc::is_native_grpc(&headers);
+    let stream_request = native_grpc
+        || (stream_threshold > 0
+            && match content_length {
+                Some(length) => length > stream_threshold,
+                None => !hyper::body::Body::is_end_stream(req.body()),
+            });
+    let upload_limit = proxy_state.upload_limit.load(Ordering::Relaxed);
+    // Bodies over the limit are refused up front when their length is declared,
+    // and cut off as soon as they cross it otherwise
+    let max_body_bytes = proxy_state.body_limit.max_bytes();
+    if let Some(length) =
+        content_length.filter(|&length| body_limit::exceeds(max_body_bytes, length))
+    {
+        return Ok(proxy_state
+            .body_limit
+            .reject(&method, uri.path(), Some(length)));
+    }
+    let body_exceeded = Arc::new(AtomicBool::new(false));
+    let (body_bytes, streamed_body) = if stream_request {
+        let bandwidth = proxy_state.bandwidth.clone();
+        let body = uploads::streamed_body(
+            req.into_body(),
+            upload_limit,
+            max_body_bytes,
+            body_exceeded.clone(),
+            move |len| bandwidth.record(len, 0),
+        );
+        (Bytes::new(), std::sync::Mutex::new(Some(body)))
+    } else {
+        match body_limit::collect(req.into_body(), max_body_bytes).await {
+            Ok(bytes) => (bytes, std::sync::Mutex::new(None)),
+            Err(body_limit::ReadError::TooLarge) => {
+                return Ok(proxy_state.body_limit.reject(&method, uri.path(), None));
+            }
+            Err(body_limit::ReadError::Failed(e)) => {
+                log::error!(target: "proxy", "[{}] Failed to read request body: {}", request_id, e);
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(full_body("Failed to read request body"))
+                    .unwrap());
+            }
         }
     };
 
-    // Build proxied request
-    let mut proxy_req = proxy_state.http_client.request(
-        reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap_or(reqwest::Method::GET),
-        &backend_url,
+    // Build proxied request. The method is forwarded verbatim, including extension
+    // methods such as PROPFIND or REPORT; malformed tokens are already rejected with
+    // 400 by hyper before the request reaches this handler.
+    let sent_bytes = body_bytes.len() as u64;
+    let base_timeout = path_timeouts::timeout_for(
+        &proxy_state.path_timeouts,
+        path,
+        proxy_state.request_timeout,
     );
+    let request_timeout = if stream_request {
+        uploads::streamed_timeout(base_timeout, content_length, upload_limit)
+    } else if upload_limit > 0 && sent_bytes > 0 {
+        uploads::throttled_timeout(base_timeout, sent_bytes, upload_limit)
+    } else {
+        base_timeout
+    };
+    let mut upstream_headers = headers.clone();
+    header_rules::apply(
+        &proxy_state.header_rules,
+        header_rules::Direction::Request,
+        uri.path(),
+        &mut upstream_headers,
+    );
+    // Replayable requests to HTTPS backends may try HTTP/3 first
+    let http_version = proxy_state
+        .http3
+        .as_ref()
+        .filter(|_| !stream_request && !native_grpc && backend_url.starts_with("https://"))
+        .and_then(|http3| http3.version());
+    let build_request = |authorization: Option<&str>| {
+        let client = if native_grpc {
+            &proxy_state.grpc_client
+        } else {
+            &proxy_state.http_client
+        };
+        let mut proxy_req = client.request(method.clone(), &backend_url);
+        if let Some(version) = http_version {
+            proxy_req = proxy_req.version(version);
+        }
 
-    // Copy headers (except host)
-    for (name, value) in headers.iter() {
-        if name != "host" {
-            if let Ok(v) = value.to_str() {
-                proxy_req = proxy_req.header(name.as_str(), v);
+        // Copy headers, except host and hop-by-hop headers that do not apply to the
+        // upstream connection (and are not allowed over HTTP/2)
+        for (name, value) in upstream_headers.iter() {
+            if name != "host"
+                && name != request_ids::HEADER
+                && !HOP_BY_HOP_HEADERS.contains(&name.as_str())
+                && !(decode_content_encoding && name == "accept-encoding")
+            {
+                if let Ok(v) = value.to_str() {
+                    proxy_req = proxy_req.header(name.as_str(), v);
+                }
             }
         }
-    }
+        proxy_req = proxy_req.header(request_ids::HEADER, request_id);
+        // The Host override belongs to the primary backend
+        if let Some(host) = proxy_state
+            .host_header
+            .as_ref()
+            .filter(|_| route.is_none() && alternate.is_none())
+        {
+            proxy_req = proxy_req.header("host", host.as_str());
+        }
+
+        if let Some(authorization) = authorization {
+            proxy_req = proxy_req.header("authorization", authorization);
+        }
+
+        // Only ask for encodings the proxy can undo when it decodes responses
+        if decode_content_encoding {
+            proxy_req = proxy_req.header(
+                "accept-encoding",
+                content_encoding::DECODABLE_ACCEPT_ENCODING,
+            );
+        }
+
+        // Ask the backend for lighter responses (e.g. smaller images)
+        if data_saver {
+            proxy_req = proxy_req.header("save-data", "on");
+        }
+
+        // Add body if present; a streamed body can only be sent once
+        let streamed = streamed_body
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(body) = streamed {
+            proxy_req = proxy_req.body(body);
+        } else if !body_bytes.is_empty() {
+            proxy_req = if upload_limit > 0 {
+                proxy_req.body(uploads::throttled_body(
+                    body_bytes.clone(),
+                    upload_limit,
+                    |_| {},
+                ))
+            } else {
+                proxy_req.body(body_bytes.to_vec())
+            };
+        }
+        proxy_req
+    };
 
-    // Add body if present
-    if !body_bytes.is_empty() {
-        proxy_req = proxy_req.body(body_bytes.to_vec());
+    // Attach the managed access token unless the client sent its own (or the
+    // upstream is not trusted with it), holding the request back while an expired
+    // session is being recovered
+    let managed_auth =
+        !headers.contains_key("authorization") && route.is_none_or(|route| route.send_auth);
+    if managed_auth {
+        proxy_state.auth.wait_for_reauth().await;
     }
+    let authorization = managed_auth
+        .then(|| proxy_state.auth.authorization_header())
+        .flatten();
 
     // Execute request
-    match proxy_req.send().await {
-        Ok(resp) => {
-            let status = resp.status();
+    let started = Instant::now();
+    let coalesce_key = coalesce::request_key(
+        &method,
+        &backend_url,
+        &headers,
+        authorization.as_deref(),
+        stream_request || !body_bytes.is_empty(),
+    );
+    // Answer from the cache when possible. In stale-while-revalidate mode, stale
+    // entries are refreshed in the background; in revalidate mode, they are
+    // confirmed with the backend first
+    let cached = coalesce_key
+        .as_deref()
+        .and_then(|key| proxy_state.response_cache.get(key));
+    let mut cache_status = cached
+        .as_ref()
+        .filter(|hit| !hit.stale || hit.serve_stale)
+        .map(|hit| if hit.stale { "stale" } else { "fresh" });
+    // Fail fast while the backend is known to be down, unless the cache can answer
+    if cache_status.is_none() && route.is_none() && proxy_state.circuit.is_open() {
+        return Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header("content-type", "application/json")
+            .header(
+                "retry-after",
+                proxy_state.reconnect.delay(0).as_secs().max(1).to_string(),
+            )
+            .body(full_body(
+                serde_json::json!({
+                    "error": "backend_unavailable",
+                    "message": format!(
+                        "The backend at {} is not responding; the proxy is retrying in the background",
+                        backend_base
+                    ),
+                })
+                .to_string(),
+            ))
+            .unwrap());
+    }
+    // Hold a backend slot for the whole exchange, including a streamed body
+    let permit = if cache_status.is_none() {
+        match proxy_state.limiter.acquire().await {
+            Ok(permit) => permit,
+            Err(rejected) => {
+                log::warn!(
+                    target: "proxy",
+                    "[{}] Rejected {} {}: too many requests in flight ({:?}, {} queued)",
+                    request_id,
+                    method,
+                    uri.path(),
+                    rejected,
+                    proxy_state.limiter.queued()
+                );
+                return Ok(Response::builder()
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .header("retry-after", "1")
+                    .body(full_body("Too many requests in flight to the backend"))
+                    .unwrap());
+            }
+        }
+    } else {
+        None
+    };
+    let (upstream, coalesced) = match (cached, coalesce_key.as_deref()) {
+        (Some(hit), Some(key)) if hit.stale && !hit.serve_stale => {
+            let request =
+                conditional_request(build_request(authorization.as_deref()), &hit.response);
+            match send_upstream(request, request_timeout).await {
+                Ok(response) if response.status == reqwest::StatusCode::NOT_MODIFIED => {
+                    log::debug!(target: "proxy", "Revalidated cached {}", uri.path());
+                    cache_status = Some("revalidated");
+                    let cached =
+                        proxy_state
+                            .response_cache
+                            .not_modified(key, hit.response, &response);
+                    (Ok(cached), true)
+                }
+                result => (result, false),
+            }
+        }
+        (Some(hit), Some(key)) => {
+            if hit.stale && proxy_state.response_cache.begin_revalidation(key) {
+                let request =
+                    conditional_request(build_request(authorization.as_deref()), &hit.response);
+                let cache = proxy_state.response_cache.clone();
+                let key = key.to_string();
+                tokio::spawn(async move {
+                    let result = send_upstream(request, request_timeout).await.ok();
+                    cache.finish_revalidation(&key, result);
+                });
+            }
+            log::debug!(
+                target: "proxy",
+                "Served {} from cache ({}, {}s old)",
+                uri.path(),
+                if hit.stale { "stale" } else { "fresh" },
+                hit.age.as_secs()
+            );
+            (Ok(hit.response), true)
+        }
+        // Identical GETs already in flight share that response instead of refetching
+        (None, Some(key)) => {
+            let request = build_request(authorization.as_deref());
+            let retried = retry::send_with_retry(
+                request,
+                request_timeout,
+                proxy_state.retry.clone(),
+                method.clone(),
+                uri.path().to_string(),
+                request_id.to_string(),
+            );
+            proxy_state.get_coalescer.run(key, || retried.boxed()).await
+        }
+        // Transient failures are retried while the request body can be replayed
+        _ => (
+            retry::send_with_retry(
+                build_request(authorization.as_deref()),
+                request_timeout,
+                proxy_state.retry.clone(),
+                method.clone(),
+                uri.path().to_string(),
+                request_id.to_string(),
+            )
+            .await,
+            false,
+        ),
+    };
+
+    // A streamed body can only be read once, so callers that joined a coalesced
+    // request which turned out to stream fetch their own copy
+    let (mut upstream, coalesced) =
+        if coalesced && upstream.as_ref().is_ok_and(|r| r.stream.is_some()) {
+            let request = build_request(authorization.as_deref());
+            (send_upstream(request, request_timeout).await, false)
+        } else {
+            (upstream, coalesced)
+        };
+
+    if !coalesced && route.is_none() {
+        proxy_state.circuit.record(&upstream);
+    }
+    if let (Some(http3), Some(_), false) = (&proxy_state.http3, http_version, coalesced) {
+        http3.record(upstream.as_ref().ok().map(|response| response.version));
+    }
+
+    // Re-authenticate and replay once if the session expired, rather than handing
+    // the webview a login page
+    if authorization.is_some()
+        && upstream
+            .as_ref()
+            .is_ok_and(|response| response.session_expired(uri.path()))
+    {
+        log::info!(target: "auth", "Session expired on {}", uri.path());
+        // A streamed body has already been consumed and cannot be replayed
+        if proxy_state
+            .auth
+            .reauthenticate(authorization.as_deref())
+            .await
+            && !stream_request
+        {
+            let authorization = proxy_state.auth.authorization_header();
+            upstream =
+                send_upstream(build_request(authorization.as_deref()), request_timeout).await;
+        }
+        if upstream
+            .as_ref()
+            .is_ok_and(|response| response.session_expired(uri.path()))
+        {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header("content-type", "application/json")
+                .body(full_body(r#"{"error":"session_expired"}"#))
+                .unwrap());
+        }
+    }
+
+    if let (None, Some(key), Ok(response)) = (cache_status, &coalesce_key, &upstream) {
+        proxy_state.response_cache.store(key, path, response);
+    }
+
+    match upstream {
+        Ok(UpstreamResponse {
+            status,
+            headers: mut resp_headers,
+            body: mut bytes,
+            trailers,
+            stream,
+            ttfb,
+            download,
+            ..
+        }) => {
+            let trailers = if wants_trailers { trailers } else { None };
+            let mut body_changed = false;
+            let mut content_encoding = None;
+            // A partial body cannot be decoded on its own, so ranges pass through
+            let decode = decode_content_encoding && status != reqwest::StatusCode::PARTIAL_CONTENT;
+
+            let streamed = stream.and_then(|stream| stream.take());
+            let stream_decoder = streamed
+                .as_ref()
+                .filter(|_| decode)
+                .and_then(|_| StreamDecoder::for_headers(&resp_headers));
+            if streamed.is_some() {
+                if stream_decoder.is_some() {
+                    resp_headers.remove(reqwest::header::CONTENT_ENCODING);
+                    resp_headers.remove(reqwest::header::CONTENT_LENGTH);
+                }
+                if !coalesced {
+                    proxy_state.bandwidth.record(sent_bytes, 0);
+                }
+                proxy_state.connection_quality.record_request(
+                    !status.is_server_error(),
+                    0,
+                    started.elapsed(),
+                );
+            } else {
+                if !coalesced {
+                    proxy_state.bandwidth.record(sent_bytes, bytes.len() as u64);
+                }
+                proxy_state.connection_quality.record_request(
+                    !status.is_server_error(),
+                    bytes.len() as u64,
+                    started.elapsed(),
+                );
+
+                // Decode before rewriting and compressing, which skip encoded bodies
+                if decode {
+                    match content_encoding::decode(&resp_headers, &bytes) {
+                        Some(Ok(plain)) => {
+                            bytes = Bytes::from(plain);
+                            resp_headers.remove(reqwest::header::CONTENT_ENCODING);
+                            resp_headers.remove(reqwest::header::CONTENT_LENGTH);
+                        }
+                        Some(Err(e)) => {
+                            log::warn!(target: "proxy", "[{}] Failed to decode {}: {}", request_id, uri.path(), e)
+                        }
+                        None => {}
+                    }
+                }
+
+                // Point absolute backend URLs at the local proxy for opted-in content types
+                let rewritten = url_rewrite::rewrite_response_body(
+                    &bytes,
+                    &headers,
+                    &resp_headers,
+                    backend_base,
+                    &proxy_state.rewrite_content_types,
+                );
+                body_changed |= rewritten.is_some();
+                if let Some(rewritten) = rewritten {
+                    bytes = Bytes::from(rewritten);
+                }
+
+                // Compress large text responses on the loopback hop when enabled
+                if proxy_state.compress_responses.load(Ordering::Relaxed)
+                    && status != reqwest::StatusCode::PARTIAL_CONTENT
+                {
+                    if let Some((encoding, compressed)) =
+                        compression::compress_response(&bytes, &headers, &resp_headers)
+                    {
+                        bytes = Bytes::from(compressed);
+                        body_changed = true;
+                        content_encoding = Some(encoding);
+                    }
+                }
+            }
+
+            header_rules::apply(
+                &proxy_state.header_rules,
+                header_rules::Direction::Response,
+                uri.path(),
+                &mut resp_headers,
+            );
+
+            // Redirects passed through to the webview must keep it on the proxy
+            if proxy_state.redirects.passes_through() && status.is_redirection() && route.is_none()
+            {
+                let location = resp_headers
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .zip(headers.get("host").and_then(|value| value.to_str().ok()))
+                    .and_then(|(location, host)| {
+                        redirects::rewrite_location(
+                            location,
+                            backend_base,
+                            &format!("http://{}", host),
+                        )
+                    });
+                if let Some(location) = location.and_then(|l| l.parse().ok()) {
+                    resp_headers.insert(reqwest::header::LOCATION, location);
+                }
+            }
+
             let mut builder = Response::builder().status(status.as_u16());
 
             // Copy response headers
-            for (name, value) in resp.headers() {
-                // Skip transfer-encoding since we're not chunking
-                if name != "transfer-encoding" {
-                    builder = builder.header(name.as_str(), value.as_bytes());
+            for (name, value) in resp_headers.iter() {
+                // Skip transfer-encoding since hyper decides the framing; trailers
+                // require chunked encoding and rewritten bodies change length, so
+                // drop content-length as well
+                if name == "transfer-encoding"
+                    || ((trailers.is_some() || body_changed) && name == "content-length")
+                {
+                    continue;
                 }
+                builder = builder.header(name.as_str(), value.as_bytes());
+            }
+            if let Some(encoding) = content_encoding {
+                builder = builder
+                    .header("content-encoding", encoding.as_str())
+                    .header("vary", "accept-encoding");
             }
 
-            // Get response body
-            match resp.bytes().await {
-                Ok(bytes) => Ok(builder.body(Full::new(bytes)).unwrap()),
-                Err(e) => {
-                    eprintln!("[proxy] Failed to read response body: {}", e);
-                    Ok(Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .body(Full::new(Bytes::from("Failed to read response")))
-                        .unwrap())
-                }
+            // Report the proxy's own phases next to any backend Server-Timing entries;
+            // the webview is cross-origin, so timing must be explicitly exposed to it.
+            // Streamed bodies are still in flight, so their download time is zero
+            let timing = server_timing::ProxyTiming {
+                queue: started.duration_since(received_at),
+                upstream: ttfb,
+                download,
+                total: received_at.elapsed(),
+            };
+            builder = builder.header("server-timing", timing.header_value());
+            if let Some(cache_status) = cache_status {
+                builder = builder.header("x-proxy-cache", cache_status);
+            }
+            if !resp_headers.contains_key("timing-allow-origin") {
+                builder = builder.header("timing-allow-origin", "*");
             }
+
+            let body = match (streamed, trailers) {
+                // Forward chunks (e.g. Server-Sent Events) as they arrive
+                (Some(response), _) => {
+                    let bandwidth = proxy_state.bandwidth.clone();
+                    let on_chunk = move |len: usize| {
+                        // Released when the body finishes or the webview drops it
+                        let _slot = &permit;
+                        bandwidth.record(0, len as u64)
+                    };
+                    // Event streams resume from the last event if the backend drops
+                    if method == hyper::Method::GET
+                        && stream_decoder.is_none()
+                        && sse::is_event_stream(response.headers())
+                    {
+                        let (client, request) = build_request(None).build_split();
+                        match request {
+                            Ok(request) => sse::resumable_body(
+                                response,
+                                sse::Resume {
+                                    client,
+                                    request,
+                                    auth: managed_auth.then(|| proxy_state.auth.clone()),
+                                    backoff: proxy_state.reconnect.clone(),
+                                },
+                                on_chunk,
+                            ),
+                            Err(_) => response_stream::into_body(response, None, on_chunk),
+                        }
+                    } else {
+                        response_stream::into_body(response, stream_decoder, on_chunk)
+                    }
+                }
+                (None, Some(trailers)) => {
+                    BodyExt::boxed(StreamBody::new(futures_util::stream::iter([
+                        Ok::<_, BoxError>(Frame::data(bytes)),
+                        Ok(Frame::trailers(trailers)),
+                    ])))
+                }
+                (None, None) => full_body(bytes),
+            };
+            Ok(builder.body(body).unwrap())
         }
-        Err(e) => {
-            eprintln!("[proxy] Request failed: {}", e);
+        Err(UpstreamError::Body(e)) => {
+            log::error!(target: "proxy", "[{}] Failed to read response body: {}", request_id, e);
+            proxy_state
+                .connection_quality
+                .record_request(false, 0, started.elapsed());
             Ok(Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
-                .body(Full::new(Bytes::from(format!("Proxy error: {}", e))))
+                .body(full_body(format!(
+                    "Failed to read response (request {})",
+                    request_id
+                )))
+                .unwrap())
+        }
+        Err(UpstreamError::Send(_)) if body_exceeded.load(Ordering::Relaxed) => {
+            Ok(proxy_state.body_limit.reject(&method, uri.path(), None))
+        }
+        Err(UpstreamError::Send(e)) => {
+            log::error!(target: "proxy", "[{}] Request failed: {}", request_id, e);
+            proxy_state
+                .connection_quality
+                .record_request(false, 0, started.elapsed());
+            Ok(Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(full_body(format!(
+                    "Proxy error: {} (request {})",
+                    e, request_id
+                )))
                 .unwrap())
         }
     }
@@ -299,205 +2027,761 @@ impl rustls::client::danger::ServerCertVerifier for NoVerifier {
     }
 }
 
+/// Backend WebSocket connection opened by the WS proxy.
+type BackendSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Web client WebSocket connection accepted by the WS proxy.
+type ClientSocket = tokio_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>;
+
+/// Why opening the backend WebSocket failed.
+enum WsConnectError {
+    /// Worth retrying, e.g. the backend could not be reached.
+    Unreachable(String),
+    /// The URL is invalid or the backend refused the handshake; retrying would not help.
+    Refused(String),
+}
+
+impl From<String> for WsConnectError {
+    fn from(error: String) -> Self {
+        Self::Unreachable(error)
+    }
+}
+
+/// An open backend WebSocket and what was agreed in its handshake.
+struct BackendConnection {
+    socket: BackendSocket,
+    /// Subprotocol the backend chose.
+    protocol: Option<hyper::header::HeaderValue>,
+    /// Size to split large text messages at, when the backend accepted chunking.
+    chunk_bytes: Option<usize>,
+}
+
+/// Open the backend WebSocket for the client's requested `path` (with its query),
+/// replaying the client's handshake `headers` and attaching the managed access
+/// token, retrying unreachable backends per the reconnect backoff.
+async fn connect_backend_ws(
+    proxy_state: &ProxyState,
+    path: &str,
+    headers: &hyper::HeaderMap,
+) -> Result<BackendConnection, String> {
+    let backoff = &proxy_state.reconnect;
+    let mut attempt = 1;
+    loop {
+        match connect_backend_ws_once(proxy_state, path, headers).await {
+            Ok(connected) => return Ok(connected),
+            Err(WsConnectError::Unreachable(e)) if attempt < backoff.max_attempts => {
+                let delay = backoff.delay(attempt - 1);
+                log::warn!(
+                    target: "ws-proxy",
+                    "{}, retrying in {}ms (attempt {} of {})",
+                    e,
+                    delay.as_millis(),
+                    attempt + 1,
+                    backoff.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(WsConnectError::Unreachable(e) | WsConnectError::Refused(e)) => return Err(e),
+        }
+    }
+}
+
+async fn connect_backend_ws_once(
+    proxy_state: &ProxyState,
+    path: &str,
+    headers: &hyper::HeaderMap,
+) -> Result<BackendConnection, WsConnectError> {
+    let on_primary = proxy_state.alternate_backend().is_none();
+    let ws_url = proxy_state.ws_url(path);
+    log::info!(target: "ws-proxy", "Connecting to backend: {}", ws_url);
+
+    let mut backend_request = ws_url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| WsConnectError::Refused(format!("Invalid backend WebSocket URL: {}", e)))?;
+    for (name, value) in headers {
+        backend_request
+            .headers_mut()
+            .append(name.clone(), value.clone());
+    }
+    if let Some(authorization) = proxy_state.auth.authorization_header() {
+        if let Ok(value) = authorization.parse() {
+            backend_request.headers_mut().insert("authorization", value);
+        }
+    }
+    if let Ok(value) = proxy_state.local_capabilities.to_header().parse() {
+        backend_request
+            .headers_mut()
+            .insert(ws_capabilities::CAPABILITIES_HEADER, value);
+    }
+    if let Some(host) = proxy_state
+        .host_header
+        .as_deref()
+        .filter(|_| on_primary)
+        .and_then(|host| host.parse().ok())
+    {
+        backend_request.headers_mut().insert("host", host);
+    }
+
+    let connector = proxy_state.skip_cert_validation.then(|| {
+        tokio_tungstenite::Connector::Rustls(Arc::new(
+            rustls::ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerifier))
+                .with_no_client_auth(),
+        ))
+    });
+    // Connect the socket ourselves so the lookup goes through the DNS cache
+    let uri = backend_request.uri();
+    let host = uri
+        .host()
+        .ok_or_else(|| {
+            WsConnectError::Refused(format!("Invalid backend WebSocket URL: {}", ws_url))
+        })?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("wss") {
+            443
+        } else {
+            80
+        });
+    let pinned = proxy_state.backend_addr.filter(|_| on_primary);
+    let secure = uri.scheme_str() == Some("wss");
+    let pac_route = match (&proxy_state.upstream_proxy, &proxy_state.pac) {
+        (None, Some(pac)) => {
+            // PAC scripts only know http(s) URLs
+            let url = ws_url.replacen("ws", "http", 1);
+            reqwest::Url::parse(&url)
+                .ok()
+                .and_then(|url| pac.route(&url))
+        }
+        _ => None,
+    };
+    let upstream_proxy = match pac_route {
+        Some(PacRoute::Proxy(proxy)) => Some(proxy),
+        Some(PacRoute::Direct) => None,
+        None => proxy_state.upstream_proxy.clone().or_else(|| {
+            proxy_state
+                .use_system_proxy
+                .then(|| system_proxy::detect(&host, port, secure))
+                .flatten()
+        }),
+    };
+    let stream = match &upstream_proxy {
+        Some(proxy) => match pinned {
+            Some(addr) => {
+                proxy
+                    .connect(&proxy_state.dns_cache, &addr.ip().to_string(), addr.port())
+                    .await?
+            }
+            None => proxy.connect(&proxy_state.dns_cache, &host, port).await?,
+        },
+        None => {
+            let pinned =
+                pinned.or_else(|| host_overrides::lookup(&proxy_state.host_overrides, &host, port));
+            let addrs = match pinned {
+                Some(addr) => vec![addr],
+                None => proxy_state
+                    .dns_cache
+                    .lookup(&host, port)
+                    .await
+                    .map_err(|e| format!("Failed to resolve {}: {}", host, e))?,
+            };
+            happy_eyeballs::connect(&addrs)
+                .await
+                .map_err(|e| format!("Failed to connect to backend WebSocket: {}", e))?
+        }
+    };
+    proxy_state.tcp.apply(&stream);
+    let result = tokio_tungstenite::client_async_tls_with_config(
+        backend_request,
+        stream,
+        Some(proxy_state.ws_limits.config()),
+        connector,
+    )
+    .await;
+    let (backend_ws, response) = result.map_err(|e| {
+        let error = format!("Failed to connect to backend WebSocket: {}", e);
+        match e {
+            tokio_tungstenite::tungstenite::Error::Http(_) => WsConnectError::Refused(error),
+            _ => WsConnectError::Unreachable(error),
+        }
+    })?;
+    let negotiated = proxy_state.ws_capabilities.negotiate(
+        &proxy_state.local_capabilities,
+        response
+            .headers()
+            .get(ws_capabilities::CAPABILITIES_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+
+    let protocol = response
+        .headers()
+        .get(hyper::header::SEC_WEBSOCKET_PROTOCOL)
+        .cloned();
+
+    log::info!(target: "ws-proxy", "Connected to backend, proxying messages");
+    proxy_state.connection_quality.reset();
+    Ok(BackendConnection {
+        socket: backend_ws,
+        protocol,
+        chunk_bytes: negotiated.chunking.then_some(proxy_state.ws_chunk_bytes),
+    })
+}
+
+/// Backend side of an accepted client WebSocket.
+enum WsBackend {
+    Direct(Box<BackendConnection>),
+    Mux(Arc<WsMux>, ws_mux::Attached),
+}
+
+/// A client upgrade the backend accepted, relayed once the client's connection
+/// is handed over.
+struct WsOpened {
+    on_upgrade: hyper::upgrade::OnUpgrade,
+    backend: WsBackend,
+    session: WsSession,
+}
+
+/// Open the backend side for a client's upgrade request, answering the client
+/// only once the backend accepted so its subprotocol can be echoed.
+async fn open_ws_backend(
+    mut request: Request<Incoming>,
+    proxy_state: &Arc<ProxyState>,
+) -> (Response<http_body_util::Empty<Bytes>>, Option<WsOpened>) {
+    let accept_key = match ws_handshake::accept_key(&request) {
+        Ok(accept_key) => accept_key,
+        Err(e) => {
+            log::warn!(target: "ws-proxy", "Rejected handshake: {}", e);
+            return (ws_handshake::reject(StatusCode::BAD_REQUEST), None);
+        }
+    };
+    let headers = ws_handshake::forwarded_headers(request.headers());
+    let path = ws_handshake::backend_path(request.uri());
+    let session = proxy_state.ws_events.open(&path);
+    // Share a single backend connection between clients of the app socket when
+    // multiplexing; other endpoints get their own
+    let mux = proxy_state
+        .ws_mux
+        .clone()
+        .filter(|_| ws_handshake::endpoint(&path) == "/ws");
+    let backend = match mux {
+        Some(mux) => mux
+            .attach(proxy_state, &path, &headers)
+            .await
+            .map(|attached| (attached.protocol.clone(), WsBackend::Mux(mux, attached))),
+        None => connect_backend_ws(proxy_state, &path, &headers)
+            .await
+            .map(|backend| {
+                (
+                    backend.protocol.clone(),
+                    WsBackend::Direct(Box::new(backend)),
+                )
+            }),
+    };
+    match backend {
+        Ok((protocol, backend)) => {
+            let opened = WsOpened {
+                on_upgrade: hyper::upgrade::on(&mut request),
+                backend,
+                session,
+            };
+            (
+                ws_handshake::switching_protocols(&accept_key, protocol),
+                Some(opened),
+            )
+        }
+        Err(e) => {
+            log::error!(target: "ws-proxy", "{}", e);
+            session.outcome().failed(e);
+            (ws_handshake::reject(StatusCode::BAD_GATEWAY), None)
+        }
+    }
+}
+
+/// Account for a backend → client frame (bandwidth, notifications, RTT). Returns
+/// `false` for proxy-internal frames that must not be relayed to clients.
+fn observe_backend_frame(proxy_state: &ProxyState, msg: &Message) -> bool {
+    proxy_state.bandwidth.record(0, msg.len() as u64);
+    match msg {
+        Message::Text(text) => proxy_state.notifier.observe_backend_message(text),
+        Message::Pong(payload) => {
+            if let Some(rtt) = connection_quality::rtt_from_pong(payload) {
+                proxy_state.connection_quality.record_rtt(rtt);
+                return false;
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
 /// Handle WebSocket connection by proxying to backend
 async fn handle_websocket_connection(
     client_stream: tokio::net::TcpStream,
     proxy_state: Arc<ProxyState>,
-    skip_cert_validation: bool,
+    mut shutdown: ShutdownSignal,
 ) {
-    // Accept WebSocket from client
-    let client_ws = match tokio_tungstenite::accept_async(client_stream).await {
-        Ok(ws) => ws,
-        Err(e) => {
-            eprintln!("[ws-proxy] Failed to accept WebSocket: {}", e);
-            return;
-        }
+    // Read the client's upgrade request and connect the backend with its headers
+    let opened = std::sync::Mutex::new(None);
+    let service = {
+        let (proxy_state, opened) = (&proxy_state, &opened);
+        service_fn(move |request: Request<Incoming>| async move {
+            let (response, upgrade) = open_ws_backend(request, proxy_state).await;
+            *opened.lock().unwrap_or_else(|e| e.into_inner()) = upgrade;
+            Ok::<_, Infallible>(response)
+        })
     };
+    let handshake = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(client_stream), service)
+        .with_upgrades();
+    let handshake = tokio::select! {
+        result = handshake => result,
+        _ = shutdown.wait() => return,
+    };
+    if let Err(e) = handshake {
+        log::error!(target: "ws-proxy", "Failed to accept WebSocket: {}", e);
+    }
+    if let Some(opened) = opened.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        relay_websocket(opened, proxy_state, shutdown).await;
+    }
+}
 
-    // Connect to backend WebSocket
-    let ws_url = proxy_state.ws_url();
-    println!("[ws-proxy] Connecting to backend: {}", ws_url);
-
-    let backend_ws = if skip_cert_validation {
-        let connector = tokio_tungstenite::Connector::Rustls(Arc::new(
-            rustls::ClientConfig::builder()
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(NoVerifier))
-                .with_no_client_auth(),
-        ));
-
-        match tokio_tungstenite::connect_async_tls_with_config(
-            &ws_url,
-            None,
-            false,
-            Some(connector),
-        )
-        .await
-        {
-            Ok((ws, _)) => ws,
-            Err(e) => {
-                eprintln!("[ws-proxy] Failed to connect to backend WebSocket: {}", e);
-                return;
+/// Relay an upgraded client socket to its backend until either side closes.
+async fn relay_websocket(
+    opened: WsOpened,
+    proxy_state: Arc<ProxyState>,
+    mut shutdown: ShutdownSignal,
+) {
+    let WsOpened {
+        on_upgrade,
+        backend,
+        mut session,
+    } = opened;
+    let upgraded = match on_upgrade.await {
+        Ok(upgraded) => upgraded,
+        Err(e) => {
+            log::error!(target: "ws-proxy", "Failed to accept WebSocket: {}", e);
+            session.outcome().failed(e.to_string());
+            if let WsBackend::Mux(mux, attached) = backend {
+                mux.detach(attached.client_id, &attached.backend_tx);
             }
+            return;
         }
-    } else {
-        match tokio_tungstenite::connect_async(&ws_url).await {
-            Ok((ws, _)) => ws,
-            Err(e) => {
-                eprintln!("[ws-proxy] Failed to connect to backend WebSocket: {}", e);
-                return;
-            }
+    };
+    let client_ws = ClientSocket::from_raw_socket(
+        TokioIo::new(upgraded),
+        tokio_tungstenite::tungstenite::protocol::Role::Server,
+        Some(proxy_state.ws_limits.config()),
+    )
+    .await;
+    let counted = proxy_state
+        .ws_sessions
+        .open(session.id(), session.endpoint());
+    let stats = &*counted.stats();
+    session.connected(counted);
+    // Failures that end the session, reported in its final state event
+    let outcome = session.outcome();
+    let BackendConnection {
+        socket: backend_ws,
+        chunk_bytes,
+        ..
+    } = match backend {
+        WsBackend::Direct(backend) => *backend,
+        WsBackend::Mux(mux, attached) => {
+            mux.serve_client(
+                attached,
+                client_ws,
+                proxy_state.ws_idle_timeout,
+                shutdown,
+                outcome,
+                stats,
+            )
+            .await;
+            return;
         }
     };
 
-    println!("[ws-proxy] Connected to backend, proxying messages");
-
+    let proxy_state = &*proxy_state;
     let (mut client_write, mut client_read) = client_ws.split();
     let (mut backend_write, mut backend_read) = backend_ws.split();
-
-    // Proxy messages bidirectionally
+    // Queue frames per direction so audio frames overtake bulk JSON messages
+    let (to_backend_tx, mut to_backend_rx) = ws_priority::priority_channel();
+    let (to_client_tx, mut to_client_rx) = ws_priority::priority_channel();
+
+    // Proxy messages bidirectionally, pinging both legs so silently dropped
+    // connections are noticed and the backend round-trip time is measured
+    let client_alive = &ws_keepalive::Liveness::new();
+    let backend_alive = &ws_keepalive::Liveness::new();
+    // Last text or binary message in either direction, for the idle timeout
+    let activity = &ws_keepalive::Liveness::new();
+    // Set once either leg is sent a close frame, bounding the closing handshake
+    let closing = &tokio::sync::Notify::new();
+    // Each read loop runs until its leg is closed, forwarding the close frame (or a
+    // stand-in when the leg failed) so the other leg completes the handshake too
+    // Oversized messages are refused with a close frame to the leg that sent them
+    let (client_refusal_tx, backend_refusal_tx) = (to_client_tx.clone(), to_backend_tx.clone());
+    // Large text messages go out in chunks, queued one by one so audio frames can
+    // overtake them
+    let mut reassembler =
+        chunk_bytes.map(|_| ws_chunking::Reassembler::new(proxy_state.ws_limits.max_message_bytes));
     let client_to_backend = async {
-        while let Some(msg) = client_read.next().await {
-            match msg {
-                Ok(msg) => {
-                    if let Err(e) = backend_write.send(msg).await {
-                        eprintln!("[ws-proxy] Failed to send to backend: {}", e);
+        let read_client = async move {
+            let mut closed = false;
+            while let Some(msg) = client_read.next().await {
+                match msg {
+                    Ok(msg) => {
+                        client_alive.seen();
+                        if msg.is_text() || msg.is_binary() {
+                            activity.seen();
+                            stats.to_backend(&msg);
+                        }
+                        if let Some(rtt) = ws_keepalive::client_pong_rtt(&msg) {
+                            log::trace!(target: "ws-proxy", "Client ping round trip {:?}", rtt);
+                            stats.client_rtt(rtt);
+                            continue;
+                        }
+                        if let Message::Close(frame) = &msg {
+                            log::info!(target: "ws-proxy", "Client closed ({})", ws_close::describe(frame.as_ref()));
+                            closed = true;
+                        }
+                        let msg = if proxy_state.ws_msgpack_transcoding {
+                            ws_msgpack::to_backend(msg)
+                        } else {
+                            msg
+                        };
+                        let outgoing = match chunk_bytes {
+                            Some(size) => ws_chunking::split(msg, size),
+                            None => vec![msg],
+                        };
+                        for msg in outgoing {
+                            let _ = to_backend_tx.send_when_ready(msg).await;
+                        }
+                    }
+                    Err(e) if ws_limits::is_too_big(&e) => {
+                        log::warn!(target: "ws-proxy", "Closing client: {}", e);
+                        outcome.failed(e.to_string());
+                        let _ = client_refusal_tx.send(ws_limits::too_big_message());
+                        break;
+                    }
+                    Err(e) => {
+                        log::error!(target: "ws-proxy", "Client read error: {}", e);
                         break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("[ws-proxy] Client read error: {}", e);
+            }
+            if !closed {
+                let _ = to_backend_tx.send(ws_close::client_lost_message());
+            }
+        };
+        let write_backend = async {
+            let mut rtt_ping = tokio::time::interval(Duration::from_secs(
+                connection_quality::RTT_PING_INTERVAL_SECS,
+            ));
+            loop {
+                let msg = tokio::select! {
+                    msg = to_backend_rx.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = rtt_ping.tick() => Message::Ping(connection_quality::rtt_ping_payload()),
+                };
+                proxy_state.bandwidth.record(msg.len() as u64, 0);
+                if msg.is_close() {
+                    // Fails harmlessly when the backend closed first
+                    closing.notify_one();
+                    let _ = backend_write.send(msg).await;
+                    break;
+                }
+                if let Err(e) = backend_write.send(msg).await {
+                    log::error!(target: "ws-proxy", "Failed to send to backend: {}", e);
                     break;
                 }
             }
-        }
+        };
+        tokio::join!(read_client, write_backend);
     };
 
     let backend_to_client = async {
-        while let Some(msg) = backend_read.next().await {
-            match msg {
-                Ok(msg) => {
-                    if let Err(e) = client_write.send(msg).await {
-                        eprintln!("[ws-proxy] Failed to send to client: {}", e);
+        let read_backend = async move {
+            let mut closed = false;
+            while let Some(msg) = backend_read.next().await {
+                match msg {
+                    Ok(msg) => {
+                        backend_alive.seen();
+                        let msg = match &mut reassembler {
+                            Some(reassembler) => match reassembler.push(msg) {
+                                Ok(Some(msg)) => msg,
+                                Ok(None) => continue,
+                                Err(e) => {
+                                    log::warn!(target: "ws-proxy", "Closing backend connection: {}", e);
+                                    outcome.backend_lost(e.to_string());
+                                    let _ = backend_refusal_tx.send(e.close_message());
+                                    break;
+                                }
+                            },
+                            None => msg,
+                        };
+                        let msg = if proxy_state.ws_msgpack_transcoding {
+                            ws_msgpack::to_client(msg)
+                        } else {
+                            msg
+                        };
+                        if msg.is_text() || msg.is_binary() {
+                            activity.seen();
+                            stats.to_client(&msg);
+                        }
+                        if let Message::Pong(payload) = &msg {
+                            if let Some(rtt) = connection_quality::rtt_from_pong(payload) {
+                                stats.backend_rtt(rtt);
+                            }
+                        }
+                        if !observe_backend_frame(proxy_state, &msg) {
+                            continue;
+                        }
+                        if let Message::Close(frame) = &msg {
+                            log::info!(target: "ws-proxy", "Backend closed ({})", ws_close::describe(frame.as_ref()));
+                            closed = true;
+                        }
+                        let _ = to_client_tx.send_when_ready(msg).await;
+                    }
+                    Err(e) if ws_limits::is_too_big(&e) => {
+                        log::warn!(target: "ws-proxy", "Closing backend connection: {}", e);
+                        outcome.backend_lost(e.to_string());
+                        let _ = backend_refusal_tx.send(ws_limits::too_big_message());
+                        break;
+                    }
+                    Err(e) => {
+                        log::error!(target: "ws-proxy", "Backend read error: {}", e);
+                        outcome.backend_lost(e.to_string());
                         break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("[ws-proxy] Backend read error: {}", e);
+            }
+            if !closed {
+                outcome.backend_lost("Backend connection lost");
+                let _ = to_client_tx.send(ws_close::backend_lost_message());
+            }
+        };
+        let write_client = async {
+            let mut keepalive = tokio::time::interval(Duration::from_secs(
+                connection_quality::RTT_PING_INTERVAL_SECS,
+            ));
+            loop {
+                let msg = tokio::select! {
+                    msg = to_client_rx.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = keepalive.tick() => ws_keepalive::client_ping(),
+                };
+                if msg.is_close() {
+                    closing.notify_one();
+                    let _ = client_write.send(msg).await;
+                    break;
+                }
+                if let Err(e) = client_write.send(msg).await {
+                    log::error!(target: "ws-proxy", "Failed to send to client: {}", e);
                     break;
                 }
             }
-        }
+        };
+        tokio::join!(read_backend, write_client);
     };
 
+    let mut client_close = None;
+    let mut backend_close = None;
     tokio::select! {
-        _ = client_to_backend => {},
-        _ = backend_to_client => {},
+        _ = async { tokio::join!(client_to_backend, backend_to_client) } => {}
+        _ = async {
+            closing.notified().await;
+            tokio::time::sleep(Duration::from_secs(ws_close::CLOSE_TIMEOUT_SECS)).await;
+        } => {
+            log::warn!(target: "ws-proxy", "Closing handshake timed out");
+        }
+        _ = shutdown.wait() => {
+            client_close = Some(shutdown::close_message());
+            backend_close = Some(shutdown::close_message());
+        }
+        _ = backend_alive.dead() => {
+            log::warn!(target: "ws-proxy", "Backend stopped answering pings, closing session");
+            outcome.backend_lost("Backend stopped answering pings");
+            client_close = Some(ws_keepalive::backend_dead_message());
+        }
+        _ = client_alive.dead() => {
+            log::warn!(target: "ws-proxy", "Client stopped answering pings, closing session");
+            outcome.failed("Client stopped answering pings");
+            backend_close = Some(ws_close::client_lost_message());
+        }
+        _ = ws_sessions::idle(activity, proxy_state.ws_idle_timeout) => {
+            log::info!(target: "ws-proxy", "Closing idle session");
+            client_close = Some(ws_sessions::idle_message());
+            backend_close = Some(ws_sessions::idle_message());
+        }
+    }
+    if let Some(msg) = client_close {
+        let _ = client_write.send(msg).await;
+    }
+    if let Some(msg) = backend_close {
+        let _ = backend_write.send(msg).await;
     }
 
-    println!("[ws-proxy] Connection closed");
+    log::info!(target: "ws-proxy", "Connection closed");
 }
 
-/// Start the HTTP proxy server
+/// Start the HTTP proxy server. Once `shutdown` fires, it stops accepting and lets
+/// in-flight requests finish before resolving `stopped`.
 async fn start_http_proxy(
-    backend_url: String,
-    skip_cert_validation: bool,
-) -> Result<(u16, oneshot::Sender<()>), String> {
+    shared: Arc<SharedProxyState>,
+    (mut shutdown, stopped): (ShutdownSignal, oneshot::Sender<()>),
+) -> Result<u16, String> {
     let addr = SocketAddr::from(([127, 0, 0, 1], 0));
     let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
     let port = listener.local_addr().map_err(|e| e.to_string())?.port();
 
-    let proxy_state = Arc::new(ProxyState::new(backend_url.clone(), skip_cert_validation));
-    let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
-
-    println!(
-        "[http-proxy] Starting on http://localhost:{} -> {}",
-        port, backend_url
+    log::info!(
+        target: "http-proxy",
+        "Starting on http://localhost:{} -> {}",
+        port,
+        shared.load().backend_url
     );
 
     tokio::spawn(async move {
+        let graceful = GracefulShutdown::new();
+        // WebSocket upgrades on this port, relayed like sessions of the WS proxy
+        let (upgrades_tx, mut upgrades) = mpsc::unbounded_channel();
+        let session_shutdown = shutdown.clone();
+        let mut sessions = JoinSet::new();
         loop {
-            tokio::select! {
-                accept_result = listener.accept() => {
-                    match accept_result {
-                        Ok((stream, _)) => {
-                            let proxy_state = proxy_state.clone();
-
-                            tokio::spawn(async move {
-                                let io = TokioIo::new(stream);
-                                let service = service_fn(move |req: Request<Incoming>| {
-                                    let proxy_state = proxy_state.clone();
-                                    async move { handle_http_request(req, proxy_state).await }
-                                });
-
-                                if let Err(e) = http1::Builder::new()
-                                    .serve_connection(io, service)
-                                    .await
-                                {
-                                    eprintln!("[http-proxy] Connection error: {}", e);
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                Some((opened, proxy_state)) = upgrades.recv() => {
+                    sessions.spawn(relay_websocket(opened, proxy_state, session_shutdown.clone()));
+                    continue;
+                }
+                Some(_) = sessions.join_next() => continue,
+                _ = shutdown.wait() => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    shared.load().tcp.apply(&stream);
+                    let shared = shared.clone();
+                    let upgrades = upgrades_tx.clone();
+                    let watcher = graceful.watcher();
+
+                    tokio::spawn(async move {
+                        let io = TokioIo::new(stream);
+                        // Each request picks up the current state, so keep-alive
+                        // connections follow settings changes
+                        let service = service_fn(move |req: Request<Incoming>| {
+                            let proxy_state = shared.load();
+                            let upgrades = upgrades.clone();
+                            async move {
+                                if !ws_handshake::wants_upgrade(req.headers()) {
+                                    return handle_http_request(req, proxy_state).await;
                                 }
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("[http-proxy] Accept error: {}", e);
+                                let (response, opened) = open_ws_backend(req, &proxy_state).await;
+                                if let Some(opened) = opened {
+                                    let _ = upgrades.send((opened, proxy_state));
+                                }
+                                Ok(response.map(|_| full_body(Bytes::new())))
+                            }
+                        });
+
+                        // HTTP/1.1 for the webview (including WebSocket upgrades),
+                        // plus cleartext HTTP/2 for native gRPC clients
+                        let builder = auto::Builder::new(TokioExecutor::new());
+                        let connection = builder.serve_connection_with_upgrades(io, service);
+                        if let Err(e) = watcher.watch(connection).await {
+                            log::error!(target: "http-proxy", "Connection error: {}", e);
                         }
-                    }
+                    });
                 }
-                _ = &mut shutdown_rx => {
-                    println!("[http-proxy] Shutting down");
-                    break;
+                Err(e) => {
+                    log::error!(target: "http-proxy", "Accept error: {}", e);
                 }
             }
         }
+
+        drop(listener);
+        log::info!(
+            target: "http-proxy",
+            "Shutting down, draining {} connections and {} WebSocket sessions",
+            graceful.count(),
+            sessions.len()
+        );
+        let drain = Duration::from_secs(shutdown::DRAIN_TIMEOUT_SECS);
+        let drained = async {
+            graceful.shutdown().await;
+            while sessions.join_next().await.is_some() {}
+        };
+        if tokio::time::timeout(drain, drained).await.is_err() {
+            log::warn!(target: "http-proxy", "Requests still in flight after {:?}, dropping them", drain);
+        }
+        let _ = stopped.send(());
     });
 
-    Ok((port, shutdown_tx))
+    Ok(port)
 }
 
-/// Start the WebSocket proxy server
+/// Start the WebSocket proxy server. Once `shutdown` fires, it stops accepting and
+/// closes open sessions before resolving `stopped`.
 async fn start_ws_proxy(
-    backend_url: String,
-    skip_cert_validation: bool,
-) -> Result<(u16, oneshot::Sender<()>), String> {
+    shared: Arc<SharedProxyState>,
+    (mut shutdown, stopped): (ShutdownSignal, oneshot::Sender<()>),
+) -> Result<u16, String> {
     let addr = SocketAddr::from(([127, 0, 0, 1], 0));
     let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
     let port = listener.local_addr().map_err(|e| e.to_string())?.port();
 
-    let proxy_state = Arc::new(ProxyState::new(backend_url.clone(), skip_cert_validation));
-    let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
-
-    let ws_url = proxy_state.ws_url();
-    println!(
-        "[ws-proxy] Starting on ws://localhost:{} -> {}",
-        port, ws_url
+    log::info!(
+        target: "ws-proxy",
+        "Starting on ws://localhost:{} -> {}",
+        port,
+        shared.load().ws_url("")
     );
 
     tokio::spawn(async move {
+        let session_shutdown = shutdown.clone();
+        let mut sessions = JoinSet::new();
         loop {
-            tokio::select! {
-                accept_result = listener.accept() => {
-                    match accept_result {
-                        Ok((stream, _)) => {
-                            let proxy_state = proxy_state.clone();
-
-                            tokio::spawn(async move {
-                                handle_websocket_connection(stream, proxy_state, skip_cert_validation).await;
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("[ws-proxy] Accept error: {}", e);
-                        }
-                    }
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                Some(_) = sessions.join_next() => continue,
+                _ = shutdown.wait() => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let proxy_state = shared.load();
+                    proxy_state.tcp.apply(&stream);
+                    sessions.spawn(handle_websocket_connection(
+                        stream,
+                        proxy_state,
+                        session_shutdown.clone(),
+                    ));
                 }
-                _ = &mut shutdown_rx => {
-                    println!("[ws-proxy] Shutting down");
-                    break;
+                Err(e) => {
+                    log::error!(target: "ws-proxy", "Accept error: {}", e);
                 }
             }
         }
+
+        drop(listener);
+        log::info!(target: "ws-proxy", "Shutting down, closing {} sessions", sessions.len());
+        let drain = Duration::from_secs(shutdown::DRAIN_TIMEOUT_SECS);
+        let closed = async { while sessions.join_next().await.is_some() {} };
+        if tokio::time::timeout(drain, closed).await.is_err() {
+            log::warn!(target: "ws-proxy", "Sessions still open after {:?}, dropping them", drain);
+        }
+        let _ = stopped.send(());
     });
 
-    Ok((port, shutdown_tx))
+    Ok(port)
 }
 
 /// Get the current backend URL setting.
@@ -526,33 +2810,444 @@ async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String>
 
 /// Update settings and restart proxy if needed.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn update_settings(
+    app: AppHandle,
     backend_url: Option<String>,
     skip_cert_validation: Option<bool>,
+    fallback_backend_urls: Option<Vec<String>>,
+    host_header_override: Option<String>,
+    tls_server_name_override: Option<String>,
+    upstream_proxy: Option<UpstreamProxy>,
+    proxy_pac_url: Option<String>,
+    use_system_proxy: Option<bool>,
+    quick_capture_shortcut: Option<String>,
+    insert_text_shortcut: Option<String>,
+    dictation_shortcut: Option<String>,
+    log_format: Option<LogFormat>,
+    data_saver: Option<bool>,
+    stale_while_revalidate: Option<bool>,
+    revalidate_cached_responses: Option<bool>,
+    persist_response_cache: Option<bool>,
+    warm_up_connections: Option<bool>,
+    prefetch_on_startup: Option<bool>,
+    tcp_keepalive_secs: Option<u64>,
+    tcp_nodelay: Option<bool>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout_secs: Option<u64>,
+    dns_cache_ttl_secs: Option<u64>,
+    host_overrides: Option<HostOverrides>,
+    dns_over_https_url: Option<String>,
+    proxy_connect_timeout_secs: Option<u64>,
+    proxy_request_timeout_secs: Option<u64>,
+    proxy_path_timeouts: Option<Vec<PathTimeout>>,
+    proxy_retry: Option<RetryPolicy>,
+    reconnect_backoff: Option<ReconnectBackoff>,
+    proxy_redirects: Option<RedirectPolicy>,
+    proxy_upstreams: Option<Vec<Upstream>>,
+    proxy_path_rewrites: Option<Vec<PathRewrite>>,
+    proxy_header_rules: Option<Vec<HeaderRule>>,
+    circuit_breaker_threshold: Option<u32>,
+    max_concurrent_requests: Option<usize>,
+    max_queued_requests: Option<usize>,
+    proxy_rate_limits: Option<Vec<RateLimit>>,
+    force_http1: Option<bool>,
+    http3: Option<bool>,
+    upload_limit_bytes_per_sec: Option<u64>,
+    max_request_body_bytes: Option<u64>,
+    stream_request_threshold_bytes: Option<u64>,
+    rewrite_content_types: Option<Vec<String>>,
+    compress_responses: Option<bool>,
+    response_content_encoding: Option<ContentEncodingMode>,
+    multiplex_websockets: Option<bool>,
+    ws_max_message_bytes: Option<usize>,
+    ws_max_frame_bytes: Option<usize>,
+    ws_chunk_bytes: Option<usize>,
+    ws_msgpack_transcoding: Option<bool>,
+    ws_idle_timeout_secs: Option<u64>,
+    echo_cancellation: Option<bool>,
+    noise_suppression: Option<bool>,
     state: State<'_, AppState>,
 ) -> Result<AppSettings, String> {
+    if proxy_connect_timeout_secs == Some(0) || proxy_request_timeout_secs == Some(0) {
+        return Err("Proxy timeouts must be at least 1 second".to_string());
+    }
+    if let Some(overrides) = &proxy_path_timeouts {
+        path_timeouts::validate(overrides)?;
+    }
+    if let Some(limits) = &proxy_rate_limits {
+        rate_limit::validate(limits)?;
+    }
+    if let Some(enabled) = http3 {
+        http3::validate(enabled)?;
+    }
+    if let Some(policy) = &proxy_redirects {
+        policy.validate()?;
+    }
+    if let Some(backoff) = &reconnect_backoff {
+        backoff.validate()?;
+    }
+    if let Some(urls) = &fallback_backend_urls {
+        failover::validate(urls)?;
+    }
+    if let Some(upstreams) = &proxy_upstreams {
+        upstreams::validate(upstreams)?;
+    }
+    if let Some(rewrites) = &proxy_path_rewrites {
+        path_rewrite::validate(rewrites)?;
+    }
+    if let Some(rules) = &proxy_header_rules {
+        header_rules::validate(rules)?;
+    }
+    let host_overrides = host_overrides.map(host_overrides::normalize);
+    // An empty URL switches back to the system resolver
+    let dns_over_https_url =
+        dns_over_https_url.map(|url| Some(url.trim().to_string()).filter(|url| !url.is_empty()));
+    if let Some(Some(url)) = &dns_over_https_url {
+        doh::validate(url)?;
+    }
+    if let Some(overrides) = &host_overrides {
+        host_overrides::validate(overrides)?;
+    }
+    // A proxy with an empty host turns it off
+    let upstream_proxy =
+        upstream_proxy.map(|proxy| Some(proxy).filter(|proxy| !proxy.host.trim().is_empty()));
+    if let Some(Some(proxy)) = &upstream_proxy {
+        proxy.validate()?;
+    }
+    // An empty PAC URL turns auto-config off
+    let proxy_pac_url =
+        proxy_pac_url.map(|url| Some(url.trim().to_string()).filter(|url| !url.is_empty()));
+    if let Some(Some(url)) = &proxy_pac_url {
+        pac::validate(url)?;
+    }
     let mut needs_proxy_restart = false;
 
     {
         let mut settings = state.settings.lock().await;
+        let ws_max_message_bytes = ws_max_message_bytes.unwrap_or(settings.ws_max_message_bytes);
+        let ws_max_frame_bytes = ws_max_frame_bytes.unwrap_or(settings.ws_max_frame_bytes);
+        ws_limits::validate(ws_max_message_bytes, ws_max_frame_bytes)?;
+        let ws_chunk_bytes = ws_chunk_bytes.unwrap_or(settings.ws_chunk_bytes);
+        ws_chunking::validate(ws_chunk_bytes, ws_max_message_bytes)?;
+        // An empty override clears it; overrides are checked against the new URL
+        let host_header_override = host_header_override
+            .map(|host| Some(host.trim().to_string()).filter(|h| !h.is_empty()));
+        let tls_server_name_override = tls_server_name_override
+            .map(|name| Some(name.trim().to_string()).filter(|n| !n.is_empty()));
+        backend_host::validate(
+            backend_url.as_deref().unwrap_or(&settings.backend_url),
+            host_header_override
+                .clone()
+                .unwrap_or_else(|| settings.host_header_override.clone())
+                .as_deref(),
+            tls_server_name_override
+                .clone()
+                .unwrap_or_else(|| settings.tls_server_name_override.clone())
+                .as_deref(),
+        )?;
+        if let Some(host) = host_header_override {
+            if host != settings.host_header_override {
+                settings.host_header_override = host;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(name) = tls_server_name_override {
+            if name != settings.tls_server_name_override {
+                settings.tls_server_name_override = name;
+                needs_proxy_restart = true;
+            }
+        }
         if let Some(url) = backend_url {
             if url != settings.backend_url {
                 settings.backend_url = url;
                 needs_proxy_restart = true;
             }
         }
+        if let Some(urls) = fallback_backend_urls {
+            if urls != settings.fallback_backend_urls {
+                settings.fallback_backend_urls = urls;
+                // Start over on the primary; the monitor fails over again if needed
+                state
+                    .failover
+                    .reset(&BackendHost::from_settings(&settings).url);
+            }
+        }
         if let Some(skip) = skip_cert_validation {
             if skip != settings.skip_cert_validation {
                 settings.skip_cert_validation = skip;
                 needs_proxy_restart = true;
             }
         }
+        if let Some(proxy) = upstream_proxy {
+            if proxy != settings.upstream_proxy {
+                settings.upstream_proxy = proxy;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(url) = proxy_pac_url {
+            if url != settings.proxy_pac_url {
+                settings.proxy_pac_url = url;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(enabled) = use_system_proxy {
+            if enabled != settings.use_system_proxy {
+                settings.use_system_proxy = enabled;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(shortcut) = quick_capture_shortcut {
+            if shortcut != settings.quick_capture_shortcut {
+                #[cfg(desktop)]
+                quick_capture::register_shortcut(
+                    &app,
+                    &settings.quick_capture_shortcut,
+                    &shortcut,
+                )?;
+                settings.quick_capture_shortcut = shortcut;
+            }
+        }
+        if let Some(shortcut) = insert_text_shortcut {
+            if shortcut != settings.insert_text_shortcut {
+                #[cfg(desktop)]
+                text_insertion::register_shortcut(&app, &settings.insert_text_shortcut, &shortcut)?;
+                settings.insert_text_shortcut = shortcut;
+            }
+        }
+        if let Some(shortcut) = dictation_shortcut {
+            if shortcut != settings.dictation_shortcut {
+                #[cfg(desktop)]
+                dictation::register_shortcut(&app, &settings.dictation_shortcut, &shortcut)?;
+                settings.dictation_shortcut = shortcut;
+            }
+        }
+        if let Some(format) = log_format {
+            logging::set_format(format);
+            settings.log_format = format;
+        }
+        if let Some(enabled) = data_saver {
+            state.data_saver.store(enabled, Ordering::Relaxed);
+            settings.data_saver = enabled;
+        }
+        if let Some(enabled) = stale_while_revalidate {
+            state.response_cache.set_stale_while_revalidate(enabled);
+            settings.stale_while_revalidate = enabled;
+        }
+        if let Some(enabled) = revalidate_cached_responses {
+            state.response_cache.set_revalidate(enabled);
+            settings.revalidate_cached_responses = enabled;
+        }
+        if let Some(enabled) = persist_response_cache {
+            state.response_cache.set_persist(enabled);
+            settings.persist_response_cache = enabled;
+        }
+        if let Some(enabled) = warm_up_connections {
+            state.warm_up_connections.store(enabled, Ordering::Relaxed);
+            settings.warm_up_connections = enabled;
+        }
+        if let Some(enabled) = prefetch_on_startup {
+            settings.prefetch_on_startup = enabled;
+        }
+        if let Some(secs) = tcp_keepalive_secs {
+            if secs != settings.tcp_keepalive_secs {
+                settings.tcp_keepalive_secs = secs;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(enabled) = tcp_nodelay {
+            if enabled != settings.tcp_nodelay {
+                settings.tcp_nodelay = enabled;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(max) = pool_max_idle_per_host {
+            if max != settings.pool_max_idle_per_host {
+                settings.pool_max_idle_per_host = max;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(secs) = pool_idle_timeout_secs {
+            if secs != settings.pool_idle_timeout_secs {
+                settings.pool_idle_timeout_secs = secs;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(secs) = dns_cache_ttl_secs {
+            state.dns_cache.set_ttl(secs);
+            settings.dns_cache_ttl_secs = secs;
+        }
+        if let Some(url) = dns_over_https_url {
+            state.dns_cache.set_doh(url.as_deref())?;
+            settings.dns_over_https_url = url;
+        }
+        if let Some(overrides) = host_overrides {
+            if overrides != settings.host_overrides {
+                settings.host_overrides = overrides;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(secs) = proxy_connect_timeout_secs {
+            if secs != settings.proxy_connect_timeout_secs {
+                settings.proxy_connect_timeout_secs = secs;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(secs) = proxy_request_timeout_secs {
+            if secs != settings.proxy_request_timeout_secs {
+                settings.proxy_request_timeout_secs = secs;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(overrides) = proxy_path_timeouts {
+            if overrides != settings.proxy_path_timeouts {
+                settings.proxy_path_timeouts = overrides;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(policy) = proxy_retry {
+            if policy != settings.proxy_retry {
+                settings.proxy_retry = policy;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(backoff) = reconnect_backoff {
+            if backoff != settings.reconnect_backoff {
+                settings.reconnect_backoff = backoff;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(policy) = proxy_redirects {
+            if policy != settings.proxy_redirects {
+                settings.proxy_redirects = policy;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(upstreams) = proxy_upstreams {
+            if upstreams != settings.proxy_upstreams {
+                settings.proxy_upstreams = upstreams;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(rewrites) = proxy_path_rewrites {
+            if rewrites != settings.proxy_path_rewrites {
+                settings.proxy_path_rewrites = rewrites;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(rules) = proxy_header_rules {
+            if rules != settings.proxy_header_rules {
+                settings.proxy_header_rules = rules;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(threshold) = circuit_breaker_threshold {
+            state.circuit.set_threshold(threshold);
+            settings.circuit_breaker_threshold = threshold;
+        }
+        if let Some(max) = max_concurrent_requests {
+            if max != settings.max_concurrent_requests {
+                settings.max_concurrent_requests = max;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(max) = max_queued_requests {
+            if max != settings.max_queued_requests {
+                settings.max_queued_requests = max;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(limits) = proxy_rate_limits {
+            state.rate_limiter.set_limits(limits.clone());
+            settings.proxy_rate_limits = limits;
+        }
+        if let Some(force) = force_http1 {
+            if force != settings.force_http1 {
+                settings.force_http1 = force;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(enabled) = http3 {
+            if enabled != settings.http3 {
+                settings.http3 = enabled;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(limit) = upload_limit_bytes_per_sec {
+            state.upload_limit.store(limit, Ordering::Relaxed);
+            settings.upload_limit_bytes_per_sec = limit;
+        }
+        if let Some(max_bytes) = max_request_body_bytes {
+            state.body_limit.set_max_bytes(max_bytes);
+            settings.max_request_body_bytes = max_bytes;
+        }
+        if let Some(threshold) = stream_request_threshold_bytes {
+            state
+                .stream_request_threshold
+                .store(threshold, Ordering::Relaxed);
+            settings.stream_request_threshold_bytes = threshold;
+        }
+        if let Some(content_types) = rewrite_content_types {
+            if content_types != settings.rewrite_content_types {
+                settings.rewrite_content_types = content_types;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(enabled) = compress_responses {
+            state.compress_responses.store(enabled, Ordering::Relaxed);
+            settings.compress_responses = enabled;
+        }
+        if let Some(mode) = response_content_encoding {
+            state
+                .decode_content_encoding
+                .store(mode == ContentEncodingMode::Decode, Ordering::Relaxed);
+            settings.response_content_encoding = mode;
+        }
+        if let Some(enabled) = multiplex_websockets {
+            if enabled != settings.multiplex_websockets {
+                settings.multiplex_websockets = enabled;
+                needs_proxy_restart = true;
+            }
+        }
+        if ws_max_message_bytes != settings.ws_max_message_bytes
+            || ws_max_frame_bytes != settings.ws_max_frame_bytes
+        {
+            settings.ws_max_message_bytes = ws_max_message_bytes;
+            settings.ws_max_frame_bytes = ws_max_frame_bytes;
+            needs_proxy_restart = true;
+        }
+        if ws_chunk_bytes != settings.ws_chunk_bytes {
+            settings.ws_chunk_bytes = ws_chunk_bytes;
+            needs_proxy_restart = true;
+        }
+        if let Some(enabled) = ws_msgpack_transcoding {
+            if enabled != settings.ws_msgpack_transcoding {
+                settings.ws_msgpack_transcoding = enabled;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(secs) = ws_idle_timeout_secs {
+            if secs != settings.ws_idle_timeout_secs {
+                settings.ws_idle_timeout_secs = secs;
+                needs_proxy_restart = true;
+            }
+        }
+        if let Some(enabled) = echo_cancellation {
+            state.audio_processing.set_echo_cancellation(enabled);
+            settings.echo_cancellation = enabled;
+        }
+        if let Some(enabled) = noise_suppression {
+            state.audio_processing.set_noise_suppression(enabled);
+            settings.noise_suppression = enabled;
+        }
     }
 
     state.save().await?;
 
     if needs_proxy_restart {
         restart_proxy_internal(&state).await?;
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { version_check::check_backend(&app).await });
     }
 
     let settings = state.settings.lock().await;
@@ -571,7 +3266,8 @@ async fn get_proxy_url(state: State<'_, AppState>) -> Result<String, String> {
     }
 }
 
-/// Get the WebSocket proxy port.
+/// Get the WebSocket proxy port. Deprecated in favour of WebSocket upgrades on the
+/// HTTP proxy port.
 #[tauri::command]
 async fn get_ws_proxy_port(state: State<'_, AppState>) -> Result<u16, String> {
     let settings = state.settings.lock().await;
@@ -633,43 +3329,55 @@ async fn open_temp_html_attachment_file(
         .map_err(|e| e.to_string())
 }
 
-/// Restart the proxy with current settings.
+/// Apply the current settings to the proxies, starting them on first use. Later
+/// calls swap in a new `ProxyState` and keep the listening ports.
 async fn restart_proxy_internal(state: &AppState) -> Result<(), String> {
-    // Stop existing proxies
-    if let Some(tx) = state.proxy_shutdown_tx.lock().await.take() {
-        let _ = tx.send(());
-    }
-    if let Some(tx) = state.ws_proxy_shutdown_tx.lock().await.take() {
-        let _ = tx.send(());
+    let settings = state.settings.lock().await.clone();
+    state
+        .pac
+        .load(
+            settings.proxy_pac_url.as_deref(),
+            settings.skip_cert_validation,
+        )
+        .await;
+
+    let proxy_state = Arc::new(ProxyState::new(&settings, state));
+    // Have an upstream connection ready before the first real request
+    tokio::spawn(warmup::run_keep_warm(Arc::downgrade(&proxy_state)));
+    tokio::spawn(circuit_breaker::run_probe(Arc::downgrade(&proxy_state)));
+
+    let mut proxy = state.proxy.lock().await;
+    if let Some(running) = proxy.as_ref() {
+        log::info!(target: "proxy", "Applying new settings -> {}", settings.backend_url);
+        running.shared.store(proxy_state);
+        return Ok(());
     }
 
-    // Get settings
-    let (backend_url, skip_cert_validation) = {
-        let settings = state.settings.lock().await;
-        (settings.backend_url.clone(), settings.skip_cert_validation)
-    };
-
-    // Start HTTP proxy
-    let (http_port, http_shutdown_tx) =
-        start_http_proxy(backend_url.clone(), skip_cert_validation).await?;
+    let shared = Arc::new(SharedProxyState::new(proxy_state));
+    let mut shutdown = Shutdown::new();
+    let http_port = start_http_proxy(shared.clone(), shutdown.register()).await?;
+    let ws_port = start_ws_proxy(shared.clone(), shutdown.register()).await?;
+    *proxy = Some(RunningProxies { shared, shutdown });
+    drop(proxy);
 
-    // Start WebSocket proxy
-    let (ws_port, ws_shutdown_tx) = start_ws_proxy(backend_url, skip_cert_validation).await?;
-
-    // Update state
     {
         let mut settings = state.settings.lock().await;
         settings.proxy_port = http_port;
         settings.ws_proxy_port = ws_port;
     }
-    *state.proxy_shutdown_tx.lock().await = Some(http_shutdown_tx);
-    *state.ws_proxy_shutdown_tx.lock().await = Some(ws_shutdown_tx);
-
     state.save().await?;
 
     Ok(())
 }
 
+/// Stop the local proxies, draining their connections.
+async fn stop_proxies(state: &AppState) {
+    let running = state.proxy.lock().await.take();
+    if let Some(running) = running {
+        running.shutdown.stop().await;
+    }
+}
+
 fn install_crypto_provider() {
     if let Err(err) = rustls::crypto::ring::default_provider().install_default() {
         eprintln!("[tls] Failed to install rustls crypto provider: {:?}", err);
@@ -683,16 +3391,86 @@ pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
             let app_handle = app.handle().clone();
-            let state = AppState::load(&app_handle);
+            let data_dir = data_dir::prepare(&app_handle);
+            logging::init(data_dir.log_dir(&app_handle));
+            if let Some(e) = &data_dir.migration_error {
+                log::error!(target: "data-dir", "{}", e);
+            }
+            let state = AppState::load(&app_handle, data_dir);
+            logging::set_format(state.settings.blocking_lock().log_format);
+            let auth_manager = state.auth.clone();
+            let safe_mode = state.startup.safe_mode();
+
+            #[cfg(desktop)]
+            if !safe_mode {
+                app.handle()
+                    .plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+                app.handle()
+                    .plugin(tauri_plugin_clipboard_manager::init())?;
+                let settings = state.settings.blocking_lock().clone();
+                if let Err(e) = quick_capture::register_shortcut(
+                    &app_handle,
+                    "",
+                    &settings.quick_capture_shortcut,
+                ) {
+                    log::error!(target: "quick-capture", "Failed to register shortcut: {}", e);
+                }
+                if let Err(e) = text_insertion::register_shortcut(
+                    &app_handle,
+                    "",
+                    &settings.insert_text_shortcut,
+                ) {
+                    log::error!(target: "insert-text", "Failed to register shortcut: {}", e);
+                }
+                if let Err(e) =
+                    dictation::register_shortcut(&app_handle, "", &settings.dictation_shortcut)
+                {
+                    log::error!(target: "dictation", "Failed to register shortcut: {}", e);
+                }
+            }
+
             app.manage(state);
 
+            // Upload new files from watched folders
+            if !safe_mode {
+                tauri::async_runtime::spawn(watch_folders::run_watch_folders(app_handle.clone()));
+                tauri::async_runtime::spawn(notes_search::run_notes_indexer(app_handle.clone()));
+                tauri::async_runtime::spawn(scheduler::run_scheduler(app_handle.clone()));
+            }
+
+            // Follow the selected audio output across device changes
+            #[cfg(desktop)]
+            tauri::async_runtime::spawn(audio_devices::run_audio_output_monitor(
+                app_handle.clone(),
+            ));
+
+            // Move the proxies to a fallback backend while the primary is down
+            tauri::async_runtime::spawn(failover::run_failover_monitor(app_handle.clone()));
+
+            // Report connection quality to the UI
+            tauri::async_runtime::spawn(connection_quality::run_quality_monitor(
+                app_handle.clone(),
+            ));
+
+            // Keep the access token fresh
+            tauri::async_runtime::spawn(auth::run_refresh_scheduler(
+                app_handle.clone(),
+                auth_manager,
+            ));
+
             // Start the proxy
             let app_handle_clone = app_handle.clone();
             tauri::async_runtime::spawn(async move {
                 let state: State<'_, AppState> = app_handle_clone.state();
                 if let Err(e) = restart_proxy_internal(&state).await {
-                    eprintln!("[proxy] Failed to start: {}", e);
+                    log::error!(target: "proxy", "Failed to start: {}", e);
                 } else {
+                    // The app came up far enough to be usable; reset crash-loop detection
+                    state.startup.confirm();
+                    if safe_mode {
+                        let _ = app_handle_clone.emit("safe-mode", state.startup.status());
+                    }
+
                     // Emit event with proxy ports
                     let settings = state.settings.lock().await;
                     let _ = app_handle_clone.emit(
@@ -702,6 +3480,13 @@ pub fn run() {
                             "ws_port": settings.ws_proxy_port,
                         }),
                     );
+                    let prefetch_port = settings.prefetch_on_startup.then_some(settings.proxy_port);
+                    drop(settings);
+
+                    if let Some(port) = prefetch_port {
+                        tokio::spawn(warmup::prefetch(port));
+                    }
+                    version_check::check_backend(&app_handle_clone).await;
                 }
             });
 
@@ -710,6 +3495,7 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             get_backend_url,
             set_backend_url,
@@ -719,7 +3505,91 @@ pub fn run() {
             get_ws_proxy_port,
             save_artifact_file,
             open_temp_html_attachment_file,
+            auth::set_auth_tokens,
+            auth::clear_auth_tokens,
+            notifications::set_notification_rule,
+            notifications::list_notification_rules,
+            local_files::grant_local_file_preview,
+            local_files::revoke_local_file_preview,
+            ocr::ocr_image,
+            #[cfg(desktop)]
+            text_insertion::insert_text_into_focus,
+            #[cfg(desktop)]
+            dictation::update_dictation,
+            #[cfg(desktop)]
+            dictation::stop_dictation,
+            watch_folders::add_watch_folder,
+            watch_folders::remove_watch_folder,
+            watch_folders::set_watch_folder_enabled,
+            watch_folders::get_watch_folder_status,
+            transfers::begin_transfer,
+            transfers::update_transfer,
+            transfers::end_transfer,
+            uploads::upload_file_to_artifacts,
+            safe_mode::get_safe_mode_status,
+            connection_quality::get_proxy_stats,
+            ws_sessions::get_active_ws_connections,
+            ws_sessions::get_ws_endpoints,
+            ws_stats::get_ws_stats,
+            request_ids::get_recent_requests,
+            circuit_breaker::get_backend_circuit,
+            failover::get_active_backend,
+            canary::set_canary_routing,
+            canary::get_canary_routing,
+            discovery::discover_backends,
+            dns_cache::flush_dns_cache,
+            data_dir::get_data_dir,
+            data_dir::choose_data_dir,
+            data_dir::reset_data_dir,
+            bandwidth::get_bandwidth_usage,
+            audio_processing::set_tts_playback_active,
+            tools::list_local_tools,
+            tools::set_tool_consent,
+            tools::call_local_tool,
+            calendar::set_calendar_account,
+            calendar::clear_calendar_account,
+            calendar::get_calendar_account,
+            notes_search::add_notes_folder,
+            notes_search::remove_notes_folder,
+            notes_search::reindex_notes,
+            notes_search::get_notes_index_status,
+            scheduler::schedule_task,
+            scheduler::list_scheduled_tasks,
+            scheduler::cancel_task,
+            timers::start_timer,
+            timers::list_timers,
+            timers::cancel_timer,
+            url_preview::fetch_url_preview,
+            external_links::open_external_url,
+            external_links::set_link_allowlist,
+            downloads::download_file,
+            downloads::list_downloads,
+            downloads::open_download,
+            downloads::set_allow_executable_downloads,
+            download_scan::set_download_scan_command,
+            version_check::get_backend_compatibility,
+            #[cfg(desktop)]
+            audio_devices::list_audio_outputs,
+            #[cfg(desktop)]
+            audio_devices::get_audio_output,
+            #[cfg(desktop)]
+            audio_devices::set_audio_output,
+            #[cfg(desktop)]
+            audio_devices::list_audio_inputs,
+            #[cfg(desktop)]
+            audio_devices::set_audio_input,
+            #[cfg(desktop)]
+            audio_devices::start_mic_preview,
+            #[cfg(desktop)]
+            audio_devices::stop_mic_preview,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Let in-flight requests finish and close WebSocket sessions cleanly
+                let state: State<'_, AppState> = app.state();
+                tauri::async_runtime::block_on(stop_proxies(&state));
+            }
+        });
 }
@@ -0,0 +1,298 @@
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Bytes, Frame};
+use hyper::{HeaderMap, Method, Response, StatusCode};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::byte_range::ByteRange;
+use crate::{full_body, mime, AppState, BoxError, ProxyBody};
+
+/// Proxy route prefix under which granted local files are served.
+pub const LOCAL_FILES_ROUTE: &str = "/__assistant/local-files/";
+/// Size of the chunks a granted file is streamed in.
+const FILE_CHUNK_BYTES: u64 = 64 * 1024;
+
+fn random_hex(bytes: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..bytes)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+/// Compare without stopping at the first difference, so timing reveals nothing
+/// about how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Local files the user explicitly granted for preview through the HTTP proxy.
+///
+/// Every URL carries a per-launch token, and only granted files can be served.
+pub struct LocalFileGrants {
+    token: String,
+    grants: RwLock<HashMap<String, PathBuf>>,
+}
+
+impl LocalFileGrants {
+    pub fn new() -> Self {
+        Self {
+            token: random_hex(32),
+            grants: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn grant(&self, path: PathBuf) -> String {
+        let id = random_hex(16);
+        self.grants
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.clone(), path);
+        id
+    }
+
+    fn revoke(&self, id: &str) -> bool {
+        self.grants
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+            .is_some()
+    }
+
+    /// Resolve a `LOCAL_FILES_ROUTE` request path + query to a granted file.
+    ///
+    /// Returns `None` when the token is wrong, the id is unknown, or the file
+    /// no longer resolves to the granted location (e.g. swapped for a symlink).
+    pub fn resolve(&self, path_and_query: &str) -> Option<PathBuf> {
+        let rest = path_and_query.strip_prefix(LOCAL_FILES_ROUTE)?;
+        let (id, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let token = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))?;
+        if !constant_time_eq(token.as_bytes(), self.token.as_bytes()) {
+            return None;
+        }
+        self.granted_path(id)
+    }
+
+    /// The file granted under `id`, for commands that read it natively (e.g. OCR).
+    ///
+    /// Returns `None` when the id is unknown or the file no longer resolves to the
+    /// granted location.
+    pub fn granted_path(&self, id: &str) -> Option<PathBuf> {
+        let granted = self
+            .grants
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)?
+            .clone();
+        let current = granted.canonicalize().ok()?;
+        (current == granted && current.is_file()).then_some(current)
+    }
+}
+
+/// Serve a granted local file for a `LOCAL_FILES_ROUTE` request, honouring a
+/// single-range `Range` header so media can seek.
+pub async fn serve(
+    grants: &LocalFileGrants,
+    path_and_query: &str,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Response<ProxyBody> {
+    if method != Method::GET && method != Method::HEAD {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header("allow", "GET, HEAD")
+            .body(full_body("Method not allowed"))
+            .unwrap();
+    }
+
+    let Some(path) = grants.resolve(path_and_query) else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(full_body("Not found"))
+            .unwrap();
+    };
+
+    let opened = match tokio::fs::File::open(&path).await {
+        Ok(file) => file.metadata().await.map(|metadata| (file, metadata.len())),
+        Err(e) => Err(e),
+    };
+    let (mut file, len) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            log::error!(target: "local-files", "Failed to read {}: {}", path.display(), e);
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(full_body("Failed to read file"))
+                .unwrap();
+        }
+    };
+
+    // Previews carry no validators, so a conditional range always gets the whole file
+    let range = if headers.contains_key("if-range") {
+        ByteRange::Full
+    } else {
+        ByteRange::parse(
+            headers.get("range").and_then(|value| value.to_str().ok()),
+            len,
+        )
+    };
+    let mut builder = Response::builder()
+        .header("content-type", mime::mime_type_for_path(&path))
+        .header("accept-ranges", "bytes")
+        .header("cache-control", "no-store")
+        .header("x-content-type-options", "nosniff")
+        // Granted files are previews, never active content with proxy-origin access
+        .header("content-security-policy", "sandbox");
+    let (start, end) = match range {
+        ByteRange::Full => {
+            builder = builder.status(StatusCode::OK);
+            (0, len.saturating_sub(1))
+        }
+        ByteRange::Partial { start, end } => {
+            builder = builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header("content-range", format!("bytes {}-{}/{}", start, end, len));
+            (start, end)
+        }
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header("content-range", format!("bytes */{}", len))
+                .body(full_body(""))
+                .unwrap();
+        }
+    };
+    let remaining = if len == 0 { 0 } else { end - start + 1 };
+    builder = builder.header("content-length", remaining);
+    if method == Method::HEAD || remaining == 0 {
+        return builder.body(full_body("")).unwrap();
+    }
+    if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+        log::error!(target: "local-files", "Failed to seek {}: {}", path.display(), e);
+        return Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(full_body("Failed to read file"))
+            .unwrap();
+    }
+    builder.body(file_body(file, remaining)).unwrap()
+}
+
+/// Stream `remaining` bytes from the current position of `file`.
+fn file_body(file: tokio::fs::File, remaining: u64) -> ProxyBody {
+    let chunks =
+        futures_util::stream::unfold((file, remaining), |(mut file, remaining)| async move {
+            if remaining == 0 {
+                return None;
+            }
+            let mut chunk = vec![0; remaining.min(FILE_CHUNK_BYTES) as usize];
+            match file.read(&mut chunk).await {
+                Ok(0) => None,
+                Ok(read) => {
+                    chunk.truncate(read);
+                    let frame = Frame::data(Bytes::from(chunk));
+                    Some((Ok(frame), (file, remaining - read as u64)))
+                }
+                Err(e) => Some((Err(Box::new(e) as BoxError), (file, 0))),
+            }
+        });
+    BodyExt::boxed(StreamBody::new(chunks))
+}
+
+/// A file the user granted for preview.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalFilePreview {
+    /// Pass to `revoke_local_file_preview`.
+    pub id: String,
+    /// Proxy-relative URL to load the file from.
+    pub url: String,
+    /// File name, for display.
+    pub name: String,
+}
+
+/// Let the user pick a local file in a native dialog and grant the webview read
+/// access to it. Page content never names the path, so it can only reach files
+/// the user chose. Returns `None` if the picker was cancelled.
+#[tauri::command]
+pub async fn grant_local_file_preview(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<LocalFilePreview>, String> {
+    let picker = app.dialog().file().set_title("Choose a file to preview");
+    let picked = tauri::async_runtime::spawn_blocking(move || picker.blocking_pick_file())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| e.to_string())?
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    if !path.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let id = state.local_files.grant(path);
+    Ok(Some(LocalFilePreview {
+        url: format!(
+            "{}{}?token={}",
+            LOCAL_FILES_ROUTE, id, state.local_files.token
+        ),
+        id,
+        name,
+    }))
+}
+
+/// Revoke a previously granted file preview URL (by its id segment).
+#[tauri::command]
+pub async fn revoke_local_file_preview(
+    id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state.local_files.revoke(&id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, LocalFileGrants, LOCAL_FILES_ROUTE};
+
+    #[test]
+    fn serves_only_granted_files_with_the_token() {
+        let grants = LocalFileGrants::new();
+        let file = std::env::current_exe().unwrap().canonicalize().unwrap();
+        let id = grants.grant(file.clone());
+        let url = format!("{}{}?token={}", LOCAL_FILES_ROUTE, id, grants.token);
+
+        assert_eq!(grants.resolve(&url), Some(file));
+        assert_eq!(
+            grants.resolve(&format!("{}{}?token=wrong", LOCAL_FILES_ROUTE, id)),
+            None
+        );
+        assert_eq!(
+            grants.resolve(&format!(
+                "{}unknown?token={}",
+                LOCAL_FILES_ROUTE, grants.token
+            )),
+            None
+        );
+
+        assert!(grants.revoke(&id));
+        assert_eq!(grants.resolve(&url), None);
+
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+}
@@ -0,0 +1,258 @@
+use log::kv::{Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::now_ms;
+
+/// Rotate the log file once it grows past this size.
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept next to the active log (`assistant.log.1` … `assistant.log.N`).
+const MAX_ROTATED_LOG_FILES: u32 = 3;
+const LOG_FILE_NAME: &str = "assistant.log";
+
+/// Line format for log output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines (`<timestamp> <LEVEL> [target] message`).
+    #[default]
+    Pretty,
+    /// One JSON object per line with `timestamp`, `level`, `target`, `request_id` and `message`.
+    Json,
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(dir: PathBuf) -> std::io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 > MAX_LOG_FILE_BYTES {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let path = |index: u32| {
+            if index == 0 {
+                self.dir.join(LOG_FILE_NAME)
+            } else {
+                self.dir.join(format!("{}.{}", LOG_FILE_NAME, index))
+            }
+        };
+        for index in (0..MAX_ROTATED_LOG_FILES).rev() {
+            let from = path(index);
+            if from.exists() {
+                fs::rename(&from, path(index + 1))?;
+            }
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(path(0))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Logger writing to stderr and a size-rotated file in the app log directory.
+struct AppLogger {
+    json: AtomicBool,
+    file: Mutex<Option<LogFile>>,
+}
+
+static LOGGER: OnceLock<AppLogger> = OnceLock::new();
+
+/// Picks the `request_id` key out of a record's key-values.
+#[derive(Default)]
+struct RequestIdVisitor(Option<String>);
+
+impl<'kvs> VisitSource<'kvs> for RequestIdVisitor {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        if key.as_str() == "request_id" {
+            self.0 = Some(value.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Format a Unix timestamp in milliseconds as RFC 3339 UTC.
+pub fn format_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let rem = secs % 86_400;
+    // Days since the epoch to a civil date (proleptic Gregorian calendar)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        ms % 1000
+    )
+}
+
+fn format_line(
+    format: LogFormat,
+    timestamp_ms: u64,
+    level: Level,
+    target: &str,
+    request_id: Option<&str>,
+    message: &str,
+) -> String {
+    let timestamp = format_timestamp(timestamp_ms);
+    match format {
+        LogFormat::Pretty => match request_id {
+            Some(request_id) => format!(
+                "{} {:<5} [{}] {} request_id={}\n",
+                timestamp, level, target, message, request_id
+            ),
+            None => format!("{} {:<5} [{}] {}\n", timestamp, level, target, message),
+        },
+        LogFormat::Json => format!(
+            "{}\n",
+            serde_json::json!({
+                "timestamp": timestamp,
+                "level": level.as_str().to_ascii_lowercase(),
+                "target": target,
+                "request_id": request_id,
+                "message": message,
+            })
+        ),
+    }
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut request_id = RequestIdVisitor::default();
+        let _ = record.key_values().visit(&mut request_id);
+        let format = if self.json.load(Ordering::Relaxed) {
+            LogFormat::Json
+        } else {
+            LogFormat::Pretty
+        };
+        let line = format_line(
+            format,
+            now_ms(),
+            record.level(),
+            record.target(),
+            request_id.0.as_deref(),
+            &record.args().to_string(),
+        );
+
+        let _ = std::io::stderr().write_all(line.as_bytes());
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(log_file) = file.as_mut() {
+            if log_file.write_line(&line).is_err() {
+                // Stop writing to a broken file rather than failing every record
+                *file = None;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(log_file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = log_file.file.flush();
+        }
+    }
+}
+
+/// Install the app logger, writing rotating log files into `log_dir`.
+pub fn init(log_dir: PathBuf) {
+    let file = match LogFile::open(log_dir) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("[logging] Failed to open log file: {}", e);
+            None
+        }
+    };
+    let logger = LOGGER.get_or_init(|| AppLogger {
+        json: AtomicBool::new(false),
+        file: Mutex::new(file),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Switch the output format of the running logger.
+pub fn set_format(format: LogFormat) {
+    if let Some(logger) = LOGGER.get() {
+        logger
+            .json
+            .store(format == LogFormat::Json, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_line, format_timestamp, LogFormat};
+    use log::Level;
+
+    #[test]
+    fn formats_rfc3339_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(1_700_000_000_123),
+            "2023-11-14T22:13:20.123Z"
+        );
+    }
+
+    #[test]
+    fn formats_json_lines_with_consistent_fields() {
+        let line = format_line(
+            LogFormat::Json,
+            0,
+            Level::Warn,
+            "ws-proxy",
+            None,
+            "Backend \"read\" error",
+        );
+        assert!(line.ends_with('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["timestamp"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(value["level"], "warn");
+        assert_eq!(value["target"], "ws-proxy");
+        assert!(value["request_id"].is_null());
+        assert_eq!(value["message"], "Backend \"read\" error");
+
+        assert_eq!(
+            format_line(LogFormat::Pretty, 0, Level::Info, "auth", Some("r1"), "ok"),
+            "1970-01-01T00:00:00.000Z INFO  [auth] ok request_id=r1\n"
+        );
+    }
+}
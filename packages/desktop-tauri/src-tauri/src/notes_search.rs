@@ -0,0 +1,552 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::Notify;
+
+use crate::tools::LocalTool;
+use crate::{now_ms, AppState};
+
+/// Consent scope covering the notes search tool.
+pub const CONSENT_SCOPE: &str = "notes";
+
+/// How often indexed folders are rescanned for changes.
+const REINDEX_INTERVAL_SECS: u64 = 600;
+/// Files larger than this are skipped.
+const MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+/// Indexing stops after this many files per scan.
+const MAX_FILES: usize = 20_000;
+/// Target passage size in characters.
+const PASSAGE_CHARS: usize = 800;
+const DEFAULT_RESULTS: usize = 5;
+const MAX_RESULTS: usize = 20;
+
+// BM25 parameters
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+const TEXT_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "text", "org", "rst"];
+const OFFICE_EXTENSIONS: &[&str] = &["docx", "pptx", "xlsx", "odt", "odp", "ods"];
+
+/// Size and modification time used to skip unchanged files on rescans.
+type FileSignature = (u64, u128);
+
+struct Passage {
+    text: String,
+    terms: HashMap<String, u32>,
+    length: u32,
+}
+
+struct IndexedFile {
+    signature: FileSignature,
+    passages: Vec<Passage>,
+}
+
+/// Index status returned by `get_notes_index_status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotesIndexStatus {
+    pub folders: Vec<String>,
+    pub files: usize,
+    pub passages: usize,
+    pub indexing: bool,
+    pub last_indexed_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// A passage returned by `notes_search`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotesSearchResult {
+    pub path: String,
+    pub passage: String,
+    pub score: f64,
+}
+
+/// In-memory full-text index of the user's notes folders.
+pub struct NotesIndex {
+    files: RwLock<HashMap<PathBuf, Arc<IndexedFile>>>,
+    status: Mutex<NotesIndexStatus>,
+    wake: Notify,
+}
+
+impl NotesIndex {
+    pub fn new() -> Self {
+        Self {
+            files: RwLock::new(HashMap::new()),
+            status: Mutex::new(NotesIndexStatus::default()),
+            wake: Notify::new(),
+        }
+    }
+
+    fn status(&self) -> NotesIndexStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn update_status(&self, update: impl FnOnce(&mut NotesIndexStatus)) {
+        update(&mut self.status.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// Rescan `folders`, re-extracting only files that changed since the last scan.
+    fn reindex(&self, folders: &[String]) {
+        let mut paths = Vec::new();
+        for folder in folders {
+            collect_files(Path::new(folder), &mut paths);
+        }
+        paths.truncate(MAX_FILES);
+
+        // Searches keep using the current index until the new one is swapped in
+        let previous = self.files.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut files = HashMap::new();
+        for path in paths {
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            let signature = file_signature(&metadata);
+            if let Some(file) = previous
+                .get(&path)
+                .filter(|file| file.signature == signature)
+            {
+                files.insert(path, file.clone());
+                continue;
+            }
+            match extract_text(&path) {
+                Ok(text) => {
+                    let passages = split_passages(&text)
+                        .into_iter()
+                        .map(|text| {
+                            let tokens = tokenize(&text);
+                            let mut terms = HashMap::new();
+                            for token in &tokens {
+                                *terms.entry(token.clone()).or_insert(0) += 1;
+                            }
+                            Passage {
+                                text,
+                                terms,
+                                length: tokens.len() as u32,
+                            }
+                        })
+                        .collect();
+                    files.insert(
+                        path,
+                        Arc::new(IndexedFile {
+                            signature,
+                            passages,
+                        }),
+                    );
+                }
+                Err(e) => {
+                    log::warn!(target: "notes", "Failed to index {}: {}", path.display(), e);
+                }
+            }
+        }
+
+        let passages = files.values().map(|file| file.passages.len()).sum();
+        let count = files.len();
+        *self.files.write().unwrap_or_else(|e| e.into_inner()) = files;
+        self.update_status(|status| {
+            status.files = count;
+            status.passages = passages;
+        });
+    }
+
+    /// Rank passages against the query with BM25.
+    fn search(&self, query: &str, limit: usize) -> Vec<NotesSearchResult> {
+        let query: HashSet<String> = tokenize(query).into_iter().collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let files = self.files.read().unwrap_or_else(|e| e.into_inner());
+        let passages: Vec<(&PathBuf, &Passage)> = files
+            .iter()
+            .flat_map(|(path, file)| file.passages.iter().map(move |passage| (path, passage)))
+            .collect();
+        if passages.is_empty() {
+            return Vec::new();
+        }
+
+        let total = passages.len() as f64;
+        let average_length = passages
+            .iter()
+            .map(|(_, p)| f64::from(p.length))
+            .sum::<f64>()
+            / total;
+        let idf: HashMap<&str, f64> = query
+            .iter()
+            .map(|term| {
+                let df = passages
+                    .iter()
+                    .filter(|(_, passage)| passage.terms.contains_key(term))
+                    .count() as f64;
+                (term.as_str(), ((total - df + 0.5) / (df + 0.5) + 1.0).ln())
+            })
+            .collect();
+
+        let mut results: Vec<NotesSearchResult> = passages
+            .iter()
+            .filter_map(|(path, passage)| {
+                let length_norm =
+                    1.0 - BM25_B + BM25_B * f64::from(passage.length) / average_length.max(1.0);
+                let score: f64 = query
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = f64::from(*passage.terms.get(term)?);
+                        Some(
+                            idf[term.as_str()] * tf * (BM25_K1 + 1.0)
+                                / (tf + BM25_K1 * length_norm),
+                        )
+                    })
+                    .sum();
+                (score > 0.0).then(|| NotesSearchResult {
+                    path: path.to_string_lossy().into_owned(),
+                    passage: passage.text.clone(),
+                    score,
+                })
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit);
+        results
+    }
+}
+
+fn file_signature(metadata: &fs::Metadata) -> FileSignature {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    (metadata.len(), modified)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn is_indexable(path: &Path) -> bool {
+    let ext = extension(path);
+    ext == "pdf"
+        || TEXT_EXTENSIONS.contains(&ext.as_str())
+        || OFFICE_EXTENSIONS.contains(&ext.as_str())
+}
+
+/// Recursively collect indexable files, skipping hidden files and directories.
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if out.len() >= MAX_FILES {
+            return;
+        }
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_files(&path, out),
+            Ok(kind) if kind.is_file() && is_indexable(&path) => out.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// Text content of an office document's XML part, with paragraph breaks.
+fn xml_text(xml: &str) -> String {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut text = String::new();
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Text(content)) => {
+                if let Ok(content) = content.unescape() {
+                    text.push_str(&content);
+                }
+            }
+            Ok(quick_xml::events::Event::End(end)) => {
+                // Paragraphs (docx/odf), shared strings (xlsx) and table cells
+                if matches!(end.local_name().as_ref(), b"p" | b"si" | b"h" | b"tc") {
+                    text.push_str("\n\n");
+                }
+            }
+            Ok(quick_xml::events::Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    text
+}
+
+fn office_text(path: &Path) -> Result<String, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut parts: Vec<String> = archive
+        .file_names()
+        .filter(|name| {
+            *name == "word/document.xml"
+                || *name == "content.xml"
+                || *name == "xl/sharedStrings.xml"
+                || (name.starts_with("ppt/slides/slide") && name.ends_with(".xml"))
+        })
+        .map(str::to_string)
+        .collect();
+    parts.sort();
+
+    let mut text = String::new();
+    for part in parts {
+        let mut xml = String::new();
+        archive
+            .by_name(&part)
+            .map_err(|e| e.to_string())?
+            .read_to_string(&mut xml)
+            .map_err(|e| e.to_string())?;
+        text.push_str(&xml_text(&xml));
+    }
+    Ok(text)
+}
+
+fn extract_text(path: &Path) -> Result<String, String> {
+    let ext = extension(path);
+    if ext == "pdf" {
+        let data = fs::read(path).map_err(|e| e.to_string())?;
+        // The PDF parser panics on some malformed files
+        return std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(&data))
+            .map_err(|_| "PDF parser failed".to_string())?
+            .map_err(|e| e.to_string());
+    }
+    if OFFICE_EXTENSIONS.contains(&ext.as_str()) {
+        return office_text(path);
+    }
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Split text into passages of roughly `PASSAGE_CHARS`, on paragraph boundaries when possible.
+fn split_passages(text: &str) -> Vec<String> {
+    let mut passages = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n") {
+        let paragraph = paragraph.split_whitespace().collect::<Vec<_>>().join(" ");
+        if paragraph.is_empty() {
+            continue;
+        }
+        if !current.is_empty() && current.len() + paragraph.len() > PASSAGE_CHARS {
+            passages.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&paragraph);
+        // Break up single paragraphs that are far too long
+        while current.len() > PASSAGE_CHARS * 2 {
+            let mut split = PASSAGE_CHARS;
+            while !current.is_char_boundary(split) {
+                split += 1;
+            }
+            let split = current[..split].rfind(' ').unwrap_or(split);
+            let rest = current.split_off(split);
+            passages.push(std::mem::replace(
+                &mut current,
+                rest.trim_start().to_string(),
+            ));
+        }
+    }
+    if !current.is_empty() {
+        passages.push(current);
+    }
+    passages
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Keep the index up to date with the configured folders.
+pub async fn run_notes_indexer(app: AppHandle) {
+    loop {
+        let folders = {
+            let state: State<'_, AppState> = app.state();
+            let settings = state.settings.lock().await;
+            settings.notes_folders.clone()
+        };
+
+        let index_app = app.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            let state: State<'_, AppState> = index_app.state();
+            state.notes_index.update_status(|status| {
+                status.folders = folders.clone();
+                status.indexing = true;
+            });
+            state.notes_index.reindex(&folders);
+        })
+        .await;
+
+        let state: State<'_, AppState> = app.state();
+        state.notes_index.update_status(|status| {
+            status.indexing = false;
+            status.last_indexed_at = Some(now_ms());
+            status.last_error = result.as_ref().err().map(|e| e.to_string());
+        });
+
+        tokio::select! {
+            _ = state.notes_index.wake.notified() => {}
+            _ = tokio::time::sleep(Duration::from_secs(REINDEX_INTERVAL_SECS)) => {}
+        }
+    }
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    vec![LocalTool {
+        name: "notes_search",
+        description: "Search the user's local notes and documents; returns matching passages with file paths.",
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "limit": { "type": "integer", "description": "Maximum number of passages (default 5, max 20)" }
+            },
+            "required": ["query"]
+        }),
+        consent: CONSENT_SCOPE,
+        requires_approval: false,
+    }]
+}
+
+#[derive(Deserialize)]
+struct SearchArgs {
+    query: String,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+/// Run the notes search tool.
+pub fn call(index: &NotesIndex, name: &str, arguments: Value) -> Result<Value, String> {
+    match name {
+        "notes_search" => {
+            let args: SearchArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let limit = args.limit.unwrap_or(DEFAULT_RESULTS).clamp(1, MAX_RESULTS);
+            serde_json::to_value(index.search(&args.query, limit)).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+/// Let the user pick a folder in a native dialog and add it to the notes index.
+/// Page content never names the path, so only folders the user chose are searched.
+/// Returns the folder, or `None` if the picker was cancelled.
+#[tauri::command]
+pub async fn add_notes_folder(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let picker = app
+        .dialog()
+        .file()
+        .set_title("Choose a notes folder to search");
+    let picked = tauri::async_runtime::spawn_blocking(move || picker.blocking_pick_folder())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {}", path.display()));
+    }
+    let path = path.to_string_lossy().into_owned();
+    {
+        let mut settings = state.settings.lock().await;
+        if !settings.notes_folders.contains(&path) {
+            settings.notes_folders.push(path.clone());
+        }
+    }
+    state.save().await?;
+    state.notes_index.wake.notify_one();
+    Ok(Some(path))
+}
+
+/// Remove a folder from the notes index.
+#[tauri::command]
+pub async fn remove_notes_folder(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .settings
+        .lock()
+        .await
+        .notes_folders
+        .retain(|folder| folder != &path);
+    state.save().await?;
+    state.notes_index.wake.notify_one();
+    Ok(())
+}
+
+/// Rescan the notes folders now.
+#[tauri::command]
+pub async fn reindex_notes(state: State<'_, AppState>) -> Result<(), String> {
+    state.notes_index.wake.notify_one();
+    Ok(())
+}
+
+/// Get the notes index status.
+#[tauri::command]
+pub async fn get_notes_index_status(
+    state: State<'_, AppState>,
+) -> Result<NotesIndexStatus, String> {
+    Ok(state.notes_index.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_passages, xml_text, NotesIndex, PASSAGE_CHARS};
+    use std::fs;
+
+    #[test]
+    fn ranks_passages_by_relevance() {
+        let dir = std::env::temp_dir().join(format!("notes-search-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(
+            dir.join("garden.md"),
+            "# Garden\n\nTomatoes need full sun and regular watering.\n\nBasil grows well next to tomatoes.",
+        )
+        .unwrap();
+        fs::write(dir.join("work.txt"), "Quarterly planning meeting notes.").unwrap();
+        fs::write(dir.join(".hidden/secret.md"), "tomatoes").unwrap();
+        fs::write(dir.join("image.png"), "tomatoes").unwrap();
+
+        let index = NotesIndex::new();
+        index.reindex(&[dir.to_string_lossy().into_owned()]);
+        let results = index.search("watering tomatoes", 5);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("garden.md"));
+        assert!(results[0].passage.contains("regular watering"));
+        assert!(index.search("meeting", 5)[0].path.ends_with("work.txt"));
+    }
+
+    #[test]
+    fn splits_text_into_bounded_passages() {
+        let long = "word ".repeat(PASSAGE_CHARS);
+        let passages = split_passages(&format!("short one\n\n{}", long));
+        assert!(passages.len() > 2);
+        assert!(passages.iter().all(|p| p.len() <= PASSAGE_CHARS * 2));
+        assert_eq!(
+            xml_text("<w:p><w:r><w:t>A &amp; B</w:t></w:r></w:p><w:p><w:t>C</w:t></w:p>"),
+            "A & B\n\nC\n\n"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::AppState;
+
+/// How long a raised notification is remembered, so the copy relayed over another
+/// backend connection (another window, or multiplexing off) is not raised again.
+const DEDUP_WINDOW_SECS: u64 = 60;
+/// Most notifications remembered for deduplication.
+const DEDUP_CAPACITY: usize = 256;
+
+/// How native notifications are raised for a single conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationRule {
+    /// Never raise native notifications.
+    Mute,
+    /// Only raise notifications that ask for the user's attention.
+    MentionsOnly,
+    /// Raise every notification (the default when no rule is set).
+    Always,
+}
+
+/// A notification rule for one conversation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationNotificationRule {
+    pub conversation_id: String,
+    pub rule: NotificationRule,
+}
+
+/// Per-conversation notification rules persisted next to the settings file.
+pub struct NotificationRules {
+    rules: RwLock<HashMap<String, NotificationRule>>,
+    path: PathBuf,
+}
+
+impl NotificationRules {
+    pub fn load(path: PathBuf) -> Self {
+        let rules = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        Self {
+            rules: RwLock::new(rules),
+            path,
+        }
+    }
+
+    fn get(&self, conversation_id: &str) -> Option<NotificationRule> {
+        self.rules
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(conversation_id)
+            .copied()
+    }
+
+    fn set(&self, conversation_id: String, rule: Option<NotificationRule>) -> Result<(), String> {
+        let data = {
+            let mut rules = self.rules.write().unwrap_or_else(|e| e.into_inner());
+            match rule {
+                Some(rule) => {
+                    rules.insert(conversation_id, rule);
+                }
+                None => {
+                    rules.remove(&conversation_id);
+                }
+            }
+            serde_json::to_string_pretty(&*rules).map_err(|e| e.to_string())?
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&self.path, data).map_err(|e| e.to_string())
+    }
+
+    fn list(&self) -> Vec<ConversationNotificationRule> {
+        let rules = self.rules.read().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<_> = rules
+            .iter()
+            .map(|(conversation_id, rule)| ConversationNotificationRule {
+                conversation_id: conversation_id.clone(),
+                rule: *rule,
+            })
+            .collect();
+        list.sort_by(|a, b| a.conversation_id.cmp(&b.conversation_id));
+        list
+    }
+}
+
+/// Notification carried by a backend `notification_event` message.
+#[derive(Debug, PartialEq)]
+struct PendingNotification {
+    id: Option<String>,
+    session_id: Option<String>,
+    kind: String,
+    title: String,
+    body: String,
+}
+
+/// Extract a newly raised, unread notification from a backend WS text message.
+fn pending_notification(text: &str) -> Option<PendingNotification> {
+    // Cheap pre-check so ordinary chat traffic is not parsed as JSON
+    if !text.contains("\"notification_event\"") {
+        return None;
+    }
+    let message: serde_json::Value = serde_json::from_str(text).ok()?;
+    if message.get("type")?.as_str()? != "notification_event" {
+        return None;
+    }
+    if !matches!(message.get("event")?.as_str()?, "created" | "upserted") {
+        return None;
+    }
+    let notification = message.get("notification")?;
+    if !notification.get("readAt").is_none_or(|v| v.is_null()) {
+        return None;
+    }
+    Some(PendingNotification {
+        id: notification
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        session_id: notification
+            .get("sessionId")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        kind: notification.get("kind")?.as_str()?.to_string(),
+        title: notification.get("title")?.as_str()?.to_string(),
+        body: notification
+            .get("body")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+fn should_notify(rule: Option<NotificationRule>, kind: &str) -> bool {
+    match rule.unwrap_or(NotificationRule::Always) {
+        NotificationRule::Mute => false,
+        NotificationRule::MentionsOnly => kind == "session_attention",
+        NotificationRule::Always => true,
+    }
+}
+
+impl PendingNotification {
+    /// Identifies the notification across connections: its id, or its content if it has none.
+    fn dedup_key(&self) -> String {
+        match &self.id {
+            Some(id) => format!("id:{}", id),
+            None => format!(
+                "content:{}\0{}\0{}\0{}",
+                self.session_id.as_deref().unwrap_or_default(),
+                self.kind,
+                self.title,
+                self.body
+            ),
+        }
+    }
+}
+
+/// Notifications raised recently, oldest first.
+#[derive(Default)]
+struct RecentNotifications {
+    entries: VecDeque<(String, Instant)>,
+}
+
+impl RecentNotifications {
+    /// Remember `key`; returns `false` if it was already raised within the window.
+    fn first_seen(&mut self, key: String, now: Instant) -> bool {
+        let window = Duration::from_secs(DEDUP_WINDOW_SECS);
+        while self
+            .entries
+            .front()
+            .is_some_and(|(_, seen)| now.saturating_duration_since(*seen) >= window)
+        {
+            self.entries.pop_front();
+        }
+        if self.entries.iter().any(|(seen, _)| *seen == key) {
+            return false;
+        }
+        if self.entries.len() >= DEDUP_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, now));
+        true
+    }
+}
+
+/// Raises native notifications for backend messages relayed by the WS proxy.
+///
+/// Every relayed backend connection passes its messages through here, so each
+/// notification is raised once even when several connections carry it.
+pub struct Notifier {
+    app: AppHandle,
+    rules: Arc<NotificationRules>,
+    recent: Mutex<RecentNotifications>,
+}
+
+impl Notifier {
+    pub fn new(app: AppHandle, rules: Arc<NotificationRules>) -> Self {
+        Self {
+            app,
+            rules,
+            recent: Mutex::new(RecentNotifications::default()),
+        }
+    }
+
+    /// Inspect a backend → client text message and notify if the conversation's rule allows it.
+    pub fn observe_backend_message(&self, text: &str) {
+        let Some(pending) = pending_notification(text) else {
+            return;
+        };
+        let rule = pending
+            .session_id
+            .as_deref()
+            .and_then(|session_id| self.rules.get(session_id));
+        if !should_notify(rule, &pending.kind) {
+            return;
+        }
+        let first_seen = self
+            .recent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .first_seen(pending.dedup_key(), Instant::now());
+        if !first_seen {
+            return;
+        }
+        if let Err(e) = self
+            .app
+            .notification()
+            .builder()
+            .title(pending.title)
+            .body(pending.body)
+            .show()
+        {
+            log::error!(target: "notifications", "Failed to show notification: {}", e);
+        }
+    }
+}
+
+/// Set (or clear, when `rule` is omitted) the notification rule for a conversation.
+#[tauri::command]
+pub async fn set_notification_rule(
+    conversation_id: String,
+    rule: Option<NotificationRule>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.notification_rules.set(conversation_id, rule)
+}
+
+/// List all per-conversation notification rules.
+#[tauri::command]
+pub async fn list_notification_rules(
+    state: State<'_, AppState>,
+) -> Result<Vec<ConversationNotificationRule>, String> {
+    Ok(state.notification_rules.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pending_notification, should_notify, NotificationRule, PendingNotification,
+        RecentNotifications, DEDUP_WINDOW_SECS,
+    };
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn extracts_unread_notification_events() {
+        let text = r#"{"type":"notification_event","event":"created","revision":1,
+            "notification":{"id":"n1","kind":"session_attention","title":"Reply","body":"Done",
+            "readAt":null,"sessionId":"session-1"}}"#;
+        assert_eq!(
+            pending_notification(text),
+            Some(PendingNotification {
+                id: Some("n1".to_string()),
+                session_id: Some("session-1".to_string()),
+                kind: "session_attention".to_string(),
+                title: "Reply".to_string(),
+                body: "Done".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_read_or_removed_notifications() {
+        let read = r#"{"type":"notification_event","event":"updated","revision":2,
+            "notification":{"kind":"notification","title":"t","readAt":"2026-01-01T00:00:00Z"}}"#;
+        assert_eq!(pending_notification(read), None);
+        assert_eq!(pending_notification(r#"{"type":"text_delta"}"#), None);
+    }
+
+    #[test]
+    fn applies_conversation_rules() {
+        assert!(should_notify(None, "notification"));
+        assert!(!should_notify(
+            Some(NotificationRule::Mute),
+            "session_attention"
+        ));
+        assert!(should_notify(
+            Some(NotificationRule::MentionsOnly),
+            "session_attention"
+        ));
+        assert!(!should_notify(
+            Some(NotificationRule::MentionsOnly),
+            "notification"
+        ));
+    }
+
+    #[test]
+    fn raises_each_notification_once_across_connections() {
+        let mut recent = RecentNotifications::default();
+        let start = Instant::now();
+        assert!(recent.first_seen("id:n1".to_string(), start));
+        assert!(!recent.first_seen("id:n1".to_string(), start));
+        assert!(recent.first_seen("id:n2".to_string(), start));
+
+        let later = start + Duration::from_secs(DEDUP_WINDOW_SECS);
+        assert!(recent.first_seen("id:n1".to_string(), later));
+    }
+}
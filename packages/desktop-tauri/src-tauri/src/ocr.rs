@@ -0,0 +1,79 @@
+use tauri::State;
+
+use crate::AppState;
+
+/// Default Tesseract language model.
+#[cfg(feature = "ocr")]
+const DEFAULT_OCR_LANGUAGE: &str = "eng";
+
+/// Tesseract language specs are model names joined with `+` (e.g. `eng+deu`).
+fn is_valid_language(language: &str) -> bool {
+    !language.is_empty()
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+')
+}
+
+#[cfg(feature = "ocr")]
+async fn run_tesseract(path: &std::path::Path, language: &str) -> Result<String, String> {
+    let output = tokio::process::Command::new("tesseract")
+        .arg(path)
+        .arg("stdout")
+        .arg("-l")
+        .arg(language)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run tesseract: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("tesseract failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Extract text from an image the user granted with `grant_local_file_preview`
+/// (`grant_id` is the preview's `id`), on-device. Page content cannot name a path,
+/// so it can only read images the user picked.
+///
+/// Requires a build with the `ocr` feature and a `tesseract` binary on `PATH`.
+#[tauri::command]
+pub async fn ocr_image(
+    grant_id: String,
+    language: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if let Some(language) = &language {
+        if !is_valid_language(language) {
+            return Err(format!("Invalid OCR language: {}", language));
+        }
+    }
+    let path = state
+        .local_files
+        .granted_path(&grant_id)
+        .ok_or("Image not granted; choose it with grant_local_file_preview")?;
+
+    #[cfg(feature = "ocr")]
+    {
+        run_tesseract(&path, language.as_deref().unwrap_or(DEFAULT_OCR_LANGUAGE)).await
+    }
+
+    #[cfg(not(feature = "ocr"))]
+    {
+        let _ = path;
+        Err("OCR support is not enabled in this build".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_language;
+
+    #[test]
+    fn validates_tesseract_language_specs() {
+        assert!(is_valid_language("eng"));
+        assert!(is_valid_language("eng+chi_sim"));
+        assert!(!is_valid_language(""));
+        assert!(!is_valid_language("eng --psm 0"));
+    }
+}
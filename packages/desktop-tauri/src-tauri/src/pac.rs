@@ -0,0 +1,319 @@
+use rquickjs::{Context, Ctx, Function, Runtime};
+use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::upstream_proxy::{ProxyKind, UpstreamProxy};
+
+/// How long a host's PAC decision is reused before the script is asked again.
+const DECISION_TTL: Duration = Duration::from_secs(300);
+const FETCH_TIMEOUT_SECS: u64 = 15;
+/// Limits for a single `FindProxyForURL` call.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(2);
+const EVAL_MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// The standard PAC helper functions, other than `dnsResolve` and `myIpAddress`
+/// which are provided natively. `dateRange` is not supported.
+const PAC_UTILS: &str = r#"
+function isPlainHostName(host) { return host.indexOf('.') < 0; }
+function dnsDomainIs(host, domain) {
+  return host.length >= domain.length && host.substring(host.length - domain.length) === domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+  return host === hostdom || hostdom.lastIndexOf(host + '.', 0) === 0;
+}
+function isResolvable(host) { return !!dnsResolve(host); }
+function isInNet(ip, pattern, mask) {
+  if (!/^\d+\.\d+\.\d+\.\d+$/.test(ip)) { ip = dnsResolve(ip); if (!ip) return false; }
+  var toInt = function (a) {
+    var p = a.split('.');
+    return ((+p[0] << 24) | (+p[1] << 16) | (+p[2] << 8) | +p[3]) >>> 0;
+  };
+  return ((toInt(ip) & toInt(mask)) >>> 0) === ((toInt(pattern) & toInt(mask)) >>> 0);
+}
+function dnsDomainLevels(host) { return host.split('.').length - 1; }
+function shExpMatch(str, exp) {
+  var re = exp.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+  return new RegExp('^' + re + '$').test(str);
+}
+function weekdayRange(from, to, gmt) {
+  var days = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+  if (to === 'GMT') { gmt = to; to = undefined; }
+  var now = new Date();
+  var day = gmt === 'GMT' ? now.getUTCDay() : now.getDay();
+  var lo = days.indexOf(from), hi = to === undefined ? lo : days.indexOf(to);
+  if (lo < 0 || hi < 0) return false;
+  return lo <= hi ? day >= lo && day <= hi : day >= lo || day <= hi;
+}
+function timeRange() {
+  var args = Array.prototype.slice.call(arguments);
+  var gmt = args[args.length - 1] === 'GMT';
+  if (gmt) args.pop();
+  var now = new Date();
+  var h = gmt ? now.getUTCHours() : now.getHours();
+  var m = gmt ? now.getUTCMinutes() : now.getMinutes();
+  var s = gmt ? now.getUTCSeconds() : now.getSeconds();
+  if (args.length === 1) return h === args[0];
+  var from, to, cur;
+  if (args.length === 2) { from = args[0]; to = args[1]; cur = h; }
+  else if (args.length === 4) { from = args[0] * 60 + args[1]; to = args[2] * 60 + args[3]; cur = h * 60 + m; }
+  else if (args.length === 6) {
+    from = args[0] * 3600 + args[1] * 60 + args[2]; to = args[3] * 3600 + args[4] * 60 + args[5];
+    cur = h * 3600 + m * 60 + s;
+  } else return false;
+  return from <= to ? cur >= from && cur < to : cur >= from || cur < to;
+}
+"#;
+
+/// Where the PAC script sends a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacRoute {
+    Direct,
+    Proxy(UpstreamProxy),
+}
+
+/// Check a PAC URL before it is saved.
+pub fn validate(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid PAC URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https" | "file") {
+        return Err(format!("PAC URL must use http, https or file: {}", url));
+    }
+    Ok(())
+}
+
+/// First entry of a `FindProxyForURL` result (`PROXY a:3128; SOCKS5 b:1080;
+/// DIRECT`) that the proxies can use. `HTTPS` and SOCKS4 entries are skipped.
+fn parse_result(result: &str) -> PacRoute {
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        let kind = match parts.next().map(str::to_ascii_uppercase).as_deref() {
+            Some("DIRECT") => return PacRoute::Direct,
+            Some("PROXY" | "HTTP") => ProxyKind::Http,
+            Some("SOCKS5") => ProxyKind::Socks5,
+            _ => continue,
+        };
+        let Some((host, port)) = parts.next().and_then(|address| address.rsplit_once(':')) else {
+            continue;
+        };
+        let Ok(port) = port.parse() else {
+            continue;
+        };
+        return PacRoute::Proxy(UpstreamProxy {
+            kind,
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port,
+            username: None,
+            password: None,
+        });
+    }
+    PacRoute::Direct
+}
+
+/// First IPv4 address for `host`, as PAC scripts expect.
+fn dns_resolve(host: &str) -> Option<String> {
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .map(|addr| addr.ip())
+        .find(IpAddr::is_ipv4)
+        .map(|ip| ip.to_string())
+}
+
+/// Address of the interface used for outbound traffic. Connecting a UDP socket
+/// sends nothing; it only picks the route.
+fn my_ip_address() -> String {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("192.0.2.1:80")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_string())
+}
+
+fn js_error(ctx: &Ctx<'_>, error: rquickjs::Error) -> String {
+    if error.is_exception() {
+        let exception = ctx.catch();
+        if let Some(message) = exception.as_exception().and_then(|e| e.message()) {
+            return message;
+        }
+    }
+    error.to_string()
+}
+
+/// Run the script's `FindProxyForURL(url, host)`.
+fn evaluate(script: &str, url: &str, host: &str) -> Result<String, String> {
+    let runtime = Runtime::new().map_err(|e| e.to_string())?;
+    runtime.set_memory_limit(EVAL_MEMORY_LIMIT);
+    let started = Instant::now();
+    runtime.set_interrupt_handler(Some(Box::new(move || started.elapsed() > EVAL_TIMEOUT)));
+    let context = Context::full(&runtime).map_err(|e| e.to_string())?;
+    context.with(|ctx| {
+        let run = || -> rquickjs::Result<String> {
+            let globals = ctx.globals();
+            globals.set(
+                "dnsResolve",
+                Function::new(ctx.clone(), |host: String| dns_resolve(&host))?,
+            )?;
+            globals.set("myIpAddress", Function::new(ctx.clone(), my_ip_address)?)?;
+            ctx.eval::<(), _>(PAC_UTILS)?;
+            ctx.eval::<(), _>(script)?;
+            let find: Function = globals.get("FindProxyForURL")?;
+            find.call((url, host))
+        };
+        run().map_err(|e| js_error(&ctx, e))
+    })
+}
+
+/// Proxy auto-config: fetches the script at `proxy_pac_url` and asks it which
+/// proxy to use for each backend host, caching the answers.
+pub struct Pac {
+    script: Mutex<Option<Arc<String>>>,
+    decisions: Mutex<HashMap<String, (PacRoute, Instant)>>,
+}
+
+impl Pac {
+    pub fn new() -> Self {
+        Self {
+            script: Mutex::new(None),
+            decisions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fetch the script (or drop it when `url` is `None`) and forget cached
+    /// decisions. A script that cannot be fetched leaves connections direct.
+    pub async fn load(&self, url: Option<&str>, skip_cert_validation: bool) {
+        let script = match url {
+            Some(url) => match fetch(url, skip_cert_validation).await {
+                Ok(script) => {
+                    log::info!(target: "http-proxy", "Loaded PAC script from {}", url);
+                    Some(Arc::new(script))
+                }
+                Err(e) => {
+                    log::warn!(target: "http-proxy", "{}; connecting directly", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        *self.script.lock().unwrap_or_else(|e| e.into_inner()) = script;
+        self.decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// The route for `url`, or `None` when no PAC script is loaded.
+    pub fn route(&self, url: &reqwest::Url) -> Option<PacRoute> {
+        let script = self
+            .script
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()?;
+        let host = url.host_str()?.to_string();
+        if let Some((route, at)) = self
+            .decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&host)
+        {
+            if at.elapsed() < DECISION_TTL {
+                return Some(route.clone());
+            }
+        }
+        let route = match evaluate(&script, url.as_str(), &host) {
+            Ok(result) => parse_result(&result),
+            Err(e) => {
+                log::warn!(target: "http-proxy", "PAC script failed for {}: {}", host, e);
+                PacRoute::Direct
+            }
+        };
+        self.decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(host, (route.clone(), Instant::now()));
+        Some(route)
+    }
+
+    /// Route a reqwest client's requests through the PAC script's choice.
+    pub fn client_proxy(self: &Arc<Self>) -> reqwest::Proxy {
+        let pac = self.clone();
+        reqwest::Proxy::custom(move |url| match pac.route(url)? {
+            PacRoute::Direct => None,
+            PacRoute::Proxy(proxy) => proxy.url().ok(),
+        })
+    }
+}
+
+async fn fetch(url: &str, skip_cert_validation: bool) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid PAC URL {}: {}", url, e))?;
+    if parsed.scheme() == "file" {
+        let path = parsed
+            .to_file_path()
+            .map_err(|_| format!("Invalid PAC file URL: {}", url))?;
+        return tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("Failed to read PAC file {}: {}", path.display(), e));
+    }
+    // The PAC script itself is always fetched directly
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .danger_accept_invalid_certs(skip_cert_validation)
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch PAC script {}: {}", url, e))?;
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to fetch PAC script {}: {}", url, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, parse_result, PacRoute};
+    use crate::upstream_proxy::ProxyKind;
+
+    #[test]
+    fn picks_the_proxy_from_the_script() {
+        let script = r#"
+            function FindProxyForURL(url, host) {
+                if (isPlainHostName(host) || shExpMatch(host, "*.internal")) return "DIRECT";
+                if (isInNet(host, "10.0.0.0", "255.0.0.0")) return "SOCKS5 127.0.0.1:1080";
+                return "HTTPS secure.corp:443; PROXY proxy.corp:3128; DIRECT";
+            }
+        "#;
+        let route = |url: &str, host: &str| parse_result(&evaluate(script, url, host).unwrap());
+
+        assert_eq!(route("https://assistant/", "assistant"), PacRoute::Direct);
+        assert_eq!(route("https://a.internal/", "a.internal"), PacRoute::Direct);
+        let PacRoute::Proxy(socks) = route("http://10.1.2.3/", "10.1.2.3") else {
+            panic!("expected a proxy");
+        };
+        assert_eq!((socks.kind, socks.port), (ProxyKind::Socks5, 1080));
+        let PacRoute::Proxy(http) = route("https://example.com/", "example.com") else {
+            panic!("expected a proxy");
+        };
+        assert_eq!(
+            (http.kind, http.host.as_str()),
+            (ProxyKind::Http, "proxy.corp")
+        );
+
+        assert!(evaluate(
+            "function FindProxyForURL() { throw new Error('boom'); }",
+            "",
+            ""
+        )
+        .unwrap_err()
+        .contains("boom"));
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Maps request paths starting with `from` to `to` before they are sent to the
+/// backend, e.g. `/v1` → `/api/v1`, or `/assistant` → `/` to strip a prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+/// Check a rewrite table before it is saved.
+pub fn validate(rewrites: &[PathRewrite]) -> Result<(), String> {
+    for rewrite in rewrites {
+        if !rewrite.from.starts_with('/') || !rewrite.to.starts_with('/') {
+            return Err(format!(
+                "Path rewrites must map between paths starting with '/': {} -> {}",
+                rewrite.from, rewrite.to
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The part of `path` after `prefix`, if `prefix` matches whole path segments.
+fn strip_segments<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix)?;
+    (prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?'])).then_some(rest)
+}
+
+/// Rewrite a path (with its query) using the rule with the longest matching
+/// `from`; paths without a match are returned as they are.
+pub fn rewrite<'a>(rewrites: &[PathRewrite], path: &'a str) -> Cow<'a, str> {
+    let Some((rewrite, rest)) = rewrites
+        .iter()
+        .filter_map(|rewrite| Some((rewrite, strip_segments(path, &rewrite.from)?)))
+        .max_by_key(|(rewrite, _)| rewrite.from.len())
+    else {
+        return Cow::Borrowed(path);
+    };
+    let rest = rest.trim_start_matches('/');
+    if rest.is_empty() {
+        return Cow::Owned(rewrite.to.clone());
+    }
+    Cow::Owned(format!("{}/{}", rewrite.to.trim_end_matches('/'), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite, PathRewrite};
+
+    #[test]
+    fn maps_prefixes_on_segment_boundaries() {
+        let rewrites = vec![
+            PathRewrite {
+                from: "/assistant".to_string(),
+                to: "/".to_string(),
+            },
+            PathRewrite {
+                from: "/v1/".to_string(),
+                to: "/api/v1/".to_string(),
+            },
+            PathRewrite {
+                from: "/v1/legacy".to_string(),
+                to: "/old".to_string(),
+            },
+        ];
+        assert_eq!(rewrite(&rewrites, "/assistant/api/x?a=1"), "/api/x?a=1");
+        assert_eq!(rewrite(&rewrites, "/assistant"), "/");
+        assert_eq!(rewrite(&rewrites, "/assistants/x"), "/assistants/x");
+        assert_eq!(rewrite(&rewrites, "/v1/chat"), "/api/v1/chat");
+        assert_eq!(rewrite(&rewrites, "/v1/legacy/a"), "/old/a");
+        assert_eq!(rewrite(&rewrites, "/v1/legacy"), "/old");
+        assert_eq!(rewrite(&rewrites, "/other"), "/other");
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Request timeout for backend paths under `path_prefix`, replacing the default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathTimeout {
+    pub path_prefix: String,
+    pub timeout_secs: u64,
+}
+
+/// Check a timeout table before it is saved.
+pub fn validate(overrides: &[PathTimeout]) -> Result<(), String> {
+    for entry in overrides {
+        if !entry.path_prefix.starts_with('/') {
+            return Err(format!(
+                "Timeout path prefix must start with '/': {}",
+                entry.path_prefix
+            ));
+        }
+        if entry.timeout_secs == 0 {
+            return Err(format!(
+                "Timeout for {} must be at least 1 second",
+                entry.path_prefix
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Timeout for a request to `path`: the entry with the longest matching prefix,
+/// or `default` if none matches.
+pub fn timeout_for(overrides: &[PathTimeout], path: &str, default: Duration) -> Duration {
+    overrides
+        .iter()
+        .filter(|entry| path.starts_with(&entry.path_prefix))
+        .max_by_key(|entry| entry.path_prefix.len())
+        .map_or(default, |entry| Duration::from_secs(entry.timeout_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{timeout_for, validate, PathTimeout};
+    use std::time::Duration;
+
+    fn entry(path_prefix: &str, timeout_secs: u64) -> PathTimeout {
+        PathTimeout {
+            path_prefix: path_prefix.to_string(),
+            timeout_secs,
+        }
+    }
+
+    #[test]
+    fn picks_the_longest_matching_prefix() {
+        let overrides = vec![
+            entry("/api", 5),
+            entry("/api/chat/stream", 600),
+            entry("/api/transcribe", 300),
+        ];
+        let default = Duration::from_secs(120);
+        assert_eq!(
+            timeout_for(&overrides, "/api/chat/stream?id=1", default),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            timeout_for(&overrides, "/api/plugins", default),
+            Duration::from_secs(5)
+        );
+        assert_eq!(timeout_for(&overrides, "/preferences", default), default);
+
+        assert!(validate(&overrides).is_ok());
+        assert!(validate(&[entry("api", 5)]).is_err());
+        assert!(validate(&[entry("/api", 0)]).is_err());
+    }
+}
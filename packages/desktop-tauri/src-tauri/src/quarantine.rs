@@ -0,0 +1,128 @@
+use std::path::Path;
+
+/// Extensions of files the OS would run or install when opened, including disk
+/// images and containers whose contents escape Mark-of-the-Web.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "app",
+    "appimage",
+    "application",
+    "appx",
+    "appxbundle",
+    "bat",
+    "cmd",
+    "com",
+    "command",
+    "cpl",
+    "deb",
+    "dmg",
+    "exe",
+    "gadget",
+    "hta",
+    "img",
+    "iso",
+    "jar",
+    "js",
+    "jse",
+    "lnk",
+    "msi",
+    "msix",
+    "msixbundle",
+    "msp",
+    "pif",
+    "pkg",
+    "ps1",
+    "psm1",
+    "reg",
+    "rpm",
+    "scf",
+    "scr",
+    "settingcontent-ms",
+    "sh",
+    "url",
+    "vbe",
+    "vbs",
+    "vhd",
+    "vhdx",
+    "workflow",
+    "wsf",
+    "xll",
+];
+
+/// Whether opening the file would run code rather than show a document.
+pub fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            EXECUTABLE_EXTENSIONS
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        })
+}
+
+/// Mark-of-the-Web stream contents for a file from `url` (Internet zone).
+#[cfg_attr(not(windows), allow(dead_code))]
+fn zone_identifier(url: &str) -> String {
+    let url = url.replace(['\r', '\n'], "");
+    format!("[ZoneTransfer]\r\nZoneId=3\r\nHostUrl={}\r\n", url)
+}
+
+/// Tag a downloaded file the way browsers do, so Gatekeeper / SmartScreen and
+/// Office Protected View treat it as coming from the internet.
+///
+/// Sets `com.apple.quarantine` on macOS and the `Zone.Identifier` stream on
+/// Windows. Other platforms have no equivalent and are left unchanged. Returns
+/// whether the file was marked.
+#[cfg(target_os = "macos")]
+pub fn mark_downloaded(path: &Path, _url: &str) -> Result<bool, String> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    // 0083: downloaded by an app, not yet approved by the user
+    let value = format!("0083;{:x};Assistant;", timestamp);
+    let output = std::process::Command::new("xattr")
+        .args(["-w", "com.apple.quarantine", &value])
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(true)
+}
+
+#[cfg(windows)]
+pub fn mark_downloaded(path: &Path, url: &str) -> Result<bool, String> {
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":Zone.Identifier");
+    std::fs::write(&stream, zone_identifier(url)).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn mark_downloaded(_path: &Path, _url: &str) -> Result<bool, String> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_executable, zone_identifier};
+    use std::path::Path;
+
+    #[test]
+    fn detects_executables_and_builds_zone_identifier() {
+        assert!(is_executable(Path::new("setup.EXE")));
+        assert!(is_executable(Path::new("/tmp/install.sh")));
+        // Disk images are mounted and their contents run without Mark-of-the-Web
+        assert!(is_executable(Path::new("invoice.iso")));
+        assert!(is_executable(Path::new("backup.VHDX")));
+        assert!(is_executable(Path::new("open.settingcontent-ms")));
+        assert!(!is_executable(Path::new("report.pdf")));
+        assert!(!is_executable(Path::new("Makefile")));
+
+        assert_eq!(
+            zone_identifier("https://example.com/a\r\nZoneId=0"),
+            "[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/aZoneId=0\r\n"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::{input, shortcuts};
+
+/// Time allowed for the frontmost app to place its selection on the clipboard.
+const CLIPBOARD_SETTLE_MS: u64 = 150;
+
+/// Copy the selection of the frontmost app via the clipboard, restoring the previous contents.
+async fn capture_selection(app: &AppHandle) -> Result<Option<String>, String> {
+    let clipboard = app.clipboard();
+    let previous = clipboard.read_text().ok();
+
+    // Clear first so an empty selection is not mistaken for stale clipboard text
+    clipboard.write_text("").map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(input::send_copy_shortcut)
+        .await
+        .map_err(|e| e.to_string())??;
+    tokio::time::sleep(Duration::from_millis(CLIPBOARD_SETTLE_MS)).await;
+
+    let captured = clipboard
+        .read_text()
+        .ok()
+        .filter(|text| !text.trim().is_empty());
+
+    if let Some(previous) = previous {
+        if let Err(e) = clipboard.write_text(previous) {
+            log::error!(target: "quick-capture", "Failed to restore clipboard: {}", e);
+        }
+    }
+
+    Ok(captured)
+}
+
+/// Capture the selection, bring the quick prompt to the front and hand the text to it.
+async fn run_quick_capture(app: AppHandle) {
+    let text = match capture_selection(&app).await {
+        Ok(Some(text)) => text,
+        Ok(None) => {
+            log::info!(target: "quick-capture", "No selection captured");
+            return;
+        }
+        Err(e) => {
+            log::error!(target: "quick-capture", "Failed to capture selection: {}", e);
+            return;
+        }
+    };
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    let _ = app.emit("quick-capture", serde_json::json!({ "text": text }));
+}
+
+/// Register the global quick-capture shortcut in place of `previous`. An empty shortcut disables it.
+pub fn register_shortcut(app: &AppHandle, previous: &str, shortcut: &str) -> Result<(), String> {
+    shortcuts::replace_shortcut(app, previous, shortcut, |app| {
+        tauri::async_runtime::spawn(run_quick_capture(app.clone()));
+    })
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Token bucket for requests to backend paths under `path_prefix`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub path_prefix: String,
+    /// Sustained rate the bucket refills at.
+    pub requests_per_sec: f64,
+    /// Requests allowed in a burst when the bucket is full.
+    pub burst: u32,
+}
+
+/// Check a rate limit table before it is saved.
+pub fn validate(limits: &[RateLimit]) -> Result<(), String> {
+    for limit in limits {
+        if !limit.path_prefix.starts_with('/') {
+            return Err(format!(
+                "Rate limit path prefix must start with '/': {}",
+                limit.path_prefix
+            ));
+        }
+        if !limit.requests_per_sec.is_finite() || limit.requests_per_sec <= 0.0 || limit.burst == 0
+        {
+            return Err(format!(
+                "Rate limit for {} needs a positive rate and burst",
+                limit.path_prefix
+            ));
+        }
+    }
+    Ok(())
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Set once a request was rejected, so `proxy-throttled` fires once per episode.
+    throttling: bool,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+            throttling: false,
+        }
+    }
+
+    /// Take a token, or return how long until one is available.
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_sec).min(limit.burst as f64);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.requests_per_sec,
+            ))
+        }
+    }
+}
+
+/// Local per-path-prefix rate limiting for proxied requests, so a runaway loop in
+/// the web client cannot hammer the backend.
+pub struct RateLimiter {
+    app: AppHandle,
+    limits: Mutex<Vec<RateLimit>>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(app: AppHandle, limits: Vec<RateLimit>) -> Self {
+        Self {
+            app,
+            limits: Mutex::new(limits),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_limits(&self, limits: Vec<RateLimit>) {
+        *self.limits.lock().unwrap_or_else(|e| e.into_inner()) = limits;
+        self.buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Count a request to `path` against the limit with the longest matching
+    /// prefix. Returns how long to wait before retrying if it is over the limit.
+    pub fn check(&self, path: &str) -> Result<(), Duration> {
+        let limit = {
+            let limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+            match limits
+                .iter()
+                .filter(|limit| path.starts_with(&limit.path_prefix))
+                .max_by_key(|limit| limit.path_prefix.len())
+            {
+                Some(limit) => limit.clone(),
+                None => return Ok(()),
+            }
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets
+            .entry(limit.path_prefix.clone())
+            .or_insert_with(|| Bucket::full(&limit));
+        match bucket.take(&limit, Instant::now()) {
+            Ok(()) => {
+                bucket.throttling = false;
+                Ok(())
+            }
+            Err(retry_after) => {
+                if !std::mem::replace(&mut bucket.throttling, true) {
+                    drop(buckets);
+                    log::warn!(
+                        target: "proxy",
+                        "Throttling requests to {} ({}/s, burst {})",
+                        limit.path_prefix,
+                        limit.requests_per_sec,
+                        limit.burst
+                    );
+                    let _ = self.app.emit(
+                        "proxy-throttled",
+                        serde_json::json!({
+                            "path_prefix": limit.path_prefix,
+                            "path": path,
+                            "retry_after_ms": retry_after.as_millis() as u64,
+                        }),
+                    );
+                }
+                Err(retry_after)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Bucket, RateLimit};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn refills_at_the_configured_rate() {
+        let limit = RateLimit {
+            path_prefix: "/api".to_string(),
+            requests_per_sec: 2.0,
+            burst: 2,
+        };
+        let mut bucket = Bucket::full(&limit);
+        let start = bucket.refilled_at;
+        assert!(bucket.take(&limit, start).is_ok());
+        assert!(bucket.take(&limit, start).is_ok());
+        let wait = bucket.take(&limit, start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        assert!(bucket
+            .take(&limit, start + Duration::from_millis(500))
+            .is_ok());
+        assert!(bucket
+            .take(&limit, Instant::now() + Duration::from_secs(60))
+            .is_ok());
+
+        assert!(validate(std::slice::from_ref(&limit)).is_ok());
+        assert!(validate(&[RateLimit { burst: 0, ..limit }]).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// How the HTTP proxy treats redirects from the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectMode {
+    /// Follow redirects anywhere, up to reqwest's default of 10 hops.
+    #[default]
+    Follow,
+    /// Pass every redirect through to the webview.
+    Manual,
+    /// Follow up to `max_redirects` hops within the backend's origin; anything
+    /// else is passed through.
+    Limited,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedirectPolicy {
+    pub mode: RedirectMode,
+    /// Hops followed in `limited` mode.
+    pub max_redirects: usize,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RedirectMode::Follow,
+            max_redirects: 5,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mode == RedirectMode::Limited && self.max_redirects == 0 {
+            return Err("Limited redirects need max_redirects of at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether redirects can reach the webview, so their `Location` needs to
+    /// point back at the proxy.
+    pub fn passes_through(&self) -> bool {
+        self.mode != RedirectMode::Follow
+    }
+
+    /// reqwest policy for clients that talk to `backend_url`.
+    pub fn client_policy(&self, backend_url: &str) -> reqwest::redirect::Policy {
+        match self.mode {
+            RedirectMode::Follow => reqwest::redirect::Policy::default(),
+            RedirectMode::Manual => reqwest::redirect::Policy::none(),
+            RedirectMode::Limited => {
+                let origin = reqwest::Url::parse(backend_url)
+                    .ok()
+                    .map(|url| url.origin());
+                let max_redirects = self.max_redirects;
+                reqwest::redirect::Policy::custom(move |attempt| {
+                    if attempt.previous().len() > max_redirects
+                        || origin.as_ref() != Some(&attempt.url().origin())
+                    {
+                        attempt.stop()
+                    } else {
+                        attempt.follow()
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Point an absolute `Location` under `backend_url` at the local proxy origin.
+/// Relative locations already resolve against the proxy and are left alone, as
+/// are redirects to other sites.
+pub fn rewrite_location(location: &str, backend_url: &str, local_origin: &str) -> Option<String> {
+    let backend_base = backend_url.trim_end_matches('/');
+    let rest = location.strip_prefix(backend_base)?;
+    if !(rest.is_empty() || rest.starts_with(['/', '?', '#'])) {
+        return None;
+    }
+    let rest = if rest.is_empty() { "/" } else { rest };
+    Some(format!("{}{}", local_origin.trim_end_matches('/'), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rewrite_location;
+
+    #[test]
+    fn points_backend_locations_at_the_proxy() {
+        let backend = "https://assistant/work/";
+        let local = "http://127.0.0.1:4000";
+        assert_eq!(
+            rewrite_location("https://assistant/work/login?next=%2F", backend, local).as_deref(),
+            Some("http://127.0.0.1:4000/login?next=%2F")
+        );
+        assert_eq!(
+            rewrite_location("https://assistant/work", backend, local).as_deref(),
+            Some("http://127.0.0.1:4000/")
+        );
+        assert_eq!(
+            rewrite_location("https://assistant/workspace", backend, local),
+            None
+        );
+        assert_eq!(
+            rewrite_location("https://idp.example/auth", backend, local),
+            None
+        );
+        assert_eq!(rewrite_location("/login", backend, local), None);
+    }
+}
@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+use crate::AppState;
+
+/// Header carrying the request ID to the backend and back to the webview.
+pub const HEADER: &str = "x-request-id";
+/// Requests kept for `get_recent_requests`.
+const RECENT_REQUESTS: usize = 200;
+/// Longest client-supplied ID that is reused rather than replaced.
+const MAX_CLIENT_ID_LEN: usize = 128;
+
+/// Reuse the webview's own `X-Request-Id` when it sent a sane one, so its logs
+/// line up too; otherwise generate a new ID.
+pub fn for_request(headers: &hyper::HeaderMap) -> String {
+    headers
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_CLIENT_ID_LEN
+                && id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()))
+}
+
+/// A proxied request, as reported by `get_recent_requests`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestRecord {
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    /// Time until the response headers were sent back.
+    pub duration_ms: u64,
+    /// Unix time in milliseconds when the request arrived.
+    pub started_at: u64,
+}
+
+/// The most recent proxied requests, newest last.
+#[derive(Default)]
+pub struct RecentRequests {
+    records: Mutex<VecDeque<RequestRecord>>,
+}
+
+impl RecentRequests {
+    pub fn record(
+        &self,
+        request_id: String,
+        method: &hyper::Method,
+        path: &str,
+        status: u16,
+        duration: Duration,
+    ) {
+        let started_at = SystemTime::now()
+            .checked_sub(duration)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        if records.len() == RECENT_REQUESTS {
+            records.pop_front();
+        }
+        records.push_back(RequestRecord {
+            request_id,
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            duration_ms: duration.as_millis() as u64,
+            started_at,
+        });
+    }
+
+    /// Up to `limit` requests, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<RequestRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// IDs and status codes of the most recent proxied requests (default 50), for
+/// matching desktop proxy logs with backend logs.
+#[tauri::command]
+pub async fn get_recent_requests(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<RequestRecord>, String> {
+    Ok(state.recent_requests.recent(limit.unwrap_or(50)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_request, RecentRequests, HEADER, RECENT_REQUESTS};
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_the_newest_requests() {
+        let recent = RecentRequests::default();
+        for i in 0..RECENT_REQUESTS + 5 {
+            recent.record(
+                i.to_string(),
+                &hyper::Method::GET,
+                "/api/x",
+                200,
+                Duration::ZERO,
+            );
+        }
+        let records = recent.recent(2);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].request_id, (RECENT_REQUESTS + 4).to_string());
+        assert_eq!(recent.recent(usize::MAX).len(), RECENT_REQUESTS);
+
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(for_request(&headers).len(), 32);
+        headers.insert(HEADER, "client-123".parse().unwrap());
+        assert_eq!(for_request(&headers), "client-123");
+    }
+}
@@ -0,0 +1,589 @@
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::{AppSettings, UpstreamResponse};
+
+/// Largest response body kept in the cache.
+const MAX_ENTRY_BYTES: usize = 1024 * 1024;
+/// Total body bytes kept before the oldest entries are evicted.
+const MAX_CACHE_BYTES: usize = 32 * 1024 * 1024;
+/// How long a stale entry may still be served while it is revalidated, unless the
+/// backend sets `stale-while-revalidate` itself.
+const DEFAULT_STALE_SECS: u64 = 24 * 60 * 60;
+/// Headers a `304 Not Modified` may update on the cached response.
+const NOT_MODIFIED_HEADERS: &[reqwest::header::HeaderName] = &[
+    reqwest::header::CACHE_CONTROL,
+    reqwest::header::DATE,
+    reqwest::header::ETAG,
+    reqwest::header::EXPIRES,
+    reqwest::header::LAST_MODIFIED,
+];
+
+struct CacheEntry {
+    response: UpstreamResponse,
+    /// Request path and query, as reported in `resource-updated`.
+    path: String,
+    stored_at: Instant,
+    fresh_for: Duration,
+    stale_for: Duration,
+}
+
+impl CacheEntry {
+    fn age(&self) -> Duration {
+        self.stored_at.elapsed()
+    }
+
+    fn has_validator(&self) -> bool {
+        let headers = &self.response.headers;
+        headers.contains_key(reqwest::header::ETAG)
+            || headers.contains_key(reqwest::header::LAST_MODIFIED)
+    }
+}
+
+/// A cached response and whether it needs revalidating.
+pub struct CacheHit {
+    pub response: UpstreamResponse,
+    pub stale: bool,
+    /// A stale hit that may be served while it is revalidated in the background;
+    /// otherwise it must be revalidated before use.
+    pub serve_stale: bool,
+    pub age: Duration,
+}
+
+/// Entry metadata as persisted next to its `.body` file.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    path: String,
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Unix time the response was stored or last revalidated, in seconds.
+    stored_at: u64,
+    fresh_for_secs: u64,
+    stale_for_secs: u64,
+}
+
+impl DiskEntry {
+    fn from_entry(entry: &CacheEntry) -> Self {
+        let stored_at = SystemTime::now()
+            .checked_sub(entry.age())
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Self {
+            path: entry.path.clone(),
+            url: entry.response.url.to_string(),
+            status: entry.response.status.as_u16(),
+            headers: entry
+                .response
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            stored_at: stored_at.as_secs(),
+            fresh_for_secs: entry.fresh_for.as_secs(),
+            stale_for_secs: entry.stale_for.as_secs(),
+        }
+    }
+
+    fn into_entry(self, body: Vec<u8>) -> Option<CacheEntry> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in self.headers {
+            headers.append(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).ok()?,
+                reqwest::header::HeaderValue::from_str(&value).ok()?,
+            );
+        }
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.stored_at))
+            .unwrap_or_default();
+        Some(CacheEntry {
+            response: UpstreamResponse {
+                status: reqwest::StatusCode::from_u16(self.status).ok()?,
+                headers,
+                url: self.url.parse().ok()?,
+                version: reqwest::Version::default(),
+                body: Bytes::from(body),
+                trailers: None,
+                stream: None,
+                ttfb: Duration::ZERO,
+                download: Duration::ZERO,
+            },
+            path: self.path,
+            stored_at: Instant::now().checked_sub(age)?,
+            fresh_for: Duration::from_secs(self.fresh_for_secs),
+            stale_for: Duration::from_secs(self.stale_for_secs),
+        })
+    }
+}
+
+/// Entries are stored under a hash of their key, which includes the
+/// `Authorization` header, so credentials never reach the disk.
+fn entry_id(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Copy the validators and freshness headers of a `304 Not Modified` onto the
+/// cached response it confirmed.
+fn apply_not_modified(cached: &mut UpstreamResponse, not_modified: &UpstreamResponse) {
+    for name in NOT_MODIFIED_HEADERS {
+        if let Some(value) = not_modified.headers.get(name) {
+            cached.headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Freshness and stale-serving windows for a response, or `None` if it must not
+/// be cached.
+fn cache_policy(response: &UpstreamResponse) -> Option<(Duration, Duration)> {
+    if response.status != reqwest::StatusCode::OK
+        || response.stream.is_some()
+        || response.body.len() > MAX_ENTRY_BYTES
+        || response.trailers.is_some()
+        || response.headers.contains_key(reqwest::header::SET_COOKIE)
+    {
+        return None;
+    }
+    let vary_all = response
+        .headers
+        .get(reqwest::header::VARY)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim() == "*");
+    if vary_all {
+        return None;
+    }
+
+    let cache_control = response
+        .headers
+        .get_all(reqwest::header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_ascii_lowercase();
+    let mut max_age = None;
+    let mut stale_while_revalidate = None;
+    for directive in cache_control.split(',').map(str::trim) {
+        match directive.split_once('=') {
+            Some(("max-age", value)) => max_age = value.trim_matches('"').parse().ok(),
+            Some(("stale-while-revalidate", value)) => {
+                stale_while_revalidate = value.trim_matches('"').parse().ok()
+            }
+            None if directive == "no-store" || directive == "no-cache" => return None,
+            _ => {}
+        }
+    }
+    let has_validator = response.headers.contains_key(reqwest::header::ETAG)
+        || response
+            .headers
+            .contains_key(reqwest::header::LAST_MODIFIED);
+    if max_age.is_none() && !has_validator {
+        return None;
+    }
+    Some((
+        Duration::from_secs(max_age.unwrap_or(0)),
+        Duration::from_secs(stale_while_revalidate.unwrap_or(DEFAULT_STALE_SECS)),
+    ))
+}
+
+/// Cache of proxied GET responses, kept in memory and optionally mirrored to disk.
+///
+/// In stale-while-revalidate mode, stale entries are served straight away and
+/// refreshed in the background. In revalidate mode, they are confirmed with a
+/// conditional request first and served locally if the backend answers `304`.
+pub struct ResponseCache {
+    app: AppHandle,
+    stale_while_revalidate: AtomicBool,
+    revalidate: AtomicBool,
+    /// Directory persisted entries are written to.
+    disk_dir: PathBuf,
+    persist: AtomicBool,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    revalidating: Mutex<HashSet<String>>,
+}
+
+impl ResponseCache {
+    /// Create the cache, reloading persisted entries from `disk_dir` when persistence is on.
+    pub fn new(app: AppHandle, disk_dir: PathBuf, settings: &AppSettings) -> Self {
+        let cache = Self {
+            app,
+            stale_while_revalidate: AtomicBool::new(settings.stale_while_revalidate),
+            revalidate: AtomicBool::new(settings.revalidate_cached_responses),
+            disk_dir,
+            persist: AtomicBool::new(settings.persist_response_cache),
+            entries: Mutex::new(HashMap::new()),
+            revalidating: Mutex::new(HashSet::new()),
+        };
+        if cache.persisting() {
+            let mut entries = load_dir(&cache.disk_dir);
+            let evicted = evict(&mut entries);
+            log::info!(target: "proxy", "Loaded {} cached responses from disk", entries.len());
+            cache.remove_files(evicted);
+            *cache.entries.lock().unwrap_or_else(|e| e.into_inner()) = entries;
+        }
+        cache
+    }
+
+    pub fn set_stale_while_revalidate(&self, enabled: bool) {
+        self.stale_while_revalidate
+            .store(enabled, Ordering::Relaxed);
+        self.clear_if_disabled();
+    }
+
+    pub fn set_revalidate(&self, enabled: bool) {
+        self.revalidate.store(enabled, Ordering::Relaxed);
+        self.clear_if_disabled();
+    }
+
+    /// Mirror entries to disk (writing the current ones out), or delete the copies on disk.
+    pub fn set_persist(&self, enabled: bool) {
+        if self.persist.swap(enabled, Ordering::Relaxed) == enabled {
+            return;
+        }
+        if enabled {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            for (id, entry) in entries.iter() {
+                self.write_entry(id, entry);
+            }
+        } else {
+            self.remove_dir();
+        }
+    }
+
+    fn clear_if_disabled(&self) {
+        if self.enabled() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        if self.persisting() {
+            self.remove_dir();
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.stale_while_revalidate.load(Ordering::Relaxed)
+            || self.revalidate.load(Ordering::Relaxed)
+    }
+
+    fn persisting(&self) -> bool {
+        self.persist.load(Ordering::Relaxed)
+    }
+
+    /// A cached response that may still be served, fresh or stale. In revalidate
+    /// mode, entries with a validator are kept past their stale window, since a
+    /// `304` can still confirm them.
+    pub fn get(&self, key: &str) -> Option<CacheHit> {
+        if !self.enabled() {
+            return None;
+        }
+        let id = entry_id(key);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get(&id)?;
+        let age = entry.age();
+        let expired = age > entry.fresh_for + entry.stale_for;
+        if expired && !(self.revalidate.load(Ordering::Relaxed) && entry.has_validator()) {
+            entries.remove(&id);
+            drop(entries);
+            self.remove_files(vec![id]);
+            return None;
+        }
+        let stale = age >= entry.fresh_for;
+        Some(CacheHit {
+            response: entry.response.clone(),
+            stale,
+            serve_stale: stale && !expired && self.stale_while_revalidate.load(Ordering::Relaxed),
+            age,
+        })
+    }
+
+    /// Cache a response if it is eligible.
+    pub fn store(&self, key: &str, path: &str, response: &UpstreamResponse) {
+        if !self.enabled() {
+            return;
+        }
+        let Some((fresh_for, stale_for)) = cache_policy(response) else {
+            return;
+        };
+        let id = entry_id(key);
+        let entry = CacheEntry {
+            response: response.clone(),
+            path: path.to_string(),
+            stored_at: Instant::now(),
+            fresh_for,
+            stale_for,
+        };
+        self.write_entry(&id, &entry);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(id, entry);
+        let evicted = evict(&mut entries);
+        drop(entries);
+        self.remove_files(evicted);
+    }
+
+    /// Serve the cached copy a `304 Not Modified` confirmed, refreshing the entry.
+    /// Returns `cached` if the entry has gone in the meantime.
+    pub fn not_modified(
+        &self,
+        key: &str,
+        mut cached: UpstreamResponse,
+        not_modified: &UpstreamResponse,
+    ) -> UpstreamResponse {
+        let id = entry_id(key);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get_mut(&id) {
+            refresh(entry, not_modified);
+            self.write_entry(&id, entry);
+            entry.response.clone()
+        } else {
+            apply_not_modified(&mut cached, not_modified);
+            cached
+        }
+    }
+
+    /// Mark `key` as being revalidated. Returns `false` if a revalidation is already running.
+    pub fn begin_revalidation(&self, key: &str) -> bool {
+        self.revalidating
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(entry_id(key))
+    }
+
+    /// Apply the backend's answer to a conditional revalidation request, emitting
+    /// `resource-updated` when the content changed.
+    pub fn finish_revalidation(&self, key: &str, result: Option<UpstreamResponse>) {
+        let id = entry_id(key);
+        self.revalidating
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        let Some(response) = result else {
+            // Keep serving the stale copy if the backend could not be reached
+            return;
+        };
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = entries.get_mut(&id) else {
+            return;
+        };
+        if response.status == reqwest::StatusCode::NOT_MODIFIED {
+            refresh(entry, &response);
+            self.write_entry(&id, entry);
+            return;
+        }
+        let changed =
+            response.status != entry.response.status || response.body != entry.response.body;
+        let path = entry.path.clone();
+        match cache_policy(&response) {
+            Some((fresh_for, stale_for)) => {
+                entry.response = response;
+                entry.stored_at = Instant::now();
+                entry.fresh_for = fresh_for;
+                entry.stale_for = stale_for;
+                self.write_entry(&id, entry);
+            }
+            None => {
+                entries.remove(&id);
+                self.remove_files(vec![id]);
+            }
+        }
+        drop(entries);
+        if changed {
+            log::info!(target: "proxy", "Cached response for {} changed", path);
+            let _ = self
+                .app
+                .emit("resource-updated", serde_json::json!({ "path": path }));
+        }
+    }
+
+    /// Write an entry to disk in the background, if persistence is on.
+    fn write_entry(&self, id: &str, entry: &CacheEntry) {
+        if !self.persisting() {
+            return;
+        }
+        let meta = match serde_json::to_vec(&DiskEntry::from_entry(entry)) {
+            Ok(meta) => meta,
+            Err(e) => {
+                log::warn!(target: "proxy", "Failed to serialize cached response: {}", e);
+                return;
+            }
+        };
+        let body = entry.response.body.clone();
+        let base = self.disk_dir.join(id);
+        tauri::async_runtime::spawn_blocking(move || {
+            let result = base
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(base.with_extension("body"), &body))
+                .and_then(|_| fs::write(base.with_extension("json"), &meta));
+            if let Err(e) = result {
+                log::warn!(target: "proxy", "Failed to persist cached response: {}", e);
+            }
+        });
+    }
+
+    fn remove_files(&self, ids: Vec<String>) {
+        if ids.is_empty() || !self.persisting() {
+            return;
+        }
+        let dir = self.disk_dir.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            for id in ids {
+                let base = dir.join(id);
+                let _ = fs::remove_file(base.with_extension("json"));
+                let _ = fs::remove_file(base.with_extension("body"));
+            }
+        });
+    }
+
+    fn remove_dir(&self) {
+        let dir = self.disk_dir.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let _ = fs::remove_dir_all(dir);
+        });
+    }
+}
+
+/// Mark an entry as freshly validated by a `304 Not Modified`.
+fn refresh(entry: &mut CacheEntry, not_modified: &UpstreamResponse) {
+    apply_not_modified(&mut entry.response, not_modified);
+    if let Some((fresh_for, stale_for)) = cache_policy(&entry.response) {
+        entry.fresh_for = fresh_for;
+        entry.stale_for = stale_for;
+    }
+    entry.stored_at = Instant::now();
+}
+
+/// Evict the oldest entries until the cache fits its size limit, returning their ids.
+fn evict(entries: &mut HashMap<String, CacheEntry>) -> Vec<String> {
+    let mut evicted = Vec::new();
+    let mut total: usize = entries
+        .values()
+        .map(|entry| entry.response.body.len())
+        .sum();
+    while total > MAX_CACHE_BYTES {
+        let Some(oldest) = entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.stored_at)
+            .map(|(id, _)| id.clone())
+        else {
+            break;
+        };
+        if let Some(entry) = entries.remove(&oldest) {
+            total -= entry.response.body.len();
+        }
+        evicted.push(oldest);
+    }
+    evicted
+}
+
+/// Read the entries persisted in `dir`, skipping any that are unreadable.
+fn load_dir(dir: &Path) -> HashMap<String, CacheEntry> {
+    let Ok(files) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    files
+        .filter_map(|file| {
+            let path = file.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            let id = path.file_stem()?.to_str()?.to_string();
+            let meta: DiskEntry = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+            let body = fs::read(path.with_extension("body")).ok()?;
+            Some((id, meta.into_entry(body)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_not_modified, cache_policy, CacheEntry, DiskEntry};
+    use crate::UpstreamResponse;
+    use hyper::body::Bytes;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::StatusCode;
+    use std::time::{Duration, Instant};
+
+    fn response(cache_control: Option<&'static str>, etag: bool) -> UpstreamResponse {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = cache_control {
+            headers.insert("cache-control", HeaderValue::from_static(value));
+        }
+        if etag {
+            headers.insert("etag", HeaderValue::from_static("\"v1\""));
+        }
+        UpstreamResponse {
+            status: StatusCode::OK,
+            headers,
+            url: "https://assistant/api/config".parse().unwrap(),
+            version: reqwest::Version::HTTP_11,
+            body: Bytes::from_static(b"{}"),
+            trailers: None,
+            stream: None,
+            ttfb: Duration::ZERO,
+            download: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn caches_only_eligible_responses() {
+        assert_eq!(
+            cache_policy(&response(
+                Some("max-age=60, stale-while-revalidate=300"),
+                false
+            )),
+            Some((Duration::from_secs(60), Duration::from_secs(300)))
+        );
+        assert_eq!(
+            cache_policy(&response(None, true)),
+            Some((Duration::ZERO, Duration::from_secs(24 * 60 * 60)))
+        );
+        assert_eq!(cache_policy(&response(None, false)), None);
+        assert_eq!(cache_policy(&response(Some("no-store"), true)), None);
+    }
+
+    #[test]
+    fn round_trips_entries_and_applies_not_modified() {
+        let entry = CacheEntry {
+            response: response(Some("max-age=60"), true),
+            path: "/api/config".to_string(),
+            stored_at: Instant::now() - Duration::from_secs(30),
+            fresh_for: Duration::from_secs(60),
+            stale_for: Duration::from_secs(300),
+        };
+        let restored = DiskEntry::from_entry(&entry)
+            .into_entry(entry.response.body.to_vec())
+            .unwrap();
+        assert_eq!(restored.path, "/api/config");
+        assert_eq!(restored.response.headers, entry.response.headers);
+        assert_eq!(restored.response.body, entry.response.body);
+        assert_eq!(restored.fresh_for, Duration::from_secs(60));
+        assert!(restored.age() >= Duration::from_secs(29));
+
+        let mut cached = entry.response.clone();
+        let mut not_modified = response(Some("max-age=120"), false);
+        not_modified.status = StatusCode::NOT_MODIFIED;
+        not_modified
+            .headers
+            .insert("etag", HeaderValue::from_static("\"v2\""));
+        apply_not_modified(&mut cached, &not_modified);
+        assert_eq!(cached.headers["etag"], "\"v2\"");
+        assert_eq!(cached.headers["cache-control"], "max-age=120");
+        assert_eq!(cached.body, entry.response.body);
+    }
+}
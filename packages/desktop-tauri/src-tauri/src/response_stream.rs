@@ -0,0 +1,193 @@
+use futures_util::StreamExt;
+use http_body_util::{BodyExt, BodyStream, StreamBody};
+use hyper::body::{Bytes, Frame};
+use std::sync::{Arc, Mutex};
+
+use crate::content_encoding::StreamDecoder;
+use crate::{grpc, BoxError, ProxyBody};
+
+/// Responses with a known length above this are streamed rather than buffered.
+const STREAM_THRESHOLD_BYTES: u64 = 1024 * 1024;
+/// Content types that deliver their body incrementally.
+const STREAMING_CONTENT_TYPES: &[&str] = &[
+    "text/event-stream",
+    "application/x-ndjson",
+    "application/jsonl",
+    "application/stream+json",
+];
+
+/// Whether to forward a backend response as it arrives instead of reading it in
+/// full. Only successful responses are streamed, since error responses are
+/// inspected for session expiry. Partial content (media seeking) always streams.
+pub fn should_stream(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap) -> bool {
+    if !status.is_success() || status == reqwest::StatusCode::NO_CONTENT {
+        return false;
+    }
+    if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        return true;
+    }
+    let content_type = headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+    if content_type.as_deref().is_some_and(|content_type| {
+        STREAMING_CONTENT_TYPES.contains(&content_type) || grpc::is_grpc_content_type(content_type)
+    }) {
+        return true;
+    }
+    // Without a length the backend is writing the body in chunks as it goes
+    match headers
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+    {
+        Some(length) => length > STREAM_THRESHOLD_BYTES,
+        None => true,
+    }
+}
+
+/// Backend response whose body has not been read. Shared so the response stays
+/// `Clone`, but only one caller can take it.
+#[derive(Clone)]
+pub struct UpstreamStream(Arc<Mutex<Option<reqwest::Response>>>);
+
+impl UpstreamStream {
+    pub fn new(response: reqwest::Response) -> Self {
+        Self(Arc::new(Mutex::new(Some(response))))
+    }
+
+    pub fn take(&self) -> Option<reqwest::Response> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// Forward the body chunk by chunk, calling `on_chunk` with each chunk's size as
+/// received from the backend. With a `decoder`, chunks are decompressed on the way.
+pub fn into_body(
+    response: reqwest::Response,
+    decoder: Option<StreamDecoder>,
+    on_chunk: impl Fn(usize) + Send + Sync + 'static,
+) -> ProxyBody {
+    let body = hyper::Response::<reqwest::Body>::from(response).into_body();
+    let Some(decoder) = decoder else {
+        return body
+            .map_frame(move |frame| {
+                if let Some(data) = frame.data_ref() {
+                    on_chunk(data.len());
+                }
+                frame
+            })
+            .map_err(|e| Box::new(e) as BoxError)
+            .boxed();
+    };
+    let state = Decoding {
+        frames: BodyStream::new(body),
+        decoder: Some(decoder),
+        trailers: None,
+    };
+    let on_chunk = Arc::new(on_chunk);
+    let frames = futures_util::stream::unfold(state, move |mut state| {
+        let on_chunk = on_chunk.clone();
+        async move {
+            let frame = state.next_frame(&*on_chunk).await?;
+            Some((frame, state))
+        }
+    });
+    BodyExt::boxed(StreamBody::new(frames))
+}
+
+/// Body being decompressed as it streams.
+struct Decoding {
+    frames: BodyStream<reqwest::Body>,
+    /// Dropped once the body has been fully decoded.
+    decoder: Option<StreamDecoder>,
+    /// Trailers held back until the decoder's remaining output has been sent.
+    trailers: Option<Frame<Bytes>>,
+}
+
+impl Decoding {
+    async fn next_frame(
+        &mut self,
+        on_chunk: &impl Fn(usize),
+    ) -> Option<Result<Frame<Bytes>, BoxError>> {
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else {
+                return self.trailers.take().map(Ok);
+            };
+            let decoded = match self.frames.next().await {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        on_chunk(data.len());
+                        decoder.push(&data)
+                    }
+                    // Trailers end the body
+                    Err(trailers) => {
+                        self.trailers = Some(trailers);
+                        let rest = decoder.finish();
+                        self.decoder = None;
+                        rest
+                    }
+                },
+                Some(Err(e)) => return Some(Err(Box::new(e))),
+                None => {
+                    let rest = decoder.finish();
+                    self.decoder = None;
+                    rest
+                }
+            };
+            match decoded {
+                Ok(decoded) if decoded.is_empty() => continue,
+                Ok(decoded) => return Some(Ok(Frame::data(Bytes::from(decoded)))),
+                Err(e) => {
+                    self.decoder = None;
+                    self.trailers = None;
+                    return Some(Err(Box::new(e)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::should_stream;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::StatusCode;
+
+    fn headers(content_type: &'static str, length: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static(content_type));
+        if let Some(length) = length {
+            headers.insert("content-length", HeaderValue::from_static(length));
+        }
+        headers
+    }
+
+    #[test]
+    fn streams_incremental_and_large_responses() {
+        let events = headers("text/event-stream; charset=utf-8", None);
+        assert!(should_stream(StatusCode::OK, &events));
+        assert!(should_stream(
+            StatusCode::OK,
+            &headers("application/json", None)
+        ));
+        assert!(should_stream(
+            StatusCode::OK,
+            &headers("application/zip", Some("5000000"))
+        ));
+        assert!(!should_stream(
+            StatusCode::OK,
+            &headers("application/json", Some("120"))
+        ));
+        assert!(should_stream(
+            StatusCode::OK,
+            &headers("application/grpc+proto", Some("5"))
+        ));
+        assert!(should_stream(
+            StatusCode::PARTIAL_CONTENT,
+            &headers("video/mp4", Some("1024"))
+        ));
+        assert!(!should_stream(StatusCode::UNAUTHORIZED, &events));
+    }
+}
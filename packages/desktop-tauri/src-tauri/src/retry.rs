@@ -0,0 +1,155 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::{send_upstream, UpstreamError, UpstreamResult};
+
+/// Methods retried without opting in, since repeating them has no side effects.
+const IDEMPOTENT_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS"];
+/// Longest `Retry-After` the proxy waits for before giving up instead.
+const MAX_RETRY_AFTER_SECS: u64 = 10;
+
+/// How the HTTP proxy retries requests that failed transiently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first (1 disables retries).
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each further one.
+    pub base_delay_ms: u64,
+    /// Upper bound for a single delay.
+    pub max_delay_ms: u64,
+    /// No retry starts later than this after the first attempt.
+    pub deadline_secs: u64,
+    /// Non-idempotent methods to retry as well (e.g. `PUT`, `DELETE`).
+    pub extra_methods: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 5_000,
+            deadline_secs: 30,
+            extra_methods: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn retries_method(&self, method: &hyper::Method) -> bool {
+        IDEMPOTENT_METHODS.contains(&method.as_str())
+            || self
+                .extra_methods
+                .iter()
+                .any(|extra| extra.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    /// Exponential backoff with full jitter: a random delay up to
+    /// `base_delay_ms * 2^retry`, capped at `max_delay_ms`.
+    fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay_ms
+            .saturating_mul(1u64 << retry.min(20))
+            .min(self.max_delay_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+}
+
+/// Whether a failed attempt is worth repeating, and how long the backend asked
+/// us to wait first, if it did.
+fn should_retry(result: &UpstreamResult) -> Option<Option<Duration>> {
+    match result {
+        Err(UpstreamError::Send(_) | UpstreamError::Body(_)) => Some(None),
+        Ok(response)
+            if matches!(
+                response.status,
+                reqwest::StatusCode::BAD_GATEWAY
+                    | reqwest::StatusCode::SERVICE_UNAVAILABLE
+                    | reqwest::StatusCode::GATEWAY_TIMEOUT
+            ) =>
+        {
+            let retry_after = response
+                .headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok());
+            match retry_after {
+                Some(secs) if secs > MAX_RETRY_AFTER_SECS => None,
+                Some(secs) => Some(Some(Duration::from_secs(secs))),
+                None => Some(None),
+            }
+        }
+        Ok(_) => None,
+    }
+}
+
+/// Send a request upstream, retrying transient failures (connection errors,
+/// timeouts, 502/503/504) per `policy`. Requests whose body cannot be replayed,
+/// such as streamed uploads, are sent once.
+pub async fn send_with_retry(
+    request: reqwest::RequestBuilder,
+    timeout: Duration,
+    policy: RetryPolicy,
+    method: hyper::Method,
+    path: String,
+    request_id: String,
+) -> UpstreamResult {
+    if policy.max_attempts <= 1 || !policy.retries_method(&method) {
+        return send_upstream(request, timeout).await;
+    }
+    let deadline = Instant::now() + Duration::from_secs(policy.deadline_secs);
+    let mut request = request;
+    let mut attempt = 1;
+    loop {
+        let next = request.try_clone();
+        let result = send_upstream(request, timeout).await;
+        let Some(retry_after) = should_retry(&result) else {
+            return result;
+        };
+        let Some(next) = next.filter(|_| attempt < policy.max_attempts) else {
+            return result;
+        };
+        let delay = retry_after.unwrap_or_else(|| policy.delay(attempt - 1));
+        if Instant::now() + delay > deadline {
+            return result;
+        }
+        log::warn!(
+            target: "proxy",
+            "[{}] Retrying {} {} in {}ms (attempt {} of {})",
+            request_id,
+            method,
+            path,
+            delay.as_millis(),
+            attempt + 1,
+            policy.max_attempts
+        );
+        tokio::time::sleep(delay).await;
+        request = next;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use hyper::Method;
+    use std::time::Duration;
+
+    #[test]
+    fn backs_off_within_bounds_for_idempotent_methods() {
+        let policy = RetryPolicy {
+            extra_methods: vec!["put".to_string()],
+            ..RetryPolicy::default()
+        };
+        assert!(policy.retries_method(&Method::GET));
+        assert!(policy.retries_method(&Method::PUT));
+        assert!(!policy.retries_method(&Method::POST));
+
+        for retry in 0..10 {
+            let ceiling = (200u64 << retry).min(5_000);
+            assert!(policy.delay(retry) <= Duration::from_millis(ceiling));
+        }
+    }
+}
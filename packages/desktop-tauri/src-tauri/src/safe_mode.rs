@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::AppState;
+
+/// Consecutive unconfirmed startups after which the app boots into safe mode.
+const CRASH_LOOP_THRESHOLD: u32 = 3;
+/// Marker counting startups that have not yet reached a running proxy.
+const STARTUP_MARKER_FILE: &str = "startup_attempts";
+
+/// Safe mode status, returned by `get_safe_mode_status` and sent with the `safe-mode` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct SafeModeStatus {
+    pub active: bool,
+    pub failed_starts: u32,
+}
+
+/// Tracks startups via a marker file so a crash loop can be detected on the next launch.
+pub struct StartupGuard {
+    marker_path: PathBuf,
+    failed_starts: u32,
+}
+
+impl StartupGuard {
+    /// Record a startup attempt; the marker stays in place until `confirm` is called.
+    pub fn begin(data_dir: &Path) -> Self {
+        let marker_path = data_dir.join(STARTUP_MARKER_FILE);
+        let failed_starts = fs::read_to_string(&marker_path)
+            .ok()
+            .and_then(|data| data.trim().parse().ok())
+            .unwrap_or(0);
+        let _ = fs::create_dir_all(data_dir);
+        if let Err(e) = fs::write(&marker_path, (failed_starts + 1).to_string()) {
+            log::error!(target: "safe-mode", "Failed to write startup marker: {}", e);
+        }
+        Self {
+            marker_path,
+            failed_starts,
+        }
+    }
+
+    pub fn safe_mode(&self) -> bool {
+        self.failed_starts >= CRASH_LOOP_THRESHOLD
+    }
+
+    pub fn status(&self) -> SafeModeStatus {
+        SafeModeStatus {
+            active: self.safe_mode(),
+            failed_starts: self.failed_starts,
+        }
+    }
+
+    /// Mark this startup as successful so the next launch boots normally.
+    pub fn confirm(&self) {
+        if let Err(e) = fs::remove_file(&self.marker_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::error!(target: "safe-mode", "Failed to clear startup marker: {}", e);
+            }
+        }
+    }
+}
+
+/// Whether the app started in safe mode and how many failed starts preceded it.
+#[tauri::command]
+pub async fn get_safe_mode_status(state: State<'_, AppState>) -> Result<SafeModeStatus, String> {
+    Ok(state.startup.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StartupGuard, CRASH_LOOP_THRESHOLD};
+
+    #[test]
+    fn enters_safe_mode_after_repeated_unconfirmed_starts() {
+        let dir = std::env::temp_dir().join(format!("assistant-safe-mode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for _ in 0..CRASH_LOOP_THRESHOLD {
+            assert!(!StartupGuard::begin(&dir).safe_mode());
+        }
+        let guard = StartupGuard::begin(&dir);
+        assert!(guard.safe_mode());
+
+        guard.confirm();
+        assert!(!StartupGuard::begin(&dir).safe_mode());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
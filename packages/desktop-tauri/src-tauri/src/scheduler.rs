@@ -0,0 +1,517 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::Notify;
+
+use crate::tools::{self, LocalTool};
+use crate::{now_ms, AppState};
+
+/// Consent scope covering the scheduling tools.
+pub const CONSENT_SCOPE: &str = "scheduler";
+
+const MIN_REPEAT_SECS: u64 = 60;
+/// Longest sleep between checks, so time spent suspended or offline is noticed promptly.
+const MAX_SLEEP_SECS: u64 = 30;
+
+/// What a scheduled task does when it comes due.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduledAction {
+    /// Show a native notification.
+    Notify {
+        title: String,
+        #[serde(default)]
+        body: String,
+    },
+    /// Ask the web client to send a prompt to the assistant.
+    Prompt {
+        text: String,
+        #[serde(default)]
+        session_id: Option<String>,
+    },
+    /// Run a local tool, approved by the user when it was scheduled.
+    Tool {
+        name: String,
+        #[serde(default)]
+        arguments: Value,
+    },
+}
+
+/// A task persisted in `scheduled_tasks.json`. Times are Unix milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub id: u64,
+    #[serde(default)]
+    pub label: Option<String>,
+    pub action: ScheduledAction,
+    pub run_at: u64,
+    /// Run again this many seconds after each run.
+    #[serde(default)]
+    pub repeat_secs: Option<u64>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub last_run_at: Option<u64>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// Payload of the `scheduled-prompt` event.
+#[derive(Debug, Clone, Serialize)]
+struct ScheduledPrompt {
+    task_id: u64,
+    text: String,
+    session_id: Option<String>,
+}
+
+/// Payload of the `scheduled-task-ran` event.
+#[derive(Debug, Clone, Serialize)]
+struct TaskRan {
+    task_id: u64,
+    /// Milliseconds between the due time and the run, e.g. after the app was closed.
+    late_ms: u64,
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+/// Scheduled tasks, persisted next to the settings file.
+pub struct Scheduler {
+    tasks: RwLock<Vec<ScheduledTask>>,
+    path: PathBuf,
+    wake: Notify,
+}
+
+impl Scheduler {
+    pub fn load(path: PathBuf) -> Self {
+        let tasks = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            tasks: RwLock::new(tasks),
+            path,
+            wake: Notify::new(),
+        }
+    }
+
+    fn save(&self, tasks: &[ScheduledTask]) -> Result<(), String> {
+        let data = serde_json::to_string_pretty(tasks).map_err(|e| e.to_string())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&self.path, data).map_err(|e| e.to_string())
+    }
+
+    fn add(
+        &self,
+        label: Option<String>,
+        action: ScheduledAction,
+        run_at: u64,
+        repeat_secs: Option<u64>,
+    ) -> Result<ScheduledTask, String> {
+        let task = {
+            let mut tasks = self.tasks.write().unwrap_or_else(|e| e.into_inner());
+            let task = ScheduledTask {
+                id: tasks.iter().map(|task| task.id).max().unwrap_or(0) + 1,
+                label,
+                action,
+                run_at,
+                repeat_secs: repeat_secs.map(|secs| secs.max(MIN_REPEAT_SECS)),
+                created_at: now_ms(),
+                last_run_at: None,
+                last_error: None,
+            };
+            tasks.push(task.clone());
+            self.save(&tasks)?;
+            task
+        };
+        self.wake.notify_one();
+        Ok(task)
+    }
+
+    fn cancel(&self, id: u64) -> Result<bool, String> {
+        let mut tasks = self.tasks.write().unwrap_or_else(|e| e.into_inner());
+        let before = tasks.len();
+        tasks.retain(|task| task.id != id);
+        if tasks.len() == before {
+            return Ok(false);
+        }
+        self.save(&tasks)?;
+        Ok(true)
+    }
+
+    fn list(&self) -> Vec<ScheduledTask> {
+        let mut tasks = self.tasks.read().unwrap_or_else(|e| e.into_inner()).clone();
+        tasks.sort_by_key(|task| task.run_at);
+        tasks
+    }
+
+    /// Remove due one-off tasks and move due repeating tasks to their next run.
+    ///
+    /// A repeating task that missed several runs (app closed, machine asleep) runs
+    /// once and is moved to its first run after `now`.
+    fn take_due(&self, now: u64) -> Vec<ScheduledTask> {
+        let mut tasks = self.tasks.write().unwrap_or_else(|e| e.into_inner());
+        let due: Vec<ScheduledTask> = tasks
+            .iter()
+            .filter(|task| task.run_at <= now)
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            return due;
+        }
+        tasks.retain_mut(|task| {
+            if task.run_at > now {
+                return true;
+            }
+            let Some(repeat_secs) = task.repeat_secs else {
+                return false;
+            };
+            let interval = repeat_secs * 1000;
+            task.run_at += (now - task.run_at) / interval * interval + interval;
+            task.last_run_at = Some(now);
+            true
+        });
+        if let Err(e) = self.save(&tasks) {
+            log::error!(target: "scheduler", "Failed to save tasks: {}", e);
+        }
+        due
+    }
+
+    fn record_error(&self, id: u64, error: Option<String>) {
+        let mut tasks = self.tasks.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(task) = tasks.iter_mut().find(|task| task.id == id) {
+            task.last_error = error;
+            let _ = self.save(&tasks);
+        }
+    }
+
+    fn next_run_at(&self) -> Option<u64> {
+        self.tasks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|task| task.run_at)
+            .min()
+    }
+}
+
+/// Validate a new task, asking the user to approve a scheduled tool call.
+async fn schedule(
+    app: &AppHandle,
+    label: Option<String>,
+    action: ScheduledAction,
+    run_at: u64,
+    repeat_secs: Option<u64>,
+) -> Result<ScheduledTask, String> {
+    match &action {
+        ScheduledAction::Notify { title, .. } if title.trim().is_empty() => {
+            return Err("Notification title must not be empty".to_string());
+        }
+        ScheduledAction::Prompt { text, .. } if text.trim().is_empty() => {
+            return Err("Prompt text must not be empty".to_string());
+        }
+        ScheduledAction::Tool { name, arguments } => {
+            let tool = tools::find_tool(name)?;
+            if tool.consent == CONSENT_SCOPE {
+                return Err("Scheduled tasks cannot schedule other tasks".to_string());
+            }
+            if tool.requires_approval {
+                let when = DateTime::<Utc>::from_timestamp_millis(run_at as i64)
+                    .map(|time| time.with_timezone(&chrono::Local).format("%c").to_string())
+                    .unwrap_or_default();
+                let repeat = repeat_secs
+                    .map(|secs| {
+                        format!(
+                            " and every {} seconds after that",
+                            secs.max(MIN_REPEAT_SECS)
+                        )
+                    })
+                    .unwrap_or_default();
+                let message = format!(
+                    "Allow the assistant to run {} at {}{}?\n\n{}",
+                    tool.name,
+                    when,
+                    repeat,
+                    tools::describe_call(&tool, arguments)
+                );
+                if !tools::confirm(app, "Assistant Tools", &message, "Schedule").await? {
+                    return Err(format!("The user declined to schedule {}", tool.name));
+                }
+            }
+        }
+        _ => {}
+    }
+    let state: State<'_, AppState> = app.state();
+    state.scheduler.add(label, action, run_at, repeat_secs)
+}
+
+async fn run_task(app: AppHandle, task: ScheduledTask, now: u64) {
+    let result = match &task.action {
+        ScheduledAction::Notify { title, body } => app
+            .notification()
+            .builder()
+            .title(title)
+            .body(body)
+            .show()
+            .map(|_| None)
+            .map_err(|e| e.to_string()),
+        ScheduledAction::Prompt { text, session_id } => app
+            .emit(
+                "scheduled-prompt",
+                ScheduledPrompt {
+                    task_id: task.id,
+                    text: text.clone(),
+                    session_id: session_id.clone(),
+                },
+            )
+            .map(|_| None)
+            .map_err(|e| e.to_string()),
+        ScheduledAction::Tool { name, arguments } => match tools::find_tool(name) {
+            Ok(tool) => tools::run_approved(&app, &tool, arguments.clone())
+                .await
+                .map(Some),
+            Err(e) => Err(e),
+        },
+    };
+
+    let error = result.as_ref().err().cloned();
+    if let Some(e) = &error {
+        log::warn!(target: "scheduler", "Task {} failed: {}", task.id, e);
+    }
+    let state: State<'_, AppState> = app.state();
+    state.scheduler.record_error(task.id, error.clone());
+    let _ = app.emit(
+        "scheduled-task-ran",
+        TaskRan {
+            task_id: task.id,
+            late_ms: now.saturating_sub(task.run_at),
+            result: result.ok().flatten(),
+            error,
+        },
+    );
+}
+
+/// Run tasks as they come due. Tasks missed while the app was closed run at startup.
+pub async fn run_scheduler(app: AppHandle) {
+    loop {
+        let state: State<'_, AppState> = app.state();
+        let now = now_ms();
+        for task in state.scheduler.take_due(now) {
+            tauri::async_runtime::spawn(run_task(app.clone(), task, now));
+        }
+
+        let wait_ms = state
+            .scheduler
+            .next_run_at()
+            .map(|run_at| run_at.saturating_sub(now_ms()))
+            .unwrap_or(u64::MAX)
+            .min(MAX_SLEEP_SECS * 1000);
+        tokio::select! {
+            _ = state.scheduler.wake.notified() => {}
+            _ = tokio::time::sleep(Duration::from_millis(wait_ms)) => {}
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ScheduleArgs {
+    action: ScheduledAction,
+    #[serde(default)]
+    run_at: Option<String>,
+    #[serde(default)]
+    delay_secs: Option<u64>,
+    #[serde(default)]
+    repeat_secs: Option<u64>,
+    #[serde(default)]
+    label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CancelArgs {
+    task_id: u64,
+}
+
+fn rfc3339(ms: u64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp_millis(ms as i64).map(|time| time.to_rfc3339())
+}
+
+/// A task as returned to the assistant, with RFC 3339 times.
+fn task_json(task: &ScheduledTask) -> Value {
+    json!({
+        "task_id": task.id,
+        "label": task.label,
+        "action": task.action,
+        "run_at": rfc3339(task.run_at),
+        "repeat_secs": task.repeat_secs,
+        "last_run_at": task.last_run_at.and_then(rfc3339),
+        "last_error": task.last_error,
+    })
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    vec![
+        LocalTool {
+            name: "schedule_task",
+            description: "Schedule a notification, a prompt to yourself, or a local tool run for later. Runs even if the app was closed or offline at the due time (as soon as it is back).",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "oneOf": [
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "type": { "const": "notify" },
+                                    "title": { "type": "string" },
+                                    "body": { "type": "string" }
+                                },
+                                "required": ["type", "title"]
+                            },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "type": { "const": "prompt" },
+                                    "text": { "type": "string" },
+                                    "session_id": { "type": "string" }
+                                },
+                                "required": ["type", "text"]
+                            },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "type": { "const": "tool" },
+                                    "name": { "type": "string" },
+                                    "arguments": { "type": "object" }
+                                },
+                                "required": ["type", "name"]
+                            }
+                        ]
+                    },
+                    "run_at": { "type": "string", "description": "RFC 3339 time" },
+                    "delay_secs": { "type": "integer", "description": "Run this many seconds from now, instead of run_at" },
+                    "repeat_secs": { "type": "integer", "description": "Repeat at this interval (min 60)" },
+                    "label": { "type": "string" }
+                },
+                "required": ["action"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "list_scheduled_tasks",
+            description: "List scheduled tasks.",
+            parameters: json!({ "type": "object", "properties": {} }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "cancel_scheduled_task",
+            description: "Cancel a scheduled task.",
+            parameters: json!({
+                "type": "object",
+                "properties": { "task_id": { "type": "integer" } },
+                "required": ["task_id"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+    ]
+}
+
+/// Run a scheduling tool.
+pub async fn call(app: &AppHandle, name: &str, arguments: Value) -> Result<Value, String> {
+    let state: State<'_, AppState> = app.state();
+    match name {
+        "schedule_task" => {
+            let args: ScheduleArgs =
+                serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let run_at = match (args.run_at, args.delay_secs) {
+                (Some(run_at), _) => DateTime::parse_from_rfc3339(run_at.trim())
+                    .map_err(|e| format!("Invalid time {:?}: {}", run_at, e))?
+                    .timestamp_millis()
+                    .max(0) as u64,
+                (None, Some(delay_secs)) => now_ms() + delay_secs * 1000,
+                (None, None) => return Err("Either run_at or delay_secs is required".to_string()),
+            };
+            let task = schedule(app, args.label, args.action, run_at, args.repeat_secs).await?;
+            Ok(task_json(&task))
+        }
+        "list_scheduled_tasks" => Ok(Value::Array(
+            state.scheduler.list().iter().map(task_json).collect(),
+        )),
+        "cancel_scheduled_task" => {
+            let args: CancelArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            if !state.scheduler.cancel(args.task_id)? {
+                return Err(format!("No scheduled task {}", args.task_id));
+            }
+            Ok(json!({}))
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+/// Schedule a task at `run_at` (Unix milliseconds).
+#[tauri::command]
+pub async fn schedule_task(
+    app: AppHandle,
+    action: ScheduledAction,
+    run_at: u64,
+    repeat_secs: Option<u64>,
+    label: Option<String>,
+) -> Result<ScheduledTask, String> {
+    schedule(&app, label, action, run_at, repeat_secs).await
+}
+
+/// List scheduled tasks, soonest first.
+#[tauri::command]
+pub async fn list_scheduled_tasks(
+    state: State<'_, AppState>,
+) -> Result<Vec<ScheduledTask>, String> {
+    Ok(state.scheduler.list())
+}
+
+/// Cancel a scheduled task. Returns whether it existed.
+#[tauri::command]
+pub async fn cancel_task(id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    state.scheduler.cancel(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduledAction, Scheduler};
+
+    #[test]
+    fn due_tasks_run_once_and_repeats_skip_missed_runs() {
+        let path = std::env::temp_dir().join(format!(
+            "assistant-scheduler-test-{}.json",
+            std::process::id()
+        ));
+        let scheduler = Scheduler::load(path.clone());
+        let notify = ScheduledAction::Notify {
+            title: "Stretch".to_string(),
+            body: String::new(),
+        };
+        let once = scheduler.add(None, notify.clone(), 1_000, None).unwrap();
+        let repeat = scheduler.add(None, notify, 2_000, Some(60)).unwrap();
+
+        assert!(scheduler.take_due(500).is_empty());
+        // Offline for a few intervals: each task runs once
+        let due = scheduler.take_due(200_000);
+        assert_eq!(
+            due.iter().map(|task| task.id).collect::<Vec<_>>(),
+            vec![once.id, repeat.id]
+        );
+        let tasks = Scheduler::load(path.clone()).list();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].run_at, 242_000);
+        assert!(scheduler.take_due(241_999).is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+/// Time spent by the HTTP proxy on one request.
+pub struct ProxyTiming {
+    /// From the request arriving until it was sent upstream (reading the body,
+    /// waiting out a re-authentication).
+    pub queue: Duration,
+    /// Until the backend's response headers arrived, including connection setup.
+    pub upstream: Duration,
+    /// Reading the response body.
+    pub download: Duration,
+    pub total: Duration,
+}
+
+impl ProxyTiming {
+    /// `Server-Timing` value for the proxy's phases, named `proxy-*` so they do not
+    /// collide with metrics the backend reports itself.
+    pub fn header_value(&self) -> String {
+        [
+            ("proxy-queue", self.queue, "Proxy queue"),
+            ("proxy-ttfb", self.upstream, "Upstream TTFB"),
+            ("proxy-download", self.download, "Upstream body"),
+            ("proxy-total", self.total, "Proxy total"),
+        ]
+        .iter()
+        .map(|(name, duration, description)| {
+            format!(
+                "{};desc=\"{}\";dur={:.1}",
+                name,
+                description,
+                duration.as_secs_f64() * 1000.0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProxyTiming;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_proxy_phases() {
+        let timing = ProxyTiming {
+            queue: Duration::from_micros(300),
+            upstream: Duration::from_millis(42),
+            download: Duration::from_millis(3),
+            total: Duration::from_micros(45_340),
+        };
+        assert_eq!(
+            timing.header_value(),
+            "proxy-queue;desc=\"Proxy queue\";dur=0.3, proxy-ttfb;desc=\"Upstream TTFB\";dur=42.0, \
+             proxy-download;desc=\"Upstream body\";dur=3.0, proxy-total;desc=\"Proxy total\";dur=45.3"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+/// Register a global shortcut in place of `previous`. An empty shortcut only unregisters.
+///
+/// The handler fires on key release so the shortcut's modifiers are no longer held
+/// when the handler synthesizes keystrokes.
+pub fn replace_shortcut<F>(
+    app: &AppHandle,
+    previous: &str,
+    shortcut: &str,
+    on_release: F,
+) -> Result<(), String>
+where
+    F: Fn(&AppHandle) + Send + Sync + 'static,
+{
+    let global_shortcut = app.global_shortcut();
+    let previous = previous.trim();
+    if !previous.is_empty() && global_shortcut.is_registered(previous) {
+        global_shortcut
+            .unregister(previous)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let shortcut = shortcut.trim();
+    if shortcut.is_empty() {
+        return Ok(());
+    }
+
+    global_shortcut
+        .on_shortcut(shortcut, move |app, _shortcut, event| {
+            if event.state == ShortcutState::Released {
+                on_release(app);
+            }
+        })
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,77 @@
+use tokio::sync::{oneshot, watch};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// How long in-flight requests and WebSocket sessions get to finish once the
+/// proxies stop.
+pub const DRAIN_TIMEOUT_SECS: u64 = 5;
+
+/// Stops the local proxies, letting their connections drain.
+pub struct Shutdown {
+    stop: watch::Sender<bool>,
+    stopped: Vec<oneshot::Receiver<()>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            stop: watch::channel(false).0,
+            stopped: Vec::new(),
+        }
+    }
+
+    /// Signal for a listener, and the sender it resolves once it has drained.
+    pub fn register(&mut self) -> (ShutdownSignal, oneshot::Sender<()>) {
+        let (stopped_tx, stopped_rx) = oneshot::channel();
+        self.stopped.push(stopped_rx);
+        (ShutdownSignal(self.stop.subscribe()), stopped_tx)
+    }
+
+    /// Tell every listener to stop and wait until they have drained.
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        for stopped in self.stopped {
+            let _ = stopped.await;
+        }
+    }
+}
+
+/// Watched by accept loops and WebSocket sessions to learn when to stop.
+#[derive(Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Resolves once the proxies are stopping.
+    pub async fn wait(&mut self) {
+        if self.0.wait_for(|stop| *stop).await.is_err() {
+            // The sender is gone, so no stop is coming
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Close frame sent to both ends of a WebSocket session the proxy is shutting down.
+pub fn close_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "Proxy shutting down".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shutdown;
+
+    #[tokio::test]
+    async fn stops_registered_listeners() {
+        let mut shutdown = Shutdown::new();
+        let (mut signal, stopped) = shutdown.register();
+        let listener = tokio::spawn(async move {
+            signal.wait().await;
+            let _ = stopped.send(());
+        });
+        shutdown.stop().await;
+        listener.await.unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use futures_util::StreamExt;
+use http_body_util::{BodyStream, StreamBody};
+use hyper::body::{Bytes, Frame};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::AuthManager;
+use crate::backoff::ReconnectBackoff;
+use crate::{http3, BoxError, ProxyBody};
+
+const RECONNECT_TIMEOUT_SECS: u64 = 30;
+
+/// Whether the response is a Server-Sent Events stream.
+pub fn is_event_stream(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+/// Splits an event stream into complete events, remembering the last event ID and
+/// reconnection delay the backend sent.
+#[derive(Default)]
+struct EventSplitter {
+    pending: Vec<u8>,
+    /// How far `pending` has been read, and where the current line starts.
+    scanned: usize,
+    line_start: usize,
+    /// `id:` of the event being read, applied once the event is complete.
+    event_id: Option<String>,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventSplitter {
+    /// Append `data`, returning the events it completes (possibly none).
+    fn push(&mut self, data: &[u8]) -> Bytes {
+        self.pending.extend_from_slice(data);
+        let mut complete = 0;
+        while let Some(&byte) = self.pending.get(self.scanned) {
+            if byte != b'\n' && byte != b'\r' {
+                self.scanned += 1;
+                continue;
+            }
+            let mut next = self.scanned + 1;
+            if byte == b'\r' {
+                // Wait to see whether this is a CRLF
+                match self.pending.get(next) {
+                    None => break,
+                    Some(b'\n') => next += 1,
+                    Some(_) => {}
+                }
+            }
+            let line = &self.pending[self.line_start..self.scanned];
+            if line.is_empty() {
+                if let Some(id) = self.event_id.take() {
+                    self.last_event_id = Some(id).filter(|id| !id.is_empty());
+                }
+                complete = next;
+            } else {
+                let (name, value) = match line.iter().position(|&b| b == b':') {
+                    Some(colon) => {
+                        let value = &line[colon + 1..];
+                        (&line[..colon], value.strip_prefix(b" ").unwrap_or(value))
+                    }
+                    None => (line, &b""[..]),
+                };
+                let value = String::from_utf8_lossy(value);
+                match name {
+                    b"id" if !value.contains('\0') => self.event_id = Some(value.into_owned()),
+                    b"retry" => {
+                        if let Ok(ms) = value.parse() {
+                            self.retry = Some(Duration::from_millis(ms));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.scanned = next;
+            self.line_start = next;
+        }
+        let events = Bytes::copy_from_slice(&self.pending[..complete]);
+        self.pending.drain(..complete);
+        self.scanned -= complete;
+        self.line_start -= complete;
+        events
+    }
+
+    /// Forget the event that was cut off by a dropped connection.
+    fn discard_partial(&mut self) {
+        self.pending.clear();
+        self.scanned = 0;
+        self.line_start = 0;
+        self.event_id = None;
+    }
+}
+
+/// The original request, replayed with `Last-Event-ID` to resume the stream.
+pub struct Resume {
+    pub client: reqwest::Client,
+    pub request: reqwest::Request,
+    /// Set when the proxy manages the access token, so reconnects use a fresh one.
+    pub auth: Option<Arc<AuthManager>>,
+    /// Reconnect attempts in a row before the stream is given up, and their delay
+    /// when the backend sent no `retry:` field.
+    pub backoff: ReconnectBackoff,
+}
+
+impl Resume {
+    fn request(&self, last_event_id: &str) -> Option<reqwest::RequestBuilder> {
+        let mut request = self.request.try_clone()?;
+        let headers = request.headers_mut();
+        headers.insert("last-event-id", last_event_id.parse().ok()?);
+        if let Some(authorization) = self.auth.as_ref().and_then(|a| a.authorization_header()) {
+            headers.insert("authorization", authorization.parse().ok()?);
+        }
+        Some(reqwest::RequestBuilder::from_parts(
+            self.client.clone(),
+            request,
+        ))
+    }
+}
+
+fn frames(response: reqwest::Response) -> BodyStream<reqwest::Body> {
+    BodyStream::new(hyper::Response::<reqwest::Body>::from(response).into_body())
+}
+
+/// Event stream being forwarded to the web client.
+struct Resumable<F> {
+    frames: BodyStream<reqwest::Body>,
+    splitter: EventSplitter,
+    resume: Resume,
+    failures: u32,
+    on_chunk: F,
+}
+
+impl<F: Fn(usize)> Resumable<F> {
+    async fn next_frame(&mut self) -> Option<Result<Frame<Bytes>, BoxError>> {
+        loop {
+            let error = match self.frames.next().await {
+                // Event streams have no trailers
+                Some(Ok(frame)) => {
+                    let Ok(data) = frame.into_data() else {
+                        continue;
+                    };
+                    (self.on_chunk)(data.len());
+                    let events = self.splitter.push(&data);
+                    if events.is_empty() {
+                        continue;
+                    }
+                    self.failures = 0;
+                    return Some(Ok(Frame::data(events)));
+                }
+                None => return None,
+                Some(Err(e)) => e,
+            };
+            // Without an event ID, reconnecting would replay the stream from the start
+            let Some(last_event_id) = self.splitter.last_event_id.clone() else {
+                return Some(Err(Box::new(error)));
+            };
+            self.splitter.discard_partial();
+            log::warn!(
+                target: "proxy",
+                "Event stream dropped ({}), resuming after event {}",
+                error,
+                last_event_id
+            );
+            loop {
+                self.failures += 1;
+                if self.failures > self.resume.backoff.max_attempts {
+                    return Some(Err(Box::new(error)));
+                }
+                let delay = self
+                    .splitter
+                    .retry
+                    .unwrap_or_else(|| self.resume.backoff.delay(self.failures - 1));
+                tokio::time::sleep(delay).await;
+                let Some(request) = self.resume.request(&last_event_id) else {
+                    return Some(Err(Box::new(error)));
+                };
+                let response = tokio::time::timeout(
+                    Duration::from_secs(RECONNECT_TIMEOUT_SECS),
+                    http3::send(request),
+                )
+                .await;
+                match response {
+                    Ok(Ok(response))
+                        if response.status() == reqwest::StatusCode::OK
+                            && is_event_stream(response.headers()) =>
+                    {
+                        self.frames = frames(response);
+                        break;
+                    }
+                    // The backend has nothing more to send
+                    Ok(Ok(response)) if response.status() == reqwest::StatusCode::NO_CONTENT => {
+                        return None
+                    }
+                    Ok(Ok(response)) => {
+                        log::warn!(target: "proxy", "Event stream resume answered {}", response.status());
+                    }
+                    Ok(Err(e)) => log::warn!(target: "proxy", "Event stream resume failed: {}", e),
+                    Err(_) => log::warn!(target: "proxy", "Event stream resume timed out"),
+                }
+            }
+        }
+    }
+}
+
+/// Forward an event stream event by event, resuming it from the last event ID
+/// when the backend connection drops. `on_chunk` is called with the size of each
+/// chunk received from the backend.
+pub fn resumable_body(
+    response: reqwest::Response,
+    resume: Resume,
+    on_chunk: impl Fn(usize) + Send + Sync + 'static,
+) -> ProxyBody {
+    let state = Resumable {
+        frames: frames(response),
+        splitter: EventSplitter::default(),
+        resume,
+        failures: 0,
+        on_chunk,
+    };
+    let frames = futures_util::stream::unfold(state, |mut state| async move {
+        let frame = state.next_frame().await?;
+        Some((frame, state))
+    });
+    http_body_util::BodyExt::boxed(StreamBody::new(frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventSplitter;
+    use std::time::Duration;
+
+    #[test]
+    fn splits_complete_events() {
+        let mut splitter = EventSplitter::default();
+        assert!(splitter.push(b"retry: 500\r\nid: 1\r\ndata: a").is_empty());
+        assert_eq!(splitter.last_event_id, None);
+        assert_eq!(splitter.retry, Some(Duration::from_millis(500)));
+        // A lone CR may be the start of a CRLF
+        assert!(splitter.push(b"\r\n\r").is_empty());
+        assert_eq!(
+            &splitter.push(b"\nid: 2\ndata: b\n\n: ping\n")[..],
+            b"retry: 500\r\nid: 1\r\ndata: a\r\n\r\nid: 2\ndata: b\n\n"
+        );
+        assert_eq!(splitter.last_event_id.as_deref(), Some("2"));
+
+        // A cut-off event is dropped, and the last complete one is kept
+        splitter.push(b"id: 3\ndata: c");
+        splitter.discard_partial();
+        assert_eq!(&splitter.push(b"data: d\n\n")[..], b"data: d\n\n");
+        assert_eq!(splitter.last_event_id.as_deref(), Some("2"));
+    }
+}
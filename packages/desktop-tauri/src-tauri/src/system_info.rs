@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sysinfo::{Disks, Networks, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::tools::LocalTool;
+
+/// Consent scope covering the system information tool.
+pub const CONSENT_SCOPE: &str = "system_info";
+
+const DEFAULT_TOP_PROCESSES: usize = 5;
+const MAX_TOP_PROCESSES: usize = 20;
+
+#[derive(Debug, Serialize)]
+struct OsInfo {
+    name: Option<String>,
+    version: Option<String>,
+    kernel: Option<String>,
+    arch: String,
+    host_name: Option<String>,
+    uptime_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CpuInfo {
+    brand: Option<String>,
+    logical_cores: usize,
+    physical_cores: Option<usize>,
+    usage_percent: f32,
+    /// 1, 5 and 15 minute load averages; zero on Windows.
+    load_average: [f64; 3],
+}
+
+#[derive(Debug, Serialize)]
+struct MemoryInfo {
+    total_bytes: u64,
+    used_bytes: u64,
+    available_bytes: u64,
+    swap_total_bytes: u64,
+    swap_used_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct DiskInfo {
+    mount_point: String,
+    file_system: String,
+    total_bytes: u64,
+    available_bytes: u64,
+    removable: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkInterface {
+    name: String,
+    mac_address: String,
+    addresses: Vec<String>,
+    received_bytes: u64,
+    transmitted_bytes: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct BatteryInfo {
+    percent: u8,
+    /// `charging`, `discharging`, `full` or `unknown`.
+    status: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ProcessInfo {
+    pid: u32,
+    name: String,
+    cpu_percent: f32,
+    memory_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SystemInfo {
+    os: OsInfo,
+    cpu: CpuInfo,
+    memory: MemoryInfo,
+    disks: Vec<DiskInfo>,
+    battery: Option<BatteryInfo>,
+    network: Vec<NetworkInterface>,
+    top_processes: Vec<ProcessInfo>,
+}
+
+#[derive(Deserialize)]
+struct SystemInfoArgs {
+    #[serde(default)]
+    top_processes: Option<usize>,
+}
+
+fn battery_status(status: &str) -> String {
+    match status.trim().to_ascii_lowercase().as_str() {
+        "charging" => "charging",
+        "discharging" | "not charging" => "discharging",
+        "full" | "charged" => "full",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+/// Parse `pmset -g batt` output, e.g. `-InternalBattery-0 (id=1)\t85%; charging; 1:02 remaining`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_pmset(output: &str) -> Option<BatteryInfo> {
+    let line = output
+        .lines()
+        .find(|line| line.contains("InternalBattery"))?;
+    let mut fields = line.split('\t').nth(1)?.split(';');
+    let percent = fields.next()?.trim().trim_end_matches('%').parse().ok()?;
+    let status = fields.next().map(battery_status).unwrap_or_default();
+    Some(BatteryInfo { percent, status })
+}
+
+#[cfg(target_os = "linux")]
+fn battery() -> Option<BatteryInfo> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok();
+    std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| read(path.join("type")).is_some_and(|kind| kind.trim() == "Battery"))
+        .and_then(|path| {
+            Some(BatteryInfo {
+                percent: read(path.join("capacity"))?.trim().parse().ok()?,
+                status: battery_status(&read(path.join("status")).unwrap_or_default()),
+            })
+        })
+}
+
+#[cfg(target_os = "macos")]
+fn battery() -> Option<BatteryInfo> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn battery() -> Option<BatteryInfo> {
+    None
+}
+
+/// Collect a snapshot. Blocks for about `MINIMUM_CPU_UPDATE_INTERVAL` to measure CPU usage.
+fn collect(top_processes: usize) -> SystemInfo {
+    let mut system = System::new();
+    system.refresh_memory();
+    system.refresh_cpu_all();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_cpu_usage();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+
+    let load = System::load_average();
+    let cpu = CpuInfo {
+        brand: system
+            .cpus()
+            .first()
+            .map(|cpu| cpu.brand().trim().to_string()),
+        logical_cores: system.cpus().len(),
+        physical_cores: System::physical_core_count(),
+        usage_percent: system.global_cpu_usage(),
+        load_average: [load.one, load.five, load.fifteen],
+    };
+
+    let mut processes: Vec<ProcessInfo> = system
+        .processes()
+        .iter()
+        .map(|(pid, process)| ProcessInfo {
+            pid: pid.as_u32(),
+            name: process.name().to_string_lossy().into_owned(),
+            cpu_percent: process.cpu_usage(),
+            memory_bytes: process.memory(),
+        })
+        .collect();
+    processes.sort_by(|a, b| {
+        b.cpu_percent
+            .total_cmp(&a.cpu_percent)
+            .then(b.memory_bytes.cmp(&a.memory_bytes))
+    });
+    processes.truncate(top_processes);
+
+    let disks = Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| DiskInfo {
+            mount_point: disk.mount_point().display().to_string(),
+            file_system: disk.file_system().to_string_lossy().into_owned(),
+            total_bytes: disk.total_space(),
+            available_bytes: disk.available_space(),
+            removable: disk.is_removable(),
+        })
+        .collect();
+
+    let mut network: Vec<NetworkInterface> = Networks::new_with_refreshed_list()
+        .iter()
+        .map(|(name, data)| NetworkInterface {
+            name: name.clone(),
+            mac_address: data.mac_address().to_string(),
+            addresses: data
+                .ip_networks()
+                .iter()
+                .map(|ip| format!("{}/{}", ip.addr, ip.prefix))
+                .collect(),
+            received_bytes: data.total_received(),
+            transmitted_bytes: data.total_transmitted(),
+        })
+        .collect();
+    network.sort_by(|a, b| a.name.cmp(&b.name));
+
+    SystemInfo {
+        os: OsInfo {
+            name: System::name(),
+            version: System::long_os_version(),
+            kernel: System::kernel_version(),
+            arch: System::cpu_arch(),
+            host_name: System::host_name(),
+            uptime_secs: System::uptime(),
+        },
+        cpu,
+        memory: MemoryInfo {
+            total_bytes: system.total_memory(),
+            used_bytes: system.used_memory(),
+            available_bytes: system.available_memory(),
+            swap_total_bytes: system.total_swap(),
+            swap_used_bytes: system.used_swap(),
+        },
+        disks,
+        battery: battery(),
+        network,
+        top_processes: processes,
+    }
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    vec![LocalTool {
+        name: "system_info",
+        description: "Get this device's OS version, CPU and memory usage, disk space, battery, network interfaces and the busiest processes, e.g. to troubleshoot a slow machine.",
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "top_processes": { "type": "integer", "description": "Number of busiest processes to include (default 5, max 20)" }
+            }
+        }),
+        consent: CONSENT_SCOPE,
+        requires_approval: true,
+    }]
+}
+
+/// Run the system information tool.
+pub async fn call(name: &str, arguments: Value) -> Result<Value, String> {
+    let arguments = if arguments.is_null() {
+        json!({})
+    } else {
+        arguments
+    };
+    match name {
+        "system_info" => {
+            let args: SystemInfoArgs =
+                serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let top = args
+                .top_processes
+                .unwrap_or(DEFAULT_TOP_PROCESSES)
+                .min(MAX_TOP_PROCESSES);
+            let info = tauri::async_runtime::spawn_blocking(move || collect(top))
+                .await
+                .map_err(|e| e.to_string())?;
+            serde_json::to_value(info).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pmset, BatteryInfo};
+
+    #[test]
+    fn parses_pmset_battery() {
+        let output = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t85%; charging; 1:02 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(output),
+            Some(BatteryInfo {
+                percent: 85,
+                status: "charging".to_string(),
+            })
+        );
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+    }
+}
@@ -0,0 +1,100 @@
+use base64::Engine;
+use hyper::Uri;
+use hyper_util::client::proxy::matcher::{Intercept, Matcher};
+
+use crate::upstream_proxy::{authority, ProxyKind, UpstreamProxy};
+
+/// The OS proxy configured for `host:port`, if any: the `HTTP(S)_PROXY`,
+/// `ALL_PROXY` and `NO_PROXY` environment variables, then the macOS
+/// SystemConfiguration or Windows Internet Settings. reqwest reads the same
+/// sources for HTTP requests; this covers the WebSocket connection.
+pub fn detect(host: &str, port: u16, secure: bool) -> Option<UpstreamProxy> {
+    let scheme = if secure { "https" } else { "http" };
+    let destination: Uri = format!("{}://{}", scheme, authority(host, port))
+        .parse()
+        .ok()?;
+    let intercept = Matcher::from_system().intercept(&destination)?;
+    let proxy = from_intercept(&intercept);
+    if proxy.is_none() {
+        log::warn!(
+            target: "ws-proxy",
+            "Ignoring unsupported system proxy {} for WebSocket connections",
+            intercept.uri()
+        );
+    }
+    proxy
+}
+
+fn from_intercept(intercept: &Intercept) -> Option<UpstreamProxy> {
+    let credentials = intercept
+        .raw_auth()
+        .map(|(username, password)| (username.to_string(), password.to_string()))
+        .or_else(|| {
+            intercept
+                .basic_auth()
+                .and_then(|value| value.to_str().ok())
+                .and_then(decode_basic)
+        });
+    from_uri(intercept.uri(), credentials)
+}
+
+/// `username:password` from a `Basic` authorization value.
+fn decode_basic(value: &str) -> Option<(String, String)> {
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Map a proxy URI onto the proxies the WebSocket connector can tunnel through.
+/// TLS connections to the proxy itself (`https://` proxies) are not supported.
+fn from_uri(uri: &Uri, credentials: Option<(String, String)>) -> Option<UpstreamProxy> {
+    let (kind, default_port) = match uri.scheme_str()? {
+        "http" => (ProxyKind::Http, 80),
+        "socks5" | "socks5h" => (ProxyKind::Socks5, 1080),
+        _ => return None,
+    };
+    let (username, password) = match credentials {
+        Some((username, password)) => (Some(username), Some(password).filter(|p| !p.is_empty())),
+        None => (None, None),
+    };
+    Some(UpstreamProxy {
+        kind,
+        host: uri.host()?.to_string(),
+        port: uri.port_u16().unwrap_or(default_port),
+        username,
+        password,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_basic, from_uri};
+    use crate::upstream_proxy::ProxyKind;
+
+    #[test]
+    fn maps_system_proxy_uris() {
+        let proxy = from_uri(&"http://proxy.corp:3128".parse().unwrap(), None).unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Http);
+        assert_eq!((proxy.host.as_str(), proxy.port), ("proxy.corp", 3128));
+
+        let socks = from_uri(
+            &"socks5h://127.0.0.1".parse().unwrap(),
+            Some(("alice".to_string(), String::new())),
+        )
+        .unwrap();
+        assert_eq!(socks.kind, ProxyKind::Socks5);
+        assert_eq!(socks.port, 1080);
+        assert_eq!(socks.username.as_deref(), Some("alice"));
+        assert_eq!(socks.password, None);
+
+        assert!(from_uri(&"https://proxy.corp".parse().unwrap(), None).is_none());
+        assert_eq!(
+            decode_basic("Basic YWxpY2U6c2VjcmV0"),
+            Some(("alice".to_string(), "secret".to_string()))
+        );
+    }
+}
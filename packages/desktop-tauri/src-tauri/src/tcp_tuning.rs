@@ -0,0 +1,74 @@
+use socket2::{SockRef, TcpKeepalive};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+use crate::AppSettings;
+
+/// Socket options applied to local listener connections and upstream connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Idle time before keepalive probes start, and the interval between them.
+    pub keepalive: Option<Duration>,
+    pub nodelay: bool,
+}
+
+impl TcpOptions {
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            keepalive: (settings.tcp_keepalive_secs > 0)
+                .then(|| Duration::from_secs(settings.tcp_keepalive_secs)),
+            nodelay: settings.tcp_nodelay,
+        }
+    }
+
+    /// Apply the options to a connected socket.
+    pub fn apply(&self, stream: &TcpStream) {
+        if let Err(e) = stream.set_nodelay(self.nodelay) {
+            log::warn!(target: "proxy", "Failed to set TCP_NODELAY: {}", e);
+        }
+        let socket = SockRef::from(stream);
+        let result = match self.keepalive {
+            Some(interval) => socket.set_tcp_keepalive(
+                &TcpKeepalive::new()
+                    .with_time(interval)
+                    .with_interval(interval),
+            ),
+            None => socket.set_keepalive(false),
+        };
+        if let Err(e) = result {
+            log::warn!(target: "proxy", "Failed to set TCP keepalive: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TcpOptions;
+    use socket2::SockRef;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn applies_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        TcpOptions {
+            keepalive: Some(Duration::from_secs(30)),
+            nodelay: true,
+        }
+        .apply(&stream);
+        assert!(stream.nodelay().unwrap());
+        assert!(SockRef::from(&stream).keepalive().unwrap());
+
+        TcpOptions {
+            keepalive: None,
+            nodelay: false,
+        }
+        .apply(&stream);
+        assert!(!stream.nodelay().unwrap());
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+    }
+}
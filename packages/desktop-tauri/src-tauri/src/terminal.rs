@@ -0,0 +1,490 @@
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::tools::LocalTool;
+use crate::AppState;
+
+/// Consent scope covering the terminal tools.
+pub const CONSENT_SCOPE: &str = "terminal";
+
+const MAX_SESSIONS: usize = 4;
+const DEFAULT_LIFETIME_SECS: u64 = 600;
+const MAX_LIFETIME_SECS: u64 = 3600;
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 100;
+const MAX_ROWS: u16 = 200;
+const MAX_COLS: u16 = 400;
+/// Output lines kept per session for `terminal_read`.
+const SCROLLBACK_LINES: usize = 2000;
+/// Output bytes kept per session, for output without newlines (e.g. `\r` progress bars).
+const SCROLLBACK_BYTES: usize = 256 * 1024;
+
+/// Terminal output, trimmed to the last `SCROLLBACK_LINES` lines and `SCROLLBACK_BYTES` bytes.
+///
+/// Offsets count bytes of decoded output since the session started, so readers can
+/// ask for everything after the point they last saw.
+#[derive(Default)]
+struct Scrollback {
+    text: String,
+    start: u64,
+    pending: Vec<u8>,
+}
+
+impl Scrollback {
+    /// Append raw PTY bytes, holding back an incomplete UTF-8 sequence. Returns the new text.
+    fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let rest = self.pending.split_off(valid);
+        let chunk = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+
+        self.text.push_str(&chunk);
+        let lines = self.text.matches('\n').count();
+        if lines > SCROLLBACK_LINES {
+            let cut = self
+                .text
+                .match_indices('\n')
+                .nth(lines - SCROLLBACK_LINES - 1)
+                .map(|(index, _)| index + 1)
+                .unwrap_or_default();
+            self.text.drain(..cut);
+            self.start += cut as u64;
+        }
+        if self.text.len() > SCROLLBACK_BYTES {
+            let mut cut = self.text.len() - SCROLLBACK_BYTES;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.start += cut as u64;
+        }
+        chunk
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.text.len() as u64
+    }
+
+    /// Output after `since`; `truncated` when part of it already left the scrollback.
+    fn read(&self, since: u64) -> (String, bool) {
+        if since < self.start {
+            return (self.text.clone(), true);
+        }
+        let offset = ((since - self.start) as usize).min(self.text.len());
+        match self.text.get(offset..) {
+            Some(text) => (text.to_string(), false),
+            None => (self.text.clone(), true),
+        }
+    }
+}
+
+struct Session {
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+    output: Mutex<Scrollback>,
+    exit_code: Mutex<Option<u32>>,
+}
+
+/// Payload of the `pty-output` event.
+#[derive(Debug, Clone, Serialize)]
+struct PtyOutput {
+    session_id: u64,
+    data: String,
+}
+
+/// Payload of the `pty-exit` event.
+#[derive(Debug, Clone, Serialize)]
+struct PtyExit {
+    session_id: u64,
+    exit_code: Option<u32>,
+    reason: &'static str,
+}
+
+/// Open PTY sessions started by the assistant.
+pub struct Terminals {
+    sessions: Mutex<HashMap<u64, Arc<Session>>>,
+    next_id: AtomicU64,
+}
+
+impl Terminals {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    fn get(&self, id: u64) -> Result<Arc<Session>, String> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("No terminal session {}", id))
+    }
+
+    /// Kill the session's process and forget it.
+    fn close(&self, app: &AppHandle, id: u64, reason: &'static str) -> bool {
+        let session = self
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        let Some(session) = session else {
+            return false;
+        };
+        let _ = session
+            .killer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .kill();
+        let exit_code = *session.exit_code.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = app.emit(
+            "pty-exit",
+            PtyExit {
+                session_id: id,
+                exit_code,
+                reason,
+            },
+        );
+        true
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenArgs {
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    rows: Option<u16>,
+    #[serde(default)]
+    cols: Option<u16>,
+    #[serde(default)]
+    lifetime_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SessionArgs {
+    session_id: u64,
+}
+
+#[derive(Deserialize)]
+struct WriteArgs {
+    session_id: u64,
+    input: String,
+}
+
+#[derive(Deserialize)]
+struct ReadArgs {
+    session_id: u64,
+    #[serde(default)]
+    since: Option<u64>,
+}
+
+fn command_builder(command: Option<&str>) -> CommandBuilder {
+    let Some(command) = command.map(str::trim).filter(|c| !c.is_empty()) else {
+        return CommandBuilder::new_default_prog();
+    };
+    if cfg!(windows) {
+        let mut builder = CommandBuilder::new("cmd.exe");
+        builder.args(["/C", command]);
+        builder
+    } else {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        let mut builder = CommandBuilder::new(shell);
+        builder.args(["-lc", command]);
+        builder
+    }
+}
+
+/// Forward PTY output to the scrollback and the `pty-output` event until the process exits.
+///
+/// The event reaches local windows only; relaying output to the backend is a planned
+/// follow-up (`.plans/desktop-terminal-relay/PLAN.md`).
+fn pump_output(
+    app: AppHandle,
+    id: u64,
+    session: Arc<Session>,
+    mut reader: Box<dyn Read + Send>,
+    mut child: Box<dyn portable_pty::Child + Send + Sync>,
+) {
+    let mut buf = [0u8; 8192];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let data = session
+                    .output
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(&buf[..n]);
+                if !data.is_empty() {
+                    let _ = app.emit(
+                        "pty-output",
+                        PtyOutput {
+                            session_id: id,
+                            data,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    let exit_code = child.wait().ok().map(|status| status.exit_code());
+    *session.exit_code.lock().unwrap_or_else(|e| e.into_inner()) = exit_code;
+    let _ = app.emit(
+        "pty-exit",
+        PtyExit {
+            session_id: id,
+            exit_code,
+            reason: "exited",
+        },
+    );
+}
+
+fn open(app: &AppHandle, terminals: &Terminals, args: OpenArgs) -> Result<Value, String> {
+    {
+        let sessions = terminals.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let running = sessions
+            .values()
+            .filter(|session| {
+                session
+                    .exit_code
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .is_none()
+            })
+            .count();
+        if running >= MAX_SESSIONS {
+            return Err(format!(
+                "At most {} terminal sessions can run at once",
+                MAX_SESSIONS
+            ));
+        }
+    }
+
+    let size = PtySize {
+        rows: args.rows.unwrap_or(DEFAULT_ROWS).clamp(1, MAX_ROWS),
+        cols: args.cols.unwrap_or(DEFAULT_COLS).clamp(1, MAX_COLS),
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+    let lifetime = args
+        .lifetime_secs
+        .unwrap_or(DEFAULT_LIFETIME_SECS)
+        .clamp(1, MAX_LIFETIME_SECS);
+
+    let pair = native_pty_system()
+        .openpty(size)
+        .map_err(|e| e.to_string())?;
+    let mut command = command_builder(args.command.as_deref());
+    if let Some(cwd) = args.cwd.filter(|cwd| !cwd.is_empty()) {
+        command.cwd(cwd);
+    }
+    let child = pair
+        .slave
+        .spawn_command(command)
+        .map_err(|e| e.to_string())?;
+    drop(pair.slave);
+    let reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+    let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+    let id = terminals.next_id.fetch_add(1, Ordering::Relaxed);
+    let session = Arc::new(Session {
+        master: Mutex::new(pair.master),
+        writer: Mutex::new(writer),
+        killer: Mutex::new(child.clone_killer()),
+        output: Mutex::new(Scrollback::default()),
+        exit_code: Mutex::new(None),
+    });
+    terminals
+        .sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(id, session.clone());
+
+    let pump_app = app.clone();
+    std::thread::spawn(move || pump_output(pump_app, id, session, reader, child));
+
+    // Enforce the session lifetime even if nobody closes it
+    let expire_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(lifetime)).await;
+        let state: State<'_, AppState> = expire_app.state();
+        if state.terminals.close(&expire_app, id, "expired") {
+            log::info!(target: "terminal", "Session {} reached its lifetime", id);
+        }
+    });
+
+    Ok(json!({ "session_id": id, "rows": size.rows, "cols": size.cols, "lifetime_secs": lifetime }))
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    let session_id = json!({ "type": "integer" });
+    vec![
+        LocalTool {
+            name: "terminal_open",
+            description: "Open a terminal session (an interactive shell, or a single command). Output streams as it is produced; read it with terminal_read.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "Command line to run; omit for an interactive shell" },
+                    "cwd": { "type": "string" },
+                    "rows": { "type": "integer" },
+                    "cols": { "type": "integer" },
+                    "lifetime_secs": { "type": "integer", "description": "Kill the session after this long (default 600, max 3600)" }
+                }
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: true,
+        },
+        LocalTool {
+            name: "terminal_write",
+            description: "Send input (keystrokes, use \\n for Enter) to a terminal session.",
+            parameters: json!({
+                "type": "object",
+                "properties": { "session_id": session_id, "input": { "type": "string" } },
+                "required": ["session_id", "input"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "terminal_read",
+            description: "Read terminal output after offset `since` (default: all retained output). Returns `next` for the following read.",
+            parameters: json!({
+                "type": "object",
+                "properties": { "session_id": session_id, "since": { "type": "integer" } },
+                "required": ["session_id"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "terminal_close",
+            description: "Kill a terminal session.",
+            parameters: json!({
+                "type": "object",
+                "properties": { "session_id": session_id },
+                "required": ["session_id"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+    ]
+}
+
+/// Run a terminal tool.
+pub fn call(
+    app: &AppHandle,
+    terminals: &Terminals,
+    name: &str,
+    arguments: Value,
+) -> Result<Value, String> {
+    let arguments = if arguments.is_null() {
+        json!({})
+    } else {
+        arguments
+    };
+    match name {
+        "terminal_open" => {
+            let args = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            open(app, terminals, args)
+        }
+        "terminal_write" => {
+            let args: WriteArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let session = terminals.get(args.session_id)?;
+            let mut writer = session.writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer
+                .write_all(args.input.as_bytes())
+                .and_then(|_| writer.flush())
+                .map_err(|e| e.to_string())?;
+            Ok(json!({}))
+        }
+        "terminal_read" => {
+            let args: ReadArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let session = terminals.get(args.session_id)?;
+            let output = session.output.lock().unwrap_or_else(|e| e.into_inner());
+            let (text, truncated) = output.read(args.since.unwrap_or(0));
+            let exit_code = *session.exit_code.lock().unwrap_or_else(|e| e.into_inner());
+            let rows = session
+                .master
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_size()
+                .map(|size| size.rows)
+                .unwrap_or(DEFAULT_ROWS);
+            Ok(json!({
+                "output": text,
+                "next": output.end(),
+                "truncated": truncated,
+                "rows": rows,
+                "exited": exit_code.is_some(),
+                "exit_code": exit_code,
+            }))
+        }
+        "terminal_close" => {
+            let args: SessionArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            if !terminals.close(app, args.session_id, "closed") {
+                return Err(format!("No terminal session {}", args.session_id));
+            }
+            Ok(json!({}))
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scrollback, SCROLLBACK_BYTES, SCROLLBACK_LINES};
+
+    #[test]
+    fn scrollback_keeps_recent_lines_and_offsets() {
+        let mut scrollback = Scrollback::default();
+        // "é" split across two reads is decoded once complete
+        assert_eq!(scrollback.push(b"caf\xc3"), "caf");
+        assert_eq!(scrollback.push(b"\xa9\n"), "é\n");
+        let mark = scrollback.end();
+        scrollback.push(b"next\n");
+        assert_eq!(scrollback.read(mark), ("next\n".to_string(), false));
+
+        for _ in 0..SCROLLBACK_LINES {
+            scrollback.push(b"line\n");
+        }
+        let (text, truncated) = scrollback.read(0);
+        assert!(truncated);
+        assert_eq!(text.lines().count(), SCROLLBACK_LINES);
+        assert_eq!(scrollback.read(scrollback.end()).0, "");
+    }
+
+    #[test]
+    fn scrollback_caps_output_without_newlines() {
+        let mut scrollback = Scrollback::default();
+        let progress = "é\r".repeat(1000);
+        let pushes = SCROLLBACK_BYTES / progress.len() + 2;
+        for _ in 0..pushes {
+            scrollback.push(progress.as_bytes());
+        }
+        let (text, truncated) = scrollback.read(0);
+        assert!(truncated);
+        assert!(text.len() <= SCROLLBACK_BYTES);
+        assert!(text.ends_with("é\r"));
+        assert_eq!(scrollback.end(), (pushes * progress.len()) as u64);
+    }
+}
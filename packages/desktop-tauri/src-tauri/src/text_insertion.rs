@@ -0,0 +1,77 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::{input, shortcuts, AppState};
+
+/// How long an insert-shortcut press authorizes a single `insert_text_into_focus` call.
+const INSERT_GESTURE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Tracks the user gesture that authorizes typing into another application.
+pub struct InsertGesture {
+    armed_at: Mutex<Option<Instant>>,
+}
+
+impl InsertGesture {
+    pub fn new() -> Self {
+        Self {
+            armed_at: Mutex::new(None),
+        }
+    }
+
+    fn arm(&self) {
+        *self.armed_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    /// Consume the pending gesture; true if it was armed recently enough.
+    fn take(&self) -> bool {
+        self.armed_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .is_some_and(|armed_at| armed_at.elapsed() <= INSERT_GESTURE_WINDOW)
+    }
+}
+
+/// Register the insert-text shortcut in place of `previous`. An empty shortcut disables it.
+pub fn register_shortcut(app: &AppHandle, previous: &str, shortcut: &str) -> Result<(), String> {
+    shortcuts::replace_shortcut(app, previous, shortcut, |app| {
+        let state: State<'_, AppState> = app.state();
+        state.insert_gesture.arm();
+        let _ = app.emit("insert-text-requested", ());
+    })
+}
+
+/// Type text into the focused application.
+///
+/// Only allowed right after the user pressed the insert-text shortcut, so page
+/// content cannot type into other applications on its own.
+#[tauri::command]
+pub async fn insert_text_into_focus(
+    text: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !state.insert_gesture.take() {
+        return Err("Text insertion requires pressing the insert-text shortcut first".to_string());
+    }
+    if text.contains('\0') {
+        return Err("Text must not contain NUL characters".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || input::type_text(&text))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InsertGesture;
+
+    #[test]
+    fn gesture_authorizes_a_single_insert() {
+        let gesture = InsertGesture::new();
+        assert!(!gesture.take());
+        gesture.arm();
+        assert!(gesture.take());
+        assert!(!gesture.take());
+    }
+}
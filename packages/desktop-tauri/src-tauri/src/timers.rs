@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::tools::LocalTool;
+use crate::{now_ms, AppState};
+
+/// Consent scope covering the timer tools.
+pub const CONSENT_SCOPE: &str = "timers";
+
+const MAX_DURATION_SECS: u64 = 7 * 24 * 60 * 60;
+/// Longest sleep between clock checks, so time spent suspended still counts.
+const MAX_SLEEP_SECS: u64 = 15;
+
+#[cfg(target_os = "macos")]
+const ALARM_SOUND: &str = "Glass";
+#[cfg(windows)]
+const ALARM_SOUND: &str = "Alarm";
+#[cfg(not(any(target_os = "macos", windows)))]
+const ALARM_SOUND: &str = "alarm-clock-elapsed";
+
+/// A running timer. Times are Unix milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Timer {
+    pub id: u64,
+    pub label: Option<String>,
+    pub duration_secs: u64,
+    pub started_at: u64,
+    pub ends_at: u64,
+    pub sound: bool,
+}
+
+/// Timers run in the Rust process, so they fire while the window is hidden in the tray.
+pub struct Timers {
+    next_id: AtomicU64,
+    running: Mutex<HashMap<u64, (Timer, JoinHandle<()>)>>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn list(&self) -> Vec<Timer> {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let mut timers: Vec<Timer> = running.values().map(|(timer, _)| timer.clone()).collect();
+        timers.sort_by_key(|timer| timer.ends_at);
+        timers
+    }
+
+    fn cancel(&self, id: u64) -> bool {
+        let removed = self
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        match removed {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Human-readable duration for notifications, e.g. `1 hour 5 minutes`.
+fn describe_duration(secs: u64) -> String {
+    let parts = [
+        (secs / 3600, "hour"),
+        (secs % 3600 / 60, "minute"),
+        (secs % 60, "second"),
+    ];
+    let text: Vec<String> = parts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| format!("{} {}{}", count, unit, if *count == 1 { "" } else { "s" }))
+        .collect();
+    text.join(" ")
+}
+
+fn finish(app: &AppHandle, timer: &Timer) {
+    let title = timer.label.clone().unwrap_or_else(|| "Timer".to_string());
+    let mut notification = app.notification().builder().title(title).body(format!(
+        "{} timer is done",
+        describe_duration(timer.duration_secs)
+    ));
+    if timer.sound {
+        notification = notification.sound(ALARM_SOUND);
+    }
+    if let Err(e) = notification.show() {
+        log::error!(target: "timers", "Failed to show notification: {}", e);
+    }
+    let _ = app.emit("timer-finished", timer);
+}
+
+fn start(
+    app: &AppHandle,
+    duration_secs: u64,
+    label: Option<String>,
+    sound: bool,
+) -> Result<Timer, String> {
+    if duration_secs == 0 || duration_secs > MAX_DURATION_SECS {
+        return Err(format!(
+            "Timer duration must be between 1 and {} seconds",
+            MAX_DURATION_SECS
+        ));
+    }
+    let state: State<'_, AppState> = app.state();
+    let started_at = now_ms();
+    let timer = Timer {
+        id: state.timers.next_id.fetch_add(1, Ordering::Relaxed),
+        label: label.filter(|label| !label.trim().is_empty()),
+        duration_secs,
+        started_at,
+        ends_at: started_at + duration_secs * 1000,
+        sound,
+    };
+
+    // Hold the lock until the timer is inserted so the task cannot finish first
+    let mut running = state
+        .timers
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let task_app = app.clone();
+    let task_timer = timer.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            let remaining = task_timer.ends_at.saturating_sub(now_ms());
+            if remaining == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(remaining.min(MAX_SLEEP_SECS * 1000))).await;
+        }
+        let state: State<'_, AppState> = task_app.state();
+        let removed = state
+            .timers
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&task_timer.id);
+        if removed.is_some() {
+            finish(&task_app, &task_timer);
+        }
+    });
+    running.insert(timer.id, (timer.clone(), handle));
+    Ok(timer)
+}
+
+#[derive(Deserialize)]
+struct StartArgs {
+    duration_secs: u64,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    sound: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct CancelArgs {
+    timer_id: u64,
+}
+
+pub fn tools() -> Vec<LocalTool> {
+    vec![
+        LocalTool {
+            name: "start_timer",
+            description: "Start a countdown timer that shows a notification (with sound by default) when it ends.",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "duration_secs": { "type": "integer" },
+                    "label": { "type": "string" },
+                    "sound": { "type": "boolean" }
+                },
+                "required": ["duration_secs"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "list_timers",
+            description: "List running timers.",
+            parameters: json!({ "type": "object", "properties": {} }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+        LocalTool {
+            name: "cancel_timer",
+            description: "Cancel a running timer.",
+            parameters: json!({
+                "type": "object",
+                "properties": { "timer_id": { "type": "integer" } },
+                "required": ["timer_id"]
+            }),
+            consent: CONSENT_SCOPE,
+            requires_approval: false,
+        },
+    ]
+}
+
+/// Run a timer tool.
+pub fn call(app: &AppHandle, name: &str, arguments: Value) -> Result<Value, String> {
+    let state: State<'_, AppState> = app.state();
+    match name {
+        "start_timer" => {
+            let args: StartArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            let timer = start(
+                app,
+                args.duration_secs,
+                args.label,
+                args.sound.unwrap_or(true),
+            )?;
+            serde_json::to_value(timer).map_err(|e| e.to_string())
+        }
+        "list_timers" => serde_json::to_value(state.timers.list()).map_err(|e| e.to_string()),
+        "cancel_timer" => {
+            let args: CancelArgs = serde_json::from_value(arguments).map_err(|e| e.to_string())?;
+            if !state.timers.cancel(args.timer_id) {
+                return Err(format!("No timer {}", args.timer_id));
+            }
+            Ok(json!({}))
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+/// Start a timer; a notification is shown (with a sound unless `sound` is false) when it ends.
+#[tauri::command]
+pub async fn start_timer(
+    app: AppHandle,
+    duration_secs: u64,
+    label: Option<String>,
+    sound: Option<bool>,
+) -> Result<Timer, String> {
+    start(&app, duration_secs, label, sound.unwrap_or(true))
+}
+
+/// List running timers, soonest first.
+#[tauri::command]
+pub async fn list_timers(state: State<'_, AppState>) -> Result<Vec<Timer>, String> {
+    Ok(state.timers.list())
+}
+
+/// Cancel a running timer. Returns whether it was running.
+#[tauri::command]
+pub async fn cancel_timer(id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.timers.cancel(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::describe_duration;
+
+    #[test]
+    fn describes_durations() {
+        assert_eq!(describe_duration(20 * 60), "20 minutes");
+        assert_eq!(
+            describe_duration(3600 + 5 * 60 + 1),
+            "1 hour 5 minutes 1 second"
+        );
+        assert_eq!(describe_duration(45), "45 seconds");
+    }
+}
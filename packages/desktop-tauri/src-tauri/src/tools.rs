@@ -0,0 +1,204 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+#[cfg(desktop)]
+use crate::terminal;
+use crate::{calendar, notes_search, scheduler, system_info, timers, AppState};
+
+/// A tool the assistant can run on this device through `call_local_tool`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalTool {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// JSON Schema of the tool arguments.
+    pub parameters: Value,
+    /// Consent scope the user must grant before the tool runs.
+    pub consent: &'static str,
+    /// Ask the user to approve each call, showing its arguments.
+    pub requires_approval: bool,
+}
+
+/// A local tool plus whether its consent scope has been granted.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalToolInfo {
+    #[serde(flatten)]
+    pub tool: LocalTool,
+    pub granted: bool,
+}
+
+fn all_tools() -> Vec<LocalTool> {
+    let mut tools = calendar::tools();
+    tools.extend(notes_search::tools());
+    tools.extend(system_info::tools());
+    tools.extend(scheduler::tools());
+    tools.extend(timers::tools());
+    #[cfg(desktop)]
+    tools.extend(terminal::tools());
+    tools
+}
+
+/// What granting a consent scope allows, shown in the native consent dialog.
+fn consent_prompt(scope: &str) -> Option<&'static str> {
+    match scope {
+        calendar::CONSENT_SCOPE => {
+            Some("Allow the assistant to read your calendar and create events and reminders on it?")
+        }
+        notes_search::CONSENT_SCOPE => {
+            Some("Allow the assistant to search the notes and documents in your indexed folders?")
+        }
+        scheduler::CONSENT_SCOPE => Some(
+            "Allow the assistant to schedule notifications, prompts and tool runs for later? Scheduled tool runs still need your approval.",
+        ),
+        timers::CONSENT_SCOPE => Some("Allow the assistant to start and cancel timers?"),
+        system_info::CONSENT_SCOPE => Some(
+            "Allow the assistant to read system information such as OS version, resource usage, disks, battery, network interfaces and running processes?",
+        ),
+        #[cfg(desktop)]
+        terminal::CONSENT_SCOPE => Some(
+            "Allow the assistant to open terminal sessions on this device? Each new session still needs your approval.",
+        ),
+        _ => None,
+    }
+}
+
+/// Ask the user in a native dialog, which page content cannot answer on their behalf.
+pub async fn confirm(
+    app: &AppHandle,
+    title: &str,
+    message: &str,
+    ok: &str,
+) -> Result<bool, String> {
+    let builder = app
+        .dialog()
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            ok.to_string(),
+            "Cancel".to_string(),
+        ));
+    tauri::async_runtime::spawn_blocking(move || builder.blocking_show())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List the local tools with their consent state.
+#[tauri::command]
+pub async fn list_local_tools(state: State<'_, AppState>) -> Result<Vec<LocalToolInfo>, String> {
+    let settings = state.settings.lock().await;
+    Ok(all_tools()
+        .into_iter()
+        .map(|tool| LocalToolInfo {
+            granted: settings
+                .tool_consents
+                .iter()
+                .any(|scope| scope == tool.consent),
+            tool,
+        })
+        .collect())
+}
+
+/// Grant or revoke a consent scope. Granting asks the user in a native dialog.
+#[tauri::command]
+pub async fn set_tool_consent(
+    app: AppHandle,
+    scope: String,
+    granted: bool,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let prompt =
+        consent_prompt(&scope).ok_or_else(|| format!("Unknown consent scope: {}", scope))?;
+    if granted && !confirm(&app, "Assistant Tools", prompt, "Allow").await? {
+        return Ok(false);
+    }
+    {
+        let mut settings = state.settings.lock().await;
+        settings.tool_consents.retain(|existing| existing != &scope);
+        if granted {
+            settings.tool_consents.push(scope);
+        }
+    }
+    state.save().await?;
+    Ok(granted)
+}
+
+/// Look up a local tool by name.
+pub fn find_tool(name: &str) -> Result<LocalTool, String> {
+    all_tools()
+        .into_iter()
+        .find(|tool| tool.name == name)
+        .ok_or_else(|| format!("Unknown tool: {}", name))
+}
+
+/// The tool call as shown in approval dialogs.
+pub fn describe_call(tool: &LocalTool, arguments: &Value) -> String {
+    format!(
+        "{}:\n\n{}",
+        tool.name,
+        serde_json::to_string_pretty(arguments).unwrap_or_default()
+    )
+}
+
+async fn check_consent(state: &AppState, tool: &LocalTool) -> Result<(), String> {
+    let settings = state.settings.lock().await;
+    if !settings
+        .tool_consents
+        .iter()
+        .any(|scope| scope == tool.consent)
+    {
+        return Err(format!(
+            "The user has not allowed {} access for the assistant",
+            tool.consent
+        ));
+    }
+    Ok(())
+}
+
+/// Run a tool whose call needs no approval, or was already approved by the user.
+///
+/// The tool's consent scope is still checked, so revoking consent stops scheduled calls too.
+pub async fn run_approved(
+    app: &AppHandle,
+    tool: &LocalTool,
+    arguments: Value,
+) -> Result<Value, String> {
+    let state: State<'_, AppState> = app.state();
+    check_consent(&state, tool).await?;
+
+    match tool.consent {
+        calendar::CONSENT_SCOPE => calendar::call(&state, tool.name, arguments).await,
+        notes_search::CONSENT_SCOPE => notes_search::call(&state.notes_index, tool.name, arguments),
+        system_info::CONSENT_SCOPE => system_info::call(tool.name, arguments).await,
+        scheduler::CONSENT_SCOPE => scheduler::call(app, tool.name, arguments).await,
+        timers::CONSENT_SCOPE => timers::call(app, tool.name, arguments),
+        #[cfg(desktop)]
+        terminal::CONSENT_SCOPE => terminal::call(app, &state.terminals, tool.name, arguments),
+        _ => Err(format!("Unknown tool: {}", tool.name)),
+    }
+}
+
+/// Run a local tool on behalf of the assistant and return its JSON result.
+#[tauri::command]
+pub async fn call_local_tool(
+    app: AppHandle,
+    name: String,
+    arguments: Option<Value>,
+    state: State<'_, AppState>,
+) -> Result<Value, String> {
+    let tool = find_tool(&name)?;
+    check_consent(&state, &tool).await?;
+
+    let arguments = arguments.unwrap_or(Value::Null);
+    if tool.requires_approval {
+        let message = format!(
+            "The assistant wants to run {}",
+            describe_call(&tool, &arguments)
+        );
+        if !confirm(&app, "Assistant Tools", &message, "Run").await? {
+            return Err(format!("The user declined to run {}", tool.name));
+        }
+    }
+    run_approved(&app, &tool, arguments).await
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    transferred: u64,
+    total: Option<u64>,
+}
+
+/// Aggregate progress shown on the taskbar/dock icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AggregateProgress {
+    Idle,
+    Indeterminate,
+    Percent(u64),
+}
+
+fn aggregate_progress<'a>(transfers: impl Iterator<Item = &'a Transfer>) -> AggregateProgress {
+    let mut any = false;
+    let mut transferred = 0u64;
+    let mut total = 0u64;
+    for transfer in transfers {
+        any = true;
+        let Some(transfer_total) = transfer.total else {
+            return AggregateProgress::Indeterminate;
+        };
+        transferred += transfer.transferred.min(transfer_total);
+        total += transfer_total;
+    }
+    if !any {
+        return AggregateProgress::Idle;
+    }
+    match (transferred * 100).checked_div(total) {
+        Some(percent) => AggregateProgress::Percent(percent),
+        None => AggregateProgress::Indeterminate,
+    }
+}
+
+/// Active uploads/downloads, mirrored onto the platform taskbar/dock progress bar.
+pub struct TransferTracker {
+    app: AppHandle,
+    next_id: AtomicU64,
+    transfers: Mutex<HashMap<u64, Transfer>>,
+    shown: Mutex<AggregateProgress>,
+}
+
+impl TransferTracker {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            next_id: AtomicU64::new(1),
+            transfers: Mutex::new(HashMap::new()),
+            shown: Mutex::new(AggregateProgress::Idle),
+        }
+    }
+
+    /// Register a transfer of `total` bytes (if known) and return its id.
+    pub fn start(&self, total: Option<u64>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.transfers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                id,
+                Transfer {
+                    transferred: 0,
+                    total,
+                },
+            );
+        self.refresh();
+        id
+    }
+
+    pub fn update(&self, id: u64, transferred: u64) {
+        if let Some(transfer) = self
+            .transfers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&id)
+        {
+            transfer.transferred = transferred;
+        }
+        self.refresh();
+    }
+
+    /// Remove a completed or failed transfer; the progress bar clears once none remain.
+    pub fn finish(&self, id: u64) {
+        self.transfers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        self.refresh();
+    }
+
+    fn refresh(&self) {
+        let progress = {
+            let transfers = self.transfers.lock().unwrap_or_else(|e| e.into_inner());
+            aggregate_progress(transfers.values())
+        };
+        {
+            let mut shown = self.shown.lock().unwrap_or_else(|e| e.into_inner());
+            if *shown == progress {
+                return;
+            }
+            *shown = progress;
+        }
+        self.show(progress);
+    }
+
+    #[cfg(desktop)]
+    fn show(&self, progress: AggregateProgress) {
+        use tauri::window::{ProgressBarState, ProgressBarStatus};
+        use tauri::Manager;
+
+        let Some(window) = self.app.get_webview_window("main") else {
+            return;
+        };
+        let state = match progress {
+            AggregateProgress::Idle => ProgressBarState {
+                status: Some(ProgressBarStatus::None),
+                progress: None,
+            },
+            AggregateProgress::Indeterminate => ProgressBarState {
+                status: Some(ProgressBarStatus::Indeterminate),
+                progress: None,
+            },
+            AggregateProgress::Percent(percent) => ProgressBarState {
+                status: Some(ProgressBarStatus::Normal),
+                progress: Some(percent),
+            },
+        };
+        if let Err(e) = window.set_progress_bar(state) {
+            log::error!(target: "transfers", "Failed to update progress bar: {}", e);
+        }
+    }
+
+    #[cfg(not(desktop))]
+    fn show(&self, _progress: AggregateProgress) {
+        let _ = &self.app;
+    }
+}
+
+/// Register a transfer managed by the web client; returns its id.
+#[tauri::command]
+pub async fn begin_transfer(total: Option<u64>, state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(state.transfers.start(total))
+}
+
+/// Report bytes transferred so far for a web client transfer.
+#[tauri::command]
+pub async fn update_transfer(
+    id: u64,
+    transferred: u64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.transfers.update(id, transferred);
+    Ok(())
+}
+
+/// Mark a web client transfer as finished (completed or failed).
+#[tauri::command]
+pub async fn end_transfer(id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    state.transfers.finish(id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aggregate_progress, AggregateProgress, Transfer};
+
+    #[test]
+    fn aggregates_progress_across_transfers() {
+        let transfers = [
+            Transfer {
+                transferred: 50,
+                total: Some(100),
+            },
+            Transfer {
+                transferred: 250,
+                total: Some(300),
+            },
+        ];
+        assert_eq!(
+            aggregate_progress(transfers.iter()),
+            AggregateProgress::Percent(75)
+        );
+        assert_eq!(aggregate_progress([].iter()), AggregateProgress::Idle);
+        assert_eq!(
+            aggregate_progress(
+                [Transfer {
+                    transferred: 10,
+                    total: None
+                }]
+                .iter()
+            ),
+            AggregateProgress::Indeterminate
+        );
+    }
+}
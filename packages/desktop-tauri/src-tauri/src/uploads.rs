@@ -0,0 +1,276 @@
+use base64::Engine;
+use futures_util::StreamExt;
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Incoming};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::backend_host::BackendHost;
+use crate::{body_limit, mime, AppState, BoxError};
+
+/// Files larger than this are not uploaded (uploads are sent as base64 JSON).
+pub const MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+const UPLOAD_TIMEOUT_SECS: u64 = 120;
+/// Largest chunk handed to the connection at once by a throttled upload.
+const MAX_UPLOAD_CHUNK_BYTES: u64 = 64 * 1024;
+/// Slowest connection a streamed proxy request body is given time to upload over.
+const MIN_STREAMED_UPLOAD_BYTES_PER_SEC: u64 = 64 * 1024;
+/// Upload time allowed for a streamed request body of unknown length.
+const UNKNOWN_LENGTH_UPLOAD_SECS: u64 = 60 * 60;
+/// Artifacts plugin operation used to store uploaded files.
+const ARTIFACTS_UPLOAD_PATH: &str = "/api/plugins/artifacts/operations/upload";
+
+/// Chunk size for a throttled upload: roughly a tenth of a second of data.
+fn chunk_size(bytes_per_sec: u64) -> u64 {
+    if bytes_per_sec == 0 {
+        MAX_UPLOAD_CHUNK_BYTES
+    } else {
+        (bytes_per_sec / 10).clamp(1024, MAX_UPLOAD_CHUNK_BYTES)
+    }
+}
+
+/// How long after the start of an upload the byte at `offset` may be sent.
+fn send_delay(offset: u64, bytes_per_sec: u64) -> Duration {
+    if bytes_per_sec == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_secs_f64(offset as f64 / bytes_per_sec as f64)
+    }
+}
+
+/// Request timeout extended by the time a throttled body of `len` bytes takes to send.
+pub fn throttled_timeout(base: Duration, len: u64, bytes_per_sec: u64) -> Duration {
+    base + send_delay(len, bytes_per_sec)
+}
+
+/// Request timeout for a streamed body of `len` bytes (`None` if unknown), sent at
+/// `bytes_per_sec` (0 for unlimited) or the slowest expected connection.
+pub fn streamed_timeout(base: Duration, len: Option<u64>, bytes_per_sec: u64) -> Duration {
+    let rate = match bytes_per_sec {
+        0 => MIN_STREAMED_UPLOAD_BYTES_PER_SEC,
+        limit => limit.min(MIN_STREAMED_UPLOAD_BYTES_PER_SEC),
+    };
+    match len {
+        Some(len) => throttled_timeout(base, len, rate),
+        None => base + Duration::from_secs(UNKNOWN_LENGTH_UPLOAD_SECS),
+    }
+}
+
+/// Stream `data` as a request body paced to `bytes_per_sec` (0 for unlimited),
+/// reporting the number of bytes handed to the connection.
+pub fn throttled_body(
+    data: Bytes,
+    bytes_per_sec: u64,
+    on_progress: impl Fn(u64) + Send + Sync + 'static,
+) -> reqwest::Body {
+    let start = tokio::time::Instant::now();
+    let chunk = chunk_size(bytes_per_sec);
+    let on_progress = Arc::new(on_progress);
+    let stream = futures_util::stream::unfold(0u64, move |offset| {
+        let data = data.clone();
+        let on_progress = on_progress.clone();
+        async move {
+            let len = data.len() as u64;
+            if offset >= len {
+                return None;
+            }
+            tokio::time::sleep_until(start + send_delay(offset, bytes_per_sec)).await;
+            let end = (offset + chunk).min(len);
+            on_progress(end);
+            Some((
+                Ok::<_, std::io::Error>(data.slice(offset as usize..end as usize)),
+                end,
+            ))
+        }
+    });
+    reqwest::Body::wrap_stream(stream)
+}
+
+/// Pipe an incoming request body upstream as it arrives, paced to `bytes_per_sec`
+/// (0 for unlimited), reporting the size of each chunk sent. The upload is aborted
+/// and `exceeded` set once more than `max_bytes` (0 for unlimited) have arrived.
+pub fn streamed_body(
+    body: Incoming,
+    bytes_per_sec: u64,
+    max_bytes: u64,
+    exceeded: Arc<AtomicBool>,
+    on_chunk: impl Fn(u64) + Send + Sync + 'static,
+) -> reqwest::Body {
+    let start = tokio::time::Instant::now();
+    let on_chunk = Arc::new(on_chunk);
+    let stream = futures_util::stream::unfold(
+        (body.into_data_stream(), 0u64),
+        move |(mut data, offset)| {
+            let on_chunk = on_chunk.clone();
+            let exceeded = exceeded.clone();
+            async move {
+                let chunk = match data.next().await? {
+                    Ok(chunk) => chunk,
+                    Err(e) => return Some((Err(BoxError::from(e)), (data, offset))),
+                };
+                if body_limit::exceeds(max_bytes, offset + chunk.len() as u64) {
+                    exceeded.store(true, Ordering::Relaxed);
+                    return Some((Err("request body too large".into()), (data, offset)));
+                }
+                tokio::time::sleep_until(start + send_delay(offset, bytes_per_sec)).await;
+                on_chunk(chunk.len() as u64);
+                let offset = offset + chunk.len() as u64;
+                Some((Ok(chunk), (data, offset)))
+            }
+        },
+    );
+    reqwest::Body::wrap_stream(stream)
+}
+
+/// Upload a local file to the backend's artifacts plugin, tracking its progress.
+///
+/// `bytes_per_sec` overrides the configured upload limit (0 for unlimited).
+pub async fn upload_artifact(
+    app: &AppHandle,
+    path: &Path,
+    instance_id: Option<&str>,
+    bytes_per_sec: Option<u64>,
+) -> Result<(), String> {
+    let state: State<'_, AppState> = app.state();
+    let (builder, backend) = {
+        let settings = state.settings.lock().await;
+        (
+            crate::app_client_builder(&settings, &state),
+            BackendHost::from_settings(&settings),
+        )
+    };
+    let bytes_per_sec = bytes_per_sec.unwrap_or_else(|| state.upload_limit.load(Ordering::Relaxed));
+
+    let size = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| e.to_string())?
+        .len();
+    if size > MAX_UPLOAD_BYTES {
+        return Err(format!("File is larger than {} bytes", MAX_UPLOAD_BYTES));
+    }
+    let content = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("upload")
+        .to_string();
+    let mut body = serde_json::json!({
+        "title": file_name,
+        "filename": file_name,
+        "content": base64::engine::general_purpose::STANDARD.encode(content),
+        "mimeType": mime::mime_type_for_path(path),
+    });
+    if let Some(instance_id) = instance_id {
+        body["instance_id"] = serde_json::Value::String(instance_id.to_string());
+    }
+    let body = Bytes::from(serde_json::to_vec(&body).map_err(|e| e.to_string())?);
+
+    let client = builder
+        .timeout(throttled_timeout(
+            Duration::from_secs(UPLOAD_TIMEOUT_SECS),
+            body.len() as u64,
+            bytes_per_sec,
+        ))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let transfer_id = state.transfers.start(Some(body.len() as u64));
+    let progress_app = app.clone();
+    let body = throttled_body(body, bytes_per_sec, move |sent| {
+        progress_app
+            .state::<AppState>()
+            .transfers
+            .update(transfer_id, sent);
+    });
+    let mut request = client
+        .post(format!(
+            "{}{}",
+            backend.url.trim_end_matches('/'),
+            ARTIFACTS_UPLOAD_PATH
+        ))
+        .header("content-type", "application/json")
+        .body(body);
+    if let Some(host) = &backend.host_header {
+        request = request.header("host", host);
+    }
+    if let Some(authorization) = state.auth.authorization_header() {
+        request = request.header("authorization", authorization);
+    }
+
+    let result = request.send().await.map_err(|e| e.to_string());
+    state.transfers.finish(transfer_id);
+    let resp = result?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Upload failed with status {}", resp.status()))
+    }
+}
+
+/// Let the user pick a local file in a native dialog and upload it to the artifacts
+/// plugin, optionally overriding the upload rate limit. Page content never names the
+/// path, so only files the user chose are sent. Returns the file name, or `None` if
+/// the picker was cancelled.
+#[tauri::command]
+pub async fn upload_file_to_artifacts(
+    app: AppHandle,
+    instance_id: Option<String>,
+    upload_limit_bytes_per_sec: Option<u64>,
+) -> Result<Option<String>, String> {
+    let picker = app.dialog().file().set_title("Choose a file to upload");
+    let picked = tauri::async_runtime::spawn_blocking(move || picker.blocking_pick_file())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    upload_artifact(
+        &app,
+        &path,
+        instance_id.as_deref().filter(|id| !id.trim().is_empty()),
+        upload_limit_bytes_per_sec,
+    )
+    .await?;
+    Ok(Some(
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_size, send_delay, streamed_timeout, MAX_UPLOAD_CHUNK_BYTES};
+    use std::time::Duration;
+
+    #[test]
+    fn paces_chunks_to_the_rate_limit() {
+        assert_eq!(chunk_size(0), MAX_UPLOAD_CHUNK_BYTES);
+        assert_eq!(chunk_size(100 * 1024), 10 * 1024);
+        assert_eq!(chunk_size(100), 1024);
+        assert_eq!(send_delay(1_000_000, 0), Duration::ZERO);
+        assert_eq!(send_delay(500_000, 1_000_000), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn allows_time_for_streamed_uploads() {
+        let base = Duration::from_secs(30);
+        assert_eq!(
+            streamed_timeout(base, Some(64 * 1024 * 10), 0),
+            Duration::from_secs(40)
+        );
+        assert_eq!(
+            streamed_timeout(base, Some(32 * 1024 * 10), 32 * 1024),
+            Duration::from_secs(40)
+        );
+        assert_eq!(
+            streamed_timeout(base, None, 0),
+            Duration::from_secs(30 + 60 * 60)
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::dns_cache::DnsCache;
+use crate::happy_eyeballs;
+
+/// Largest CONNECT response head accepted from the proxy.
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyKind {
+    /// HTTP proxy; HTTPS backends and WebSockets are tunneled with `CONNECT`.
+    #[default]
+    Http,
+    /// SOCKS5 proxy (e.g. `ssh -D` or Tor). Host names are resolved by the proxy.
+    Socks5,
+}
+
+/// Proxy that backend traffic is sent through, for networks without direct
+/// access.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamProxy {
+    #[serde(default)]
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    /// Credentials: `Proxy-Authorization: Basic` for HTTP proxies,
+    /// username/password authentication for SOCKS5.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// `host:port` with IPv6 addresses in brackets.
+pub fn authority(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+impl UpstreamProxy {
+    /// Check the proxy before it is saved.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.is_empty()
+            || self
+                .host
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '/' | '?' | '#' | '@'))
+        {
+            return Err(format!("Invalid upstream proxy host: {}", self.host));
+        }
+        if self.port == 0 {
+            return Err("Upstream proxy port must be between 1 and 65535".to_string());
+        }
+        if self.password.is_some() && self.username.is_none() {
+            return Err("Upstream proxy password needs a username".to_string());
+        }
+        if self.kind == ProxyKind::Socks5
+            && [&self.username, &self.password]
+                .into_iter()
+                .flatten()
+                .any(|credential| credential.is_empty() || credential.len() > 255)
+        {
+            return Err("SOCKS5 credentials must be 1 to 255 bytes long".to_string());
+        }
+        Ok(())
+    }
+
+    fn authorization(&self) -> Option<String> {
+        let username = self.username.as_deref()?;
+        let credentials = format!("{}:{}", username, self.password.as_deref().unwrap_or(""));
+        Some(format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        ))
+    }
+
+    /// Proxy URL in the form reqwest expects, credentials included.
+    pub fn url(&self) -> Result<reqwest::Url, String> {
+        let scheme = match self.kind {
+            ProxyKind::Http => "http",
+            ProxyKind::Socks5 => "socks5h",
+        };
+        let url = format!("{}://{}", scheme, authority(&self.host, self.port));
+        let mut parsed = reqwest::Url::parse(&url)
+            .map_err(|e| format!("Invalid upstream proxy {}: {}", url, e))?;
+        if let Some(username) = &self.username {
+            let _ = parsed.set_username(username);
+            let _ = parsed.set_password(self.password.as_deref());
+        }
+        Ok(parsed)
+    }
+
+    /// The proxy for the reqwest client.
+    pub fn client_proxy(&self) -> Result<reqwest::Proxy, String> {
+        let url = self.url()?;
+        reqwest::Proxy::all(url.clone())
+            .map_err(|e| format!("Invalid upstream proxy {}: {}", url, e))
+    }
+
+    /// Open a tunnel to `host:port` through the proxy.
+    pub async fn connect(
+        &self,
+        dns_cache: &DnsCache,
+        host: &str,
+        port: u16,
+    ) -> Result<TcpStream, String> {
+        let addrs = dns_cache
+            .lookup(
+                self.host.trim_start_matches('[').trim_end_matches(']'),
+                self.port,
+            )
+            .await
+            .map_err(|e| format!("Failed to resolve upstream proxy {}: {}", self.host, e))?;
+        let mut stream = happy_eyeballs::connect(&addrs)
+            .await
+            .map_err(|e| format!("Failed to connect to upstream proxy: {}", e))?;
+        match self.kind {
+            ProxyKind::Http => self.http_connect(&mut stream, host, port).await?,
+            ProxyKind::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let target = authority(host, port);
+        stream
+            .write_all(connect_request(&target, self.authorization().as_deref()).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send CONNECT to upstream proxy: {}", e))?;
+
+        // Read byte by byte so nothing past the response head is consumed
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_RESPONSE_HEAD {
+                return Err("Upstream proxy sent an oversized CONNECT response".to_string());
+            }
+            let byte = stream
+                .read_u8()
+                .await
+                .map_err(|e| format!("Upstream proxy closed the CONNECT tunnel: {}", e))?;
+            head.push(byte);
+        }
+        check_connect_response(&head)
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let io_error = |e: std::io::Error| format!("SOCKS5 handshake failed: {}", e);
+        let methods: &[u8] = if self.username.is_some() {
+            &[SOCKS5_NO_AUTH, SOCKS5_PASSWORD_AUTH]
+        } else {
+            &[SOCKS5_NO_AUTH]
+        };
+        let mut greeting = vec![SOCKS5_VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await.map_err(io_error)?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await.map_err(io_error)?;
+        match choice {
+            [SOCKS5_VERSION, SOCKS5_NO_AUTH] => {}
+            [SOCKS5_VERSION, SOCKS5_PASSWORD_AUTH] => {
+                let username = self.username.as_deref().unwrap_or("");
+                let password = self.password.as_deref().unwrap_or("");
+                let mut auth = vec![1, username.len() as u8];
+                auth.extend_from_slice(username.as_bytes());
+                auth.push(password.len() as u8);
+                auth.extend_from_slice(password.as_bytes());
+                stream.write_all(&auth).await.map_err(io_error)?;
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await.map_err(io_error)?;
+                if status[1] != 0 {
+                    return Err("SOCKS5 proxy rejected the credentials".to_string());
+                }
+            }
+            _ => {
+                return Err(
+                    "SOCKS5 proxy accepts none of the offered authentication methods".to_string(),
+                )
+            }
+        }
+
+        stream
+            .write_all(&socks5_request(host, port)?)
+            .await
+            .map_err(io_error)?;
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.map_err(io_error)?;
+        if reply[1] != 0 {
+            return Err(format!(
+                "SOCKS5 proxy refused the connection: {}",
+                socks5_error(reply[1])
+            ));
+        }
+        // Skip the bound address and port
+        let address_len = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => stream.read_u8().await.map_err(io_error)? as usize,
+            other => {
+                return Err(format!(
+                    "SOCKS5 proxy sent an unknown address type {}",
+                    other
+                ))
+            }
+        };
+        let mut bound = vec![0u8; address_len + 2];
+        stream.read_exact(&mut bound).await.map_err(io_error)?;
+        Ok(())
+    }
+}
+
+const SOCKS5_VERSION: u8 = 5;
+const SOCKS5_NO_AUTH: u8 = 0;
+const SOCKS5_PASSWORD_AUTH: u8 = 2;
+
+/// SOCKS5 `CONNECT` request. Host names are passed through for the proxy to
+/// resolve, so lookups happen on the far side (as with `ssh -D` or Tor).
+fn socks5_request(host: &str, port: u16) -> Result<Vec<u8>, String> {
+    let mut request = vec![SOCKS5_VERSION, 1, 0];
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let len = u8::try_from(host.len())
+                .map_err(|_| format!("Host name too long for SOCKS5: {}", host))?;
+            request.push(3);
+            request.push(len);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn connect_request(target: &str, authorization: Option<&str>) -> String {
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// Accept any 2xx answer to `CONNECT`.
+fn check_connect_response(head: &[u8]) -> Result<(), String> {
+    let head = String::from_utf8_lossy(head);
+    let status_line = head.lines().next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts.next().and_then(|code| code.parse::<u16>().ok());
+    match status {
+        Some(200..=299) if version.starts_with("HTTP/") => Ok(()),
+        Some(407) => Err("Upstream proxy requires authentication (407)".to_string()),
+        _ => Err(format!(
+            "Upstream proxy refused the tunnel: {}",
+            status_line
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        authority, check_connect_response, connect_request, socks5_request, ProxyKind,
+        UpstreamProxy,
+    };
+
+    #[test]
+    fn tunnels_with_connect() {
+        let proxy = UpstreamProxy {
+            kind: ProxyKind::Http,
+            host: "proxy.corp".to_string(),
+            port: 3128,
+            username: Some("alice".to_string()),
+            password: Some("secret".to_string()),
+        };
+        assert!(proxy.validate().is_ok());
+        assert_eq!(
+            connect_request("assistant:443", proxy.authorization().as_deref()),
+            "CONNECT assistant:443 HTTP/1.1\r\nHost: assistant:443\r\n\
+             Proxy-Authorization: Basic YWxpY2U6c2VjcmV0\r\n\r\n"
+        );
+        assert_eq!(authority("::1", 8080), "[::1]:8080");
+
+        assert!(check_connect_response(b"HTTP/1.1 200 Connection established\r\n\r\n").is_ok());
+        assert!(
+            check_connect_response(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .unwrap_err()
+                .contains("authentication")
+        );
+        assert!(check_connect_response(b"garbage\r\n\r\n").is_err());
+
+        let no_user = UpstreamProxy {
+            username: None,
+            ..proxy
+        };
+        assert!(no_user.validate().is_err());
+    }
+
+    #[test]
+    fn encodes_socks5_connect_requests() {
+        assert_eq!(
+            socks5_request("10.0.0.5", 443).unwrap(),
+            [5, 1, 0, 1, 10, 0, 0, 5, 1, 187]
+        );
+        assert_eq!(
+            socks5_request("ab.onion", 80).unwrap(),
+            [&[5, 1, 0, 3, 8][..], b"ab.onion", &[0, 80]].concat()
+        );
+        assert_eq!(socks5_request("[::1]", 80).unwrap()[3], 4);
+
+        let proxy = UpstreamProxy {
+            kind: ProxyKind::Socks5,
+            host: "127.0.0.1".to_string(),
+            port: 1080,
+            username: Some(String::new()),
+            password: None,
+        };
+        assert!(proxy.validate().is_err());
+    }
+}
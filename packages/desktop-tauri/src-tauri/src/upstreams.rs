@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+/// An extra upstream that serves requests whose path starts with `path_prefix`
+/// instead of the main backend (e.g. `/files` → an object store).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Upstream {
+    pub path_prefix: String,
+    /// Base URL the request path is appended to.
+    pub url: String,
+    /// Attach the managed backend access token to requests for this upstream.
+    #[serde(default)]
+    pub send_auth: bool,
+}
+
+/// Check an upstream table before it is saved.
+pub fn validate(upstreams: &[Upstream]) -> Result<(), String> {
+    for upstream in upstreams {
+        if !upstream.path_prefix.starts_with('/') {
+            return Err(format!(
+                "Upstream path prefix must start with '/': {}",
+                upstream.path_prefix
+            ));
+        }
+        let url = reqwest::Url::parse(&upstream.url)
+            .map_err(|e| format!("Invalid upstream URL {}: {}", upstream.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "Upstream URL must use http or https: {}",
+                upstream.url
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The upstream with the longest prefix matching `path`, or `None` for the main
+/// backend.
+pub fn route<'a>(upstreams: &'a [Upstream], path: &str) -> Option<&'a Upstream> {
+    upstreams
+        .iter()
+        .filter(|upstream| path.starts_with(&upstream.path_prefix))
+        .max_by_key(|upstream| upstream.path_prefix.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{route, validate, Upstream};
+
+    fn upstream(path_prefix: &str, url: &str) -> Upstream {
+        Upstream {
+            path_prefix: path_prefix.to_string(),
+            url: url.to_string(),
+            send_auth: false,
+        }
+    }
+
+    #[test]
+    fn routes_by_longest_prefix() {
+        let upstreams = vec![
+            upstream("/files", "https://store.internal/bucket"),
+            upstream("/files/thumbs", "https://thumbs.internal"),
+            upstream("/tts", "http://127.0.0.1:5002"),
+        ];
+        assert!(validate(&upstreams).is_ok());
+        assert_eq!(
+            route(&upstreams, "/files/a.png").map(|u| u.url.as_str()),
+            Some("https://store.internal/bucket")
+        );
+        assert_eq!(
+            route(&upstreams, "/files/thumbs/a.png").map(|u| u.url.as_str()),
+            Some("https://thumbs.internal")
+        );
+        assert!(route(&upstreams, "/api/chat").is_none());
+
+        assert!(validate(&[upstream("files", "https://x")]).is_err());
+        assert!(validate(&[upstream("/files", "ftp://x")]).is_err());
+    }
+}
@@ -0,0 +1,531 @@
+use base64::Engine;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{State, Url};
+
+use crate::dns_cache::DnsCache;
+use crate::host_overrides::{self, HostOverrides};
+use crate::AppState;
+
+const MAX_PAGE_BYTES: usize = 512 * 1024;
+const MAX_FAVICON_BYTES: usize = 64 * 1024;
+const MAX_TEXT_CHARS: usize = 300;
+const MAX_REDIRECTS: usize = 5;
+const PREVIEW_TIMEOUT_SECS: u64 = 10;
+const USER_AGENT: &str = concat!(
+    "assistant-desktop/",
+    env!("CARGO_PKG_VERSION"),
+    " (link preview)"
+);
+
+/// Metadata for rendering a link preview.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct UrlPreview {
+    /// URL after redirects.
+    pub url: String,
+    pub content_type: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// Absolute URL of the OpenGraph image.
+    pub image: Option<String>,
+    /// The favicon as a `data:` URL, so showing it needs no further request.
+    pub favicon: Option<String>,
+}
+
+/// Metadata found in a page's `<head>`.
+#[derive(Debug, Default, PartialEq)]
+struct PageMetadata {
+    title: Option<String>,
+    og_title: Option<String>,
+    description: Option<String>,
+    og_description: Option<String>,
+    site_name: Option<String>,
+    image: Option<String>,
+    icon: Option<String>,
+}
+
+/// Whether a preview may connect to `ip`. Loopback would reach this app's own
+/// proxies, and private, link-local (including cloud metadata at 169.254.169.254)
+/// and unique-local addresses would expose the local network to page content.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Whether a preview may fetch `url`. Host names are checked again once resolved
+/// (see `PublicResolver`); overridden hosts never reach the resolver, so their
+/// mapped address is checked here.
+fn is_allowed(url: &Url, overrides: &HostOverrides) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        return false;
+    }
+    if let Ok(ip) = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        return is_public(ip);
+    }
+    host_overrides::lookup(overrides, &host, 0).is_none_or(|addr| is_public(addr.ip()))
+}
+
+/// Resolves through the app's DNS cache, dropping addresses previews may not reach,
+/// so a public name pointing at a local address is refused.
+struct PublicResolver(Arc<DnsCache>);
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolving = self.0.resolve(name);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = resolving
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err("Host resolves only to local or private addresses".into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let Some(end) = rest.find(';').filter(|end| *end <= 10) else {
+            result.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Decode entities, collapse whitespace and cap the length.
+fn clean_text(text: &str) -> Option<String> {
+    let decoded = decode_entities(text);
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    Some(collapsed.chars().take(MAX_TEXT_CHARS).collect())
+}
+
+/// Attributes of a start tag, given the text between the tag name and `>`.
+fn parse_attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(quote).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = decode_entities(raw);
+            rest = remaining;
+        } else if name.is_empty() {
+            // Stray `/` or `=`
+            rest = &rest[1.min(rest.len())..];
+        }
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+        rest = rest.trim_start();
+    }
+    attributes
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Scan the document head for the title, OpenGraph/Twitter meta tags and icon links.
+fn parse_metadata(html: &str) -> PageMetadata {
+    // ASCII lowercasing keeps byte offsets, so `lower` indexes `html` too
+    let lower = html.to_ascii_lowercase();
+    let mut metadata = PageMetadata::default();
+    let mut apple_touch_icon = None;
+    let mut position = 0;
+
+    while let Some(offset) = lower[position..].find('<') {
+        let start = position + offset + 1;
+        let name_end = lower[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map(|end| start + end)
+            .unwrap_or(lower.len());
+        let name = &lower[start..name_end];
+        let Some(tag_end) = lower[name_end..].find('>').map(|end| name_end + end) else {
+            break;
+        };
+        position = tag_end + 1;
+
+        match name {
+            "body" => break,
+            "title" if metadata.title.is_none() => {
+                let end = lower[position..]
+                    .find("</title")
+                    .map(|end| position + end)
+                    .unwrap_or(lower.len());
+                metadata.title = clean_text(&html[position..end]);
+                position = end;
+            }
+            "meta" => {
+                let attributes = parse_attributes(&html[name_end..tag_end]);
+                let key = attribute(&attributes, "property")
+                    .or_else(|| attribute(&attributes, "name"))
+                    .map(str::to_ascii_lowercase);
+                let Some(content) = attribute(&attributes, "content").and_then(clean_text) else {
+                    continue;
+                };
+                let slot = match key.as_deref() {
+                    Some("og:title") | Some("twitter:title") => &mut metadata.og_title,
+                    Some("description") => &mut metadata.description,
+                    Some("og:description") | Some("twitter:description") => {
+                        &mut metadata.og_description
+                    }
+                    Some("og:site_name") => &mut metadata.site_name,
+                    Some("og:image")
+                    | Some("og:image:url")
+                    | Some("og:image:secure_url")
+                    | Some("twitter:image") => &mut metadata.image,
+                    _ => continue,
+                };
+                slot.get_or_insert(content);
+            }
+            "link" => {
+                let attributes = parse_attributes(&html[name_end..tag_end]);
+                let rel = attribute(&attributes, "rel")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let Some(href) = attribute(&attributes, "href").filter(|href| !href.is_empty())
+                else {
+                    continue;
+                };
+                if rel.split_whitespace().any(|token| token == "icon") {
+                    metadata.icon.get_or_insert_with(|| href.to_string());
+                } else if rel.starts_with("apple-touch-icon") {
+                    apple_touch_icon.get_or_insert_with(|| href.to_string());
+                }
+            }
+            "script" | "style" => {
+                let close = format!("</{}", name);
+                position = lower[position..]
+                    .find(&close)
+                    .map(|end| position + end)
+                    .unwrap_or(lower.len());
+            }
+            _ => {}
+        }
+    }
+
+    metadata.icon = metadata.icon.or(apple_touch_icon);
+    metadata
+}
+
+fn content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+        })
+}
+
+/// Read at most `limit` bytes of the body, stopping early once `stop_at` has been seen.
+async fn read_capped(
+    response: &mut reqwest::Response,
+    limit: usize,
+    stop_at: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        let searched = body.len().saturating_sub(stop_at.map_or(0, |s| s.len()));
+        body.extend_from_slice(&chunk);
+        if body.len() >= limit {
+            body.truncate(limit);
+            break;
+        }
+        if let Some(stop_at) = stop_at {
+            if body[searched..]
+                .windows(stop_at.len())
+                .any(|window| window.eq_ignore_ascii_case(stop_at))
+            {
+                break;
+            }
+        }
+    }
+    Ok(body)
+}
+
+async fn fetch_favicon(
+    client: &reqwest::Client,
+    url: &Url,
+    overrides: &HostOverrides,
+) -> Option<String> {
+    if !is_allowed(url, overrides) {
+        return None;
+    }
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_FAVICON_BYTES as u64)
+    {
+        return None;
+    }
+    let content_type = match content_type(&response) {
+        Some(content_type) if content_type.starts_with("image/") => content_type,
+        // Servers often send `.ico` files without an image type
+        _ if url.path().ends_with(".ico") => "image/x-icon".to_string(),
+        _ => return None,
+    };
+    let body = read_capped(&mut response, MAX_FAVICON_BYTES + 1, None)
+        .await
+        .ok()?;
+    if body.is_empty() || body.len() > MAX_FAVICON_BYTES {
+        return None;
+    }
+    Some(format!(
+        "data:{};base64,{}",
+        content_type,
+        base64::engine::general_purpose::STANDARD.encode(body)
+    ))
+}
+
+/// Fetch title, OpenGraph metadata and the favicon of a page for a link preview.
+///
+/// The request goes out from this device through the upstream proxy or PAC script,
+/// without backend credentials. Certificates are always validated, whatever
+/// `skip_cert_validation` says. Only the first 512 KB of the page is read. Loopback, private
+/// and link-local addresses are refused, whether given directly or resolved from a
+/// host name; through an upstream proxy, the proxy resolves names instead.
+#[tauri::command]
+pub async fn fetch_url_preview(
+    url: String,
+    state: State<'_, AppState>,
+) -> Result<UrlPreview, String> {
+    let url = Url::parse(url.trim()).map_err(|e| e.to_string())?;
+    let (builder, overrides) = {
+        let settings = state.settings.lock().await;
+        (
+            crate::external_client_builder(&settings, &state),
+            settings.host_overrides.clone(),
+        )
+    };
+    if !is_allowed(&url, &overrides) {
+        return Err("Only http and https links to public hosts can be previewed".to_string());
+    }
+
+    let redirect_overrides = overrides.clone();
+    let client = builder
+        .dns_resolver(Arc::new(PublicResolver(state.dns_cache.clone())))
+        .timeout(Duration::from_secs(PREVIEW_TIMEOUT_SECS))
+        .user_agent(USER_AGENT)
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !is_allowed(attempt.url(), &redirect_overrides) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut response = client
+        .get(url)
+        .header(
+            reqwest::header::ACCEPT,
+            "text/html,application/xhtml+xml;q=0.9,*/*;q=0.5",
+        )
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let final_url = response.url().clone();
+    let mut preview = UrlPreview {
+        url: final_url.to_string(),
+        content_type: content_type(&response),
+        ..UrlPreview::default()
+    };
+    if !matches!(
+        preview.content_type.as_deref(),
+        Some("text/html") | Some("application/xhtml+xml")
+    ) {
+        return Ok(preview);
+    }
+
+    let body = read_capped(&mut response, MAX_PAGE_BYTES, Some(b"</head")).await?;
+    let metadata = parse_metadata(&String::from_utf8_lossy(&body));
+    preview.title = metadata.og_title.or(metadata.title);
+    preview.description = metadata.og_description.or(metadata.description);
+    preview.site_name = metadata.site_name;
+    preview.image = metadata
+        .image
+        .and_then(|image| final_url.join(&image).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(|image| image.to_string());
+
+    let icon_url = match metadata.icon {
+        Some(icon) => final_url.join(&icon).ok(),
+        None => final_url.join("/favicon.ico").ok(),
+    };
+    if let Some(icon_url) = icon_url {
+        preview.favicon = fetch_favicon(&client, &icon_url, &overrides).await;
+    }
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_allowed, parse_metadata, PageMetadata};
+    use crate::host_overrides::HostOverrides;
+    use tauri::Url;
+
+    #[test]
+    fn parses_head_metadata() {
+        let html = r#"<!doctype html><html><head>
+            <meta charset="utf-8">
+            <title>
+              Fish &amp; Chips
+            </title>
+            <script>var s = "<meta property='og:title' content='Nope'>";</script>
+            <META name="description" content="Plain description">
+            <meta property="og:description" content='Crispy &#x1F41F; recipes'>
+            <meta property=og:site_name content=Cookbook>
+            <meta property="og:image" content="/img/fish.png">
+            <link rel="apple-touch-icon" href="/touch.png">
+            <link rel="shortcut icon" href="/favicon.png">
+            </head><body><meta property="og:title" content="Body"></body></html>"#;
+        assert_eq!(
+            parse_metadata(html),
+            PageMetadata {
+                title: Some("Fish & Chips".to_string()),
+                og_title: None,
+                description: Some("Plain description".to_string()),
+                og_description: Some("Crispy 🐟 recipes".to_string()),
+                site_name: Some("Cookbook".to_string()),
+                image: Some("/img/fish.png".to_string()),
+                icon: Some("/favicon.png".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_local_private_and_non_web_urls() {
+        let overrides = HostOverrides::from([
+            ("intranet".to_string(), "10.1.2.3".parse().unwrap()),
+            ("public".to_string(), "93.184.216.34".parse().unwrap()),
+        ]);
+        let allowed = |url: &str| is_allowed(&Url::parse(url).unwrap(), &overrides);
+        assert!(allowed("https://example.com/page"));
+        assert!(allowed("http://93.184.216.34/"));
+        assert!(allowed("http://public/"));
+        assert!(!allowed("http://localhost:8080/"));
+        assert!(!allowed("http://localhost.:8080/"));
+        assert!(!allowed("http://app.localhost/"));
+        assert!(!allowed("http://127.0.0.1/"));
+        assert!(!allowed("http://[::1]/"));
+        assert!(!allowed("http://10.0.0.1/"));
+        assert!(!allowed("http://172.16.5.4/"));
+        assert!(!allowed("http://192.168.1.10/"));
+        assert!(!allowed("http://169.254.169.254/latest/meta-data/"));
+        assert!(!allowed("http://100.64.0.1/"));
+        assert!(!allowed("http://[fd00::1]/"));
+        assert!(!allowed("http://[fe80::1]/"));
+        assert!(!allowed("http://[::ffff:10.0.0.1]/"));
+        assert!(!allowed("http://intranet/"));
+        assert!(!allowed("file:///etc/passwd"));
+    }
+}
@@ -0,0 +1,116 @@
+/// Whether a response `content-type` is one of the configured rewrite types (compared by
+/// MIME essence, ignoring parameters such as `charset`).
+pub fn should_rewrite(content_type: Option<&str>, content_types: &[String]) -> bool {
+    let Some(content_type) = content_type else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    content_types
+        .iter()
+        .any(|candidate| candidate.trim().eq_ignore_ascii_case(essence))
+}
+
+/// Characters that continue a host, port, or path segment, so a match followed by one of
+/// them points somewhere other than the backend base URL.
+fn continues_url(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '@')
+}
+
+fn replace_prefix(body: &str, from: &str, to: &str) -> Option<String> {
+    if !body.contains(from) {
+        return None;
+    }
+    let mut result = String::with_capacity(body.len());
+    let mut rest = body;
+    let mut changed = false;
+    while let Some(index) = rest.find(from) {
+        let after = &rest[index + from.len()..];
+        result.push_str(&rest[..index]);
+        if after.chars().next().is_some_and(continues_url) {
+            result.push_str(from);
+        } else {
+            result.push_str(to);
+            changed = true;
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    changed.then_some(result)
+}
+
+/// Map absolute URLs under `backend_base` (e.g. `https://assistant/assistant-work`) to
+/// `local_origin`, in plain and JSON-escaped (`https:\/\/…`) form. Returns `None` when
+/// nothing was rewritten.
+pub fn rewrite_backend_urls(body: &str, backend_base: &str, local_origin: &str) -> Option<String> {
+    let backend_base = backend_base.trim_end_matches('/');
+    let local_origin = local_origin.trim_end_matches('/');
+    if backend_base.is_empty() {
+        return None;
+    }
+
+    let plain = replace_prefix(body, backend_base, local_origin);
+    let current = plain.as_deref().unwrap_or(body);
+    let escaped = replace_prefix(
+        current,
+        &backend_base.replace('/', "\\/"),
+        &local_origin.replace('/', "\\/"),
+    );
+    escaped.or(plain)
+}
+
+/// Rewrite a buffered response body when its content type is opted in. The local origin is
+/// taken from the webview's `Host` header; compressed bodies are left untouched.
+pub fn rewrite_response_body(
+    body: &[u8],
+    request_headers: &hyper::HeaderMap,
+    response_headers: &reqwest::header::HeaderMap,
+    backend_url: &str,
+    content_types: &[String],
+) -> Option<String> {
+    if content_types.is_empty() {
+        return None;
+    }
+    let header = |name: &str| {
+        response_headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    if !should_rewrite(header("content-type"), content_types)
+        || header("content-encoding").is_some_and(|encoding| encoding != "identity")
+    {
+        return None;
+    }
+    let host = request_headers.get("host")?.to_str().ok()?;
+    let body = std::str::from_utf8(body).ok()?;
+    rewrite_backend_urls(body, backend_url, &format!("http://{}", host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_backend_urls, should_rewrite};
+
+    #[test]
+    fn matches_configured_content_types() {
+        let types = vec!["application/json".to_string(), "text/html".to_string()];
+        assert!(should_rewrite(Some("text/html; charset=utf-8"), &types));
+        assert!(should_rewrite(Some("Application/JSON"), &types));
+        assert!(!should_rewrite(Some("image/png"), &types));
+        assert!(!should_rewrite(None, &types));
+    }
+
+    #[test]
+    fn rewrites_backend_urls_to_local_origin() {
+        let body = r#"{"a":"https://assistant/work/api/x","b":"https:\/\/assistant\/work\/img.png","c":"https://assistant.example/work","d":"https://assistant/workspace"}"#;
+        assert_eq!(
+            rewrite_backend_urls(body, "https://assistant/work/", "http://localhost:1234")
+                .as_deref(),
+            Some(
+                r#"{"a":"http://localhost:1234/api/x","b":"http:\/\/localhost:1234\/img.png","c":"https://assistant.example/work","d":"https://assistant/workspace"}"#
+            )
+        );
+        assert_eq!(
+            rewrite_backend_urls("no urls", "https://assistant", "http://localhost:1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::AppState;
+
+/// WebSocket protocol versions (`CURRENT_PROTOCOL_VERSION` in `@assistant/shared`)
+/// this shell's web client can speak.
+const SUPPORTED_PROTOCOL_VERSIONS: RangeInclusive<u32> = 5..=5;
+/// Oldest server release the desktop-specific endpoints are known to work with.
+const MIN_BACKEND_VERSION: &str = "0.18.0";
+const VERSION_CHECK_TIMEOUT_SECS: u64 = 10;
+
+/// Response of the backend's `GET /api/version`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendVersion {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    protocol_version: Option<u32>,
+}
+
+/// Outcome of the startup version handshake.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendCompatibility {
+    pub compatible: bool,
+    pub backend_version: Option<String>,
+    pub protocol_version: Option<u32>,
+    pub shell_version: String,
+    pub min_backend_version: &'static str,
+    pub supported_protocol_versions: [u32; 2],
+    /// Upgrade guidance when incompatible.
+    pub message: Option<String>,
+}
+
+/// Last handshake result, for a UI that loads after the event was emitted.
+pub struct VersionCheck {
+    last: Mutex<Option<BackendCompatibility>>,
+}
+
+impl VersionCheck {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+}
+
+/// `major.minor.patch`, ignoring any pre-release or build suffix.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+/// Upgrade guidance if the backend is outside the supported range.
+fn incompatibility(backend: &BackendVersion) -> Option<String> {
+    if let Some(protocol) = backend.protocol_version {
+        if protocol < *SUPPORTED_PROTOCOL_VERSIONS.start() {
+            return Some(format!(
+                "The assistant server speaks protocol {}, but this desktop app needs {}. Upgrade the server.",
+                protocol,
+                SUPPORTED_PROTOCOL_VERSIONS.start()
+            ));
+        }
+        if protocol > *SUPPORTED_PROTOCOL_VERSIONS.end() {
+            return Some(format!(
+                "The assistant server speaks protocol {}, newer than this desktop app supports ({}). Update the desktop app.",
+                protocol,
+                SUPPORTED_PROTOCOL_VERSIONS.end()
+            ));
+        }
+    }
+    let version = backend.version.as_deref()?;
+    let minimum = parse_version(MIN_BACKEND_VERSION)?;
+    match parse_version(version) {
+        Some(parsed) if parsed < minimum => Some(format!(
+            "The assistant server is version {}, but this desktop app needs {} or newer. Upgrade the server.",
+            version, MIN_BACKEND_VERSION
+        )),
+        _ => None,
+    }
+}
+
+/// Ask the backend for its version through the local proxy and emit
+/// `backend-incompatible` if this shell does not support it.
+///
+/// Backends that predate `/api/version` are assumed compatible, since their
+/// version cannot be known.
+pub async fn check_backend(app: &AppHandle) {
+    let state: State<'_, AppState> = app.state();
+    let proxy_port = state.settings.lock().await.proxy_port;
+    let url = format!("http://127.0.0.1:{}/api/version", proxy_port);
+    let response = reqwest::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(VERSION_CHECK_TIMEOUT_SECS))
+        .send()
+        .await;
+    let backend = match response {
+        Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+            log::info!(target: "version", "Backend does not report its version");
+            return;
+        }
+        Ok(response) => match response.error_for_status() {
+            Ok(response) => response.json::<BackendVersion>().await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    let backend = match backend {
+        Ok(backend) => backend,
+        Err(e) => {
+            log::warn!(target: "version", "Version check failed: {}", e);
+            return;
+        }
+    };
+
+    let message = incompatibility(&backend);
+    let result = BackendCompatibility {
+        compatible: message.is_none(),
+        backend_version: backend.version,
+        protocol_version: backend.protocol_version,
+        shell_version: app.package_info().version.to_string(),
+        min_backend_version: MIN_BACKEND_VERSION,
+        supported_protocol_versions: [
+            *SUPPORTED_PROTOCOL_VERSIONS.start(),
+            *SUPPORTED_PROTOCOL_VERSIONS.end(),
+        ],
+        message,
+    };
+    match &result.message {
+        Some(message) => {
+            log::warn!(target: "version", "{}", message);
+            let _ = app.emit("backend-incompatible", &result);
+        }
+        None => log::info!(
+            target: "version",
+            "Backend {} (protocol {}) is compatible",
+            result.backend_version.as_deref().unwrap_or("unknown"),
+            result
+                .protocol_version
+                .map_or("unknown".to_string(), |v| v.to_string())
+        ),
+    }
+    *state
+        .version_check
+        .last
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(result);
+}
+
+/// Result of the last backend version check, if one has completed.
+#[tauri::command]
+pub async fn get_backend_compatibility(
+    state: State<'_, AppState>,
+) -> Result<Option<BackendCompatibility>, String> {
+    Ok(state
+        .version_check
+        .last
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{incompatibility, BackendVersion};
+
+    fn backend(version: Option<&str>, protocol_version: Option<u32>) -> BackendVersion {
+        BackendVersion {
+            version: version.map(str::to_string),
+            protocol_version,
+        }
+    }
+
+    #[test]
+    fn flags_backends_outside_supported_range() {
+        assert_eq!(incompatibility(&backend(Some("0.18.2"), Some(5))), None);
+        assert_eq!(incompatibility(&backend(Some("1.0.0-rc.1"), None)), None);
+        assert_eq!(incompatibility(&backend(None, None)), None);
+
+        let old = incompatibility(&backend(Some("0.17.9"), Some(5))).unwrap();
+        assert!(old.contains("Upgrade the server"));
+        let old_protocol = incompatibility(&backend(Some("0.18.2"), Some(4))).unwrap();
+        assert!(old_protocol.contains("Upgrade the server"));
+        let new_protocol = incompatibility(&backend(Some("0.19.0"), Some(6))).unwrap();
+        assert!(new_protocol.contains("Update the desktop app"));
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::Ordering;
+use std::sync::Weak;
+use std::time::{Duration, Instant};
+
+use crate::ProxyState;
+
+/// Unauthenticated endpoint used to open and keep alive the upstream connection.
+const WARM_UP_PATH: &str = "/api/version";
+/// Endpoints the web client loads first, prefetched through the proxy when enabled.
+const PREFETCH_PATHS: &[&str] = &["/api/plugins", "/preferences"];
+/// Below common NAT and load-balancer idle timeouts and reqwest's 90s pool idle timeout.
+const KEEP_WARM_INTERVAL_SECS: u64 = 45;
+const WARM_UP_TIMEOUT_SECS: u64 = 10;
+
+/// Open a pooled connection to the backend (DNS, TCP, TLS and HTTP/2 setup) and
+/// keep it alive until the proxy that owns the client shuts down.
+pub async fn run_keep_warm(proxy_state: Weak<ProxyState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(KEEP_WARM_INTERVAL_SECS));
+    let mut warmed = false;
+    loop {
+        interval.tick().await;
+        let Some(proxy_state) = proxy_state.upgrade() else {
+            break;
+        };
+        if !proxy_state.warm_up_connections.load(Ordering::Relaxed) {
+            continue;
+        }
+        let url = format!(
+            "{}{}",
+            proxy_state.backend_base().trim_end_matches('/'),
+            WARM_UP_PATH
+        );
+        let started = Instant::now();
+        let result = proxy_state
+            .http_client
+            .get(&url)
+            .timeout(Duration::from_secs(WARM_UP_TIMEOUT_SECS))
+            .send()
+            .await;
+        match result {
+            // Drain the body so the connection goes back to the pool
+            Ok(response) => {
+                let version = response.version();
+                let _ = response.bytes().await;
+                if !warmed {
+                    log::info!(
+                        target: "http-proxy",
+                        "Upstream connection ({:?}) warmed up in {}ms",
+                        version,
+                        started.elapsed().as_millis()
+                    );
+                    warmed = true;
+                }
+            }
+            Err(e) => {
+                log::debug!(target: "http-proxy", "Keep-warm request failed: {}", e);
+                warmed = false;
+            }
+        }
+    }
+}
+
+/// Fetch the endpoints the web client needs first through the local proxy, so the
+/// backend (and the response cache, if enabled) is ready before the UI asks.
+pub async fn prefetch(proxy_port: u16) {
+    let client = reqwest::Client::new();
+    for path in PREFETCH_PATHS {
+        let url = format!("http://127.0.0.1:{}{}", proxy_port, path);
+        let result = client
+            .get(&url)
+            .timeout(Duration::from_secs(WARM_UP_TIMEOUT_SECS))
+            .send()
+            .await;
+        match result {
+            Ok(response) => {
+                log::debug!(target: "http-proxy", "Prefetched {}: {}", path, response.status());
+                let _ = response.bytes().await;
+            }
+            Err(e) => log::debug!(target: "http-proxy", "Prefetch of {} failed: {}", path, e),
+        }
+    }
+}
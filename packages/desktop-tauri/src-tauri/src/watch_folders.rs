@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::{now_ms, uploads, AppState};
+
+/// How often watched folders are rescanned.
+const WATCH_SCAN_INTERVAL_SECS: u64 = 5;
+
+/// A directory whose new or changed files are uploaded automatically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub path: String,
+
+    #[serde(default = "default_watch_folder_enabled")]
+    pub enabled: bool,
+
+    /// File name patterns to upload (`*` wildcards, e.g. `*.pdf`); empty uploads everything.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    /// Artifacts instance to upload into (defaults to the backend's default instance).
+    #[serde(default)]
+    pub instance_id: Option<String>,
+}
+
+fn default_watch_folder_enabled() -> bool {
+    true
+}
+
+/// Sync status of a watched folder, returned by `get_watch_folder_status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WatchFolderStatus {
+    pub path: String,
+    pub enabled: bool,
+    pub uploaded: u64,
+    pub failed: u64,
+    pub last_upload_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Size and modification time used to detect new or changed files.
+type FileSignature = (u64, u128);
+
+#[derive(Default)]
+struct TrackedFile {
+    signature: Option<FileSignature>,
+    uploaded: Option<FileSignature>,
+}
+
+/// Upload counters shared between the scanner task and the status command.
+pub struct WatchFolderStats {
+    stats: Mutex<HashMap<String, WatchFolderStatus>>,
+}
+
+impl WatchFolderStats {
+    pub fn new() -> Self {
+        Self {
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn record(&self, folder: &str, result: Result<(), String>) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = stats.entry(folder.to_string()).or_default();
+        match result {
+            Ok(()) => {
+                entry.uploaded += 1;
+                entry.last_upload_at = Some(now_ms());
+                entry.last_error = None;
+            }
+            Err(e) => {
+                entry.failed += 1;
+                entry.last_error = Some(e);
+            }
+        }
+    }
+
+    fn status(&self, folder: &WatchFolder) -> WatchFolderStatus {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        WatchFolderStatus {
+            path: folder.path.clone(),
+            enabled: folder.enabled,
+            ..stats.get(&folder.path).cloned().unwrap_or_default()
+        }
+    }
+}
+
+/// Match a file name against a pattern where `*` matches any run of characters.
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Whether a file should be uploaded for the folder's patterns (temp/hidden files never are).
+fn should_upload(name: &str, patterns: &[String]) -> bool {
+    let lower = name.to_ascii_lowercase();
+    if name.starts_with('.')
+        || name.ends_with('~')
+        || [".tmp", ".part", ".crdownload", ".download"]
+            .iter()
+            .any(|suffix| lower.ends_with(suffix))
+    {
+        return false;
+    }
+    patterns.is_empty() || patterns.iter().any(|p| matches_pattern(name, p))
+}
+
+fn file_signature(metadata: &fs::Metadata) -> FileSignature {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    (metadata.len(), modified)
+}
+
+/// Scan one folder and upload files whose size/mtime settled since the previous scan.
+///
+/// Files present on the first scan only establish the baseline.
+async fn scan_folder(
+    app: &AppHandle,
+    folder: &WatchFolder,
+    tracked: &mut HashMap<PathBuf, TrackedFile>,
+    first_scan: bool,
+) {
+    let entries = match fs::read_dir(&folder.path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!(target: "watch-folders", "Failed to read {}: {}", folder.path, e);
+            return;
+        }
+    };
+
+    let mut seen = HashSet::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !metadata.is_file() || !should_upload(&name, &folder.patterns) {
+            continue;
+        }
+        seen.insert(path.clone());
+
+        let signature = file_signature(&metadata);
+        let file = tracked.entry(path.clone()).or_default();
+        if first_scan {
+            file.signature = Some(signature);
+            file.uploaded = Some(signature);
+            continue;
+        }
+
+        // Only upload once the file stopped changing between two scans
+        let settled = file.signature == Some(signature);
+        file.signature = Some(signature);
+        if !settled || file.uploaded == Some(signature) {
+            continue;
+        }
+        file.uploaded = Some(signature);
+
+        if metadata.len() > uploads::MAX_UPLOAD_BYTES {
+            log::warn!(target: "watch-folders", "Skipping {}: too large", path.display());
+            continue;
+        }
+
+        log::info!(target: "watch-folders", "Uploading {}", path.display());
+        let result =
+            uploads::upload_artifact(app, &path, folder.instance_id.as_deref(), None).await;
+        if let Err(e) = &result {
+            log::error!(target: "watch-folders", "Failed to upload {}: {}", path.display(), e);
+        }
+        let state: State<'_, AppState> = app.state();
+        state.watch_folder_stats.record(&folder.path, result);
+    }
+
+    tracked.retain(|path, _| seen.contains(path));
+}
+
+/// Periodically scan enabled watch folders and upload new or changed files.
+pub async fn run_watch_folders(app: AppHandle) {
+    let mut tracked: HashMap<String, HashMap<PathBuf, TrackedFile>> = HashMap::new();
+
+    loop {
+        let folders = {
+            let state: State<'_, AppState> = app.state();
+            let settings = state.settings.lock().await;
+            settings.watch_folders.clone()
+        };
+
+        tracked.retain(|path, _| {
+            folders
+                .iter()
+                .any(|folder| folder.enabled && &folder.path == path)
+        });
+        for folder in folders.iter().filter(|folder| folder.enabled) {
+            let first_scan = !tracked.contains_key(&folder.path);
+            let files = tracked.entry(folder.path.clone()).or_default();
+            scan_folder(&app, folder, files, first_scan).await;
+        }
+
+        tokio::time::sleep(Duration::from_secs(WATCH_SCAN_INTERVAL_SECS)).await;
+    }
+}
+
+/// Let the user pick a folder in a native dialog and watch it (or update its
+/// settings if it is already watched). Page content never names the path, so only
+/// folders the user chose are uploaded. Returns the folder, or `None` if the
+/// picker was cancelled.
+#[tauri::command]
+pub async fn add_watch_folder(
+    app: AppHandle,
+    patterns: Option<Vec<String>>,
+    instance_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let picker = app
+        .dialog()
+        .file()
+        .set_title("Choose a folder to upload automatically");
+    let picked = tauri::async_runtime::spawn_blocking(move || picker.blocking_pick_folder())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {}", path.display()));
+    }
+    let path = path.to_string_lossy().into_owned();
+    {
+        let mut settings = state.settings.lock().await;
+        let folder = WatchFolder {
+            path: path.clone(),
+            enabled: true,
+            patterns: patterns.unwrap_or_default(),
+            instance_id: instance_id.filter(|id| !id.trim().is_empty()),
+        };
+        match settings.watch_folders.iter_mut().find(|f| f.path == path) {
+            Some(existing) => *existing = folder,
+            None => settings.watch_folders.push(folder),
+        }
+    }
+    state.save().await?;
+    Ok(Some(path))
+}
+
+/// Stop watching a folder.
+#[tauri::command]
+pub async fn remove_watch_folder(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .settings
+        .lock()
+        .await
+        .watch_folders
+        .retain(|folder| folder.path != path);
+    state.save().await
+}
+
+/// Enable or disable a watched folder without forgetting it.
+#[tauri::command]
+pub async fn set_watch_folder_enabled(
+    path: String,
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().await;
+        let folder = settings
+            .watch_folders
+            .iter_mut()
+            .find(|folder| folder.path == path)
+            .ok_or_else(|| format!("Folder is not watched: {}", path))?;
+        folder.enabled = enabled;
+    }
+    state.save().await
+}
+
+/// Get the sync status of all watched folders.
+#[tauri::command]
+pub async fn get_watch_folder_status(
+    state: State<'_, AppState>,
+) -> Result<Vec<WatchFolderStatus>, String> {
+    let settings = state.settings.lock().await;
+    Ok(settings
+        .watch_folders
+        .iter()
+        .map(|folder| state.watch_folder_stats.status(folder))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_pattern, should_upload};
+
+    #[test]
+    fn matches_wildcard_patterns() {
+        assert!(matches_pattern("Report.PDF", "*.pdf"));
+        assert!(matches_pattern("scan-001.png", "scan-*.png"));
+        assert!(matches_pattern("notes", "notes"));
+        assert!(!matches_pattern("notes.txt", "*.pdf"));
+        assert!(!matches_pattern("a.pdf.txt", "*.pdf"));
+    }
+
+    #[test]
+    fn skips_temporary_and_hidden_files() {
+        assert!(!should_upload(".DS_Store", &[]));
+        assert!(!should_upload("video.mp4.crdownload", &[]));
+        assert!(should_upload("video.mp4", &[]));
+        assert!(!should_upload("video.mp4", &["*.pdf".to_string()]));
+    }
+}
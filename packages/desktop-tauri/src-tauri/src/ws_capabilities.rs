@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// Handshake header carrying the proxy's capabilities and, in the response, the backend's.
+pub const CAPABILITIES_HEADER: &str = "x-assistant-capabilities";
+
+/// Optional WebSocket features, as announced or negotiated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WsCapabilities {
+    pub compression: bool,
+    pub multiplexing: bool,
+    pub audio_streaming: bool,
+    /// Large text messages may be split into `proxy_chunk` messages.
+    pub chunking: bool,
+    pub max_message_size: Option<usize>,
+}
+
+impl WsCapabilities {
+    /// What the proxy supports with its current settings.
+    pub fn local(
+        compression: bool,
+        multiplexing: bool,
+        chunking: bool,
+        max_message_size: usize,
+    ) -> Self {
+        Self {
+            compression,
+            multiplexing,
+            audio_streaming: true,
+            chunking,
+            max_message_size: Some(max_message_size),
+        }
+    }
+
+    /// Header value, e.g. `multiplexing, audio-streaming, max-message-size=67108864`.
+    pub fn to_header(&self) -> String {
+        let mut tokens: Vec<String> = [
+            (self.compression, "compression"),
+            (self.multiplexing, "multiplexing"),
+            (self.audio_streaming, "audio-streaming"),
+            (self.chunking, "chunking"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, token)| token.to_string())
+        .collect();
+        if let Some(size) = self.max_message_size {
+            tokens.push(format!("max-message-size={}", size));
+        }
+        tokens.join(", ")
+    }
+
+    /// Parse a header value; unknown tokens are ignored.
+    pub fn parse(value: &str) -> Self {
+        let mut capabilities = Self::default();
+        for token in value.split(',').map(str::trim) {
+            match token.split_once('=') {
+                Some((key, size)) if key.trim().eq_ignore_ascii_case("max-message-size") => {
+                    capabilities.max_message_size = size.trim().parse().ok();
+                }
+                _ if token.eq_ignore_ascii_case("compression") => capabilities.compression = true,
+                _ if token.eq_ignore_ascii_case("multiplexing") => capabilities.multiplexing = true,
+                _ if token.eq_ignore_ascii_case("audio-streaming") => {
+                    capabilities.audio_streaming = true
+                }
+                _ if token.eq_ignore_ascii_case("chunking") => capabilities.chunking = true,
+                _ => {}
+            }
+        }
+        capabilities
+    }
+
+    /// Features both sides support, with the smaller message size limit.
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self {
+            compression: self.compression && other.compression,
+            multiplexing: self.multiplexing && other.multiplexing,
+            audio_streaming: self.audio_streaming && other.audio_streaming,
+            chunking: self.chunking && other.chunking,
+            max_message_size: match (self.max_message_size, other.max_message_size) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// Capabilities negotiated with the backend on the latest WebSocket connection.
+pub struct NegotiatedCapabilities {
+    app: AppHandle,
+    current: Mutex<Option<WsCapabilities>>,
+}
+
+impl NegotiatedCapabilities {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Record the outcome of a handshake. `response` is the backend's header, if any;
+    /// backends that ignore the announcement get no optional features.
+    pub fn negotiate(&self, local: &WsCapabilities, response: Option<&str>) -> WsCapabilities {
+        let negotiated = match response {
+            Some(value) => local.intersect(&WsCapabilities::parse(value)),
+            None => WsCapabilities {
+                max_message_size: local.max_message_size,
+                ..WsCapabilities::default()
+            },
+        };
+        let changed = {
+            let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            let changed = current.as_ref() != Some(&negotiated);
+            *current = Some(negotiated.clone());
+            changed
+        };
+        if changed {
+            log::info!(target: "ws-proxy", "Negotiated capabilities: {}", negotiated.to_header());
+            let _ = self.app.emit("ws-capabilities", &negotiated);
+        }
+        negotiated
+    }
+
+    pub fn current(&self) -> Option<WsCapabilities> {
+        self.current
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WsCapabilities;
+
+    #[test]
+    fn negotiates_common_capabilities() {
+        let local = WsCapabilities::local(true, true, true, 64 << 20);
+        let backend = WsCapabilities::parse(
+            "Multiplexing, audio-streaming, chunking, max-message-size=1048576, x-new",
+        );
+        assert_eq!(
+            backend,
+            WsCapabilities {
+                compression: false,
+                multiplexing: true,
+                audio_streaming: true,
+                chunking: true,
+                max_message_size: Some(1 << 20),
+            }
+        );
+        assert_eq!(
+            local.intersect(&backend).to_header(),
+            "multiplexing, audio-streaming, chunking, max-message-size=1048576"
+        );
+        assert_eq!(WsCapabilities::parse(&local.to_header()), local);
+    }
+}
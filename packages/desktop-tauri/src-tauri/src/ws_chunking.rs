@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Smallest chunk size accepted, so a typo cannot turn a message into thousands.
+const MIN_CHUNK_BYTES: usize = 1024;
+/// Every chunk envelope starts with this, which keeps other messages cheap to skip.
+const ENVELOPE_PREFIX: &str = r#"{"type":"proxy_chunk","#;
+const ENVELOPE_TYPE: &str = "proxy_chunk";
+
+/// Chunked messages get IDs unique across connections, so multiplexed clients
+/// sharing one backend socket cannot collide.
+static NEXT_MESSAGE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// One piece of a large text message, sent as its own text message.
+#[derive(Debug, Serialize, Deserialize)]
+struct Chunk {
+    #[serde(rename = "type")]
+    kind: String,
+    id: u64,
+    index: u32,
+    count: u32,
+    data: String,
+}
+
+/// Check the chunk size setting (0 turns chunking off) before it is saved.
+pub fn validate(chunk_bytes: usize, max_message_bytes: usize) -> Result<(), String> {
+    if chunk_bytes != 0 && !(MIN_CHUNK_BYTES..=max_message_bytes).contains(&chunk_bytes) {
+        return Err(format!(
+            "The WebSocket chunk size must be 0 (off) or between {} bytes and the message limit",
+            MIN_CHUNK_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Split a text message longer than `chunk_bytes` into chunk envelopes carrying
+/// at most that much text each. Other messages are returned as they are.
+pub fn split(msg: Message, chunk_bytes: usize) -> Vec<Message> {
+    let text = match msg {
+        Message::Text(text) if text.len() > chunk_bytes => text,
+        msg => return vec![msg],
+    };
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let mut end = (start + chunk_bytes).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        pieces.push(&text[start..end]);
+        start = end;
+    }
+    let id = NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
+    let count = pieces.len() as u32;
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let chunk = Chunk {
+                kind: ENVELOPE_TYPE.to_string(),
+                id,
+                index: index as u32,
+                count,
+                data: data.to_string(),
+            };
+            Message::Text(serde_json::to_string(&chunk).unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Why a chunked message from the backend was refused.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkError {
+    /// The chunks add up to more than the message limit.
+    TooBig,
+    /// A malformed or out-of-order chunk.
+    Invalid(String),
+}
+
+impl ChunkError {
+    /// Close frame for the backend that sent the chunk.
+    pub fn close_message(&self) -> Message {
+        match self {
+            Self::TooBig => crate::ws_limits::too_big_message(),
+            Self::Invalid(_) => Message::Close(Some(CloseFrame {
+                code: CloseCode::Protocol,
+                reason: "Invalid chunk".into(),
+            })),
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooBig => write!(f, "Chunked message exceeds the message limit"),
+            Self::Invalid(reason) => write!(f, "Invalid chunk: {}", reason),
+        }
+    }
+}
+
+struct Partial {
+    count: u32,
+    next: u32,
+    data: String,
+}
+
+/// Reassembles chunked messages from the backend, holding at most `max_bytes`
+/// of partial messages at a time.
+pub struct Reassembler {
+    max_bytes: usize,
+    buffered: usize,
+    partial: HashMap<u64, Partial>,
+}
+
+impl Reassembler {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            buffered: 0,
+            partial: HashMap::new(),
+        }
+    }
+
+    /// Take a message from the backend. Returns it unchanged unless it is a chunk,
+    /// then the whole message once its last chunk arrived.
+    pub fn push(&mut self, msg: Message) -> Result<Option<Message>, ChunkError> {
+        let chunk = match &msg {
+            Message::Text(text) if text.starts_with(ENVELOPE_PREFIX) => {
+                serde_json::from_str::<Chunk>(text)
+                    .map_err(|e| ChunkError::Invalid(e.to_string()))?
+            }
+            _ => return Ok(Some(msg)),
+        };
+        let partial = self.partial.entry(chunk.id).or_insert_with(|| Partial {
+            count: chunk.count,
+            next: 0,
+            data: String::new(),
+        });
+        if chunk.index != partial.next || chunk.count != partial.count {
+            return Err(ChunkError::Invalid(format!(
+                "expected part {} of {} for message {}, got {} of {}",
+                partial.next, partial.count, chunk.id, chunk.index, chunk.count
+            )));
+        }
+        self.buffered += chunk.data.len();
+        if self.buffered > self.max_bytes {
+            return Err(ChunkError::TooBig);
+        }
+        partial.data.push_str(&chunk.data);
+        partial.next += 1;
+        if partial.next < partial.count {
+            return Ok(None);
+        }
+        let whole = self.partial.remove(&chunk.id).map(|partial| partial.data);
+        let whole = whole.unwrap_or_default();
+        self.buffered -= whole.len();
+        Ok(Some(Message::Text(whole)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, ChunkError, Reassembler};
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn splits_and_reassembles_text() {
+        let text = "é".repeat(1500);
+        let chunks = split(Message::Text(text.clone()), 1024);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            split(Message::Text("short".into()), 1024),
+            [Message::Text("short".into())]
+        );
+
+        let mut reassembler = Reassembler::new(4096);
+        let other = Message::Binary(vec![1, 2, 3]);
+        assert_eq!(reassembler.push(other.clone()), Ok(Some(other)));
+        assert_eq!(reassembler.push(chunks[0].clone()), Ok(None));
+        assert_eq!(reassembler.push(chunks[1].clone()), Ok(None));
+        assert_eq!(
+            reassembler.push(chunks[2].clone()),
+            Ok(Some(Message::Text(text.clone())))
+        );
+
+        // Out of order
+        let chunks = split(Message::Text(text.clone()), 1024);
+        assert!(matches!(
+            reassembler.push(chunks[1].clone()),
+            Err(ChunkError::Invalid(_))
+        ));
+
+        let mut small = Reassembler::new(2048);
+        let chunks = split(Message::Text(text), 1024);
+        assert_eq!(small.push(chunks[0].clone()), Ok(None));
+        assert_eq!(small.push(chunks[1].clone()), Ok(None));
+        assert_eq!(small.push(chunks[2].clone()), Err(ChunkError::TooBig));
+    }
+}
@@ -0,0 +1,51 @@
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// How long a closing WebSocket session waits for the peer's close frame.
+pub const CLOSE_TIMEOUT_SECS: u64 = 5;
+
+/// Sent to the backend when the web client went away without a close frame.
+pub fn client_lost_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "Client disconnected".into(),
+    }))
+}
+
+/// Sent to the web client when the backend connection failed without a close
+/// frame, so it reconnects.
+pub fn backend_lost_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Error,
+        reason: "Backend connection lost".into(),
+    }))
+}
+
+/// Code and reason of a close frame, for the log.
+pub fn describe(frame: Option<&CloseFrame>) -> String {
+    match frame {
+        None => "no status code".to_string(),
+        Some(frame) if frame.reason.is_empty() => u16::from(frame.code).to_string(),
+        Some(frame) => format!("{} {}", u16::from(frame.code), frame.reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::describe;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+    #[test]
+    fn describes_close_frames() {
+        assert_eq!(describe(None), "no status code");
+        let mut frame = CloseFrame {
+            code: CloseCode::Library(4001),
+            reason: "".into(),
+        };
+        assert_eq!(describe(Some(&frame)), "4001");
+        frame.reason = "session expired".into();
+        assert_eq!(describe(Some(&frame)), "4001 session expired");
+    }
+}
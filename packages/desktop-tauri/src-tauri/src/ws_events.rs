@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Emitter};
+
+use crate::ws_handshake;
+use crate::ws_sessions::SessionGuard;
+
+/// Stage of a proxied WebSocket session, reported in `ws-proxy-state` events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WsProxyState {
+    /// The proxy is opening the backend side for a new client socket.
+    Connecting,
+    /// Like `Connecting`, while the endpoint's last session lost the backend.
+    Reconnecting,
+    /// Both legs are open and messages are relayed.
+    Connected,
+    /// The session ended because the backend connection failed.
+    BackendLost,
+    /// The session ended for any other reason.
+    Closed,
+}
+
+/// Payload of `ws-proxy-state` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct WsProxyEvent {
+    /// Identifies the session across its events.
+    pub session: u64,
+    /// Backend endpoint the session was opened for, without the query.
+    pub path: String,
+    pub state: WsProxyState,
+    pub error: Option<String>,
+}
+
+/// Emits `ws-proxy-state` events, so the web client can show an accurate
+/// connection indicator instead of guessing from message silence.
+pub struct WsEvents {
+    app: AppHandle,
+    next_session: AtomicU64,
+    /// Endpoints whose last session ended with the backend lost, until one connects.
+    backend_lost: Mutex<HashSet<String>>,
+}
+
+impl WsEvents {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            next_session: AtomicU64::new(1),
+            backend_lost: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn emit(&self, event: WsProxyEvent) {
+        let _ = self.app.emit("ws-proxy-state", event);
+    }
+
+    fn lost_endpoints(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.backend_lost.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Announce a new session for the backend `path`. Its final state is emitted
+    /// when the returned session is dropped.
+    pub fn open(self: &Arc<Self>, path: &str) -> WsSession {
+        let session = WsSession {
+            events: self.clone(),
+            id: self.next_session.fetch_add(1, Ordering::Relaxed),
+            endpoint: ws_handshake::endpoint(path).to_string(),
+            outcome: Outcome::default(),
+            counted: None,
+        };
+        let state = if self.lost_endpoints().contains(&session.endpoint) {
+            WsProxyState::Reconnecting
+        } else {
+            WsProxyState::Connecting
+        };
+        session.emit(state, None);
+        session
+    }
+}
+
+/// How a session ended; the first failure reported wins.
+#[derive(Default)]
+pub struct Outcome(OnceLock<(WsProxyState, String)>);
+
+impl Outcome {
+    /// The backend connection failed or stopped answering.
+    pub fn backend_lost(&self, error: impl Into<String>) {
+        let _ = self.0.set((WsProxyState::BackendLost, error.into()));
+    }
+
+    /// The session could not be opened or was refused.
+    pub fn failed(&self, error: impl Into<String>) {
+        let _ = self.0.set((WsProxyState::Closed, error.into()));
+    }
+}
+
+/// One proxied WebSocket session, as reported to the web client.
+pub struct WsSession {
+    events: Arc<WsEvents>,
+    id: u64,
+    endpoint: String,
+    outcome: Outcome,
+    /// Counts the session in its endpoint's stats once connected.
+    counted: Option<SessionGuard>,
+}
+
+impl WsSession {
+    fn emit(&self, state: WsProxyState, error: Option<String>) {
+        self.events.emit(WsProxyEvent {
+            session: self.id,
+            path: self.endpoint.clone(),
+            state,
+            error,
+        });
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Report the session connected, keeping it counted by `counted` until it ends.
+    pub fn connected(&mut self, counted: SessionGuard) {
+        self.events.lost_endpoints().remove(&self.endpoint);
+        self.counted = Some(counted);
+        self.emit(WsProxyState::Connected, None);
+    }
+
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+}
+
+impl Drop for WsSession {
+    fn drop(&mut self) {
+        let (state, error) = match self.outcome.0.take() {
+            Some((state, error)) => (state, Some(error)),
+            None => (WsProxyState::Closed, None),
+        };
+        // Failed attempts to reconnect keep reporting `reconnecting`
+        if state == WsProxyState::BackendLost {
+            self.events.lost_endpoints().insert(self.endpoint.clone());
+            if let Some(counted) = &self.counted {
+                counted.backend_lost();
+            }
+        }
+        self.emit(state, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, WsProxyEvent};
+
+    #[test]
+    fn reports_the_first_failure() {
+        let outcome = Outcome::default();
+        outcome.backend_lost("Backend stopped answering pings");
+        outcome.failed("Message too big");
+        let (state, error) = outcome.0.get().cloned().unwrap();
+        let event = WsProxyEvent {
+            session: 3,
+            path: "/ws".to_string(),
+            state,
+            error: Some(error),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "session": 3,
+                "path": "/ws",
+                "state": "backend-lost",
+                "error": "Backend stopped answering pings",
+            })
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use http_body_util::Empty;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Request, Response, StatusCode, Uri};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+/// Headers of the client's upgrade request that belong to its handshake with the
+/// proxy rather than to the backend. Extensions are not forwarded because the
+/// relay does not speak permessage-deflate.
+const CLIENT_HANDSHAKE_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "sec-websocket-extensions",
+    "sec-websocket-accept",
+];
+
+/// Whether the comma-separated header `name` lists `token`.
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Whether a request on the HTTP proxy asks to upgrade to a WebSocket.
+pub fn wants_upgrade(headers: &HeaderMap) -> bool {
+    has_token(headers, header::UPGRADE, "websocket")
+}
+
+/// `Sec-WebSocket-Accept` for a valid client upgrade request.
+pub fn accept_key(request: &Request<Incoming>) -> Result<String, String> {
+    let headers = request.headers();
+    if request.method() != hyper::Method::GET
+        || !has_token(headers, header::CONNECTION, "upgrade")
+        || !wants_upgrade(headers)
+    {
+        return Err("Not a WebSocket upgrade request".to_string());
+    }
+    if headers.get(header::SEC_WEBSOCKET_VERSION) != Some(&HeaderValue::from_static("13")) {
+        return Err("Unsupported WebSocket version".to_string());
+    }
+    let key = headers
+        .get(header::SEC_WEBSOCKET_KEY)
+        .ok_or("Missing Sec-WebSocket-Key")?;
+    Ok(derive_accept_key(key.as_bytes()))
+}
+
+/// Path (with its query) to request from the backend for a client's upgrade
+/// request. The proxy's root stands for the app socket at `/ws`, which is where the
+/// web client connects.
+pub fn backend_path(uri: &Uri) -> String {
+    let path = match uri.path() {
+        "" | "/" => "/ws",
+        path => path,
+    };
+    match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    }
+}
+
+/// Endpoint of a backend path, without its query.
+pub fn endpoint(path: &str) -> &str {
+    path.split('?').next().unwrap_or(path)
+}
+
+/// Subprotocols the client offered, in order of preference.
+fn offered_protocols(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+}
+
+/// Headers of the client's upgrade request to replay on the backend handshake:
+/// cookies, subprotocols, and any custom headers.
+pub fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = HeaderMap::new();
+    for (name, value) in headers {
+        if !CLIENT_HANDSHAKE_HEADERS.contains(&name.as_str())
+            && !crate::HOP_BY_HOP_HEADERS.contains(&name.as_str())
+            && name != header::SEC_WEBSOCKET_PROTOCOL
+        {
+            forwarded.append(name.clone(), value.clone());
+        }
+    }
+    // One list without spaces, which is how tungstenite matches the backend's choice
+    let protocols = offered_protocols(headers).collect::<Vec<_>>().join(",");
+    if let Ok(protocols) = HeaderValue::from_str(&protocols) {
+        if !protocols.is_empty() {
+            forwarded.insert(header::SEC_WEBSOCKET_PROTOCOL, protocols);
+        }
+    }
+    forwarded
+}
+
+/// Whether the client offered `protocol` in `Sec-WebSocket-Protocol`.
+pub fn offered(headers: &HeaderMap, protocol: &HeaderValue) -> bool {
+    protocol
+        .to_str()
+        .is_ok_and(|protocol| offered_protocols(headers).any(|offered| offered == protocol))
+}
+
+/// Completes the client's handshake, echoing the subprotocol the backend chose.
+pub fn switching_protocols(
+    accept_key: &str,
+    protocol: Option<HeaderValue>,
+) -> Response<Empty<Bytes>> {
+    let mut response = Response::new(Empty::new());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = response.headers_mut();
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    if let Ok(accept) = HeaderValue::from_str(accept_key) {
+        headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+    }
+    if let Some(protocol) = protocol {
+        headers.insert(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+    response
+}
+
+/// Refuses the client's handshake.
+pub fn reject(status: StatusCode) -> Response<Empty<Bytes>> {
+    let mut response = Response::new(Empty::new());
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backend_path, endpoint, forwarded_headers, offered, wants_upgrade};
+    use hyper::header::{HeaderMap, HeaderValue};
+    use hyper::Uri;
+
+    #[test]
+    fn maps_the_root_to_the_app_socket() {
+        let path = |uri: &'static str| backend_path(&Uri::from_static(uri));
+        assert_eq!(path("/"), "/ws");
+        assert_eq!(path("/?token=abc"), "/ws?token=abc");
+        assert_eq!(path("/ws/audio?session=1"), "/ws/audio?session=1");
+        assert_eq!(endpoint(&path("/ws/audio?session=1")), "/ws/audio");
+    }
+
+    #[test]
+    fn forwards_application_headers() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("host", "127.0.0.1:5000"),
+            ("connection", "Upgrade"),
+            ("upgrade", "websocket"),
+            ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="),
+            ("sec-websocket-version", "13"),
+            ("sec-websocket-extensions", "permessage-deflate"),
+            ("sec-websocket-protocol", "assistant.v2, assistant.v1"),
+            ("cookie", "session=abc"),
+            ("x-client", "desktop"),
+        ] {
+            headers.insert(name, HeaderValue::from_static(value));
+        }
+        let forwarded = forwarded_headers(&headers);
+        let mut names: Vec<_> = forwarded.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["cookie", "sec-websocket-protocol", "x-client"]);
+        assert_eq!(
+            forwarded["sec-websocket-protocol"],
+            "assistant.v2,assistant.v1"
+        );
+
+        assert!(wants_upgrade(&headers));
+        assert!(offered(&headers, &HeaderValue::from_static("assistant.v1")));
+        assert!(!offered(&headers, &HeaderValue::from_static("assistant")));
+    }
+}
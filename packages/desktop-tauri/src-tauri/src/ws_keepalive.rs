@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::connection_quality::{self, RTT_PING_INTERVAL_SECS};
+
+/// A WebSocket leg that has sent nothing for this many ping intervals is dead.
+const MISSED_PINGS: u64 = 3;
+/// Prefix of the proxy's keepalive pings to the web client.
+const CLIENT_PING_PREFIX: &[u8] = b"assistant-keepalive:";
+
+/// When one leg of a WebSocket session last sent a frame (any frame counts, so a
+/// busy connection needs no pongs to stay alive), or when a session last carried
+/// a message.
+pub struct Liveness {
+    started: Instant,
+    last_seen_ms: AtomicU64,
+}
+
+impl Liveness {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_seen_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Note that a frame arrived.
+    pub fn seen(&self) {
+        self.last_seen_ms
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn silent_for(&self, now: Instant) -> Duration {
+        let last_seen = Duration::from_millis(self.last_seen_ms.load(Ordering::Relaxed));
+        now.saturating_duration_since(self.started + last_seen)
+    }
+
+    /// Resolves once the leg has been silent through several pings, e.g. because a
+    /// NAT or middlebox dropped the connection without closing it.
+    pub async fn dead(&self) {
+        self.quiet_for(Duration::from_secs(RTT_PING_INTERVAL_SECS * MISSED_PINGS))
+            .await
+    }
+
+    /// Resolves once nothing was seen for `timeout`.
+    pub async fn quiet_for(&self, timeout: Duration) {
+        let check_every = timeout.min(Duration::from_secs(RTT_PING_INTERVAL_SECS));
+        let mut check = tokio::time::interval(check_every);
+        loop {
+            check.tick().await;
+            if self.silent_for(Instant::now()) >= timeout {
+                return;
+            }
+        }
+    }
+}
+
+/// Ping the web client leg; browsers answer it without involving the page.
+pub fn client_ping() -> Message {
+    Message::Ping(connection_quality::stamped_ping_payload(CLIENT_PING_PREFIX))
+}
+
+/// Round-trip time if `msg` answers [`client_ping`]. Such pongs are not forwarded
+/// to the backend.
+pub fn client_pong_rtt(msg: &Message) -> Option<Duration> {
+    match msg {
+        Message::Pong(payload) => {
+            connection_quality::rtt_from_stamped_pong(CLIENT_PING_PREFIX, payload)
+        }
+        _ => None,
+    }
+}
+
+/// Close frame sent to the web client when the backend leg went silent, so it
+/// reconnects.
+pub fn backend_dead_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Error,
+        reason: "Backend stopped responding".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_ping, client_pong_rtt, Liveness};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn tracks_silence() {
+        let mut liveness = Liveness::new();
+        liveness.started -= Duration::from_secs(20);
+        let later = liveness.started + Duration::from_secs(40);
+        assert_eq!(liveness.silent_for(later), Duration::from_secs(40));
+        liveness.seen();
+        assert!(liveness.silent_for(later) <= Duration::from_secs(20));
+
+        let Message::Ping(payload) = client_ping() else {
+            panic!("not a ping");
+        };
+        assert!(client_pong_rtt(&Message::Pong(payload)).is_some());
+        assert!(client_pong_rtt(&Message::Pong(b"other".to_vec())).is_none());
+    }
+}
@@ -0,0 +1,81 @@
+use tokio_tungstenite::tungstenite::error::{CapacityError, Error};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::AppSettings;
+
+/// Smallest limit accepted, so a typo cannot break every session.
+const MIN_LIMIT_BYTES: usize = 1024;
+
+/// Check the WebSocket size limit settings before they are saved.
+pub fn validate(max_message_bytes: usize, max_frame_bytes: usize) -> Result<(), String> {
+    if max_message_bytes < MIN_LIMIT_BYTES || max_frame_bytes < MIN_LIMIT_BYTES {
+        return Err(format!(
+            "WebSocket size limits must be at least {} bytes",
+            MIN_LIMIT_BYTES
+        ));
+    }
+    if max_frame_bytes > max_message_bytes {
+        return Err("The WebSocket frame limit cannot exceed the message limit".to_string());
+    }
+    Ok(())
+}
+
+/// Largest frame and message the WS proxy reads on either leg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WsLimits {
+    pub max_message_bytes: usize,
+    pub max_frame_bytes: usize,
+}
+
+impl WsLimits {
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            max_message_bytes: settings.ws_max_message_bytes,
+            max_frame_bytes: settings.ws_max_frame_bytes,
+        }
+    }
+
+    pub fn config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: Some(self.max_message_bytes),
+            max_frame_size: Some(self.max_frame_bytes),
+            ..WebSocketConfig::default()
+        }
+    }
+}
+
+/// Whether a read failed because the peer sent a frame or message over the limits.
+pub fn is_too_big(error: &Error) -> bool {
+    matches!(error, Error::Capacity(CapacityError::MessageTooLong { .. }))
+}
+
+/// Close frame for a peer that sent a message over the limits.
+pub fn too_big_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Size,
+        reason: "Message too big".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_too_big, validate};
+    use tokio_tungstenite::tungstenite::error::{CapacityError, Error};
+
+    #[test]
+    fn checks_limits() {
+        assert!(validate(64 << 20, 16 << 20).is_ok());
+        assert!(validate(512, 512).is_err());
+        assert!(validate(1 << 20, 2 << 20).is_err());
+
+        assert!(is_too_big(&Error::Capacity(
+            CapacityError::MessageTooLong {
+                size: 2,
+                max_size: 1
+            }
+        )));
+        assert!(!is_too_big(&Error::ConnectionClosed));
+    }
+}
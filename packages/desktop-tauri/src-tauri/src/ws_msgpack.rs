@@ -0,0 +1,55 @@
+use serde::Deserialize;
+use std::io::Cursor;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Decode a backend binary frame holding exactly one MessagePack map into a JSON
+/// text frame. Other frames, including audio and maps with binary values that
+/// JSON cannot hold, are returned as they are.
+pub fn to_client(msg: Message) -> Message {
+    let Message::Binary(data) = msg else {
+        return msg;
+    };
+    let mut decoder = rmp_serde::Deserializer::new(Cursor::new(&data[..]));
+    match serde_json::Value::deserialize(&mut decoder) {
+        Ok(value) if value.is_object() && decoder.position() == data.len() as u64 => {
+            Message::Text(value.to_string())
+        }
+        _ => Message::Binary(data),
+    }
+}
+
+/// Encode a client text frame holding a JSON object as a MessagePack binary
+/// frame. Other frames are returned as they are.
+pub fn to_backend(msg: Message) -> Message {
+    let Message::Text(text) = msg else {
+        return msg;
+    };
+    let encoded = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .filter(serde_json::Value::is_object)
+        .and_then(|value| rmp_serde::to_vec_named(&value).ok());
+    match encoded {
+        Some(data) => Message::Binary(data),
+        None => Message::Text(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_backend, to_client};
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn transcodes_objects_only() {
+        let text = r#"{"sessionId":"s1","type":"subscribe"}"#;
+        let encoded = to_backend(Message::Text(text.into()));
+        assert!(encoded.is_binary());
+        assert_eq!(to_client(encoded), Message::Text(text.into()));
+
+        // Audio and anything else that is not a single map passes through
+        let audio = Message::Binary(vec![0x81, 0xa1, b'a', 0x01, 0x00, 0x7f]);
+        assert_eq!(to_client(audio.clone()), audio);
+        let plain = Message::Text("ping".into());
+        assert_eq!(to_backend(plain.clone()), plain);
+    }
+}
@@ -0,0 +1,608 @@
+use futures_util::{SinkExt, StreamExt};
+use hyper::header::{HeaderMap, HeaderValue};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::shutdown::{self, ShutdownSignal};
+use crate::ws_chunking::{self, Reassembler};
+use crate::ws_events::Outcome;
+use crate::ws_keepalive::{self, Liveness};
+use crate::ws_msgpack;
+use crate::ws_priority::{priority_channel, PriorityReceiver, PrioritySender};
+use crate::ws_sessions;
+use crate::ws_stats::ConnectionStats;
+use crate::{
+    connection_quality, ws_close, ws_handshake, ws_limits, BackendConnection, ClientSocket,
+    ProxyState,
+};
+
+/// A client registered by [`WsMux::attach`] whose socket is not relayed yet.
+pub struct Attached {
+    pub client_id: u64,
+    pub backend_tx: PrioritySender,
+    client_rx: PriorityReceiver,
+    /// Subprotocol to echo to the client.
+    pub protocol: Option<HeaderValue>,
+    /// Size to split large text messages at, when the backend accepted chunking.
+    chunk_bytes: Option<usize>,
+}
+
+/// A local webview socket attached to the shared backend connection.
+struct MuxClient {
+    tx: PrioritySender,
+    /// Sessions (channels) this client subscribed to.
+    sessions: HashSet<String>,
+}
+
+#[derive(Default)]
+struct MuxState {
+    backend: Option<PrioritySender>,
+    /// Subprotocol the backend chose for the shared connection.
+    protocol: Option<HeaderValue>,
+    chunk_bytes: Option<usize>,
+    /// Incremented per backend connection so a stale reader cannot reset a newer one.
+    generation: u64,
+    hello_forwarded: bool,
+    clients: HashMap<u64, MuxClient>,
+    next_client_id: u64,
+}
+
+/// Session a backend message belongs to (`sessionId`, or `event.sessionId` for transcript events).
+fn session_id_of(text: &str) -> Option<String> {
+    let message: serde_json::Value = serde_json::from_str(text).ok()?;
+    message
+        .get("sessionId")
+        .or_else(|| message.get("event")?.get("sessionId"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+fn subscription_ids(message: &serde_json::Value) -> Vec<String> {
+    message
+        .get("subscriptions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|subscription| subscription.get("sessionId")?.as_str())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+impl MuxState {
+    fn add_client(&mut self, tx: PrioritySender) -> u64 {
+        self.next_client_id += 1;
+        self.clients.insert(
+            self.next_client_id,
+            MuxClient {
+                tx,
+                sessions: HashSet::new(),
+            },
+        );
+        self.next_client_id
+    }
+
+    fn held_by_other(&self, client_id: u64, session_id: &str) -> bool {
+        self.clients
+            .iter()
+            .any(|(id, client)| *id != client_id && client.sessions.contains(session_id))
+    }
+
+    /// Translate a client text message for the shared backend connection.
+    ///
+    /// The backend accepts one `hello` per connection, so later clients' hellos become
+    /// `subscribe` messages; unsubscribes for sessions other clients still use are dropped.
+    fn client_text(&mut self, client_id: u64, text: &str) -> Vec<Message> {
+        let forward = vec![Message::Text(text.to_string())];
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(text) else {
+            return forward;
+        };
+        let session_id = message
+            .get("sessionId")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        match message.get("type").and_then(|v| v.as_str()) {
+            Some("hello") => {
+                if let Some(client) = self.clients.get_mut(&client_id) {
+                    client.sessions.extend(subscription_ids(&message));
+                }
+                if !self.hello_forwarded {
+                    self.hello_forwarded = true;
+                    return forward;
+                }
+                message
+                    .get("subscriptions")
+                    .and_then(|v| v.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|subscription| {
+                        let mut subscribe = serde_json::json!({
+                            "type": "subscribe",
+                            "sessionId": subscription.get("sessionId")?.as_str()?.trim(),
+                        });
+                        if let Some(mask) = subscription.get("mask") {
+                            subscribe["mask"] = mask.clone();
+                        }
+                        Some(Message::Text(subscribe.to_string()))
+                    })
+                    .collect()
+            }
+            Some("subscribe") => {
+                if let (Some(session_id), Some(client)) =
+                    (session_id, self.clients.get_mut(&client_id))
+                {
+                    client.sessions.insert(session_id);
+                }
+                forward
+            }
+            Some("unsubscribe") => {
+                let Some(session_id) = session_id else {
+                    return forward;
+                };
+                if let Some(client) = self.clients.get_mut(&client_id) {
+                    client.sessions.remove(&session_id);
+                }
+                if self.held_by_other(client_id, &session_id) {
+                    Vec::new()
+                } else {
+                    forward
+                }
+            }
+            _ => forward,
+        }
+    }
+
+    /// Clients that should receive a backend message for `session_id`: its subscribers,
+    /// or every client for untagged messages and sessions nobody subscribed to yet.
+    fn recipients(&self, session_id: Option<&str>) -> Vec<PrioritySender> {
+        let subscribers: Vec<_> = match session_id {
+            Some(session_id) => self
+                .clients
+                .values()
+                .filter(|client| client.sessions.contains(session_id))
+                .map(|client| client.tx.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        if subscribers.is_empty() {
+            self.clients
+                .values()
+                .map(|client| client.tx.clone())
+                .collect()
+        } else {
+            subscribers
+        }
+    }
+
+    /// Remove a client and return unsubscribes for sessions no other client uses.
+    fn remove_client(&mut self, client_id: u64) -> Vec<Message> {
+        let Some(client) = self.clients.remove(&client_id) else {
+            return Vec::new();
+        };
+        if self.clients.is_empty() {
+            // Dropping the sender closes the backend connection
+            self.backend = None;
+            self.hello_forwarded = false;
+            return Vec::new();
+        }
+        client
+            .sessions
+            .iter()
+            .filter(|session_id| !self.held_by_other(client_id, session_id))
+            .map(|session_id| {
+                Message::Text(
+                    serde_json::json!({ "type": "unsubscribe", "sessionId": session_id })
+                        .to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Shares one backend WebSocket between all local client sockets, routing backend
+/// messages to the clients subscribed to their session.
+pub struct WsMux {
+    state: Mutex<MuxState>,
+    connecting: tokio::sync::Mutex<()>,
+    /// Transcode between client JSON and backend MessagePack frames.
+    msgpack: bool,
+}
+
+impl WsMux {
+    pub fn new(msgpack: bool) -> Self {
+        Self {
+            state: Mutex::new(MuxState::default()),
+            connecting: tokio::sync::Mutex::new(()),
+            msgpack,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MuxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a client before completing its handshake, connecting the shared
+    /// backend socket with the client's requested `path` and handshake `headers`
+    /// if needed.
+    pub async fn attach(
+        self: &Arc<Self>,
+        proxy_state: &Arc<ProxyState>,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Attached, String> {
+        let (client_tx, client_rx) = priority_channel();
+        let _connecting = self.connecting.lock().await;
+        let existing = {
+            let mut state = self.lock();
+            state.backend.clone().map(|backend| {
+                (
+                    state.add_client(client_tx.clone()),
+                    backend,
+                    state.protocol.clone(),
+                    state.chunk_bytes,
+                )
+            })
+        };
+        let (client_id, backend_tx, protocol, chunk_bytes) = match existing {
+            Some(existing) => existing,
+            None => {
+                let backend = crate::connect_backend_ws(proxy_state, path, headers).await?;
+                let (backend_tx, backend_rx) = priority_channel();
+                let (client_id, generation) = {
+                    let mut state = self.lock();
+                    state.generation += 1;
+                    state.backend = Some(backend_tx.clone());
+                    state.protocol = backend.protocol.clone();
+                    state.chunk_bytes = backend.chunk_bytes;
+                    state.hello_forwarded = false;
+                    (state.add_client(client_tx), state.generation)
+                };
+                let (protocol, chunk_bytes) = (backend.protocol.clone(), backend.chunk_bytes);
+                tokio::spawn(self.clone().run_backend(
+                    backend,
+                    backend_rx,
+                    proxy_state.clone(),
+                    generation,
+                ));
+                (client_id, backend_tx, protocol, chunk_bytes)
+            }
+        };
+        Ok(Attached {
+            client_id,
+            backend_tx,
+            client_rx,
+            // Later clients share the subprotocol chosen for the first one
+            protocol: protocol.filter(|protocol| ws_handshake::offered(headers, protocol)),
+            chunk_bytes,
+        })
+    }
+
+    /// Unregister a client, unsubscribing from sessions no other client uses.
+    pub fn detach(&self, client_id: u64, backend_tx: &PrioritySender) {
+        for msg in self.lock().remove_client(client_id) {
+            let _ = backend_tx.send(msg);
+        }
+    }
+
+    async fn run_backend(
+        self: Arc<Self>,
+        backend: BackendConnection,
+        mut backend_rx: PriorityReceiver,
+        proxy_state: Arc<ProxyState>,
+        generation: u64,
+    ) {
+        let (mut backend_write, mut backend_read) = backend.socket.split();
+        let backend_alive = Liveness::new();
+        // Chunks are reassembled before routing, since only the whole message names its session
+        let mut reassembler = backend
+            .chunk_bytes
+            .map(|_| Reassembler::new(proxy_state.ws_limits.max_message_bytes));
+
+        let to_backend = async {
+            let mut rtt_ping = tokio::time::interval(Duration::from_secs(
+                connection_quality::RTT_PING_INTERVAL_SECS,
+            ));
+            loop {
+                let msg = tokio::select! {
+                    msg = backend_rx.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = rtt_ping.tick() => Message::Ping(connection_quality::rtt_ping_payload()),
+                };
+                proxy_state.bandwidth.record(msg.len() as u64, 0);
+                if let Err(e) = backend_write.send(msg).await {
+                    log::error!(target: "ws-proxy", "Failed to send to backend: {}", e);
+                    break;
+                }
+            }
+            let _ = backend_write.close().await;
+        };
+
+        // Resolves to the close frame to send, if the backend broke the limits
+        let from_backend = async {
+            while let Some(msg) = backend_read.next().await {
+                let msg = match msg {
+                    Ok(msg) => {
+                        backend_alive.seen();
+                        msg
+                    }
+                    Err(e) if ws_limits::is_too_big(&e) => {
+                        log::warn!(target: "ws-proxy", "Closing shared backend connection: {}", e);
+                        return Some(ws_limits::too_big_message());
+                    }
+                    Err(e) => {
+                        log::error!(target: "ws-proxy", "Backend read error: {}", e);
+                        break;
+                    }
+                };
+                let msg = match &mut reassembler {
+                    Some(reassembler) => match reassembler.push(msg) {
+                        Ok(Some(msg)) => msg,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            log::warn!(target: "ws-proxy", "Closing shared backend connection: {}", e);
+                            return Some(e.close_message());
+                        }
+                    },
+                    None => msg,
+                };
+                // Decoded before routing, since sessions are read from JSON
+                let msg = if self.msgpack {
+                    ws_msgpack::to_client(msg)
+                } else {
+                    msg
+                };
+                if !crate::observe_backend_frame(&proxy_state, &msg) {
+                    continue;
+                }
+                let session_id = match &msg {
+                    Message::Text(text) => session_id_of(text),
+                    // Backend pings are answered by tungstenite itself
+                    Message::Ping(_) | Message::Pong(_) => continue,
+                    Message::Close(frame) => {
+                        log::info!(target: "ws-proxy", "Shared backend connection closed ({})", ws_close::describe(frame.as_ref()));
+                        // Pass the backend's close code on to every client
+                        for tx in self.lock().recipients(None) {
+                            let _ = tx.send(msg.clone());
+                        }
+                        break;
+                    }
+                    _ => None,
+                };
+                // Waiting on a slow client holds up the others, but keeps memory bounded
+                let recipients = self.lock().recipients(session_id.as_deref());
+                for tx in recipients {
+                    let _ = tx.send_when_ready(msg.clone()).await;
+                }
+            }
+            None
+        };
+
+        // Once the last client has left, wait for the backend to answer the close frame
+        let close_timeout = Duration::from_secs(ws_close::CLOSE_TIMEOUT_SECS);
+        let refusal = tokio::select! {
+            _ = async { to_backend.await; tokio::time::sleep(close_timeout).await } => None,
+            refusal = from_backend => refusal,
+            _ = backend_alive.dead() => {
+                log::warn!(target: "ws-proxy", "Shared backend connection stopped answering pings");
+                None
+            }
+        };
+        if let Some(msg) = refusal {
+            let _ = backend_write.send(msg).await;
+        }
+        // Sends our answer when the backend closed first
+        let _ = tokio::time::timeout(close_timeout, backend_write.close()).await;
+
+        // Close every client of this connection so they reconnect
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.backend = None;
+            state.protocol = None;
+            state.chunk_bytes = None;
+            state.hello_forwarded = false;
+            state.clients.clear();
+        }
+        log::info!(target: "ws-proxy", "Shared backend connection closed");
+    }
+
+    /// Relay one attached client socket over the shared backend connection, closing
+    /// it once idle for `idle_timeout`. Failures that end it are reported to
+    /// `outcome`, and its traffic to `stats`.
+    pub async fn serve_client(
+        self: Arc<Self>,
+        attached: Attached,
+        client_ws: ClientSocket,
+        idle_timeout: Option<Duration>,
+        mut shutdown: ShutdownSignal,
+        outcome: &Outcome,
+        stats: &ConnectionStats,
+    ) {
+        let Attached {
+            client_id,
+            backend_tx,
+            mut client_rx,
+            chunk_bytes,
+            ..
+        } = attached;
+        let (mut client_write, mut client_read) = client_ws.split();
+        let client_alive = Liveness::new();
+        // Last text or binary message to or from this client, for the idle timeout
+        let activity = Liveness::new();
+
+        let to_client = async {
+            let mut keepalive = tokio::time::interval(Duration::from_secs(
+                connection_quality::RTT_PING_INTERVAL_SECS,
+            ));
+            loop {
+                let msg = tokio::select! {
+                    msg = client_rx.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = keepalive.tick() => ws_keepalive::client_ping(),
+                };
+                if msg.is_text() || msg.is_binary() {
+                    activity.seen();
+                    stats.to_client(&msg);
+                }
+                if msg.is_close() {
+                    let _ = client_write.send(msg).await;
+                    return;
+                }
+                if let Err(e) = client_write.send(msg).await {
+                    log::error!(target: "ws-proxy", "Failed to send to client: {}", e);
+                    return;
+                }
+            }
+            // The shared backend connection is gone
+            outcome.backend_lost("Shared backend connection lost");
+            let _ = client_write.send(ws_close::backend_lost_message()).await;
+        };
+
+        // Resolves to the close frame to send, if the client broke the limits
+        let from_client = async {
+            while let Some(msg) = client_read.next().await {
+                if let Ok(msg) = &msg {
+                    client_alive.seen();
+                    if msg.is_text() || msg.is_binary() {
+                        activity.seen();
+                        stats.to_backend(msg);
+                    }
+                    if let Some(rtt) = ws_keepalive::client_pong_rtt(msg) {
+                        stats.client_rtt(rtt);
+                    }
+                }
+                let outgoing = match msg {
+                    Ok(Message::Text(text)) => self.lock().client_text(client_id, &text),
+                    // Client pings are answered locally; closes only end this client
+                    Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+                    Ok(Message::Close(frame)) => {
+                        log::info!(
+                            target: "ws-proxy",
+                            "Multiplexed client {} closed ({})",
+                            client_id,
+                            ws_close::describe(frame.as_ref())
+                        );
+                        break;
+                    }
+                    Ok(msg) => vec![msg],
+                    Err(e) if ws_limits::is_too_big(&e) => {
+                        log::warn!(target: "ws-proxy", "Closing multiplexed client {}: {}", client_id, e);
+                        outcome.failed(e.to_string());
+                        return Some(ws_limits::too_big_message());
+                    }
+                    Err(e) => {
+                        log::error!(target: "ws-proxy", "Client read error: {}", e);
+                        break;
+                    }
+                };
+                let outgoing = if self.msgpack {
+                    outgoing.into_iter().map(ws_msgpack::to_backend).collect()
+                } else {
+                    outgoing
+                };
+                let outgoing = match chunk_bytes {
+                    Some(size) => outgoing
+                        .into_iter()
+                        .flat_map(|msg| ws_chunking::split(msg, size))
+                        .collect(),
+                    None => outgoing,
+                };
+                for msg in outgoing {
+                    if backend_tx.send_when_ready(msg).await.is_err() {
+                        return None;
+                    }
+                }
+            }
+            None
+        };
+
+        // After sending a close frame, wait for the client's answer
+        let close_timeout = Duration::from_secs(ws_close::CLOSE_TIMEOUT_SECS);
+        let close = tokio::select! {
+            _ = async { to_client.await; tokio::time::sleep(close_timeout).await } => None,
+            refusal = from_client => refusal,
+            _ = shutdown.wait() => Some(shutdown::close_message()),
+            _ = client_alive.dead() => {
+                log::warn!(target: "ws-proxy", "Multiplexed client {} stopped answering pings", client_id);
+                outcome.failed("Client stopped answering pings");
+                None
+            }
+            _ = ws_sessions::idle(&activity, idle_timeout) => {
+                log::info!(target: "ws-proxy", "Closing idle multiplexed client {}", client_id);
+                Some(ws_sessions::idle_message())
+            }
+        };
+        if let Some(msg) = close {
+            let _ = client_write.send(msg).await;
+        }
+        // Sends our answer when the client closed first
+        let _ = tokio::time::timeout(close_timeout, client_write.close()).await;
+
+        self.detach(client_id, &backend_tx);
+        log::info!(target: "ws-proxy", "Multiplexed client {} closed", client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{session_id_of, MuxState};
+    use crate::ws_priority::priority_channel;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn texts(messages: Vec<Message>) -> Vec<serde_json::Value> {
+        messages
+            .into_iter()
+            .map(|msg| serde_json::from_str(msg.to_text().unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn translates_later_hellos_and_shared_unsubscribes() {
+        let mut state = MuxState::default();
+        let (tx, _rx) = priority_channel();
+        let first = state.add_client(tx.clone());
+        let second = state.add_client(tx);
+
+        let hello = r#"{"type":"hello","subscriptions":[{"sessionId":"s1"}]}"#;
+        assert_eq!(texts(state.client_text(first, hello))[0]["type"], "hello");
+        let translated = texts(state.client_text(second, hello));
+        assert_eq!(translated.len(), 1);
+        assert_eq!(translated[0]["type"], "subscribe");
+        assert_eq!(translated[0]["sessionId"], "s1");
+
+        let unsubscribe = r#"{"type":"unsubscribe","sessionId":"s1"}"#;
+        assert!(state.client_text(first, unsubscribe).is_empty());
+        assert_eq!(state.client_text(second, unsubscribe).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn routes_backend_messages_by_session() {
+        let mut state = MuxState::default();
+        let (tx1, mut rx1) = priority_channel();
+        let (tx2, mut rx2) = priority_channel();
+        let first = state.add_client(tx1);
+        state.add_client(tx2);
+        state.client_text(first, r#"{"type":"subscribe","sessionId":"s1"}"#);
+
+        assert_eq!(state.recipients(None).len(), 2);
+        assert_eq!(state.recipients(Some("unknown")).len(), 2);
+        let recipients = state.recipients(Some("s1"));
+        assert_eq!(recipients.len(), 1);
+        recipients[0].send(Message::Text("routed".into())).unwrap();
+        drop(recipients);
+        state.clients.clear();
+        assert!(rx1.recv().await.is_some());
+        assert!(rx2.recv().await.is_none());
+
+        assert_eq!(
+            session_id_of(r#"{"type":"transcript_event","event":{"sessionId":"s2"}}"#).as_deref(),
+            Some("s2")
+        );
+    }
+}
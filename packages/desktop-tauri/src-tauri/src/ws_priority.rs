@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Realtime frames sent in a row before a waiting bulk message gets a turn.
+const MAX_REALTIME_STREAK: u32 = 8;
+/// Bytes a relay queue holds before [`PrioritySender::send_when_ready`] waits for
+/// the writer to catch up.
+const MAX_QUEUED_BYTES: usize = 4 << 20;
+
+/// Whether a frame is latency-sensitive: binary frames carry voice audio, and
+/// control frames are tiny.
+fn is_realtime(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::Binary(_) | Message::Ping(_) | Message::Pong(_)
+    )
+}
+
+/// Two-level queue that sends realtime frames ahead of bulk text messages.
+#[derive(Default)]
+struct PriorityQueue {
+    realtime: VecDeque<Message>,
+    bulk: VecDeque<Message>,
+    realtime_streak: u32,
+    /// Payload bytes queued in both levels.
+    bytes: usize,
+}
+
+impl PriorityQueue {
+    fn push(&mut self, msg: Message) {
+        self.bytes += msg.len();
+        if is_realtime(&msg) {
+            self.realtime.push_back(msg);
+        } else {
+            self.bulk.push_back(msg);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Message> {
+        let msg = self.next()?;
+        self.bytes -= msg.len();
+        Some(msg)
+    }
+
+    fn next(&mut self) -> Option<Message> {
+        let starving = self.realtime_streak >= MAX_REALTIME_STREAK && !self.bulk.is_empty();
+        if !starving {
+            if let Some(msg) = self.realtime.pop_front() {
+                self.realtime_streak += 1;
+                return Some(msg);
+            }
+        }
+        self.realtime_streak = 0;
+        self.bulk.pop_front()
+    }
+}
+
+struct Shared {
+    queue: Mutex<PriorityQueue>,
+    notify: Notify,
+    /// Wakes senders waiting for room.
+    drained: Notify,
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+}
+
+impl Shared {
+    fn queue(&self) -> std::sync::MutexGuard<'_, PriorityQueue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Sending half of a WS relay queue; closed once every sender is dropped.
+pub struct PrioritySender(Arc<Shared>);
+
+/// Receiving half of a WS relay queue.
+pub struct PriorityReceiver(Arc<Shared>);
+
+/// Relay queue that delivers binary (audio) frames ahead of text, letting a bulk
+/// message through after every `MAX_REALTIME_STREAK` realtime frames. Forwarded
+/// messages are bounded by `MAX_QUEUED_BYTES` (see [`PrioritySender::send_when_ready`]).
+pub fn priority_channel() -> (PrioritySender, PriorityReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(PriorityQueue::default()),
+        notify: Notify::new(),
+        drained: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receiver_alive: AtomicBool::new(true),
+    });
+    (PrioritySender(shared.clone()), PriorityReceiver(shared))
+}
+
+impl PrioritySender {
+    /// Queue a frame right away, even over the byte limit; for the proxy's own
+    /// small control and close frames. Fails (returning it) once the receiver is gone.
+    pub fn send(&self, msg: Message) -> Result<(), Message> {
+        if !self.0.receiver_alive.load(Ordering::Acquire) {
+            return Err(msg);
+        }
+        self.0.queue().push(msg);
+        self.0.notify.notify_one();
+        Ok(())
+    }
+
+    /// Queue a relayed frame once the queue is below `MAX_QUEUED_BYTES`. A read
+    /// loop awaiting this stops reading while the writer is behind, so TCP flow
+    /// control slows the sending peer instead of the queue growing.
+    pub async fn send_when_ready(&self, msg: Message) -> Result<(), Message> {
+        loop {
+            let drained = self.0.drained.notified();
+            tokio::pin!(drained);
+            drained.as_mut().enable();
+            if !self.0.receiver_alive.load(Ordering::Acquire)
+                || self.0.queue().bytes < MAX_QUEUED_BYTES
+            {
+                return self.send(msg);
+            }
+            drained.await;
+        }
+    }
+}
+
+impl Clone for PrioritySender {
+    fn clone(&self) -> Self {
+        self.0.senders.fetch_add(1, Ordering::AcqRel);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for PrioritySender {
+    fn drop(&mut self) {
+        if self.0.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.notify.notify_one();
+        }
+    }
+}
+
+impl PriorityReceiver {
+    /// Next frame by priority, or `None` once all senders are dropped and the queue is drained.
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            let msg = self.0.queue().pop();
+            if let Some(msg) = msg {
+                self.0.drained.notify_waiters();
+                return Some(msg);
+            }
+            if self.0.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            self.0.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for PriorityReceiver {
+    fn drop(&mut self) {
+        self.0.receiver_alive.store(false, Ordering::Release);
+        self.0.drained.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{priority_channel, PriorityQueue, MAX_QUEUED_BYTES, MAX_REALTIME_STREAK};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn sends_audio_first_without_starving_text() {
+        let mut queue = PriorityQueue::default();
+        queue.push(Message::Text("bulk".into()));
+        for _ in 0..MAX_REALTIME_STREAK + 2 {
+            queue.push(Message::Binary(vec![0; 4]));
+        }
+
+        for _ in 0..MAX_REALTIME_STREAK {
+            assert!(queue.pop().unwrap().is_binary());
+        }
+        assert!(queue.pop().unwrap().is_text());
+        assert!(queue.pop().unwrap().is_binary());
+        assert!(queue.pop().unwrap().is_binary());
+        assert!(queue.pop().is_none());
+    }
+
+    #[tokio::test]
+    async fn closes_after_senders_drop() {
+        let (tx, mut rx) = priority_channel();
+        let second = tx.clone();
+        tx.send(Message::Text("a".into())).unwrap();
+        drop(tx);
+        drop(second);
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn waits_for_room() {
+        let (tx, mut rx) = priority_channel();
+        // A message larger than the limit still fits in an empty queue
+        tx.send_when_ready(Message::Binary(vec![0; MAX_QUEUED_BYTES]))
+            .await
+            .unwrap();
+        let blocked = tx.send_when_ready(Message::Text("next".into()));
+        tokio::pin!(blocked);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), blocked.as_mut())
+                .await
+                .is_err()
+        );
+
+        assert!(rx.recv().await.unwrap().is_binary());
+        blocked.await.unwrap();
+        assert!(rx.recv().await.unwrap().is_text());
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::State;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::ws_keepalive::Liveness;
+use crate::ws_stats::{ConnectionSnapshot, ConnectionStats};
+use crate::AppState;
+
+/// Sessions of one backend WebSocket endpoint, reported by `get_ws_endpoints`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EndpointStats {
+    /// Backend path, without the query.
+    pub endpoint: String,
+    /// Sessions currently relayed.
+    pub active: usize,
+    /// Sessions connected since the app started.
+    pub opened: u64,
+    /// Sessions that ended because the backend connection failed.
+    pub backend_lost: u64,
+}
+
+/// Counts the web client sockets the WS proxy is relaying, per backend endpoint,
+/// and keeps the statistics of each.
+#[derive(Default)]
+pub struct WsSessions {
+    endpoints: Mutex<HashMap<String, EndpointStats>>,
+    connections: Mutex<HashMap<u64, Arc<ConnectionStats>>>,
+}
+
+impl WsSessions {
+    fn update(&self, endpoint: &str, update: impl FnOnce(&mut EndpointStats)) {
+        let mut endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = endpoints
+            .entry(endpoint.to_string())
+            .or_insert_with(|| EndpointStats {
+                endpoint: endpoint.to_string(),
+                ..EndpointStats::default()
+            });
+        update(stats);
+    }
+
+    fn lock_connections(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Arc<ConnectionStats>>> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Count `session` of `endpoint` until the returned guard is dropped.
+    pub fn open(self: &Arc<Self>, session: u64, endpoint: &str) -> SessionGuard {
+        self.update(endpoint, |stats| {
+            stats.active += 1;
+            stats.opened += 1;
+        });
+        let stats = Arc::new(ConnectionStats::new(session, endpoint));
+        self.lock_connections().insert(session, stats.clone());
+        SessionGuard {
+            sessions: self.clone(),
+            endpoint: endpoint.to_string(),
+            stats,
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        let endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        endpoints.values().map(|stats| stats.active).sum()
+    }
+
+    /// Every endpoint that had a session, by path.
+    pub fn endpoints(&self) -> Vec<EndpointStats> {
+        let endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<_> = endpoints.values().cloned().collect();
+        stats.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        stats
+    }
+
+    /// Statistics of the open sessions, oldest first.
+    pub fn connections(&self) -> Vec<ConnectionSnapshot> {
+        let mut connections: Vec<_> = self
+            .lock_connections()
+            .values()
+            .map(|stats| stats.snapshot())
+            .collect();
+        connections.sort_by_key(|connection| connection.session);
+        connections
+    }
+}
+
+/// Keeps a WebSocket session counted while it is open.
+pub struct SessionGuard {
+    sessions: Arc<WsSessions>,
+    endpoint: String,
+    stats: Arc<ConnectionStats>,
+}
+
+impl SessionGuard {
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        self.stats.clone()
+    }
+
+    /// Count the session as ended by a failed backend connection.
+    pub fn backend_lost(&self) {
+        self.sessions
+            .update(&self.endpoint, |stats| stats.backend_lost += 1);
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions
+            .update(&self.endpoint, |stats| stats.active -= 1);
+        self.sessions
+            .lock_connections()
+            .remove(&self.stats.session());
+    }
+}
+
+/// Close frame sent to both legs of a session that carried no messages for the
+/// idle timeout.
+pub fn idle_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "Idle timeout".into(),
+    }))
+}
+
+/// Resolves once a session whose messages `activity` tracks carried none for
+/// `timeout`; never without a timeout.
+pub async fn idle(activity: &Liveness, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => activity.quiet_for(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Number of WebSocket connections the WS proxy currently relays.
+#[tauri::command]
+pub async fn get_active_ws_connections(state: State<'_, AppState>) -> Result<usize, String> {
+    Ok(state.ws_sessions.active())
+}
+
+/// Session counts for each backend WebSocket endpoint.
+#[tauri::command]
+pub async fn get_ws_endpoints(state: State<'_, AppState>) -> Result<Vec<EndpointStats>, String> {
+    Ok(state.ws_sessions.endpoints())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WsSessions;
+    use std::sync::Arc;
+
+    #[test]
+    fn counts_open_sessions() {
+        let sessions = Arc::new(WsSessions::default());
+        let first = sessions.open(1, "/ws");
+        let second = sessions.open(2, "/ws/audio");
+        assert_eq!(sessions.active(), 2);
+        assert_eq!(sessions.connections().len(), 2);
+        second.backend_lost();
+        drop(second);
+        assert_eq!(sessions.active(), 1);
+
+        let endpoints = sessions.endpoints();
+        assert_eq!(endpoints[0].endpoint, "/ws");
+        assert_eq!((endpoints[0].active, endpoints[0].backend_lost), (1, 0));
+        assert_eq!(endpoints[1].endpoint, "/ws/audio");
+        assert_eq!(
+            (
+                endpoints[1].active,
+                endpoints[1].opened,
+                endpoints[1].backend_lost
+            ),
+            (0, 1, 1)
+        );
+        drop(first);
+        assert_eq!(sessions.active(), 0);
+        assert!(sessions.connections().is_empty());
+    }
+}
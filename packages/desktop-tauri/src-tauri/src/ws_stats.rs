@@ -0,0 +1,159 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::State;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::AppState;
+
+/// Stored while no round trip has been measured.
+const NO_RTT: u64 = u64::MAX;
+
+#[derive(Default)]
+struct Traffic {
+    frames: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Traffic {
+    fn record(&self, msg: &Message) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(msg.len() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> TrafficSnapshot {
+        TrafficSnapshot {
+            frames: self.frames.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Text and binary frames, and their payload bytes, relayed in one direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TrafficSnapshot {
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+/// One connection's statistics, reported by `get_ws_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionSnapshot {
+    /// Matches `session` in `ws-proxy-state` events.
+    pub session: u64,
+    pub endpoint: String,
+    /// Unix time in milliseconds when the connection opened.
+    pub started_at: u64,
+    pub uptime_secs: u64,
+    pub to_backend: TrafficSnapshot,
+    pub to_client: TrafficSnapshot,
+    /// Last keepalive ping round trip to the web client.
+    pub client_rtt_ms: Option<u64>,
+    /// Last ping round trip to the backend (not measured per client when
+    /// multiplexing).
+    pub backend_rtt_ms: Option<u64>,
+}
+
+/// Live statistics of one relayed WebSocket connection.
+pub struct ConnectionStats {
+    session: u64,
+    endpoint: String,
+    started: Instant,
+    started_at: u64,
+    to_backend: Traffic,
+    to_client: Traffic,
+    client_rtt_ms: AtomicU64,
+    backend_rtt_ms: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn new(session: u64, endpoint: &str) -> Self {
+        Self {
+            session,
+            endpoint: endpoint.to_string(),
+            started: Instant::now(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or_default(),
+            to_backend: Traffic::default(),
+            to_client: Traffic::default(),
+            client_rtt_ms: AtomicU64::new(NO_RTT),
+            backend_rtt_ms: AtomicU64::new(NO_RTT),
+        }
+    }
+
+    pub fn session(&self) -> u64 {
+        self.session
+    }
+
+    /// Count a message from the web client to the backend.
+    pub fn to_backend(&self, msg: &Message) {
+        self.to_backend.record(msg);
+    }
+
+    /// Count a message from the backend to the web client.
+    pub fn to_client(&self, msg: &Message) {
+        self.to_client.record(msg);
+    }
+
+    pub fn client_rtt(&self, rtt: Duration) {
+        self.client_rtt_ms
+            .store(rtt.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn backend_rtt(&self, rtt: Duration) {
+        self.backend_rtt_ms
+            .store(rtt.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ConnectionSnapshot {
+        let rtt = |ms: &AtomicU64| Some(ms.load(Ordering::Relaxed)).filter(|ms| *ms != NO_RTT);
+        ConnectionSnapshot {
+            session: self.session,
+            endpoint: self.endpoint.clone(),
+            started_at: self.started_at,
+            uptime_secs: self.started.elapsed().as_secs(),
+            to_backend: self.to_backend.snapshot(),
+            to_client: self.to_client.snapshot(),
+            client_rtt_ms: rtt(&self.client_rtt_ms),
+            backend_rtt_ms: rtt(&self.backend_rtt_ms),
+        }
+    }
+}
+
+/// Statistics of every WebSocket connection the proxy currently relays, oldest
+/// first.
+#[tauri::command]
+pub async fn get_ws_stats(state: State<'_, AppState>) -> Result<Vec<ConnectionSnapshot>, String> {
+    Ok(state.ws_sessions.connections())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionStats;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn counts_each_direction() {
+        let stats = ConnectionStats::new(7, "/ws");
+        stats.to_backend(&Message::Text("hello".into()));
+        stats.to_client(&Message::Binary(vec![0; 320]));
+        stats.to_client(&Message::Text("{}".into()));
+        stats.client_rtt(Duration::from_millis(42));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.session, 7);
+        assert_eq!(
+            (snapshot.to_backend.frames, snapshot.to_backend.bytes),
+            (1, 5)
+        );
+        assert_eq!(
+            (snapshot.to_client.frames, snapshot.to_client.bytes),
+            (2, 322)
+        );
+        assert_eq!(snapshot.client_rtt_ms, Some(42));
+        assert_eq!(snapshot.backend_rtt_ms, None);
+    }
+}
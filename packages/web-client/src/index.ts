@@ -241,6 +241,7 @@ import {
   configureDesktop,
   installDesktopExternalLinkHandler,
   isDesktopNative,
+  onDesktopQuickCapture,
   waitForDesktopProxyReady,
 } from './utils/desktop';
 import { configureNativeLaunchBackend } from './utils/nativeLaunchBackend';
@@ -5779,6 +5780,20 @@ async function main(): Promise<void> {
     },
   });
 
+  // Text captured from another app with the desktop quick-capture shortcut is
+  // shown in the chat input and sent; it stays there if no session can take it.
+  onDesktopQuickCapture((text) => {
+    const runtime = getPrimaryChatInputRuntime();
+    if (!runtime) {
+      setStatus(statusEl, 'Open a chat panel to use quick capture');
+      return;
+    }
+    runtime.inputEl.value = text;
+    runtime.inputEl.dispatchEvent(new Event('input', { bubbles: true }));
+    runtime.focusInput();
+    runtime.textInputController.sendUserText(text);
+  });
+
   function setFocusedSessionItem(item: HTMLElement | null): void {
     keyboardNavigationController?.setFocusedSessionItem(item);
   }
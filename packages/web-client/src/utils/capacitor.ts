@@ -3,6 +3,8 @@
  * These functions safely no-op when not running in a Capacitor context.
  */
 
+import { isTauri, openDesktopExternal } from './desktop';
+
 export type NativeThemeScheme = 'light' | 'dark';
 
 type StatusBarPlugin = {
@@ -432,6 +434,11 @@ export async function openExternalUrl(url: string): Promise<void> {
     return;
   }
 
+  if (isTauri()) {
+    await openDesktopExternal(url);
+    return;
+  }
+
   if (isCapacitorAndroid()) {
     try {
       const { Browser } = await importModule<{
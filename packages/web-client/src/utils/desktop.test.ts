@@ -4,12 +4,14 @@ import { afterEach, describe, expect, it, vi } from 'vitest';
 import {
   configureDesktop,
   installDesktopExternalLinkHandler,
+  onDesktopQuickCapture,
   waitForDesktopProxyReady,
 } from './desktop';
 
 afterEach(() => {
   vi.useRealTimers();
   delete (window as { assistantDesktop?: unknown }).assistantDesktop;
+  delete (window as { __TAURI__?: unknown }).__TAURI__;
   delete (window as { ASSISTANT_API_HOST?: string }).ASSISTANT_API_HOST;
   delete (window as { ASSISTANT_INSECURE?: boolean }).ASSISTANT_INSECURE;
   delete (window as { ASSISTANT_WS_PORT?: number }).ASSISTANT_WS_PORT;
@@ -55,6 +57,41 @@ describe('configureDesktop', () => {
   });
 });
 
+describe('onDesktopQuickCapture', () => {
+  it('passes captured text from the Tauri event to the handler', async () => {
+    let emit: (event: { payload: unknown }) => void = () => undefined;
+    const unlisten = vi.fn();
+    const listen = vi.fn((_event: string, callback: (event: { payload: unknown }) => void) => {
+      emit = callback;
+      return Promise.resolve(unlisten);
+    });
+    (window as { __TAURI__?: unknown }).__TAURI__ = {
+      core: { invoke: vi.fn() },
+      event: { listen },
+    };
+    const handler = vi.fn();
+
+    const stop = onDesktopQuickCapture(handler);
+    await Promise.resolve();
+    emit({ payload: { text: '  explain this  ' } });
+    emit({ payload: { text: '   ' } });
+    emit({ payload: {} });
+    stop();
+
+    expect(listen).toHaveBeenCalledWith('quick-capture', expect.any(Function));
+    expect(handler).toHaveBeenCalledTimes(1);
+    expect(handler).toHaveBeenCalledWith('explain this');
+    expect(unlisten).toHaveBeenCalledTimes(1);
+  });
+
+  it('does nothing outside Tauri', () => {
+    const handler = vi.fn();
+    const stop = onDesktopQuickCapture(handler);
+    stop();
+    expect(handler).not.toHaveBeenCalled();
+  });
+});
+
 describe('waitForDesktopProxyReady', () => {
   it('resolves when the desktop proxy-ready event fires', async () => {
     (window as typeof window & { assistantDesktop?: unknown }).assistantDesktop = {};
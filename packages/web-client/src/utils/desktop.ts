@@ -44,6 +44,10 @@ type ProxyReadyPayload = {
   ws_port?: number;
 };
 
+type QuickCapturePayload = {
+  text?: string;
+};
+
 type ProxyReadyDetail = {
   apiHost: string;
   wsPort: number;
@@ -269,7 +273,8 @@ export async function openDesktopExternal(url: string): Promise<void> {
     window.open(url, '_blank');
     return;
   }
-  await invoke('plugin:shell|open', { path: url });
+  // The desktop app checks the link allowlist and confirms unknown domains
+  await invoke('open_external_url', { url });
 }
 
 function resolveExternalDesktopHref(href: string): string | null {
@@ -325,6 +330,40 @@ export function installDesktopExternalLinkHandler(root: Document | HTMLElement =
   return () => root.removeEventListener('click', handleClick, true);
 }
 
+/**
+ * Listen for text captured with the Tauri quick-capture shortcut.
+ */
+export function onDesktopQuickCapture(handler: (text: string) => void): () => void {
+  const listen = window.__TAURI__?.event?.listen;
+  if (!listen) {
+    return () => undefined;
+  }
+
+  let disposed = false;
+  let unlisten: (() => void) | null = null;
+  void listen<QuickCapturePayload>('quick-capture', (event) => {
+    const text = typeof event.payload?.text === 'string' ? event.payload.text.trim() : '';
+    if (text) {
+      handler(text);
+    }
+  })
+    .then((stop) => {
+      if (disposed) {
+        stop();
+      } else {
+        unlisten = stop;
+      }
+    })
+    .catch((err) => {
+      console.error('[desktop] Failed to listen for quick capture:', err);
+    });
+
+  return () => {
+    disposed = true;
+    unlisten?.();
+  };
+}
+
 export async function configureDesktop(): Promise<void> {
   if (!isDesktopNative()) {
     return;